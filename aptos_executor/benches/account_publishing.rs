@@ -0,0 +1,36 @@
+//! Compares publishing many accounts one by one with publishing them in one parallel batch (see
+//! `AptosDatabase::publish_accounts`). Run with `cargo bench -p aptos_executor --bench
+//! account_publishing`.
+use aptos_executor::database::AptosDatabase;
+use aptos_executor::LocalAccount;
+use std::time::Instant;
+
+const ACCOUNTS: u64 = 5_000;
+
+fn main() {
+    let accounts: Vec<_> = (0..ACCOUNTS)
+        .map(|seed| LocalAccount::generate(seed).unwrap())
+        .collect();
+    let batch: Vec<_> = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, account)| (account, 1_000 + i as u64))
+        .collect();
+
+    let cpus = std::thread::available_parallelism().map_or(1, |x| x.get());
+    println!("{} CPUs available", cpus);
+
+    let sequential = AptosDatabase::new_with_genesis().unwrap();
+    let start = Instant::now();
+    for (account, balance) in &batch {
+        sequential
+            .publish_account_resources(account, *balance)
+            .unwrap();
+    }
+    println!("one by one: {} accounts in {:?}", ACCOUNTS, start.elapsed());
+
+    let parallel = AptosDatabase::new_with_genesis().unwrap();
+    let start = Instant::now();
+    parallel.publish_accounts(&batch).unwrap();
+    println!("in a batch: {} accounts in {:?}", ACCOUNTS, start.elapsed());
+}
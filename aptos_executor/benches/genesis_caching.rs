@@ -0,0 +1,20 @@
+//! Compares constructing a database, which copies the genesis generated by the first construction,
+//! with generating the genesis again. Run with `cargo bench -p aptos_executor --bench
+//! genesis_caching`.
+use aptos_executor::database::AptosDatabase;
+use aptos_vm_genesis::{generate_genesis_change_set_for_mainnet, GenesisOptions};
+use std::time::Instant;
+
+fn main() {
+    let start = Instant::now();
+    AptosDatabase::new_with_genesis().unwrap();
+    println!("first construction: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    AptosDatabase::new_with_genesis().unwrap();
+    println!("later construction: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    generate_genesis_change_set_for_mainnet(GenesisOptions::Head);
+    println!("genesis generation: {:?}", start.elapsed());
+}
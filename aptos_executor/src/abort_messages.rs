@@ -0,0 +1,90 @@
+//! Readable messages for the abort codes of Move modules.
+
+use crate::package::load_package_artifacts;
+use anyhow::{bail, Context as _, Result};
+use aptos_types::vm::module_metadata::get_metadata_from_compiled_code;
+use move_binary_format::CompiledModule;
+use move_core_types::{language_storage::ModuleId, vm_status::AbortLocation};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[cfg(test)]
+#[path = "tests/abort_messages_tests.rs"]
+pub mod abort_messages_tests;
+
+/// Maps the abort codes of Move modules to readable messages, read from the error maps that the
+/// compiler stores in the metadata of the modules. Every node registers the same messages, so that
+/// the aborts of the same transactions are explained the same way on all of them.
+#[derive(Clone, Debug, Default)]
+pub struct AbortMessages {
+    messages: HashMap<(ModuleId, u64), String>,
+}
+
+impl AbortMessages {
+    /// Reads the error maps of the modules of a compiled package (see `resolve_package_dir`), which
+    /// must be built with `--save-metadata`.
+    pub fn from_package_dir(package_dir: &Path) -> Result<Self> {
+        let (_, modules) = load_package_artifacts(package_dir)?;
+        Self::from_compiled_modules(&modules)
+            .with_context(|| format!("invalid package at {}", package_dir.display()))
+    }
+
+    /// Reads the error maps of compiled modules. An error reads as the doc comment of its constant if
+    /// it has one, and as the name of the constant otherwise (e.g. `EORDER_NOT_FOUND` reads "order not
+    /// found").
+    pub fn from_compiled_modules(modules: &[Vec<u8>]) -> Result<Self> {
+        let mut messages = Self::default();
+        for bytes in modules {
+            let module =
+                CompiledModule::deserialize(bytes).context("failed to decode compiled module")?;
+            let Some(metadata) = get_metadata_from_compiled_code(&module) else {
+                continue;
+            };
+            let id = module.self_id();
+            for (code, error) in metadata.error_map {
+                let message = match error.code_description.is_empty() {
+                    true => constant_message(&error.code_name),
+                    false => error.code_description,
+                };
+                messages.insert(id.clone(), code, message);
+            }
+        }
+        if messages.messages.is_empty() {
+            bail!("no error map in the metadata of the modules");
+        }
+        Ok(messages)
+    }
+
+    /// Sets the message of the abort `code` of `module`.
+    pub fn insert(&mut self, module: ModuleId, code: u64, message: impl Into<String>) {
+        self.messages.insert((module, code), message.into());
+    }
+
+    /// Adds the messages of `other`, which take precedence.
+    pub fn extend(&mut self, other: AbortMessages) {
+        self.messages.extend(other.messages);
+    }
+
+    /// Returns the message of an abort with `code` at `location`, if known.
+    pub fn message(&self, location: &AbortLocation, code: u64) -> Option<&str> {
+        match location {
+            AbortLocation::Module(module) => self
+                .messages
+                .get(&(module.clone(), code))
+                .map(String::as_str),
+            AbortLocation::Script => None,
+        }
+    }
+}
+
+/// Turns the name of an error constant into a message, e.g. `ESELF_MATCH_NOT_ALLOWED` into "self match
+/// not allowed".
+pub fn constant_message(name: &str) -> String {
+    name.strip_prefix('E')
+        .unwrap_or(name)
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,11 +1,46 @@
 //! Account utilities for constructing Aptos transactions in tests and demos.
 
+use crate::database::{account_resource_key, TestDbReader};
 use anyhow::Result;
 use aptos_crypto::ed25519::{Ed25519PrivateKey, Ed25519PublicKey};
 use aptos_crypto::{hash::HashValue, PrivateKey};
+use aptos_types::account_config::AccountResource;
+use aptos_types::transaction::authenticator::AuthenticationKey;
 use aptos_types::transaction::{RawTransaction, SignedTransaction};
 use move_core_types::account_address::AccountAddress;
 use std::convert::TryFrom;
+use std::sync::Arc;
+
+/// Where the transaction builders take the sequence number of the next transaction of an account.
+/// Accounts without a source use their local counter, which drifts from the chain once some of
+/// their transactions are not executed.
+pub trait SequenceSource: Send + Sync {
+    /// Returns the sequence number of the next transaction of `address`.
+    fn sequence_number(&self, address: AccountAddress) -> Result<u64>;
+}
+
+/// Any fetcher can serve as a source, e.g. one querying the transaction API of a node.
+impl<F> SequenceSource for F
+where
+    F: Fn(AccountAddress) -> Result<u64> + Send + Sync,
+{
+    fn sequence_number(&self, address: AccountAddress) -> Result<u64> {
+        self(address)
+    }
+}
+
+/// Reads the sequence numbers from the `0x1::account::Account` resources of a database, e.g. the
+/// one of an executor. Accounts that do not exist yet start at zero.
+pub struct OnChainSequence(pub Arc<TestDbReader>);
+
+impl SequenceSource for OnChainSequence {
+    fn sequence_number(&self, address: AccountAddress) -> Result<u64> {
+        match self.0.get_state_value(&account_resource_key(address)?)? {
+            Some(value) => Ok(bcs::from_bytes::<AccountResource>(value.bytes())?.sequence_number()),
+            None => Ok(0),
+        }
+    }
+}
 
 /// Lightweight representation of an Aptos account with local signing keys.
 pub struct LocalAccount {
@@ -13,6 +48,12 @@ pub struct LocalAccount {
     pub private_key: Ed25519PrivateKey,
     pub public_key: Ed25519PublicKey,
     pub sequence_number: u64,
+    /// The on-chain authentication key after a key rotation, in which case it is derived from the
+    /// current key but the address is still derived from the original one.
+    pub rotated_auth_key: Option<AuthenticationKey>,
+    /// If set, the builders take the sequence number of the next transaction from this source
+    /// rather than from `sequence_number`.
+    pub sequence_source: Option<Arc<dyn SequenceSource>>,
 }
 
 impl LocalAccount {
@@ -27,17 +68,48 @@ impl LocalAccount {
     /// Creates an account wrapper from an existing private key.
     pub fn from_private_key(private_key: Ed25519PrivateKey, sequence_number: u64) -> Self {
         let public_key = private_key.public_key();
-        let address =
-            aptos_types::transaction::authenticator::AuthenticationKey::ed25519(&public_key)
-                .account_address();
+        let address = AuthenticationKey::ed25519(&public_key).account_address();
         Self {
             address,
             private_key,
             public_key,
             sequence_number,
+            rotated_auth_key: None,
+            sequence_source: None,
         }
     }
 
+    /// Takes the sequence numbers of the next transactions from `source`.
+    pub fn with_sequence_source(mut self, source: Arc<dyn SequenceSource>) -> Self {
+        self.sequence_source = Some(source);
+        self
+    }
+
+    /// Returns the sequence number of the next transaction, first catching the local counter up with
+    /// the sequence source, if any. The counter never goes back: the transactions signed but not
+    /// executed yet are not known to the source.
+    pub fn next_sequence_number(&mut self) -> Result<u64> {
+        if let Some(source) = &self.sequence_source {
+            let on_chain = source.sequence_number(self.address)?;
+            self.sequence_number = self.sequence_number.max(on_chain);
+        }
+        Ok(self.sequence_number)
+    }
+
+    /// Returns the authentication key the chain expects from this account.
+    pub fn authentication_key(&self) -> AuthenticationKey {
+        self.rotated_auth_key
+            .unwrap_or_else(|| AuthenticationKey::ed25519(&self.public_key))
+    }
+
+    /// Switches to `new_private_key` once a key rotation (see `rotate_key_txn`) is executed. The
+    /// address of the account does not change.
+    pub fn rotate_key(&mut self, new_private_key: Ed25519PrivateKey) {
+        self.public_key = new_private_key.public_key();
+        self.private_key = new_private_key;
+        self.rotated_auth_key = Some(AuthenticationKey::ed25519(&self.public_key));
+    }
+
     /// Signs the provided raw transaction, incrementing the local sequence number.
     pub fn sign(&mut self, raw_txn: RawTransaction) -> Result<SignedTransaction> {
         let signed = raw_txn.sign(&self.private_key, self.public_key.clone())?;
@@ -0,0 +1,204 @@
+use anyhow::{bail, Context, Result};
+use aptos_executor::{
+    scenarios::three_trader::wait_for_execution_logs, transaction_builder::apt_transfer,
+    LocalAccount,
+};
+use aptos_types::{chain_id::ChainId, transaction::SignedTransaction};
+use config::{Comm, Import, WorkerId};
+use hyper::{body, header::CONTENT_TYPE, Body, Client, Method, Request, StatusCode};
+use serde::Deserialize;
+use std::{
+    env,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{task, time::sleep};
+
+const TRANSFER_AMOUNT: u64 = 250;
+const WORKER_ID: WorkerId = 0;
+const DEFAULT_LOCAL_DIR: &str = "scripts/.local";
+/// The transfer submitted by this test plus the three submitted by `integration_test`,
+/// which runs first against the same nodes.
+const EXPECTED_EXECUTED_TXS: usize = 4;
+
+#[derive(Debug, Deserialize)]
+struct SubmissionResponse {
+    hash: Option<String>,
+    status: String,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExecutionReceipt {
+    status: String,
+    kept: bool,
+    gas_used: u64,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let local_dir = resolve_local_dir();
+    let committee_path = local_dir.join("config/committee.json");
+    let log_path = resolve_log_path(&local_dir);
+
+    println!("Loading committee from {}", committee_path.display());
+    let comm = load_committee(&committee_path)?;
+    let api_addresses = api_addresses(&comm);
+    if api_addresses.is_empty() {
+        bail!("no worker API addresses found in committee file");
+    }
+
+    println!("Discovered {} worker API endpoints", api_addresses.len());
+
+    // Use an account that the other integration tests do not touch so that sequence numbers
+    // do not collide.
+    let txn = build_transfer(ChainId::new(comm.execution.chain_id))?;
+    println!(
+        "Submitting D sends {} tokens to A over HTTP:",
+        TRANSFER_AMOUNT
+    );
+    for addr in &api_addresses {
+        let response = submit_transaction(*addr, &txn)
+            .await
+            .with_context(|| format!("failed to submit txn to {}", addr))?;
+        if response.status != "accepted" {
+            bail!(
+                "{} rejected the transaction: {}",
+                addr,
+                response.reason.unwrap_or_default()
+            );
+        }
+        println!(
+            "  ✓ {} accepted transaction {}",
+            addr,
+            response.hash.unwrap_or_default()
+        );
+    }
+
+    println!(
+        "Waiting for committer log '{}' to report executed transactions...",
+        log_path.display()
+    );
+    task::spawn_blocking(move || {
+        wait_for_execution_logs(&log_path, EXPECTED_EXECUTED_TXS, Duration::from_secs(40))
+    })
+    .await
+    .context("log watcher task failed")??;
+
+    let hash = txn.committed_hash().to_hex_literal();
+    let receipt = fetch_receipt(api_addresses[0], &hash)
+        .await
+        .with_context(|| format!("failed to fetch the result of {}", hash))?;
+    if !receipt.kept {
+        bail!("transaction {} was not kept: {}", hash, receipt.status);
+    }
+    println!(
+        "  ✓ fetched result of {}: status={}, gas_used={}",
+        hash, receipt.status, receipt.gas_used
+    );
+
+    println!("Transaction submitted over HTTP executed via consensus.");
+    Ok(())
+}
+
+fn resolve_local_dir() -> PathBuf {
+    if let Ok(path) = env::var("HYDRANGEA_LOCAL_DIR") {
+        return PathBuf::from(path);
+    }
+    PathBuf::from(DEFAULT_LOCAL_DIR)
+}
+
+fn resolve_log_path(local_dir: &Path) -> PathBuf {
+    if let Ok(path) = env::var("HYDRANGEA_NODE_LOG") {
+        return PathBuf::from(path);
+    }
+    local_dir.join("logs/node-0.log")
+}
+
+fn load_committee(path: &Path) -> Result<Comm> {
+    Comm::import(path.to_str().unwrap())
+        .with_context(|| format!("failed to import committee from {}", path.display()))
+}
+
+fn api_addresses(comm: &Comm) -> Vec<SocketAddr> {
+    let mut addresses = Vec::new();
+    for authority in comm.authorities.values() {
+        if let Some(api) = authority.workers.get(&WORKER_ID).and_then(|w| w.api) {
+            addresses.push(api);
+        }
+    }
+    addresses
+}
+
+/// Builds the transfer for the chain id set by the execution settings of the committee, which the
+/// nodes check transactions against.
+fn build_transfer(chain_id: ChainId) -> Result<SignedTransaction> {
+    let account_a = LocalAccount::generate(1).context("failed to generate account A")?;
+    let mut account_d = LocalAccount::generate(4).context("failed to generate account D")?;
+
+    apt_transfer(&mut account_d, account_a.address, TRANSFER_AMOUNT, chain_id)
+        .context("failed to build D -> A transfer")
+}
+
+async fn fetch_receipt(addr: SocketAddr, hash: &str) -> Result<ExecutionReceipt> {
+    let uri = format!("http://{}/transactions/{}", addr, hash);
+    let response = Client::new()
+        .get(uri.parse().context("invalid receipt URI")?)
+        .await
+        .context("failed to query worker API")?;
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body())
+        .await
+        .context("failed to read response body")?;
+    if status != StatusCode::OK {
+        bail!(
+            "unexpected HTTP status {}: {}",
+            status,
+            String::from_utf8_lossy(&bytes)
+        );
+    }
+    serde_json::from_slice(&bytes).context("malformed execution receipt")
+}
+
+async fn submit_transaction(
+    addr: SocketAddr,
+    txn: &SignedTransaction,
+) -> Result<SubmissionResponse> {
+    let payload = bcs::to_bytes(txn)?;
+    let client = Client::new();
+    let uri = format!("http://{}/transactions", addr);
+    let mut attempt: u32 = 0;
+    loop {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&uri)
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(Body::from(payload.clone()))
+            .context("failed to build request")?;
+        match client.request(request).await {
+            Ok(response) => {
+                let status = response.status();
+                let bytes = body::to_bytes(response.into_body())
+                    .await
+                    .context("failed to read response body")?;
+                let reply: SubmissionResponse = serde_json::from_slice(&bytes)
+                    .with_context(|| format!("malformed response (HTTP {})", status))?;
+                if status != StatusCode::OK && reply.status == "accepted" {
+                    bail!(
+                        "unexpected HTTP status {} for an accepted transaction",
+                        status
+                    );
+                }
+                return Ok(reply);
+            }
+            Err(error) => {
+                if attempt > 20 {
+                    return Err(error).context("exhausted retries connecting to worker API");
+                }
+                attempt += 1;
+                sleep(Duration::from_millis(250)).await;
+            }
+        }
+    }
+}
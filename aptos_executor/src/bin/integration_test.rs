@@ -83,13 +83,7 @@ fn resolve_log_path(local_dir: &Path) -> PathBuf {
 fn load_worker_addresses(path: &Path) -> Result<Vec<SocketAddr>> {
     let comm = Comm::import(path.to_str().unwrap())
         .with_context(|| format!("failed to import committee from {}", path.display()))?;
-    let mut addresses = Vec::new();
-    for authority in comm.authorities.values() {
-        if let Some(worker) = authority.workers.get(&WORKER_ID) {
-            addresses.push(worker.transactions);
-        }
-    }
-    Ok(addresses)
+    Ok(comm.transactions_addresses(&WORKER_ID))
 }
 
 fn build_transfer_sequence() -> Result<Vec<SignedTransaction>> {
@@ -1,39 +1,63 @@
 use anyhow::{bail, Context, Result};
 use aptos_executor::scenarios::three_trader::{
-    build_three_trader_transactions, resolve_package_dir, wait_for_execution_logs,
+    build_three_trader_transactions, resolve_package_dir, submit_step_to_quorum,
+    wait_for_execution_logs, worker_quorum, ScenarioAccounts, ScenarioOutcome, StepAck,
     EXPECTED_SCENARIO_TXNS,
 };
-use aptos_types::{chain_id::ChainId, transaction::SignedTransaction};
-use bytes::Bytes;
+use aptos_types::{
+    chain_id::ChainId, state_store::state_key::StateKey, transaction::SignedTransaction,
+};
 use config::{Comm, Import, WorkerId};
-use futures::SinkExt;
+use hyper::{body, header::CONTENT_TYPE, Body, Client, Method, Request};
+use serde::Deserialize;
 use std::{
     env,
     net::SocketAddr,
     path::{Path, PathBuf},
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::{net::TcpStream, task, time::sleep};
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio::{task, time::sleep};
 
 const WORKER_ID: WorkerId = 0;
 const DEFAULT_LOCAL_DIR: &str = "scripts/.local";
+/// How many times a step is submitted to the workers that did not accept it yet.
+const STEP_ATTEMPTS: u32 = 5;
+const STEP_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// How long every node has to reach the expected outcome once the log reports the scenario executed.
+const OUTCOME_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+struct SubmissionResponse {
+    status: String,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StateResponse {
+    value: String,
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     let local_dir = resolve_local_dir();
     let committee_path = local_dir.join("config/committee.json");
     let log_path = resolve_log_path(&local_dir);
     let package_dir = resolve_package_dir()?;
-    let chain_id = ChainId::test();
 
     println!("Loading committee from {}", committee_path.display());
-    let worker_addresses = load_worker_addresses(&committee_path)?;
-    if worker_addresses.is_empty() {
-        bail!("no worker transaction addresses found in committee file");
+    let comm = load_committee(&committee_path)?;
+    let api_addresses = api_addresses(&comm)?;
+    if api_addresses.is_empty() {
+        bail!("no worker API addresses found in committee file");
     }
+    // Transactions are signed for the chain id set by the execution settings of the committee,
+    // which the nodes check them against.
+    let chain_id = ChainId::new(comm.execution.chain_id);
+    let quorum = worker_quorum(api_addresses.len());
     println!(
-        "Discovered {} worker transaction endpoints",
-        worker_addresses.len()
+        "Discovered {} worker API endpoints, each step must be accepted by {} of them",
+        api_addresses.len(),
+        quorum
     );
 
     println!(
@@ -44,19 +68,27 @@ async fn main() -> Result<()> {
 
     println!("Submitting three-trader demo sequence to consensus:");
     for (index, scenario_txn) in scenario.iter().enumerate() {
-        for addr in &worker_addresses {
-            submit_transaction(*addr, &scenario_txn.txn)
-                .await
-                .with_context(|| {
-                    format!(
-                        "failed to submit step {} ({}) to {}",
-                        index + 1,
-                        scenario_txn.label,
-                        addr
-                    )
-                })?;
-        }
-        println!("  ✓ Step {}: {}", index + 1, scenario_txn.label);
+        let accepted = submit_step_to_quorum(
+            &api_addresses,
+            quorum,
+            STEP_ATTEMPTS,
+            STEP_RETRY_DELAY,
+            |addr| submit_transaction(addr, &scenario_txn.txn),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "step {} ({}) did not reach a quorum of workers",
+                index + 1,
+                scenario_txn.label
+            )
+        })?;
+        println!(
+            "  ✓ Step {}: {} (accepted by {} workers)",
+            index + 1,
+            scenario_txn.label,
+            accepted.len()
+        );
     }
 
     println!(
@@ -68,11 +100,68 @@ async fn main() -> Result<()> {
     })
     .await
     .context("log watcher task failed")??;
-
     println!("All three-trader demo transactions executed via consensus.");
+
+    println!("Reading the order book and the trader balances from every node:");
+    let keys = ScenarioOutcome::state_keys(&ScenarioAccounts::generate()?)?;
+    for addr in &api_addresses {
+        wait_for_outcome(*addr, &keys).await?;
+        println!("  ✓ {} reached the expected outcome", addr);
+    }
     Ok(())
 }
 
+/// Reads the outcome of the scenario from the node serving the API at `addr` until it is the expected
+/// one. Nodes execute at their own pace, so a node may not have executed the last steps yet.
+async fn wait_for_outcome(addr: SocketAddr, keys: &[StateKey]) -> Result<()> {
+    let deadline = Instant::now() + OUTCOME_TIMEOUT;
+    loop {
+        let result = read_outcome(addr, keys)
+            .await
+            .and_then(|outcome| outcome.check());
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if Instant::now() >= deadline => {
+                return Err(e).with_context(|| format!("node {} failed the scenario", addr))
+            }
+            Err(_) => sleep(Duration::from_millis(500)).await,
+        }
+    }
+}
+
+async fn read_outcome(addr: SocketAddr, keys: &[StateKey]) -> Result<ScenarioOutcome> {
+    let mut values = Vec::new();
+    for key in keys {
+        values.push(read_state_value(addr, key).await?);
+    }
+    ScenarioOutcome::decode(&values)
+}
+
+/// Reads the value stored at `key` through the state endpoint of a worker API.
+async fn read_state_value(addr: SocketAddr, key: &StateKey) -> Result<Vec<u8>> {
+    let uri = format!("http://{}/state/{}", addr, hex::encode(bcs::to_bytes(key)?));
+    let response = Client::new()
+        .get(uri.parse()?)
+        .await
+        .with_context(|| format!("failed to reach the API of {}", addr))?;
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body())
+        .await
+        .context("failed to read response body")?;
+    if !status.is_success() {
+        let reply: SubmissionResponse = serde_json::from_slice(&bytes)
+            .with_context(|| format!("malformed response (HTTP {})", status))?;
+        bail!(
+            "failed to read {:?} (HTTP {}): {}",
+            key,
+            status,
+            reply.reason.unwrap_or_default()
+        );
+    }
+    let reply: StateResponse = serde_json::from_slice(&bytes).context("malformed state value")?;
+    hex::decode(reply.value).context("malformed state value")
+}
+
 fn resolve_local_dir() -> PathBuf {
     if let Ok(path) = env::var("HYDRANGEA_LOCAL_DIR") {
         return PathBuf::from(path);
@@ -87,36 +176,57 @@ fn resolve_log_path(local_dir: &Path) -> PathBuf {
     local_dir.join("logs/node-0.log")
 }
 
-fn load_worker_addresses(path: &Path) -> Result<Vec<SocketAddr>> {
-    let comm = Comm::import(path.to_str().unwrap())
-        .with_context(|| format!("failed to import committee from {}", path.display()))?;
+fn load_committee(path: &Path) -> Result<Comm> {
+    Comm::import(path.to_str().unwrap())
+        .with_context(|| format!("failed to import committee from {}", path.display()))
+}
+
+fn api_addresses(comm: &Comm) -> Result<Vec<SocketAddr>> {
     let mut addresses = Vec::new();
     for authority in comm.authorities.values() {
-        if let Some(worker) = authority.workers.get(&WORKER_ID) {
-            addresses.push(worker.transactions);
+        match authority.workers.get(&WORKER_ID) {
+            Some(worker) => match worker.api {
+                Some(api) => addresses.push(api),
+                None => bail!(
+                    "worker {} of every authority needs a transaction API to acknowledge the steps",
+                    WORKER_ID
+                ),
+            },
+            None => continue,
         }
     }
     Ok(addresses)
 }
 
-async fn submit_transaction(addr: SocketAddr, txn: &SignedTransaction) -> Result<()> {
-    let payload = Bytes::from(bcs::to_bytes(txn)?);
+/// Submits a transaction over the transaction API of a worker and returns whether it was accepted.
+async fn submit_transaction(addr: SocketAddr, txn: &SignedTransaction) -> Result<StepAck> {
+    let payload = bcs::to_bytes(txn)?;
+    let client = Client::new();
+    let uri = format!("http://{}/transactions", addr);
     let mut attempt: u32 = 0;
     loop {
-        match TcpStream::connect(addr).await {
-            Ok(stream) => {
-                let mut codec = LengthDelimitedCodec::new();
-                codec.set_max_frame_length(5 * 1024 * 1024);
-                let mut framed = Framed::new(stream, codec);
-                framed
-                    .send(payload.clone())
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&uri)
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(Body::from(payload.clone()))
+            .context("failed to build request")?;
+        match client.request(request).await {
+            Ok(response) => {
+                let status = response.status();
+                let bytes = body::to_bytes(response.into_body())
                     .await
-                    .context("failed to send transaction bytes")?;
-                return Ok(());
+                    .context("failed to read response body")?;
+                let reply: SubmissionResponse = serde_json::from_slice(&bytes)
+                    .with_context(|| format!("malformed response (HTTP {})", status))?;
+                return Ok(match reply.status.as_str() {
+                    "accepted" => StepAck::Accepted,
+                    _ => StepAck::Rejected(reply.reason.unwrap_or_default()),
+                });
             }
             Err(error) => {
                 if attempt > 20 {
-                    return Err(error).context("exhausted retries connecting to worker");
+                    return Err(error).context("exhausted retries connecting to worker API");
                 }
                 attempt += 1;
                 sleep(Duration::from_millis(250)).await;
@@ -1,12 +1,16 @@
 use anyhow::{bail, Context, Result};
 use aptos_executor::{
+    order_book::{base_coin, quote_coin},
     scenarios::three_trader::{
-        build_three_trader_transactions, resolve_package_dir, EXPECTED_SCENARIO_TXNS,
-        TRADER_A_SEED, TRADER_B_SEED, TRADER_C_SEED, TRADER_D_SEED,
+        build_three_trader_transactions, check_crossing_trade, market_events, resolve_package_dir,
+        ScenarioAccounts, ScenarioOutcome, CROSSING_FILL_PRICE, CROSSING_FILL_SIZE,
+        EXPECTED_SCENARIO_TXNS, TRADER_A_SEED, TRADER_B_SEED, TRADER_C_BUY_STEP, TRADER_C_SEED,
+        TRADER_D_SEED,
     },
     AptosVmExecutor, LocalAccount,
 };
 use aptos_types::vm_status::VMStatus;
+use move_core_types::account_address::AccountAddress;
 
 const INITIAL_BOOTSTRAP_BALANCE: u64 = 1_000_000_000_000;
 
@@ -30,8 +34,21 @@ fn main() -> Result<()> {
         );
     }
 
+    // The scenario names the accounts generated from the C and D seeds traders B and C.
+    let module_owner = LocalAccount::generate(TRADER_A_SEED)?.address;
+    let trader_b = LocalAccount::generate(TRADER_C_SEED)?.address;
+    let trader_c = LocalAccount::generate(TRADER_D_SEED)?.address;
+    let mut before_crossing = None;
+
     println!("Executing three-trader demo via Aptos VM...");
     for (index, scenario_txn) in scenario.into_iter().enumerate() {
+        if index == TRADER_C_BUY_STEP {
+            before_crossing = Some(market_balances(
+                &executor,
+                module_owner,
+                &[trader_b, trader_c],
+            )?);
+        }
         let label = scenario_txn.label;
         let txns = vec![scenario_txn.txn];
         let mut results = executor.execute_block(&txns);
@@ -47,6 +64,18 @@ fn main() -> Result<()> {
                     label,
                     result.gas_used()
                 );
+                // A resting order emits a single event; a matching one also emits the fills.
+                let events = market_events(&result);
+                if label.contains(" places ") && !events.is_empty() {
+                    println!(
+                        "      {} order event(s): {}",
+                        events.len(),
+                        match events.len() {
+                            1 => "rested on the book",
+                            _ => "filled",
+                        }
+                    );
+                }
             }
             status => {
                 bail!(
@@ -57,18 +86,57 @@ fn main() -> Result<()> {
                 );
             }
         }
+
+        if index == TRADER_C_BUY_STEP {
+            let before = before_crossing
+                .take()
+                .context("balances were not read before the crossing bid")?;
+            let after = market_balances(&executor, module_owner, &[trader_b, trader_c])?;
+            check_crossing_trade(&before, &after)?;
+            println!(
+                "  ✓ Trade settled: {} base moved from trader B to trader C for {} quote",
+                CROSSING_FILL_SIZE,
+                CROSSING_FILL_SIZE * CROSSING_FILL_PRICE
+            );
+        }
     }
 
+    ScenarioOutcome::from_executor(&executor, &ScenarioAccounts::generate()?)?.check()?;
     println!("All scenario transactions executed successfully via Aptos VM.");
+    println!("  ✓ The order book and the balances of the traders match the expected outcome");
     Ok(())
 }
 
+/// The (base, quote) balances of each of the provided accounts.
+fn market_balances(
+    executor: &AptosVmExecutor,
+    module_owner: AccountAddress,
+    accounts: &[AccountAddress],
+) -> Result<Vec<(u64, u64)>> {
+    let base = base_coin(module_owner)?;
+    let quote = quote_coin(module_owner)?;
+    accounts
+        .iter()
+        .map(|address| {
+            let base_balance = executor
+                .coin_balance(*address, &base)
+                .with_context(|| format!("failed to read the base balance of {}", address))?;
+            let quote_balance = executor
+                .coin_balance(*address, &quote)
+                .with_context(|| format!("failed to read the quote balance of {}", address))?;
+            Ok((base_balance, quote_balance))
+        })
+        .collect()
+}
+
 fn bootstrap_deterministic_accounts(executor: &AptosVmExecutor) -> Result<()> {
     let seeds = [TRADER_A_SEED, TRADER_B_SEED, TRADER_C_SEED, TRADER_D_SEED];
     for seed in seeds {
         let account = LocalAccount::generate(seed)
             .with_context(|| format!("failed to generate account for seed {}", seed))?;
-        executor.bootstrap_account(&account, INITIAL_BOOTSTRAP_BALANCE);
+        executor
+            .bootstrap_account(&account, INITIAL_BOOTSTRAP_BALANCE)
+            .with_context(|| format!("failed to bootstrap account for seed {}", seed))?;
     }
     Ok(())
 }
@@ -1,7 +1,9 @@
 //! State management utilities for the Aptos VM integration.
 
 use crate::accounts::LocalAccount;
-use anyhow::{anyhow, Result};
+use crate::executor::run_user_transaction;
+use crate::transaction_builder::aptos_account_transfer;
+use anyhow::{anyhow, bail, Context as _, Result};
 use aptos_crypto::HashValue;
 use aptos_storage_interface::{
     state_store::state_view::db_state_view::{DbStateView, LatestDbStateCheckpointView},
@@ -10,53 +12,356 @@ use aptos_storage_interface::{
 use aptos_types::{
     account_config::{
         primary_apt_store, AccountResource, AggregatorResource, CoinStoreResource,
-        ConcurrentSupplyResource, FungibleStoreResource, MigrationFlag, ObjectCoreResource,
-        ObjectGroupResource,
+        ConcurrentFungibleBalanceResource, ConcurrentSupplyResource, FungibleStoreResource,
+        MigrationFlag, ObjectCoreResource, ObjectGroupResource,
     },
+    chain_id::ChainId,
     event::{EventHandle, EventKey},
-    state_store::{state_key::StateKey, state_value::StateValue},
-    transaction::Version,
+    on_chain_config::{ConfigurationResource, ValidatorSet},
+    state_store::{
+        errors::StateViewError,
+        state_key::{inner::StateKeyInner, StateKey},
+        state_value::StateValue,
+        StateViewResult,
+    },
+    transaction::{ExecutionStatus, TransactionStatus, Version},
     utility_coin::AptosCoinType,
-    write_set::{TransactionWrite, WriteOp},
+    write_set::{TransactionWrite, WriteOp, WriteSet},
 };
 use aptos_vm_genesis::{generate_genesis_change_set_for_mainnet, GenesisOptions};
-use move_core_types::{account_address::AccountAddress, move_resource::MoveStructType};
+use config::GenesisFramework;
+use move_core_types::{
+    account_address::AccountAddress,
+    identifier::Identifier,
+    language_storage::{StructTag, TypeTag},
+    move_resource::MoveStructType,
+};
+use once_cell::sync::OnceCell;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
     },
 };
 
+#[cfg(test)]
+#[path = "tests/database_tests.rs"]
+pub mod database_tests;
+
+/// The APT added by default on top of the balance of every account published by
+/// `publish_account_resources`, so that its first transactions can pay for gas.
+pub const DEFAULT_GAS_BUFFER: u64 = 1_000_000_000;
+
+/// The version of the format of the state snapshots written by `AptosDatabase::export_snapshot`.
+pub const STATE_SNAPSHOT_FORMAT: u32 = 2;
+
+/// The write sets of the mainnet genesis, by framework. Generating one dominates the construction of a
+/// database, and it is deterministic, so it is generated once per process and copied into every new
+/// database. Each framework has its own cell, so generating one does not block readers of the others.
+static GENESIS_WRITE_SETS: [OnceCell<Genesis>; 3] =
+    [OnceCell::new(), OnceCell::new(), OnceCell::new()];
+
+/// A genesis write set and its hash.
+struct Genesis {
+    write_set: WriteSet,
+    hash: HashValue,
+}
+
+/// Returns the genesis installing `framework`, generating it on first use.
+fn genesis(framework: GenesisFramework) -> &'static Genesis {
+    let (index, options) = match framework {
+        GenesisFramework::Head => (0, GenesisOptions::Head),
+        GenesisFramework::Testnet => (1, GenesisOptions::Testnet),
+        GenesisFramework::Mainnet => (2, GenesisOptions::Mainnet),
+    };
+    GENESIS_WRITE_SETS[index].get_or_init(|| {
+        let write_set = generate_genesis_change_set_for_mainnet(options)
+            .write_set()
+            .clone();
+        let hash = genesis_hash(&write_set);
+        Genesis { write_set, hash }
+    })
+}
+
+/// Returns the hash of a genesis write set, which covers the code of the framework it installs.
+pub fn genesis_hash(write_set: &WriteSet) -> HashValue {
+    HashValue::sha3_256_of(&bcs::to_bytes(write_set).expect("write sets should serialize"))
+}
+
+/// The framework installed by the genesis of a database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameworkVersion {
+    pub release: GenesisFramework,
+    /// The hash of the genesis write set (see `genesis_hash`), which changes with the code of the
+    /// framework, e.g. when `Head` is updated along with the Aptos dependency.
+    pub hash: HashValue,
+}
+
+/// Returns the key of the `0x1::account::Account` resource of `address`.
+pub fn account_resource_key(address: AccountAddress) -> Result<StateKey> {
+    StateKey::resource(&address, &AccountResource::struct_tag())
+        .map_err(|_| anyhow!("failed to derive account resource key"))
+}
+
+/// Returns the key of the `0x1::coin::CoinStore` of `address` for the given coin type.
+pub fn coin_store_key(address: AccountAddress, coin_type: &StructTag) -> Result<StateKey> {
+    let coin_store_tag = StructTag {
+        address: AccountAddress::ONE,
+        module: Identifier::new("coin")?,
+        name: Identifier::new("CoinStore")?,
+        type_args: vec![TypeTag::Struct(Box::new(coin_type.clone()))],
+    };
+    StateKey::resource(&address, &coin_store_tag)
+        .map_err(|_| anyhow!("failed to derive coin store key"))
+}
+
+/// Decodes the balance of a `0x1::coin::CoinStore` of any coin type.
+pub fn decode_coin_balance(bytes: &[u8]) -> Result<u64> {
+    // The coin type is a phantom parameter: every coin store has the same layout.
+    let coin_store: CoinStoreResource<AptosCoinType> = bcs::from_bytes(bytes)?;
+    Ok(coin_store.coin())
+}
+
+/// The layout of `0x1::fungible_asset::Supply`.
+#[derive(Serialize, Deserialize)]
+struct Supply {
+    current: u128,
+    maximum: Option<u128>,
+}
+
+/// The layout of `0x1::fungible_asset::ConcurrentSupply`, whose aggregator holds the supply.
+#[derive(Deserialize)]
+struct ConcurrentSupply {
+    current: Aggregator,
+}
+
+#[derive(Deserialize)]
+struct Aggregator {
+    value: u128,
+    #[allow(dead_code)]
+    max_value: u128,
+}
+
+/// Returns the tag of `0x1::fungible_asset::Supply`.
+fn supply_tag() -> Result<StructTag> {
+    Ok(StructTag {
+        address: AccountAddress::ONE,
+        module: Identifier::new("fungible_asset")?,
+        name: Identifier::new("Supply")?,
+        type_args: vec![],
+    })
+}
+
+/// Returns the key of the object group of the APT metadata object, which holds the APT supply.
+fn apt_supply_group_key() -> StateKey {
+    StateKey::resource_group(&AccountAddress::TEN, &ObjectGroupResource::struct_tag())
+}
+
+/// Returns the address of the primary store of `owner` for the fungible asset whose metadata object
+/// lives at `metadata`, as derived by `0x1::primary_fungible_store` (an object derived from the
+/// owner and the metadata address).
+pub fn primary_fungible_store(owner: AccountAddress, metadata: AccountAddress) -> AccountAddress {
+    const OBJECT_DERIVED_SCHEME: u8 = 0xFC;
+    let mut bytes = owner.to_vec();
+    bytes.extend(metadata.to_vec());
+    bytes.push(OBJECT_DERIVED_SCHEME);
+    AccountAddress::new(*HashValue::sha3_256_of(&bytes))
+}
+
+/// A state to fork, e.g. the state of a remote node at a given version. It must not change while
+/// it is forked: the values read from it are cached and may be read again at any time.
+pub trait StateSource: Send + Sync {
+    /// Fetches the value of a state key, if it has one.
+    fn fetch(&self, key: &StateKey) -> StorageResult<Option<StateValue>>;
+}
+
+/// The values fetched from a forked state, evicting the least recently read ones beyond a maximum
+/// number of entries. Evicted values are fetched again when read.
+struct FetchedStates {
+    /// The fetched values (including the absence of a value) and when they were last read.
+    values: HashMap<StateKey, (Option<StateValue>, u64)>,
+    /// The fetched keys by the time they were last read.
+    recency: BTreeMap<u64, StateKey>,
+    /// Incremented at every read.
+    clock: u64,
+    max_entries: usize,
+}
+
+impl FetchedStates {
+    fn new(max_entries: usize) -> Self {
+        Self {
+            values: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            max_entries,
+        }
+    }
+
+    /// Returns the cached value of a key (`None` if it is not cached) and marks it as recently read.
+    fn get(&mut self, key: &StateKey) -> Option<Option<StateValue>> {
+        self.clock += 1;
+        let clock = self.clock;
+        let (value, last_read) = self.values.get_mut(key)?;
+        self.recency.remove(last_read);
+        *last_read = clock;
+        self.recency.insert(clock, key.clone());
+        Some(value.clone())
+    }
+
+    /// Caches a fetched value, evicting the least recently read values if needed.
+    fn insert(&mut self, key: StateKey, value: Option<StateValue>) {
+        self.clock += 1;
+        if let Some((_, last_read)) = self.values.insert(key.clone(), (value, self.clock)) {
+            self.recency.remove(&last_read);
+        }
+        self.recency.insert(self.clock, key);
+        while self.values.len() > self.max_entries {
+            let (_, oldest) = self
+                .recency
+                .pop_first()
+                .expect("recency tracks every value");
+            self.values.remove(&oldest);
+        }
+    }
+}
+
+/// A forked state and the values fetched from it so far.
+struct Fork {
+    source: Arc<dyn StateSource>,
+    fetched: Mutex<FetchedStates>,
+}
+
+impl Fork {
+    fn read(&self, key: &StateKey) -> StorageResult<Option<StateValue>> {
+        if let Some(value) = self.fetched.lock().unwrap().get(key) {
+            return Ok(value);
+        }
+        let value = self.source.fetch(key)?;
+        self.fetched
+            .lock()
+            .unwrap()
+            .insert(key.clone(), value.clone());
+        Ok(value)
+    }
+}
+
 /// Lightweight in-memory implementation of the Aptos `DbReader` trait tailored for tests.
+///
+/// A reader may fork a `StateSource`, in which case the keys it never wrote are read from the source.
+/// Only the values fetched from the source are evicted, never the ones written locally. The helpers
+/// listing or digesting the state (`snapshot_states`, `state_root`, ...) only cover the latter.
 #[derive(Default)]
 pub struct TestDbReader {
     states: RwLock<HashMap<StateKey, StateValue>>,
     version: AtomicU64,
+    /// The forked state, if any.
+    fork: Option<Arc<Fork>>,
+    /// The keys of the forked state deleted locally.
+    deleted: RwLock<HashSet<StateKey>>,
 }
 
 impl TestDbReader {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a reader forking `source`, caching at most `max_cached_entries` of the values read
+    /// from it.
+    pub fn forking(source: Arc<dyn StateSource>, max_cached_entries: usize) -> Self {
         Self {
-            states: RwLock::new(HashMap::new()),
-            version: AtomicU64::new(0),
+            fork: Some(Arc::new(Fork {
+                source,
+                fetched: Mutex::new(FetchedStates::new(max_cached_entries)),
+            })),
+            ..Self::default()
         }
     }
 
     /// Inserts or replaces the value associated with the given state key.
     pub fn set_state_value(&self, key: StateKey, value: StateValue) {
+        if self.fork.is_some() {
+            self.deleted.write().unwrap().remove(&key);
+        }
         self.states.write().unwrap().insert(key, value);
     }
 
+    /// Inserts or replaces the values of several state keys, taking the lock only once.
+    pub fn set_state_values(&self, values: Vec<(StateKey, StateValue)>) {
+        if self.fork.is_some() {
+            let mut deleted = self.deleted.write().unwrap();
+            for (key, _) in &values {
+                deleted.remove(key);
+            }
+        }
+        self.states.write().unwrap().extend(values);
+    }
+
     /// Removes the value associated with the given state key, if any.
     pub fn remove_state_value(&self, key: &StateKey) {
+        if self.fork.is_some() {
+            self.deleted.write().unwrap().insert(key.clone());
+        }
         self.states.write().unwrap().remove(key);
     }
 
-    /// Reads the current value for a state key, if one exists.
-    pub fn get_state_value(&self, key: &StateKey) -> Option<StateValue> {
-        self.states.read().unwrap().get(key).cloned()
+    /// Reads the current value for a state key, if one exists. Fails if the value must be read from
+    /// the forked state and that fails.
+    pub fn get_state_value(&self, key: &StateKey) -> StateViewResult<Option<StateValue>> {
+        self.read(key)
+            .map_err(|e| StateViewError::Other(format!("failed to read {:?}: {}", key, e)))
+    }
+
+    /// Reads the value of a state key, from the forked state if we did not write it.
+    fn read(&self, key: &StateKey) -> StorageResult<Option<StateValue>> {
+        if let Some(value) = self.states.read().unwrap().get(key) {
+            return Ok(Some(value.clone()));
+        }
+        match &self.fork {
+            Some(fork) if !self.deleted.read().unwrap().contains(key) => fork.read(key),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns a copy of every state value currently stored.
+    pub fn snapshot_states(&self) -> HashMap<StateKey, StateValue> {
+        self.states.read().unwrap().clone()
+    }
+
+    /// Replaces the whole state with the provided one and bumps the version so that state views
+    /// taken from now on observe it.
+    pub fn restore_states(&self, states: HashMap<StateKey, StateValue>) {
+        *self.states.write().unwrap() = states;
+        self.deleted.write().unwrap().clear();
+        self.bump_version();
+    }
+
+    /// Returns a digest of the whole state, so that the states of different nodes can be compared.
+    /// Unlike the Aptos state root, it is not the root of a Merkle tree.
+    pub fn state_root(&self) -> HashValue {
+        let mut entries: Vec<(Vec<u8>, Vec<u8>)> = self
+            .states
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| {
+                (
+                    bcs::to_bytes(key).expect("failed to serialize state key"),
+                    bcs::to_bytes(value).expect("failed to serialize state value"),
+                )
+            })
+            .collect();
+        entries.sort();
+        let bytes = bcs::to_bytes(&entries).expect("failed to serialize state entries");
+        HashValue::sha3_256_of(&bytes)
+    }
+
+    /// Returns a copy of every state key currently stored.
+    pub fn state_keys(&self) -> Vec<StateKey> {
+        self.states.read().unwrap().keys().cloned().collect()
     }
 
     /// Returns the latest state version recorded by the reader.
@@ -64,6 +369,11 @@ impl TestDbReader {
         self.version.load(Ordering::SeqCst)
     }
 
+    /// Moves the reader to the given version, e.g. the version of an imported state.
+    pub fn set_version(&self, version: Version) {
+        self.version.store(version, Ordering::SeqCst);
+    }
+
     /// Applies a single write operation directly into the in-memory store.
     fn apply_write_op(&self, key: StateKey, write: &WriteOp) {
         if write.is_delete() {
@@ -84,6 +394,17 @@ impl TestDbReader {
     fn bump_version(&self) {
         self.version.fetch_add(1, Ordering::SeqCst);
     }
+
+    /// Returns an independent copy of the reader at its current version. The values fetched from the
+    /// forked state, which never change, are shared.
+    fn copy(&self) -> Self {
+        Self {
+            states: RwLock::new(self.states.read().unwrap().clone()),
+            version: AtomicU64::new(self.version.load(Ordering::SeqCst)),
+            fork: self.fork.clone(),
+            deleted: RwLock::new(self.deleted.read().unwrap().clone()),
+        }
+    }
 }
 
 impl DbReader for TestDbReader {
@@ -105,7 +426,7 @@ impl DbReader for TestDbReader {
         state_key: &StateKey,
         _version: Version,
     ) -> StorageResult<Option<StateValue>> {
-        Ok(self.states.read().unwrap().get(state_key).cloned())
+        self.read(state_key)
     }
 
     fn get_state_value_with_version_by_version(
@@ -120,14 +441,9 @@ impl DbReader for TestDbReader {
 }
 
 impl LatestDbStateCheckpointView for TestDbReader {
-    fn latest_state_checkpoint_view(
-        &self,
-    ) -> aptos_types::state_store::StateViewResult<DbStateView> {
-        let version = self.version.load(Ordering::SeqCst);
-        let snapshot = Arc::new(TestDbReader {
-            states: RwLock::new(self.states.read().unwrap().clone()),
-            version: AtomicU64::new(version),
-        });
+    fn latest_state_checkpoint_view(&self) -> StateViewResult<DbStateView> {
+        let snapshot = Arc::new(self.copy());
+        let version = snapshot.latest_version();
 
         use aptos_storage_interface::state_store::state_view::db_state_view::DbStateViewAtVersion;
         let dyn_reader: Arc<dyn DbReader> = snapshot;
@@ -135,17 +451,206 @@ impl LatestDbStateCheckpointView for TestDbReader {
     }
 }
 
+/// The whole state of a database, written to a file so that other databases can boot from it rather
+/// than generating genesis.
+#[derive(Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// The format of the snapshot, `STATE_SNAPSHOT_FORMAT` when it was written.
+    pub format: u32,
+    /// The chain whose transactions were executed against the state.
+    pub chain_id: ChainId,
+    /// The version of the state when it was exported.
+    pub version: Version,
+    /// The APT supply recorded at the genesis of the exported state.
+    pub supply: GenesisSupply,
+    /// The APT handed out by `publish_account_resources` before the export.
+    pub funded: u128,
+    pub states: Vec<(StateKey, StateValue)>,
+}
+
+impl StateSnapshot {
+    /// Reads a snapshot from a file written by `AptosDatabase::write_snapshot`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("failed to read state snapshot {}", path.display()))?;
+        bcs::from_bytes(&bytes)
+            .with_context(|| format!("malformed state snapshot {}", path.display()))
+    }
+
+    /// Writes the snapshot to a file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let bytes = bcs::to_bytes(self).context("failed to serialize state snapshot")?;
+        fs::write(path, bytes)
+            .with_context(|| format!("failed to write state snapshot {}", path.display()))
+    }
+}
+
+/// The APT supply recorded at genesis, in octas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisSupply {
+    /// The amount of APT in circulation right after genesis.
+    pub initial: u128,
+    /// The cap on the APT supply, if any.
+    pub maximum: Option<u128>,
+}
+
+impl Default for GenesisSupply {
+    fn default() -> Self {
+        Self {
+            initial: 1_000_000_000_000_000_000,
+            maximum: None,
+        }
+    }
+}
+
 /// Convenience wrapper that provides higher-level helpers on top of `TestDbReader`.
 pub struct AptosDatabase {
     reader: Arc<TestDbReader>,
+    supply: GenesisSupply,
+    /// The total amount of APT handed out by `publish_account_resources` so far.
+    funded: Mutex<u128>,
+    /// The APT already handed out in the state restored by `reset_to_genesis`.
+    genesis_funded: u128,
+    /// The state right after genesis, restored by `reset_to_genesis`.
+    genesis: HashMap<StateKey, StateValue>,
+    /// The framework installed at genesis, unknown if the state was imported or forked.
+    framework: Option<FrameworkVersion>,
 }
 
 impl AptosDatabase {
     /// Builds a fresh database populated with the Aptos mainnet genesis change set.
     pub fn new_with_genesis() -> Result<Self> {
+        Self::new_with_genesis_supply(GenesisSupply::default())
+    }
+
+    /// Same as `new_with_genesis` but with a custom APT supply.
+    pub fn new_with_genesis_supply(supply: GenesisSupply) -> Result<Self> {
+        Self::new_with_framework(supply, GenesisFramework::default())
+    }
+
+    /// Same as `new_with_genesis_supply` but installing the given release of the framework.
+    pub fn new_with_framework(supply: GenesisSupply, framework: GenesisFramework) -> Result<Self> {
+        if let Some(maximum) = supply.maximum {
+            if maximum < supply.initial {
+                bail!(
+                    "initial APT supply {} exceeds the maximum supply {}",
+                    supply.initial,
+                    maximum
+                );
+            }
+        }
+
         let reader = Arc::new(TestDbReader::new());
-        Self::apply_genesis(&reader)?;
-        Ok(Self { reader })
+        let installed = genesis(framework);
+        Self::apply_genesis(&reader, &installed.write_set, &supply)?;
+        let genesis = reader.snapshot_states();
+        Ok(Self {
+            reader,
+            supply,
+            funded: Mutex::new(0),
+            genesis_funded: 0,
+            genesis,
+            framework: Some(FrameworkVersion {
+                release: framework,
+                hash: installed.hash,
+            }),
+        })
+    }
+
+    /// Builds a database from a snapshot rather than from genesis, checking that the snapshot has the
+    /// current format and was taken on the expected chain. The imported state, along with its version,
+    /// supply, and the APT handed out before the export, takes the place of the genesis:
+    /// `reset_to_genesis` restores it.
+    pub fn from_snapshot(snapshot: StateSnapshot, chain_id: ChainId) -> Result<Self> {
+        if snapshot.format != STATE_SNAPSHOT_FORMAT {
+            bail!(
+                "unsupported state snapshot format {} (expected {})",
+                snapshot.format,
+                STATE_SNAPSHOT_FORMAT
+            );
+        }
+        if snapshot.chain_id != chain_id {
+            bail!(
+                "state snapshot of chain {} cannot be imported on chain {}",
+                snapshot.chain_id,
+                chain_id
+            );
+        }
+        if snapshot.funded > snapshot.supply.initial {
+            bail!(
+                "state snapshot funded {} octas out of a genesis APT supply of {}",
+                snapshot.funded,
+                snapshot.supply.initial
+            );
+        }
+
+        let reader = Arc::new(TestDbReader::new());
+        let states: HashMap<_, _> = snapshot.states.into_iter().collect();
+        reader.restore_states(states.clone());
+        reader.set_version(snapshot.version);
+        Ok(Self {
+            reader,
+            supply: snapshot.supply,
+            funded: Mutex::new(snapshot.funded),
+            genesis_funded: snapshot.funded,
+            genesis: states,
+            framework: None,
+        })
+    }
+
+    /// Returns a snapshot of the current state, from which other databases can boot.
+    pub fn export_snapshot(&self, chain_id: ChainId) -> StateSnapshot {
+        StateSnapshot {
+            format: STATE_SNAPSHOT_FORMAT,
+            chain_id,
+            version: self.reader.latest_version(),
+            supply: self.supply,
+            funded: *self.funded.lock().unwrap(),
+            states: self.reader.snapshot_states().into_iter().collect(),
+        }
+    }
+
+    /// Builds a database forking `source` rather than starting from genesis, caching at most
+    /// `max_cached_entries` of the values read from it.
+    pub fn fork(source: Arc<dyn StateSource>, max_cached_entries: usize) -> Self {
+        Self {
+            reader: Arc::new(TestDbReader::forking(source, max_cached_entries)),
+            supply: GenesisSupply::default(),
+            funded: Mutex::new(0),
+            genesis_funded: 0,
+            genesis: HashMap::new(),
+            framework: None,
+        }
+    }
+
+    /// Returns a throwaway copy of the database: the writes applied to either one are not seen by the
+    /// other.
+    pub fn copy(&self) -> Self {
+        Self {
+            reader: Arc::new(self.reader.copy()),
+            supply: self.supply,
+            funded: Mutex::new(*self.funded.lock().unwrap()),
+            genesis_funded: self.genesis_funded,
+            genesis: self.genesis.clone(),
+            framework: self.framework,
+        }
+    }
+
+    /// Restores the state recorded right after genesis, dropping every account published and every
+    /// output applied since. Much cheaper than building a new database.
+    pub fn reset_to_genesis(&self) {
+        self.reader.restore_states(self.genesis.clone());
+        *self.funded.lock().unwrap() = self.genesis_funded;
+    }
+
+    /// Returns the framework installed at genesis, unless the state was imported or forked.
+    pub fn framework(&self) -> Option<FrameworkVersion> {
+        self.framework
+    }
+
+    /// Returns the APT supply recorded at genesis.
+    pub fn genesis_supply(&self) -> GenesisSupply {
+        self.supply
     }
 
     /// Returns a shared reference to the underlying reader.
@@ -161,164 +666,414 @@ impl AptosDatabase {
     }
 
     /// Fetches a raw state value for the provided key, if present.
-    pub fn get_state_value(&self, key: &StateKey) -> Option<StateValue> {
+    pub fn get_state_value(&self, key: &StateKey) -> StateViewResult<Option<StateValue>> {
         self.reader.get_state_value(key)
     }
 
-    /// Applies the writes produced by a VM output back into the in-memory store.
-    pub fn apply_vm_output(&self, output: &aptos_vm_types::output::VMOutput) {
-        let tx_output = output
-            .clone()
-            .into_transaction_output()
-            .expect("VM output should convert into transaction output");
+    /// Returns a digest of the current state (see `TestDbReader::state_root`).
+    pub fn state_root(&self) -> HashValue {
+        self.reader.state_root()
+    }
 
-        for (state_key, write_op) in tx_output.write_set().write_op_iter() {
-            self.reader.apply_write_op(state_key.clone(), write_op);
+    /// Returns the on-chain time (`0x1::timestamp::CurrentTimeMicroseconds`), against which the VM
+    /// checks the expiration of transactions. Zero if it was never set.
+    pub fn timestamp_usecs(&self) -> Result<u64> {
+        match self.get_state_value(&Self::timestamp_key()?)? {
+            Some(state_value) => Ok(bcs::from_bytes(state_value.bytes())?),
+            None => Ok(0),
         }
+    }
 
-        self.reader.bump_version();
+    /// Overrides the on-chain time.
+    pub fn set_timestamp_usecs(&self, usecs: u64) -> Result<()> {
+        let bytes = bcs::to_bytes(&usecs)?;
+        self.reader
+            .set_state_value(Self::timestamp_key()?, StateValue::new_legacy(bytes.into()));
+        Ok(())
     }
 
-    /// Publishes account resources and an APT balance for the provided local account.
-    pub fn publish_account_resources(&self, account: &LocalAccount, initial_balance: u64) {
-        use aptos_types::transaction::authenticator::AuthenticationKey;
+    /// Returns the current epoch (`0x1::reconfiguration::Configuration`).
+    pub fn epoch(&self) -> Result<u64> {
+        let configuration: ConfigurationResource = self
+            .get_resource(AccountAddress::ONE)?
+            .ok_or_else(|| anyhow!("no epoch is recorded on chain"))?;
+        Ok(configuration.epoch())
+    }
 
-        let auth_key = AuthenticationKey::ed25519(&account.public_key);
-        let account_resource = AccountResource::new(
-            account.sequence_number,
-            auth_key.to_vec(),
-            EventHandle::new(EventKey::new(0, account.address), 0),
-            EventHandle::new(EventKey::new(1, account.address), 0),
-        );
+    /// Returns a validator of the current epoch, which the block prologue accepts as proposer.
+    pub fn validator(&self) -> Result<AccountAddress> {
+        let key = StateKey::on_chain_config::<ValidatorSet>()
+            .map_err(|_| anyhow!("failed to derive the validator set key"))?;
+        let state_value = self
+            .get_state_value(&key)?
+            .ok_or_else(|| anyhow!("no validator set is recorded on chain"))?;
+        let validators: ValidatorSet = bcs::from_bytes(state_value.bytes())?;
+        let validator = validators
+            .payload()
+            .next()
+            .ok_or_else(|| anyhow!("the validator set is empty"))?;
+        Ok(*validator.account_address())
+    }
 
-        let account_key = StateKey::resource(&account.address, &AccountResource::struct_tag())
-            .expect("AccountResource should serialize");
-        let account_bytes = bcs::to_bytes(&account_resource).expect("AccountResource BCS");
-        self.reader
-            .set_state_value(account_key, StateValue::new_legacy(account_bytes.into()));
+    /// Returns the chain id recorded on chain, which the transactions must carry.
+    pub fn chain_id(&self) -> Result<ChainId> {
+        let state_value = self
+            .get_state_value(&Self::chain_id_key()?)?
+            .ok_or_else(|| anyhow!("no chain id is recorded on chain"))?;
+        Ok(ChainId::new(bcs::from_bytes(state_value.bytes())?))
+    }
 
-        // include an extra buffer for gas so the first transaction never fails
-        let mut effective_balance = initial_balance;
-        if effective_balance > 0 {
-            effective_balance = effective_balance.saturating_add(1_000_000_000);
-        }
+    /// Overrides the chain id recorded at genesis (the test chain id, whatever the framework), also
+    /// in the state restored by `reset_to_genesis`.
+    pub fn set_chain_id(&mut self, chain_id: ChainId) -> Result<()> {
+        let key = Self::chain_id_key()?;
+        let value = StateValue::new_legacy(bcs::to_bytes(&chain_id.id())?.into());
+        self.reader.set_state_value(key.clone(), value.clone());
+        self.genesis.insert(key, value);
+        Ok(())
+    }
 
-        self.publish_coin_store(account.address, effective_balance);
-        self.publish_fungible_store(account.address, effective_balance);
-        self.reader.bump_version();
+    fn chain_id_key() -> Result<StateKey> {
+        let chain_id_tag = StructTag {
+            address: AccountAddress::ONE,
+            module: Identifier::new("chain_id")?,
+            name: Identifier::new("ChainId")?,
+            type_args: vec![],
+        };
+        StateKey::resource(&AccountAddress::ONE, &chain_id_tag)
+            .map_err(|_| anyhow!("failed to derive chain id key"))
     }
 
-    fn apply_genesis(reader: &Arc<TestDbReader>) -> Result<()> {
-        let genesis_change_set = generate_genesis_change_set_for_mainnet(GenesisOptions::Head);
-        for (state_key, write_op) in genesis_change_set.write_set().write_op_iter() {
-            reader.apply_write_op(state_key.clone(), write_op);
-        }
-        reader.bump_version();
-        Self::ensure_apt_supply(reader)?;
-        Ok(())
+    fn timestamp_key() -> Result<StateKey> {
+        let timestamp_tag = StructTag {
+            address: AccountAddress::ONE,
+            module: Identifier::new("timestamp")?,
+            name: Identifier::new("CurrentTimeMicroseconds")?,
+            type_args: vec![],
+        };
+        StateKey::resource(&AccountAddress::ONE, &timestamp_tag)
+            .map_err(|_| anyhow!("failed to derive timestamp key"))
     }
 
-    fn publish_coin_store(
+    /// Returns the `0x1::account::Account` resource of the provided account (its sequence number and
+    /// authentication key), or `None` if the account does not exist.
+    pub fn account_resource(&self, address: AccountAddress) -> Result<Option<AccountResource>> {
+        self.get_resource(address)
+    }
+
+    /// Returns the resource of type `T` stored under `address`, decoded from BCS, if any. `T` must
+    /// mirror the layout of the Move struct. Resources stored in a resource group (e.g. the
+    /// fungible stores of an object) are not found this way: read the group instead.
+    pub fn get_resource<T: DeserializeOwned + MoveStructType>(
         &self,
-        account_address: move_core_types::account_address::AccountAddress,
-        balance: u64,
-    ) {
-        let deposit_events = EventHandle::new(EventKey::new(2, account_address), 0);
-        let withdraw_events = EventHandle::new(EventKey::new(3, account_address), 0);
-        let coin_store = CoinStoreResource::<AptosCoinType>::new(
-            balance,
-            false,
-            deposit_events,
-            withdraw_events,
-        );
+        address: AccountAddress,
+    ) -> Result<Option<T>> {
+        let key = StateKey::resource(&address, &T::struct_tag())
+            .map_err(|_| anyhow!("failed to derive the key of {}", T::struct_tag()))?;
+        match self.get_state_value(&key)? {
+            Some(state_value) => {
+                let resource = bcs::from_bytes(state_value.bytes())
+                    .with_context(|| format!("failed to decode {}", T::struct_tag()))?;
+                Ok(Some(resource))
+            }
+            None => Ok(None),
+        }
+    }
 
-        let coin_store_key = StateKey::resource(
-            &account_address,
-            &CoinStoreResource::<AptosCoinType>::struct_tag(),
-        )
-        .expect("CoinStore resource key");
-        let coin_store_bytes = bcs::to_bytes(&coin_store).expect("CoinStore BCS");
-        self.reader.set_state_value(
-            coin_store_key,
-            StateValue::new_legacy(coin_store_bytes.into()),
-        );
+    /// Returns the fungible balance for the provided account, falling back to its coin store.
+    pub fn account_balance(&self, address: AccountAddress) -> Result<u128> {
+        let primary_store = primary_apt_store(address);
+        let object_group_key =
+            StateKey::resource_group(&primary_store, &ObjectGroupResource::struct_tag());
+        if let Some(state_value) = self.get_state_value(&object_group_key)? {
+            let object_group: ObjectGroupResource = bcs::from_bytes(state_value.bytes())?;
+            let mut fungible_balance = 0u128;
+
+            if let Some(bytes) = object_group.group.get(&FungibleStoreResource::struct_tag()) {
+                let store: FungibleStoreResource = bcs::from_bytes(bytes)?;
+                fungible_balance += u128::from(store.balance());
+            }
+
+            if let Some(bytes) = object_group
+                .group
+                .get(&ConcurrentFungibleBalanceResource::struct_tag())
+            {
+                let concurrent: ConcurrentFungibleBalanceResource = bcs::from_bytes(bytes)?;
+                fungible_balance += u128::from(concurrent.balance());
+            }
+
+            if fungible_balance > 0 {
+                return Ok(fungible_balance);
+            }
+        }
+
+        let coin_key =
+            StateKey::resource(&address, &CoinStoreResource::<AptosCoinType>::struct_tag())
+                .map_err(|_| anyhow!("failed to derive coin store key"))?;
+        let Some(state_value) = self.get_state_value(&coin_key)? else {
+            bail!("account {:?} missing coin or fungible store", address);
+        };
+
+        let coin_store: CoinStoreResource<AptosCoinType> = bcs::from_bytes(state_value.bytes())?;
+        Ok(u128::from(coin_store.coin()))
+    }
+
+    /// Returns the balance of the provided account in any coin type (e.g. a coin published by a Move
+    /// package), read from its `0x1::coin::CoinStore`.
+    pub fn coin_balance(&self, address: AccountAddress, coin_type: &StructTag) -> Result<u64> {
+        let Some(state_value) = self.get_state_value(&coin_store_key(address, coin_type)?)? else {
+            bail!("account {:?} has no coin store for {}", address, coin_type);
+        };
+        decode_coin_balance(state_value.bytes())
     }
 
-    fn publish_fungible_store(
+    /// Returns the balance of the provided account in the fungible asset whose metadata object lives
+    /// at `metadata`, read from its primary fungible store. Zero if it has no such store.
+    pub fn fungible_balance(
         &self,
-        account_address: move_core_types::account_address::AccountAddress,
-        balance: u64,
-    ) {
-        let primary_store_address = primary_apt_store(account_address);
-        let mut object_group = ObjectGroupResource::default();
-
-        let transfer_events = EventHandle::new(EventKey::new(0, primary_store_address), 0);
-        let object_core = ObjectCoreResource::new(account_address, false, transfer_events);
-        object_group.insert(
-            ObjectCoreResource::struct_tag(),
-            bcs::to_bytes(&object_core).expect("object core BCS"),
-        );
+        address: AccountAddress,
+        metadata: AccountAddress,
+    ) -> Result<u64> {
+        let store = primary_fungible_store(address, metadata);
+        let object_group_key = StateKey::resource_group(&store, &ObjectGroupResource::struct_tag());
+        let Some(state_value) = self.get_state_value(&object_group_key)? else {
+            return Ok(0);
+        };
 
-        let store = FungibleStoreResource::new(AccountAddress::TEN, balance, false);
-        object_group.insert(
-            FungibleStoreResource::struct_tag(),
-            bcs::to_bytes(&store).expect("fungible store BCS"),
-        );
+        let object_group: ObjectGroupResource = bcs::from_bytes(state_value.bytes())?;
+        let mut balance = 0u64;
+        if let Some(bytes) = object_group.group.get(&FungibleStoreResource::struct_tag()) {
+            let store: FungibleStoreResource = bcs::from_bytes(bytes)?;
+            balance += store.balance();
+        }
+        if let Some(bytes) = object_group
+            .group
+            .get(&ConcurrentFungibleBalanceResource::struct_tag())
+        {
+            let concurrent: ConcurrentFungibleBalanceResource = bcs::from_bytes(bytes)?;
+            balance += concurrent.balance();
+        }
+        Ok(balance)
+    }
 
-        object_group.insert(
-            MigrationFlag::struct_tag(),
-            bcs::to_bytes(&MigrationFlag::default()).expect("migration flag BCS"),
-        );
+    /// Returns the current APT supply, in octas, as tracked by the APT metadata object. Gas burned by
+    /// the VM is removed from it.
+    pub fn apt_supply(&self) -> Result<u128> {
+        let Some(state_value) = self.get_state_value(&apt_supply_group_key())? else {
+            bail!("the APT metadata object is missing");
+        };
+        let group: BTreeMap<StructTag, Vec<u8>> = bcs::from_bytes(state_value.bytes())
+            .map_err(|e| anyhow!("failed to decode APT supply object group: {e}"))?;
 
-        let group_bytes = object_group
-            .to_bytes()
-            .expect("fungible store object group serialization");
-        let group_key =
-            StateKey::resource_group(&primary_store_address, &ObjectGroupResource::struct_tag());
+        // The VM tracks the supply in `ConcurrentSupply` whenever the object has one.
+        if let Some(bytes) = group.get(&ConcurrentSupplyResource::struct_tag()) {
+            let concurrent: ConcurrentSupply = bcs::from_bytes(bytes)?;
+            return Ok(concurrent.current.value);
+        }
+        match group.get(&supply_tag()?) {
+            Some(bytes) => Ok(bcs::from_bytes::<Supply>(bytes)?.current),
+            None => bail!("the APT metadata object has no supply"),
+        }
+    }
+
+    /// Lists every account holding an `AccountResource` together with its balance. Fails if the
+    /// balance of an account cannot be read. Intended for tests and debugging: this scans the whole
+    /// state.
+    pub fn list_accounts(&self) -> Result<Vec<(AccountAddress, u128)>> {
+        let account_tag = AccountResource::struct_tag();
+        let mut accounts: Vec<_> = self
+            .reader
+            .state_keys()
+            .into_iter()
+            .filter_map(|key| match key.inner() {
+                StateKeyInner::AccessPath(path) => {
+                    let address = path.address;
+                    let is_account = StateKey::resource(&address, &account_tag)
+                        .map_or(false, |account_key| account_key == key);
+                    is_account.then_some(address)
+                }
+                _ => None,
+            })
+            .map(|address| Ok((address, self.account_balance(address)?)))
+            .collect::<Result<_>>()?;
+        accounts.sort_by_key(|(address, _)| *address);
+        Ok(accounts)
+    }
+
+    /// Applies the writes produced by a VM output back into the in-memory store.
+    pub fn apply_vm_output(&self, output: &aptos_vm_types::output::VMOutput) {
+        let tx_output = output
+            .clone()
+            .into_transaction_output()
+            .expect("VM output should convert into transaction output");
+
+        self.apply_write_set(tx_output.write_set());
+    }
+
+    /// Applies a write set directly, without running the VM, e.g. to set up fixtures. The version is
+    /// bumped once for the whole write set.
+    pub fn apply_write_set(&self, write_set: &WriteSet) {
+        for (state_key, write_op) in write_set.write_op_iter() {
+            self.reader.apply_write_op(state_key.clone(), write_op);
+        }
+
+        self.reader.bump_version();
+    }
+
+    /// Publishes account resources and an APT balance for the provided local account, plus
+    /// `DEFAULT_GAS_BUFFER` for gas. Fails if the accounts funded so far would hold more APT than the
+    /// genesis supply.
+    pub fn publish_account_resources(
+        &self,
+        account: &LocalAccount,
+        initial_balance: u64,
+    ) -> Result<()> {
+        self.publish_account_resources_with_buffer(account, initial_balance, DEFAULT_GAS_BUFFER)
+    }
+
+    /// Same as `publish_account_resources` but with a custom gas buffer, e.g. zero for tests that
+    /// check exact balances. An account published without balance gets no buffer either.
+    pub fn publish_account_resources_with_buffer(
+        &self,
+        account: &LocalAccount,
+        initial_balance: u64,
+        gas_buffer: u64,
+    ) -> Result<()> {
+        let balance = with_gas_buffer(initial_balance, gas_buffer);
+        self.reserve_funds(u128::from(balance), || account.address.to_string())?;
         self.reader
-            .set_state_value(group_key, StateValue::new_legacy(group_bytes.into()));
+            .set_state_values(account_states(account, balance).into());
+        self.reader.bump_version();
+        Ok(())
     }
 
-    fn ensure_apt_supply(reader: &Arc<TestDbReader>) -> Result<()> {
-        use move_core_types::{
-            account_address::AccountAddress as MoveAddress, identifier::Identifier,
-            language_storage::StructTag,
-        };
+    /// Same as `publish_account_resources` for many accounts at once, which is much faster for large
+    /// numbers of accounts: the resources are serialized on all cores, then written in one batch (and
+    /// one version). Fails without publishing any account if their total balance would exceed the
+    /// genesis supply.
+    pub fn publish_accounts(&self, accounts: &[(&LocalAccount, u64)]) -> Result<()> {
+        let balances: Vec<_> = accounts
+            .iter()
+            .map(|(_, balance)| with_gas_buffer(*balance, DEFAULT_GAS_BUFFER))
+            .collect();
+        let total = balances.iter().map(|x| u128::from(*x)).sum();
+        self.reserve_funds(total, || format!("{} accounts", accounts.len()))?;
+
+        let threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+        let chunk_size = accounts.len().div_ceil(threads).max(1);
+        let states: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = accounts
+                .chunks(chunk_size)
+                .zip(balances.chunks(chunk_size))
+                .map(|(accounts, balances)| {
+                    scope.spawn(move || {
+                        accounts
+                            .iter()
+                            .zip(balances)
+                            .flat_map(|((account, _), balance)| account_states(account, *balance))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .expect("serializing accounts should not panic")
+                })
+                .collect()
+        });
+        self.reader.set_state_values(states);
+        self.reader.bump_version();
+        Ok(())
+    }
 
-        #[derive(serde::Serialize, serde::Deserialize)]
-        struct Supply {
-            current: u128,
-            maximum: Option<u128>,
+    /// Records `amount` more APT as handed out, failing if the total would exceed the genesis supply.
+    /// `recipient` describes who gets the funds, for the error message.
+    fn reserve_funds(&self, amount: u128, recipient: impl FnOnce() -> String) -> Result<()> {
+        let mut funded = self.funded.lock().unwrap();
+        let total = funded.saturating_add(amount);
+        if total > self.supply.initial {
+            bail!(
+                "funding {} octas to {} would exceed the genesis APT supply of {} ({} already funded)",
+                amount,
+                recipient(),
+                self.supply.initial,
+                *funded
+            );
         }
+        *funded = total;
+        Ok(())
+    }
 
-        let supply_tag = StructTag {
-            address: MoveAddress::ONE,
-            module: Identifier::new("fungible_asset")?,
-            name: Identifier::new("Supply")?,
-            type_args: vec![],
-        };
+    /// Funds the accounts with real `aptos_account::transfer` transactions sent by `treasury`, which
+    /// must already hold enough APT (e.g. published by `publish_account_resources`). Unlike
+    /// `publish_account_resources`, the resources of the funded accounts are created by the VM
+    /// itself and are thus consistent with what it expects. Stops at the first failed transfer.
+    pub fn fund_via_transactions(
+        &self,
+        treasury: &mut LocalAccount,
+        accounts: &[(AccountAddress, u64)],
+    ) -> Result<()> {
+        let chain_id = self.chain_id()?;
+        for (address, amount) in accounts {
+            let txn = aptos_account_transfer(treasury, *address, *amount, chain_id)
+                .with_context(|| format!("failed to build the funding transfer to {}", address))?;
+            let result = run_user_transaction(self, &txn);
+            // Kept transactions were charged gas, even if they failed.
+            if let TransactionStatus::Keep(_) = result.output.status() {
+                self.apply_vm_output(&result.output);
+            }
+            // Follow the sequence number of the treasury on chain, which discarded transactions
+            // did not use.
+            treasury.sequence_number = self
+                .account_resource(treasury.address)?
+                .map_or(0, |resource| resource.sequence_number());
+            match result.output.status() {
+                TransactionStatus::Keep(ExecutionStatus::Success) => (),
+                TransactionStatus::Keep(status) => {
+                    bail!("funding {} failed: {:?}", address, status)
+                }
+                status => bail!("funding {} was discarded: {:?}", address, status),
+            }
+        }
+        Ok(())
+    }
 
-        let object_group_key =
-            StateKey::resource_group(&MoveAddress::TEN, &ObjectGroupResource::struct_tag());
+    fn apply_genesis(
+        reader: &Arc<TestDbReader>,
+        write_set: &WriteSet,
+        supply: &GenesisSupply,
+    ) -> Result<()> {
+        for (state_key, write_op) in write_set.write_op_iter() {
+            reader.apply_write_op(state_key.clone(), write_op);
+        }
+        reader.bump_version();
+        Self::ensure_apt_supply(reader, supply)?;
+        Ok(())
+    }
 
-        let mut group: BTreeMap<StructTag, Vec<u8>> = reader
-            .get_state_value(&object_group_key)
-            .map(|value| bcs::from_bytes(value.bytes()))
-            .transpose()
-            .map_err(|e| anyhow!("failed to decode APT supply object group: {e}"))?
-            .unwrap_or_default();
+    fn ensure_apt_supply(reader: &Arc<TestDbReader>, supply: &GenesisSupply) -> Result<()> {
+        let object_group_key = apt_supply_group_key();
+        // The supply is added to the APT metadata object, which is created if genesis did not.
+        let mut group: BTreeMap<StructTag, Vec<u8>> =
+            match reader.get_state_value(&object_group_key)? {
+                Some(value) => bcs::from_bytes(value.bytes())
+                    .map_err(|e| anyhow!("failed to decode APT supply object group: {e}"))?,
+                None => BTreeMap::new(),
+            };
 
-        let initial_supply = 1_000_000_000_000_000_000u128;
         group.insert(
-            supply_tag.clone(),
+            supply_tag()?,
             bcs::to_bytes(&Supply {
-                current: initial_supply,
-                maximum: None,
+                current: supply.initial,
+                maximum: supply.maximum,
             })?,
         );
 
         let concurrent_supply = ConcurrentSupplyResource {
-            current: AggregatorResource::new(initial_supply, u128::MAX),
+            current: AggregatorResource::new(supply.initial, supply.maximum.unwrap_or(u128::MAX)),
         };
         group.insert(
             ConcurrentSupplyResource::struct_tag(),
@@ -331,3 +1086,79 @@ impl AptosDatabase {
         Ok(())
     }
 }
+
+/// Returns the balance to publish for an account requesting `initial_balance`: accounts published
+/// with a balance also get `gas_buffer` for gas.
+fn with_gas_buffer(initial_balance: u64, gas_buffer: u64) -> u64 {
+    match initial_balance {
+        0 => 0,
+        balance => balance.saturating_add(gas_buffer),
+    }
+}
+
+/// Returns the resources published for `account`: its `AccountResource`, and `balance` APT in both its
+/// coin store and its primary fungible store.
+fn account_states(account: &LocalAccount, balance: u64) -> [(StateKey, StateValue); 3] {
+    let account_resource = AccountResource::new(
+        account.sequence_number,
+        account.authentication_key().to_vec(),
+        EventHandle::new(EventKey::new(0, account.address), 0),
+        EventHandle::new(EventKey::new(1, account.address), 0),
+    );
+    let account_key = StateKey::resource(&account.address, &AccountResource::struct_tag())
+        .expect("AccountResource should serialize");
+    let account_bytes = bcs::to_bytes(&account_resource).expect("AccountResource BCS");
+    [
+        (account_key, StateValue::new_legacy(account_bytes.into())),
+        coin_store_state(account.address, balance),
+        fungible_store_state(account.address, balance),
+    ]
+}
+
+fn coin_store_state(account_address: AccountAddress, balance: u64) -> (StateKey, StateValue) {
+    let deposit_events = EventHandle::new(EventKey::new(2, account_address), 0);
+    let withdraw_events = EventHandle::new(EventKey::new(3, account_address), 0);
+    let coin_store =
+        CoinStoreResource::<AptosCoinType>::new(balance, false, deposit_events, withdraw_events);
+
+    let coin_store_key = StateKey::resource(
+        &account_address,
+        &CoinStoreResource::<AptosCoinType>::struct_tag(),
+    )
+    .expect("CoinStore resource key");
+    let coin_store_bytes = bcs::to_bytes(&coin_store).expect("CoinStore BCS");
+    (
+        coin_store_key,
+        StateValue::new_legacy(coin_store_bytes.into()),
+    )
+}
+
+fn fungible_store_state(account_address: AccountAddress, balance: u64) -> (StateKey, StateValue) {
+    let primary_store_address = primary_apt_store(account_address);
+    let mut object_group = ObjectGroupResource::default();
+
+    let transfer_events = EventHandle::new(EventKey::new(0, primary_store_address), 0);
+    let object_core = ObjectCoreResource::new(account_address, false, transfer_events);
+    object_group.insert(
+        ObjectCoreResource::struct_tag(),
+        bcs::to_bytes(&object_core).expect("object core BCS"),
+    );
+
+    let store = FungibleStoreResource::new(AccountAddress::TEN, balance, false);
+    object_group.insert(
+        FungibleStoreResource::struct_tag(),
+        bcs::to_bytes(&store).expect("fungible store BCS"),
+    );
+
+    object_group.insert(
+        MigrationFlag::struct_tag(),
+        bcs::to_bytes(&MigrationFlag::default()).expect("migration flag BCS"),
+    );
+
+    let group_bytes = object_group
+        .to_bytes()
+        .expect("fungible store object group serialization");
+    let group_key =
+        StateKey::resource_group(&primary_store_address, &ObjectGroupResource::struct_tag());
+    (group_key, StateValue::new_legacy(group_bytes.into()))
+}
@@ -1,31 +1,84 @@
 //! Aptos VM executor for running committed transactions.
 
-use crate::{accounts::LocalAccount, database::AptosDatabase};
-use anyhow::{anyhow, bail, Result};
+use crate::{
+    abort_messages::AbortMessages,
+    accounts::LocalAccount,
+    database::{coin_store_key, AptosDatabase, FrameworkVersion, GenesisSupply, StateSnapshot},
+    order_book::{self, base_coin, quote_coin, OrderBookSnapshot},
+};
+use anyhow::{anyhow, bail, Context as _, Result};
+use aptos_crypto::HashValue;
+use aptos_storage_interface::state_store::state_view::db_state_view::DbStateView;
 use aptos_types::{
-    account_config::{
-        primary_apt_store, CoinStoreResource, ConcurrentFungibleBalanceResource,
-        FungibleStoreResource, ObjectGroupResource,
-    },
+    account_config::{ObjectCoreResource, ObjectGroupResource},
+    block_metadata::BlockMetadata as AptosBlockMetadata,
     chain_id::ChainId,
-    state_store::{state_key::StateKey, TStateView},
-    transaction::{AuxiliaryInfo, AuxiliaryInfoTrait, SignedTransaction},
-    utility_coin::AptosCoinType,
+    contract_event::ContractEvent,
+    state_store::{
+        state_key::{inner::StateKeyInner, StateKey},
+        TStateView,
+    },
+    transaction::{
+        signature_verified_transaction::SignatureVerifiedTransaction, AuxiliaryInfo,
+        AuxiliaryInfoTrait, SignedTransaction, Transaction, TransactionOutput, TransactionStatus,
+    },
     vm_status::VMStatus,
+    write_set::TransactionWrite,
 };
 use aptos_vm::{data_cache::AsMoveResolver, AptosVM};
 use aptos_vm_environment::environment::AptosEnvironment;
 use aptos_vm_logging::log_schema::AdapterLogSchema;
-use aptos_vm_types::module_and_script_storage::AsAptosCodeStorage;
-use move_core_types::{account_address::AccountAddress, move_resource::MoveStructType};
+use aptos_vm_types::{module_and_script_storage::AsAptosCodeStorage, output::VMOutput};
+use config::GenesisFramework;
+use move_core_types::{
+    account_address::AccountAddress, language_storage::StructTag, move_resource::MoveStructType,
+    vm_status::StatusCode,
+};
+use std::collections::BTreeSet;
+use std::path::Path;
+#[cfg(feature = "failure-injection")]
+use {aptos_types::transaction::ExecutionStatus, std::collections::HashMap};
+
+#[cfg(test)]
+#[path = "tests/executor_tests.rs"]
+pub mod executor_tests;
 
 /// Result of executing a single transaction through the VM.
 pub struct TransactionResult {
     pub status: VMStatus,
-    pub output: aptos_vm_types::output::VMOutput,
+    pub output: VMOutput,
+    /// What the Move abort of the transaction means, if it aborted with a known code.
+    pub abort_message: Option<String>,
+    /// The events emitted by the transaction, without the type layouts the VM keeps next to them in
+    /// `output`.
+    events: Vec<ContractEvent>,
+}
+
+/// Whether an executed transaction made it into the ledger.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeptOrDiscarded {
+    /// The transaction is part of the ledger: it ran (possibly aborting) and was charged gas.
+    Kept,
+    /// The transaction left no trace, e.g. because its sequence number is too old. It may be
+    /// resubmitted later.
+    Discarded(StatusCode),
 }
 
 impl TransactionResult {
+    fn new(status: VMStatus, output: VMOutput) -> Self {
+        let events = output
+            .events()
+            .iter()
+            .map(|(event, _)| event.clone())
+            .collect();
+        Self {
+            status,
+            output,
+            abort_message: None,
+            events,
+        }
+    }
+
     pub fn gas_used(&self) -> u64 {
         self.output.gas_used()
     }
@@ -33,6 +86,234 @@ impl TransactionResult {
     pub fn status(&self) -> &VMStatus {
         &self.status
     }
+
+    /// Returns what the Move abort of the transaction means, if it aborted with a known code.
+    pub fn abort_message(&self) -> Option<&str> {
+        self.abort_message.as_deref()
+    }
+
+    /// Explains the Move abort of the transaction, preferring the message the VM derived from the
+    /// error metadata of the module over `messages`.
+    fn with_abort_message(mut self, messages: &AbortMessages) -> Self {
+        if let VMStatus::MoveAbort {
+            location,
+            code,
+            message,
+        } = &self.status
+        {
+            self.abort_message = message
+                .clone()
+                .or_else(|| messages.message(location, *code).map(str::to_owned));
+        }
+        self
+    }
+
+    /// Whether the transaction was left out of the ledger because it expired before being executed,
+    /// as opposed to any other failure.
+    pub fn is_expired(&self) -> bool {
+        self.keep_or_discard() == KeptOrDiscarded::Discarded(StatusCode::TRANSACTION_EXPIRED)
+    }
+
+    /// Returns the output of the transaction in the standard Aptos format (as read by indexers), as
+    /// applied to the state.
+    pub fn transaction_output(&self) -> TransactionOutput {
+        self.output
+            .clone()
+            .into_transaction_output()
+            .expect("VM output should convert into transaction output")
+    }
+
+    /// Returns the events emitted by the transaction, e.g. none for the orders of a market created
+    /// without events emission.
+    pub fn events(&self) -> &[ContractEvent] {
+        &self.events
+    }
+
+    /// Returns the accounts whose resources the transaction wrote, in ascending order. The writes to
+    /// an object (e.g. the primary fungible store of an account) count as writes to its owner, and
+    /// the reserved addresses of the framework (e.g. holding the APT supply) are left out.
+    pub fn touched_accounts(&self) -> Vec<AccountAddress> {
+        let output = self.transaction_output();
+
+        let mut accounts = BTreeSet::new();
+        for (state_key, write_op) in output.write_set().write_op_iter() {
+            let address = match state_key.inner() {
+                StateKeyInner::AccessPath(path) => path.address,
+                _ => continue,
+            };
+            let owner = write_op
+                .as_state_value()
+                .and_then(|value| object_owner(state_key, address, value.bytes()));
+            let account = owner.unwrap_or(address);
+            if !account.is_special() {
+                accounts.insert(account);
+            }
+        }
+        accounts.into_iter().collect()
+    }
+
+    pub fn keep_or_discard(&self) -> KeptOrDiscarded {
+        match self.output.status() {
+            TransactionStatus::Keep(_) => KeptOrDiscarded::Kept,
+            TransactionStatus::Discard(code) => KeptOrDiscarded::Discarded(*code),
+            // Only the parallel executor asks for retries; treat them as never included.
+            TransactionStatus::Retry => {
+                KeptOrDiscarded::Discarded(StatusCode::UNKNOWN_VALIDATION_STATUS)
+            }
+        }
+    }
+}
+
+/// Returns the owner of the object at `address` if `state_key` is its resource group.
+fn object_owner(
+    state_key: &StateKey,
+    address: AccountAddress,
+    bytes: &[u8],
+) -> Option<AccountAddress> {
+    if *state_key != StateKey::resource_group(&address, &ObjectGroupResource::struct_tag()) {
+        return None;
+    }
+    let group: ObjectGroupResource = bcs::from_bytes(bytes).ok()?;
+    let core = group.group.get(&ObjectCoreResource::struct_tag())?;
+    let core: ObjectCoreResource = bcs::from_bytes(core).ok()?;
+    Some(core.owner)
+}
+
+/// The block a batch of transactions is executed in, as seen by the Move framework
+/// (`0x1::block` and `0x1::timestamp`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockMetadata {
+    pub epoch: u64,
+    pub round: u64,
+    /// A validator of the current epoch, or `AccountAddress::ZERO` (the reserved VM address) for
+    /// blocks without a proposer.
+    pub proposer: AccountAddress,
+    /// The time of the block, in microseconds. It must be later than the on-chain time, or equal to
+    /// it for blocks without a proposer.
+    pub timestamp: u64,
+}
+
+impl BlockMetadata {
+    fn to_transaction(self) -> Transaction {
+        // The framework only records the id; derive it from the position of the block.
+        let id = HashValue::sha3_256_of(
+            &bcs::to_bytes(&(self.epoch, self.round)).expect("serializing integers cannot fail"),
+        );
+        Transaction::BlockMetadata(AptosBlockMetadata::new(
+            id,
+            self.epoch,
+            self.round,
+            self.proposer,
+            /* previous_block_votes_bitvec */ Vec::new(),
+            /* failed_proposer_indices */ Vec::new(),
+            self.timestamp,
+        ))
+    }
+}
+
+/// Runs a user transaction against the current state of the database, without applying its output.
+pub(crate) fn run_user_transaction(
+    database: &AptosDatabase,
+    txn: &SignedTransaction,
+) -> TransactionResult {
+    let state_view = database.state_view();
+    BlockVm::new(&state_view).run(&state_view, txn)
+}
+
+/// The VM running the transactions of a block, built once from the state at the start of the block
+/// rather than for every transaction (which re-reads the features and the gas schedule, and rebuilds
+/// the natives). Only the state view changes between the transactions, as each one sees the writes
+/// of the previous ones.
+///
+/// Reusing the VM across the block is what the Aptos block executor does: the environment only holds
+/// the on-chain configuration (features, gas schedule, ...), which a transaction cannot change for
+/// the rest of its block (Aptos applies configuration changes at the next epoch; here, at the next
+/// block). Modules are not part of the environment: they are loaded through the code storage of the
+/// state view of every transaction, so modules published in the block are visible to the next
+/// transactions.
+struct BlockVm {
+    environment: AptosEnvironment,
+    vm: AptosVM,
+}
+
+impl BlockVm {
+    fn new(state_view: &DbStateView) -> Self {
+        let environment = AptosEnvironment::new(state_view);
+        let vm = AptosVM::new(&environment, state_view);
+        Self { environment, vm }
+    }
+
+    /// Runs a user transaction against `state_view`, without applying its output.
+    fn run(&self, state_view: &DbStateView, txn: &SignedTransaction) -> TransactionResult {
+        let storage_adapter = state_view.as_move_resolver();
+        let module_storage = state_view.as_aptos_code_storage(&self.environment);
+        let log_context = AdapterLogSchema::new(state_view.id(), 0);
+        let auxiliary_info = AuxiliaryInfo::new_empty();
+
+        let (status, output) = self.vm.execute_user_transaction(
+            &storage_adapter,
+            &module_storage,
+            txn,
+            &log_context,
+            &auxiliary_info,
+        );
+        TransactionResult::new(status, output)
+    }
+}
+
+/// The kind of write a transaction made to a state key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateChangeKind {
+    Creation,
+    Modification,
+    Deletion,
+}
+
+/// A single write made by a transaction, as needed by indexers.
+#[derive(Clone, Debug)]
+pub struct StateChange {
+    pub state_key: StateKey,
+    pub kind: StateChangeKind,
+    /// The raw (BCS) new value; `None` for deletions.
+    pub value: Option<Vec<u8>>,
+}
+
+/// Statuses forced on the transactions of every block instead of running them through the VM, so that
+/// tests exercise the handling of failures deterministically. A forced transaction changes nothing
+/// and charges no gas, not even the sequence number of its sender: an `Error` status discards it, any
+/// other status keeps it (as out of gas for `OUT_OF_GAS`, as a miscellaneous error otherwise).
+#[cfg(feature = "failure-injection")]
+#[derive(Clone, Debug, Default)]
+pub struct InjectedFailures {
+    statuses: HashMap<usize, VMStatus>,
+}
+
+#[cfg(feature = "failure-injection")]
+impl InjectedFailures {
+    /// Forces `status` on the transaction at `position` (from zero) of every block.
+    pub fn fail(mut self, position: usize, status: VMStatus) -> Self {
+        self.statuses.insert(position, status);
+        self
+    }
+
+    /// Returns the result forced on the transaction at `position`, if any.
+    fn forced_result(&self, position: usize) -> Option<TransactionResult> {
+        let status = self.statuses.get(&position)?.clone();
+        let transaction_status = match &status {
+            VMStatus::Executed => TransactionStatus::Keep(ExecutionStatus::Success),
+            VMStatus::Error { status_code, .. } => TransactionStatus::Discard(*status_code),
+            other if other.status_code() == StatusCode::OUT_OF_GAS => {
+                TransactionStatus::Keep(ExecutionStatus::OutOfGas)
+            }
+            other => TransactionStatus::Keep(ExecutionStatus::MiscellaneousError(Some(
+                other.status_code(),
+            ))),
+        };
+        Some(TransactionResult::new(
+            status,
+            VMOutput::empty_with_status(transaction_status),
+        ))
+    }
 }
 
 /// High-level executor that wires state management, VM construction, and
@@ -40,18 +321,103 @@ impl TransactionResult {
 pub struct AptosVmExecutor {
     database: AptosDatabase,
     chain_id: ChainId,
+    /// For how long (in seconds) past their expiration committed transactions are still executed.
+    expiry_grace_secs: u64,
+    /// Explains the aborts the VM leaves without a message, from the registered error maps.
+    abort_messages: AbortMessages,
+    /// The statuses forced on the transactions of every block.
+    #[cfg(feature = "failure-injection")]
+    injected_failures: InjectedFailures,
 }
 
 impl AptosVmExecutor {
     /// Constructs a new executor with Aptos genesis state.
     pub fn new() -> Result<Self> {
-        let database = AptosDatabase::new_with_genesis()?;
+        Self::new_with_chain_id(ChainId::test())
+    }
+
+    /// Constructs a new executor with Aptos genesis state, running the transactions of the given
+    /// chain (e.g. `ChainId::mainnet()`). The genesis is the same whatever the chain: only the chain id
+    /// it records, which the transactions must carry, is replaced.
+    pub fn new_with_chain_id(chain_id: ChainId) -> Result<Self> {
+        Self::with_framework(
+            GenesisSupply::default(),
+            GenesisFramework::default(),
+            chain_id,
+        )
+    }
+
+    /// Constructs a new executor with Aptos genesis state and a custom APT supply.
+    pub fn with_genesis_supply(supply: GenesisSupply) -> Result<Self> {
+        Self::with_framework(supply, GenesisFramework::default(), ChainId::test())
+    }
+
+    /// Constructs a new executor whose genesis installs the given release of the framework, running
+    /// the transactions of `chain_id`.
+    pub fn with_framework(
+        supply: GenesisSupply,
+        framework: GenesisFramework,
+        chain_id: ChainId,
+    ) -> Result<Self> {
+        let mut database = AptosDatabase::new_with_framework(supply, framework)?;
+        if chain_id != ChainId::test() {
+            database.set_chain_id(chain_id)?;
+        }
         Ok(Self {
             database,
-            chain_id: ChainId::test(),
+            chain_id,
+            expiry_grace_secs: 0,
+            abort_messages: AbortMessages::default(),
+            #[cfg(feature = "failure-injection")]
+            injected_failures: InjectedFailures::default(),
         })
     }
 
+    /// Constructs an executor whose state is imported from a snapshot file (see `export_snapshot`)
+    /// instead of generated at genesis, which is much faster. The snapshot must have been taken on
+    /// `chain_id`.
+    pub fn from_snapshot(path: &Path, chain_id: ChainId) -> Result<Self> {
+        let snapshot = StateSnapshot::read(path)?;
+        let database = AptosDatabase::from_snapshot(snapshot, chain_id)
+            .with_context(|| format!("failed to import state snapshot {}", path.display()))?;
+        Ok(Self {
+            database,
+            chain_id,
+            expiry_grace_secs: 0,
+            abort_messages: AbortMessages::default(),
+            #[cfg(feature = "failure-injection")]
+            injected_failures: InjectedFailures::default(),
+        })
+    }
+
+    /// Writes the current state to a snapshot file, from which other executors can boot.
+    pub fn export_snapshot(&self, path: &Path) -> Result<()> {
+        self.database.export_snapshot(self.chain_id).write(path)
+    }
+
+    /// Keeps executing committed transactions up to `secs` seconds after their expiration, since
+    /// they may expire while waiting to be ordered (see `execute_committed_block`).
+    pub fn set_expiry_grace(&mut self, secs: u64) {
+        self.expiry_grace_secs = secs;
+    }
+
+    /// Adds readable messages for the abort codes of published modules, surfaced on the results of
+    /// the transactions aborting with them.
+    pub fn register_abort_messages(&mut self, messages: AbortMessages) {
+        self.abort_messages.extend(messages);
+    }
+
+    /// Forces the outcome of the transactions at the given positions of every block from now on.
+    #[cfg(feature = "failure-injection")]
+    pub fn inject_failures(&mut self, failures: InjectedFailures) {
+        self.injected_failures = failures;
+    }
+
+    /// Returns the framework installed at genesis, unless the state was imported from a snapshot.
+    pub fn framework(&self) -> Option<FrameworkVersion> {
+        self.database.framework()
+    }
+
     /// Returns the configured chain id.
     pub fn chain_id(&self) -> ChainId {
         self.chain_id
@@ -62,73 +428,295 @@ impl AptosVmExecutor {
         &self.database
     }
 
-    /// Publishes account resources and funds the account with the provided balance.
-    pub fn bootstrap_account(&self, account: &LocalAccount, initial_balance: u64) {
+    /// Returns a digest of the current state, identical on every node that executed the same blocks.
+    pub fn state_root(&self) -> HashValue {
+        self.database.state_root()
+    }
+
+    /// Restores the post-genesis state without regenerating genesis, so that benchmarks can run
+    /// several iterations on the same executor. Accounts must be bootstrapped again afterwards.
+    pub fn reset_to_genesis(&mut self) {
+        self.database.reset_to_genesis();
+    }
+
+    /// Publishes account resources and funds the account with the provided balance, plus
+    /// `DEFAULT_GAS_BUFFER` for gas.
+    pub fn bootstrap_account(&self, account: &LocalAccount, initial_balance: u64) -> Result<()> {
         self.database
-            .publish_account_resources(account, initial_balance);
+            .publish_account_resources(account, initial_balance)
+    }
+
+    /// Same as `bootstrap_account` for many accounts at once, serializing their resources on all
+    /// cores. Much faster to set up thousands of benchmark accounts.
+    pub fn bootstrap_accounts(&self, accounts: &[(&LocalAccount, u64)]) -> Result<()> {
+        self.database.publish_accounts(accounts)
+    }
+
+    /// Same as `bootstrap_account` but with a custom gas buffer.
+    pub fn bootstrap_account_with_buffer(
+        &self,
+        account: &LocalAccount,
+        initial_balance: u64,
+        gas_buffer: u64,
+    ) -> Result<()> {
+        self.database
+            .publish_account_resources_with_buffer(account, initial_balance, gas_buffer)
     }
 
     /// Executes a batch of transactions sequentially, applying each output to the in-memory state.
+    /// The VM is built once for the whole batch (see `BlockVm`).
     pub fn execute_block(&mut self, txns: &[SignedTransaction]) -> Vec<TransactionResult> {
+        let vm = BlockVm::new(&self.database.state_view());
         let mut results = Vec::with_capacity(txns.len());
-        for txn in txns {
-            let state_view = self.database.state_view();
-            let environment = AptosEnvironment::new(&state_view);
-            let vm = AptosVM::new(&environment, &state_view);
-            let storage_adapter = state_view.as_move_resolver();
-            let module_storage = state_view.as_aptos_code_storage(&environment);
-            let log_context = AdapterLogSchema::new(state_view.id(), 0);
-            let auxiliary_info = AuxiliaryInfo::new_empty();
-
-            let (status, output) = vm.execute_user_transaction(
+        for (position, txn) in txns.iter().enumerate() {
+            let result = self.execute_at(&vm, position, txn);
+            self.apply(&result);
+            results.push(result);
+        }
+        results
+    }
+
+    /// Same as `execute_block`, but first runs the block prologue with the provided metadata, so that
+    /// the transactions observe the round, proposer and time of the block. Fails without executing
+    /// the transactions if the prologue is rejected, e.g. because the proposer is not a validator.
+    pub fn execute_block_with_metadata(
+        &mut self,
+        txns: &[SignedTransaction],
+        metadata: BlockMetadata,
+    ) -> Result<Vec<TransactionResult>> {
+        self.execute_block_metadata(metadata)?;
+        Ok(self.execute_block(txns))
+    }
+
+    /// Executes a block committed by consensus at `timestamp_usecs`. The on-chain time is set once
+    /// for the whole block, through the block prologue, to `expiry_grace_secs` before that time, so
+    /// that the transactions which expired while waiting to be ordered still run. The clock never
+    /// moves backwards: blocks that would not advance it run without a prologue.
+    pub fn execute_committed_block(
+        &mut self,
+        txns: &[SignedTransaction],
+        round: u64,
+        timestamp_usecs: u64,
+    ) -> Result<Vec<TransactionResult>> {
+        let grace_usecs = self.expiry_grace_secs.saturating_mul(1_000_000);
+        let timestamp = timestamp_usecs.saturating_sub(grace_usecs);
+        if timestamp <= self.database.timestamp_usecs()? {
+            return Ok(self.execute_block(txns));
+        }
+        let metadata = BlockMetadata {
+            epoch: self.database.epoch()?,
+            round,
+            proposer: self.database.validator()?,
+            timestamp,
+        };
+        self.execute_block_with_metadata(txns, metadata)
+    }
+
+    /// Runs the block prologue (`0x1::block::block_prologue`) and applies its output: it advances the
+    /// on-chain time and block height and emits a `NewBlockEvent`.
+    pub fn execute_block_metadata(&mut self, metadata: BlockMetadata) -> Result<TransactionResult> {
+        let state_view = self.database.state_view();
+        let environment = AptosEnvironment::new(&state_view);
+        let vm = AptosVM::new(&environment, &state_view);
+        let storage_adapter = state_view.as_move_resolver();
+        let module_storage = state_view.as_aptos_code_storage(&environment);
+        let log_context = AdapterLogSchema::new(state_view.id(), 0);
+        let txn = SignatureVerifiedTransaction::Valid(metadata.to_transaction());
+
+        let (status, output) = vm
+            .execute_single_transaction(
+                &txn,
                 &storage_adapter,
                 &module_storage,
-                txn,
                 &log_context,
-                &auxiliary_info,
-            );
+                &AuxiliaryInfo::new_empty(),
+            )
+            .map_err(|status| anyhow!("block prologue failed: {:?}", status))?;
+        let result = TransactionResult::new(status, output);
+        if let KeptOrDiscarded::Discarded(code) = result.keep_or_discard() {
+            return Err(anyhow!("block prologue was discarded: {:?}", code));
+        }
+        self.apply(&result);
+        Ok(result)
+    }
 
-            self.database.apply_vm_output(&output);
-            results.push(TransactionResult { status, output });
+    /// Same as `execute_block`, but also returns the state changes made by each transaction.
+    /// Discarded transactions have no state changes. Fails if the state the changes are classified
+    /// against cannot be read.
+    pub fn execute_block_with_changes(
+        &mut self,
+        txns: &[SignedTransaction],
+    ) -> Result<Vec<(TransactionResult, Vec<StateChange>)>> {
+        let vm = BlockVm::new(&self.database.state_view());
+        let mut results = Vec::with_capacity(txns.len());
+        for (position, txn) in txns.iter().enumerate() {
+            let result = self.execute_at(&vm, position, txn);
+            // Extract the changes before applying them, to tell creations from modifications.
+            let changes = match result.keep_or_discard() {
+                KeptOrDiscarded::Kept => self.state_changes(&result.output)?,
+                KeptOrDiscarded::Discarded(_) => Vec::new(),
+            };
+            self.apply(&result);
+            results.push((result, changes));
         }
-        results
+        Ok(results)
+    }
+
+    /// Predicts the result of a transaction if it were executed now, e.g. to estimate its gas or to
+    /// check that it would not abort, without changing the state: the transaction runs against the
+    /// current state and its output is never applied. As for `simulate_block`, the expiration of the
+    /// transaction is not graced.
+    pub fn simulate(&self, txn: &SignedTransaction) -> TransactionResult {
+        let state_view = self.database.state_view();
+        BlockVm::new(&state_view)
+            .run(&state_view, txn)
+            .with_abort_message(&self.abort_messages)
+    }
+
+    /// Predicts the results of a sequence of transactions if they were executed now as a block,
+    /// without changing the state: they run against a throwaway copy of the database, to which each
+    /// output is applied so that the later transactions see the effects of the earlier ones. Neither
+    /// the failures injected in the blocks nor `expiry_grace_secs` (which is meant for the transactions
+    /// already committed, see `execute_committed_block`) apply.
+    pub fn simulate_block(&self, txns: &[SignedTransaction]) -> Vec<TransactionResult> {
+        let database = self.database.copy();
+        let vm = BlockVm::new(&database.state_view());
+        txns.iter()
+            .map(|txn| {
+                let result = vm
+                    .run(&database.state_view(), txn)
+                    .with_abort_message(&self.abort_messages);
+                if result.keep_or_discard() == KeptOrDiscarded::Kept {
+                    database.apply_vm_output(&result.output);
+                }
+                result
+            })
+            .collect()
+    }
+
+    /// Runs the transaction at `position` of a block, unless its outcome is forced.
+    #[cfg(feature = "failure-injection")]
+    fn execute_at(
+        &self,
+        vm: &BlockVm,
+        position: usize,
+        txn: &SignedTransaction,
+    ) -> TransactionResult {
+        match self.injected_failures.forced_result(position) {
+            Some(result) => result,
+            None => self.execute_transaction(vm, txn),
+        }
+        .with_abort_message(&self.abort_messages)
+    }
+
+    #[cfg(not(feature = "failure-injection"))]
+    fn execute_at(
+        &self,
+        vm: &BlockVm,
+        _position: usize,
+        txn: &SignedTransaction,
+    ) -> TransactionResult {
+        self.execute_transaction(vm, txn)
+            .with_abort_message(&self.abort_messages)
+    }
+
+    /// Runs a single transaction against the current state, without applying its output.
+    fn execute_transaction(&self, vm: &BlockVm, txn: &SignedTransaction) -> TransactionResult {
+        vm.run(&self.database.state_view(), txn)
+    }
+
+    /// Applies the output of a transaction to the state. Discarded transactions have no effect.
+    fn apply(&self, result: &TransactionResult) {
+        if result.keep_or_discard() == KeptOrDiscarded::Kept {
+            self.database.apply_vm_output(&result.output);
+        }
+    }
+
+    /// Lists the writes of a VM output, classified against the current (not yet updated) state.
+    fn state_changes(&self, output: &VMOutput) -> Result<Vec<StateChange>> {
+        let tx_output = output
+            .clone()
+            .into_transaction_output()
+            .expect("VM output should convert into transaction output");
+
+        tx_output
+            .write_set()
+            .write_op_iter()
+            .map(|(state_key, write_op)| {
+                let (kind, value) = if write_op.is_delete() {
+                    (StateChangeKind::Deletion, None)
+                } else {
+                    let kind = match self.database.get_state_value(state_key)? {
+                        Some(_) => StateChangeKind::Modification,
+                        None => StateChangeKind::Creation,
+                    };
+                    let value = write_op.as_state_value().map(|v| v.bytes().to_vec());
+                    (kind, value)
+                };
+                Ok(StateChange {
+                    state_key: state_key.clone(),
+                    kind,
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the on-chain time in microseconds, as advanced by the block prologue (see
+    /// `execute_block_with_metadata`): the time at which the next transactions run.
+    pub fn current_timestamp_usecs(&self) -> Result<u64> {
+        self.database.timestamp_usecs()
     }
 
     /// Returns the fungible balance for the provided account, if present.
     pub fn account_balance(&self, address: AccountAddress) -> Result<u128> {
-        let primary_store = primary_apt_store(address);
-        let object_group_key =
-            StateKey::resource_group(&primary_store, &ObjectGroupResource::struct_tag());
-        if let Some(state_value) = self.database.get_state_value(&object_group_key) {
-            let object_group: ObjectGroupResource = bcs::from_bytes(state_value.bytes())?;
-            let mut fungible_balance = 0u128;
-
-            if let Some(bytes) = object_group.group.get(&FungibleStoreResource::struct_tag()) {
-                let store: FungibleStoreResource = bcs::from_bytes(bytes)?;
-                fungible_balance += u128::from(store.balance());
-            }
+        self.database.account_balance(address)
+    }
 
-            if let Some(bytes) = object_group
-                .group
-                .get(&ConcurrentFungibleBalanceResource::struct_tag())
-            {
-                let concurrent: ConcurrentFungibleBalanceResource = bcs::from_bytes(bytes)?;
-                fungible_balance += u128::from(concurrent.balance());
-            }
+    /// Returns the balance of the provided account in the given coin type.
+    pub fn coin_balance(&self, address: AccountAddress, coin_type: &StructTag) -> Result<u64> {
+        self.database.coin_balance(address, coin_type)
+    }
+
+    /// Returns the resting orders of the `simple_market` market at `market`, by price level. The
+    /// market was created by the package published by `module_owner`.
+    pub fn order_book(
+        &self,
+        module_owner: AccountAddress,
+        market: AccountAddress,
+    ) -> Result<OrderBookSnapshot> {
+        order_book::order_book(&self.database, module_owner, market)
+    }
 
-            if fungible_balance > 0 {
-                return Ok(fungible_balance);
+    /// Returns whether `trader` is registered for both coins of the market at `market`, as done by
+    /// `register_trader`. Placing an order fails for an unregistered trader. The market was created by
+    /// the package published by `module_owner`.
+    pub fn is_registered(
+        &self,
+        module_owner: AccountAddress,
+        market: AccountAddress,
+        trader: AccountAddress,
+    ) -> Result<bool> {
+        let market_key = order_book::order_book_key(module_owner, market)?;
+        if self.database.get_state_value(&market_key)?.is_none() {
+            bail!("no market at {}", market);
+        }
+        for coin_type in [base_coin(module_owner)?, quote_coin(module_owner)?] {
+            let key = coin_store_key(trader, &coin_type)?;
+            if self.database.get_state_value(&key)?.is_none() {
+                return Ok(false);
             }
         }
+        Ok(true)
+    }
 
-        let coin_key =
-            StateKey::resource(&address, &CoinStoreResource::<AptosCoinType>::struct_tag())
-                .map_err(|_| anyhow!("failed to derive coin store key"))?;
-        let Some(state_value) = self.database.get_state_value(&coin_key) else {
-            bail!("account {:?} missing coin or fungible store", address);
-        };
-
-        let coin_store: CoinStoreResource<AptosCoinType> = bcs::from_bytes(state_value.bytes())?;
-        Ok(u128::from(coin_store.coin()))
+    /// Returns the balance of the provided account in the fungible asset whose metadata object lives
+    /// at `metadata`.
+    pub fn fungible_balance(
+        &self,
+        address: AccountAddress,
+        metadata: AccountAddress,
+    ) -> Result<u64> {
+        self.database.fungible_balance(address, metadata)
     }
 }
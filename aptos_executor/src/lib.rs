@@ -1,8 +1,25 @@
+pub mod abort_messages;
 pub mod accounts;
 pub mod database;
 pub mod executor;
+pub mod order_book;
+pub mod package;
 pub mod scenarios;
+pub mod schedule;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod transaction_builder;
 
-pub use accounts::LocalAccount;
-pub use executor::{AptosVmExecutor, TransactionResult};
+pub use abort_messages::AbortMessages;
+pub use accounts::{LocalAccount, OnChainSequence, SequenceSource};
+pub use database::{
+    FrameworkVersion, GenesisSupply, StateSnapshot, DEFAULT_GAS_BUFFER, STATE_SNAPSHOT_FORMAT,
+};
+#[cfg(feature = "failure-injection")]
+pub use executor::InjectedFailures;
+pub use executor::{
+    AptosVmExecutor, BlockMetadata, KeptOrDiscarded, StateChange, StateChangeKind,
+    TransactionResult,
+};
+pub use order_book::{OrderBookSnapshot, PriceLevel, RestingOrder};
+pub use schedule::{plan_parallel_schedule, ParallelPlan};
@@ -0,0 +1,141 @@
+//! Decoding of the order books of the `simple_market` package.
+
+use crate::database::AptosDatabase;
+use anyhow::{anyhow, Result};
+use aptos_types::state_store::state_key::StateKey;
+use move_core_types::{
+    account_address::AccountAddress, identifier::Identifier, language_storage::StructTag,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+#[path = "tests/order_book_tests.rs"]
+pub mod order_book_tests;
+
+/// The module and name of the resource holding the resting orders of a market, published at the
+/// address of the market by the module owner.
+const ORDER_BOOK_MODULE: &str = "resting_orders";
+const ORDER_BOOK_RESOURCE: &str = "OrderBook";
+
+/// An order resting on the book, as laid out by `resting_orders::Order`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct RestingOrder {
+    /// The id assigned to the order by the market.
+    pub order_id: u128,
+    pub trader: AccountAddress,
+    pub price: u64,
+    /// The remaining size of the order.
+    pub size: u64,
+}
+
+/// The layout of `resting_orders::OrderBook`.
+#[derive(Deserialize)]
+struct OrderBookResource {
+    bids: Vec<RestingOrder>,
+    asks: Vec<RestingOrder>,
+}
+
+/// The orders resting at a price.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PriceLevel {
+    pub price: u64,
+    /// The total remaining size of the orders at this price.
+    pub size: u64,
+    pub orders: usize,
+}
+
+/// The resting orders of a market aggregated by price, best prices first: bids by descending price
+/// and asks by ascending price.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderBookSnapshot {
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+impl OrderBookSnapshot {
+    /// Aggregates resting orders into price levels.
+    pub fn from_orders(bids: &[RestingOrder], asks: &[RestingOrder]) -> Self {
+        let mut bids = price_levels(bids);
+        bids.reverse();
+        Self {
+            bids,
+            asks: price_levels(asks),
+        }
+    }
+
+    /// Returns the highest bid, if any.
+    pub fn best_bid(&self) -> Option<&PriceLevel> {
+        self.bids.first()
+    }
+
+    /// Returns the lowest ask, if any.
+    pub fn best_ask(&self) -> Option<&PriceLevel> {
+        self.asks.first()
+    }
+}
+
+/// Aggregates orders into price levels, by ascending price.
+fn price_levels(orders: &[RestingOrder]) -> Vec<PriceLevel> {
+    let mut levels: BTreeMap<u64, PriceLevel> = BTreeMap::new();
+    for order in orders {
+        let level = levels.entry(order.price).or_insert(PriceLevel {
+            price: order.price,
+            size: 0,
+            orders: 0,
+        });
+        level.size += order.size;
+        level.orders += 1;
+    }
+    levels.into_values().collect()
+}
+
+/// Reads and decodes the order book of the market at `market`, created by the `simple_market`
+/// package published by `module_owner`.
+pub fn order_book(
+    database: &AptosDatabase,
+    module_owner: AccountAddress,
+    market: AccountAddress,
+) -> Result<OrderBookSnapshot> {
+    let value = database
+        .get_state_value(&order_book_key(module_owner, market)?)?
+        .ok_or_else(|| anyhow!("no order book at {}", market))?;
+    decode_order_book(value.bytes())
+}
+
+/// Returns the key of the order book of the market at `market`, created by the `simple_market`
+/// package published by `module_owner`.
+pub fn order_book_key(module_owner: AccountAddress, market: AccountAddress) -> Result<StateKey> {
+    let tag = StructTag {
+        address: module_owner,
+        module: Identifier::new(ORDER_BOOK_MODULE)?,
+        name: Identifier::new(ORDER_BOOK_RESOURCE)?,
+        type_args: vec![],
+    };
+    StateKey::resource(&market, &tag).map_err(|_| anyhow!("failed to derive order book key"))
+}
+
+/// The coin type that the simple_market package trades as base asset.
+pub fn base_coin(module_owner: AccountAddress) -> Result<StructTag> {
+    market_coin(module_owner, "BaseCoin")
+}
+
+/// The coin type that the simple_market package trades as quote asset.
+pub fn quote_coin(module_owner: AccountAddress) -> Result<StructTag> {
+    market_coin(module_owner, "QuoteCoin")
+}
+
+fn market_coin(module_owner: AccountAddress, name: &str) -> Result<StructTag> {
+    Ok(StructTag {
+        address: module_owner,
+        module: Identifier::new("coins")?,
+        name: Identifier::new(name)?,
+        type_args: vec![],
+    })
+}
+
+/// Decodes an order book resource into its price levels.
+pub fn decode_order_book(bytes: &[u8]) -> Result<OrderBookSnapshot> {
+    let book: OrderBookResource = bcs::from_bytes(bytes)?;
+    Ok(OrderBookSnapshot::from_orders(&book.bids, &book.asks))
+}
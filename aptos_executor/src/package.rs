@@ -0,0 +1,121 @@
+//! Loading of the compiled Move packages published by `transaction_builder::publish_package`.
+
+use anyhow::{bail, Context as _, Result};
+use move_core_types::account_address::AccountAddress;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+#[cfg(test)]
+#[path = "tests/package_tests.rs"]
+pub mod package_tests;
+
+/// Reads the metadata and the compiled modules of a package built by the Move compiler, checking that
+/// the metadata declares exactly the modules found in `bytecode_modules`.
+pub fn load_package_artifacts(package_dir: &Path) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
+    let metadata_path = package_dir.join("package-metadata.bcs");
+    let metadata = std::fs::read(&metadata_path).with_context(|| {
+        format!(
+            "failed to read package metadata at {}",
+            metadata_path.display()
+        )
+    })?;
+
+    let modules_dir = package_dir.join("bytecode_modules");
+    let mut module_paths = Vec::new();
+    for entry in std::fs::read_dir(&modules_dir).with_context(|| {
+        format!(
+            "failed to list module directory at {}",
+            modules_dir.display()
+        )
+    })? {
+        let entry = entry
+            .with_context(|| format!("failed to read entry inside {}", modules_dir.display()))?;
+        module_paths.push(entry.path());
+    }
+    module_paths.sort();
+
+    let mut modules = Vec::new();
+    for path in module_paths {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("mv") {
+            continue;
+        }
+        let bytes = std::fs::read(&path)
+            .with_context(|| format!("failed to read compiled module at {}", path.display()))?;
+        modules.push(bytes);
+    }
+
+    if modules.is_empty() {
+        bail!("no compiled modules found in {}", modules_dir.display());
+    }
+
+    let module_names: Vec<String> = module_paths
+        .iter()
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("mv"))
+        .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()))
+        .map(str::to_string)
+        .collect();
+    validate_package(&metadata, &module_names)
+        .with_context(|| format!("invalid package at {}", package_dir.display()))?;
+
+    Ok((metadata, modules))
+}
+
+/// Mirrors `0x1::code::PackageMetadata`, as serialized in `package-metadata.bcs`.
+#[derive(Serialize, Deserialize)]
+struct PackageMetadata {
+    name: String,
+    upgrade_policy: UpgradePolicy,
+    upgrade_number: u64,
+    source_digest: String,
+    manifest: Vec<u8>,
+    modules: Vec<ModuleMetadata>,
+    deps: Vec<PackageDep>,
+    extension: Option<Any>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UpgradePolicy {
+    policy: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ModuleMetadata {
+    name: String,
+    source: Vec<u8>,
+    source_map: Vec<u8>,
+    extension: Option<Any>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PackageDep {
+    account: AccountAddress,
+    package_name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Any {
+    type_name: String,
+    data: Vec<u8>,
+}
+
+/// Checks that the modules declared by the package metadata are exactly the compiled modules
+/// (`bytecode_modules/<name>.mv`) about to be published.
+pub fn validate_package(metadata: &[u8], module_names: &[String]) -> Result<()> {
+    let metadata: PackageMetadata =
+        bcs::from_bytes(metadata).context("failed to decode package metadata")?;
+    let declared: BTreeSet<&str> = metadata.modules.iter().map(|x| x.name.as_str()).collect();
+    let present: BTreeSet<&str> = module_names.iter().map(String::as_str).collect();
+
+    let missing: Vec<_> = declared.difference(&present).collect();
+    let undeclared: Vec<_> = present.difference(&declared).collect();
+    if !missing.is_empty() || !undeclared.is_empty() {
+        bail!(
+            "metadata of package '{}' does not match its compiled modules: declared but missing {:?}, present but undeclared {:?}",
+            metadata.name,
+            missing,
+            undeclared
+        );
+    }
+    Ok(())
+}
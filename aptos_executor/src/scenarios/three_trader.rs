@@ -1,16 +1,34 @@
+use crate::database::{coin_store_key, decode_coin_balance, AptosDatabase};
+use crate::order_book::{
+    base_coin, decode_order_book, order_book_key, quote_coin, OrderBookSnapshot, PriceLevel,
+};
+use crate::package::load_package_artifacts;
 use crate::transaction_builder::{
     cancel_order_by_client_id, create_market, decrease_order_size_by_client_id, mint_trader_funds,
     place_limit_order_with_client_id, publish_package, register_trader, replace_order_by_client_id,
 };
-use crate::LocalAccount;
+use crate::{AptosVmExecutor, LocalAccount, TransactionResult};
 use anyhow::{bail, Context, Result};
-use aptos_types::{chain_id::ChainId, transaction::SignedTransaction};
+use aptos_types::{
+    chain_id::ChainId, contract_event::ContractEvent, state_store::state_key::StateKey,
+    transaction::SignedTransaction,
+};
+use move_core_types::{
+    account_address::AccountAddress,
+    language_storage::{StructTag, TypeTag},
+};
 use std::env;
+use std::fmt::Display;
 use std::fs::File;
+use std::future::Future;
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
+#[cfg(test)]
+#[path = "../tests/three_trader_tests.rs"]
+pub mod three_trader_tests;
+
 pub const DEFAULT_ALLOW_SELF_MATCHING: bool = false;
 pub const DEFAULT_ALLOW_EVENTS_EMISSION: bool = true;
 pub const DEFAULT_PRE_CANCEL_WINDOW: u64 = 60;
@@ -42,6 +60,17 @@ pub const TRADER_FUND_QUOTE: u64 = 1_000_000_000;
 
 pub const EXPECTED_SCENARIO_TXNS: usize = 15;
 
+/// The position in the scenario of trader A's first ask, which rests on the empty book.
+pub const TRADER_A_ASK_STEP: usize = 8;
+/// The position in the scenario of trader C's bid, which crosses trader B's ask.
+pub const TRADER_C_BUY_STEP: usize = 12;
+/// The size filled when trader C's bid crosses what is left of trader B's ask.
+pub const CROSSING_FILL_SIZE: u64 = TRADER_C_BUY_SIZE;
+/// Trades settle at the price of the resting (maker) order.
+pub const CROSSING_FILL_PRICE: u64 = TRADER_B_INITIAL_PRICE;
+/// What rests of trader A's final bid once it bought what is left of trader B's repriced ask.
+pub const TRADER_A_RESTING_SIZE: u64 = TRADER_A_FINAL_SIZE - TRADER_B_NEW_SIZE;
+
 const DEFAULT_PACKAGE_RELATIVE: &str =
     "Desktop/orderbook_poc/move/simple_market/build/simple_market";
 
@@ -50,6 +79,184 @@ pub struct ScenarioTxn {
     pub txn: SignedTransaction,
 }
 
+/// The accounts whose state the scenario changes.
+#[derive(Clone, Copy, Debug)]
+pub struct ScenarioAccounts {
+    pub module_owner: AccountAddress,
+    pub market: AccountAddress,
+    /// Traders A, B and C.
+    pub traders: [AccountAddress; 3],
+}
+
+impl ScenarioAccounts {
+    /// The accounts used by `build_three_trader_transactions`: the module owner trades as trader A,
+    /// and the accounts generated from the C and D seeds trade as traders B and C.
+    pub fn generate() -> Result<Self> {
+        let module_owner = LocalAccount::generate(TRADER_A_SEED)?.address;
+        Ok(Self {
+            module_owner,
+            market: LocalAccount::generate(TRADER_B_SEED)?.address,
+            traders: [
+                module_owner,
+                LocalAccount::generate(TRADER_C_SEED)?.address,
+                LocalAccount::generate(TRADER_D_SEED)?.address,
+            ],
+        })
+    }
+}
+
+/// The state of the market once every step of the scenario is executed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScenarioOutcome {
+    pub book: OrderBookSnapshot,
+    /// The (base, quote) balances of traders A, B and C.
+    pub balances: [(u64, u64); 3],
+}
+
+impl ScenarioOutcome {
+    /// The outcome of the scenario. Orders escrow what they may trade when placed (the base asset of
+    /// an ask, the quote asset of a bid at its limit price), cancelling or decreasing an order
+    /// releases its escrow, and trades settle at the price of the resting order. Trader A cancels
+    /// its ask, then its bid buys the two units of trader B's repriced ask and rests; trader B sells
+    /// the rest of its decreased ask to trader C and to trader A; trader C's bid is entirely filled.
+    pub fn expected() -> Self {
+        let crossing_quote = CROSSING_FILL_SIZE * CROSSING_FILL_PRICE;
+        let final_quote = TRADER_B_NEW_SIZE * TRADER_B_NEW_PRICE;
+        let resting_escrow = TRADER_A_RESTING_SIZE * TRADER_A_FINAL_PRICE;
+        Self {
+            book: OrderBookSnapshot {
+                bids: vec![PriceLevel {
+                    price: TRADER_A_FINAL_PRICE,
+                    size: TRADER_A_RESTING_SIZE,
+                    orders: 1,
+                }],
+                asks: Vec::new(),
+            },
+            balances: [
+                (
+                    TRADER_FUND_BASE + TRADER_B_NEW_SIZE,
+                    TRADER_FUND_QUOTE - final_quote - resting_escrow,
+                ),
+                (
+                    TRADER_FUND_BASE - CROSSING_FILL_SIZE - TRADER_B_NEW_SIZE,
+                    TRADER_FUND_QUOTE + crossing_quote + final_quote,
+                ),
+                (
+                    TRADER_FUND_BASE + CROSSING_FILL_SIZE,
+                    TRADER_FUND_QUOTE - crossing_quote,
+                ),
+            ],
+        }
+    }
+
+    /// The state keys from which the outcome is read: the order book of the market, then the base
+    /// and quote coin stores of every trader.
+    pub fn state_keys(accounts: &ScenarioAccounts) -> Result<Vec<StateKey>> {
+        let base = base_coin(accounts.module_owner)?;
+        let quote = quote_coin(accounts.module_owner)?;
+        let mut keys = vec![order_book_key(accounts.module_owner, accounts.market)?];
+        for trader in accounts.traders {
+            keys.push(coin_store_key(trader, &base)?);
+            keys.push(coin_store_key(trader, &quote)?);
+        }
+        Ok(keys)
+    }
+
+    /// Decodes the outcome from the values stored at the keys returned by `state_keys`, in the same
+    /// order.
+    pub fn decode(values: &[Vec<u8>]) -> Result<Self> {
+        let [book, stores @ ..] = values else {
+            bail!("the outcome is read from 7 state values, got none");
+        };
+        let [a_base, a_quote, b_base, b_quote, c_base, c_quote] = stores else {
+            bail!(
+                "the outcome is read from 7 state values, got {}",
+                values.len()
+            );
+        };
+        let balance = |bytes: &Vec<u8>| decode_coin_balance(bytes).context("malformed coin store");
+        Ok(Self {
+            book: decode_order_book(book).context("malformed order book")?,
+            balances: [
+                (balance(a_base)?, balance(a_quote)?),
+                (balance(b_base)?, balance(b_quote)?),
+                (balance(c_base)?, balance(c_quote)?),
+            ],
+        })
+    }
+
+    /// Reads the outcome from the state of an executor.
+    pub fn from_executor(executor: &AptosVmExecutor, accounts: &ScenarioAccounts) -> Result<Self> {
+        Self::from_database(executor.database(), accounts)
+    }
+
+    /// Reads the outcome from a database, failing if one of its state values is missing.
+    pub fn from_database(database: &AptosDatabase, accounts: &ScenarioAccounts) -> Result<Self> {
+        let values = Self::state_keys(accounts)?
+            .iter()
+            .map(|key| {
+                database
+                    .get_state_value(key)?
+                    .map(|value| value.bytes().to_vec())
+                    .with_context(|| format!("nothing stored at {:?}", key))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::decode(&values)
+    }
+
+    /// Fails, reporting both outcomes, unless this is the expected outcome.
+    pub fn check(&self) -> Result<()> {
+        let expected = Self::expected();
+        if *self != expected {
+            bail!(
+                "the market did not reach the expected state: got {:?}, expected {:?}",
+                self,
+                expected
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Checks that trader C's bid matched trader B's ask, given the (base, quote) balances of traders B
+/// and C before and after the bid: C receives the base asset that B escrowed when placing its ask,
+/// and B receives the quote asset C escrowed with its bid.
+pub fn check_crossing_trade(before: &[(u64, u64)], after: &[(u64, u64)]) -> Result<()> {
+    let quote_amount = CROSSING_FILL_SIZE * CROSSING_FILL_PRICE;
+    let [(b_base, b_quote), (c_base, c_quote)] = before else {
+        bail!("expected the balances of traders B and C");
+    };
+    let (Some(c_quote), Some(b_quote)) = (
+        c_quote.checked_sub(quote_amount),
+        b_quote.checked_add(quote_amount),
+    ) else {
+        bail!(
+            "trader C cannot pay {} quote for the crossing trade: balances {:?}",
+            quote_amount,
+            before
+        );
+    };
+    let expected = [(*b_base, b_quote), (c_base + CROSSING_FILL_SIZE, c_quote)];
+    if after != expected {
+        bail!(
+            "trader C's bid did not match trader B's ask: balances went from {:?} to {:?}, expected {:?}",
+            before,
+            after,
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// Locates the compiled `simple_market` package (`move/simple_market` at the root of the repository),
+/// which is built with:
+///
+/// ```text
+/// aptos move compile --package-dir move/simple_market --save-metadata
+/// ```
+///
+/// The tests executing the package are ignored by default; once it is built, run them with
+/// `cargo test -p aptos_executor -- --ignored`.
 pub fn resolve_package_dir() -> Result<PathBuf> {
     if let Ok(path) = env::var("HYDRANGEA_MARKET_PACKAGE_DIR") {
         let candidate = PathBuf::from(path);
@@ -67,6 +274,13 @@ pub fn resolve_package_dir() -> Result<PathBuf> {
         return Ok(workspace_candidate);
     }
 
+    // Tests run from the directory of this crate.
+    let crate_candidate =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../move/simple_market/build/simple_market");
+    if crate_candidate.exists() {
+        return Ok(crate_candidate);
+    }
+
     if let Ok(root) = env::var("ORDERBOOK_POC_ROOT") {
         let candidate = PathBuf::from(root).join("move/simple_market/build/simple_market");
         if candidate.exists() {
@@ -84,9 +298,42 @@ pub fn resolve_package_dir() -> Result<PathBuf> {
     bail!("unable to locate compiled simple_market package; set HYDRANGEA_MARKET_PACKAGE_DIR")
 }
 
+/// The address of the `aptos_experimental` package, whose market emits the order events.
+pub const MARKET_EVENTS_ADDRESS: AccountAddress = {
+    let mut address = [0; AccountAddress::LENGTH];
+    address[AccountAddress::LENGTH - 1] = 7;
+    AccountAddress::new(address)
+};
+
+/// Returns the events the market emitted for the orders of a transaction (none if the market was
+/// created without events emission), leaving out the events of the framework (e.g. fees).
+pub fn market_events(result: &TransactionResult) -> Vec<&ContractEvent> {
+    result
+        .events()
+        .iter()
+        .filter(|event| {
+            matches!(event.type_tag(), TypeTag::Struct(tag) if tag.address == MARKET_EVENTS_ADDRESS)
+        })
+        .collect()
+}
+
 pub fn build_three_trader_transactions(
     package_dir: &Path,
     chain_id: ChainId,
+) -> Result<Vec<ScenarioTxn>> {
+    build_three_trader_transactions_with_events(
+        package_dir,
+        chain_id,
+        DEFAULT_ALLOW_EVENTS_EMISSION,
+    )
+}
+
+/// Same as `build_three_trader_transactions`, but creating a market that emits order events only if
+/// `allow_events_emission` is set.
+pub fn build_three_trader_transactions_with_events(
+    package_dir: &Path,
+    chain_id: ChainId,
+    allow_events_emission: bool,
 ) -> Result<Vec<ScenarioTxn>> {
     let mut trader_a = LocalAccount::generate(TRADER_A_SEED)?;
     let market_signer = LocalAccount::generate(TRADER_B_SEED)?;
@@ -114,12 +361,16 @@ pub fn build_three_trader_transactions(
     });
 
     transactions.push(ScenarioTxn {
-        label: "Create market (no self-match, emit events)".to_string(),
+        label: match allow_events_emission {
+            true => "Create market (no self-match, emit events)",
+            false => "Create market (no self-match, no events)",
+        }
+        .to_string(),
         txn: create_market(
             &mut trader_a,
             &market_signer,
             DEFAULT_ALLOW_SELF_MATCHING,
-            DEFAULT_ALLOW_EVENTS_EMISSION,
+            allow_events_emission,
             DEFAULT_PRE_CANCEL_WINDOW,
             chain_id,
         )
@@ -339,42 +590,69 @@ pub fn wait_for_execution_logs(path: &Path, expected: usize, timeout: Duration)
     );
 }
 
-fn load_package_artifacts(package_dir: &Path) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
-    let metadata_path = package_dir.join("package-metadata.bcs");
-    let metadata = std::fs::read(&metadata_path).with_context(|| {
-        format!(
-            "failed to read package metadata at {}",
-            metadata_path.display()
-        )
-    })?;
-
-    let modules_dir = package_dir.join("bytecode_modules");
-    let mut module_paths = Vec::new();
-    for entry in std::fs::read_dir(&modules_dir).with_context(|| {
-        format!(
-            "failed to list module directory at {}",
-            modules_dir.display()
-        )
-    })? {
-        let entry = entry
-            .with_context(|| format!("failed to read entry inside {}", modules_dir.display()))?;
-        module_paths.push(entry.path());
-    }
-    module_paths.sort();
+/// How a worker answered the submission of a scenario step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StepAck {
+    Accepted,
+    Rejected(String),
+}
 
-    let mut modules = Vec::new();
-    for path in module_paths {
-        if path.extension().and_then(|ext| ext.to_str()) != Some("mv") {
-            continue;
-        }
-        let bytes = std::fs::read(&path)
-            .with_context(|| format!("failed to read compiled module at {}", path.display()))?;
-        modules.push(bytes);
-    }
+/// The number of workers that must accept a step: with one worker per authority and equal stakes,
+/// a quorum of authorities then has the step, so that it eventually gets certified.
+pub fn worker_quorum(workers: usize) -> usize {
+    workers * 2 / 3 + 1
+}
 
-    if modules.is_empty() {
-        bail!("no compiled modules found in {}", modules_dir.display());
+/// Submits a step to every worker with `submit`, resubmitting it to the workers that rejected it or
+/// could not be reached, until at least `quorum` of them accepted it. Later steps depend on earlier
+/// ones, so the scenario must not proceed with a step that may never be certified: fails with the
+/// reason given by every worker that did not accept the step after `attempts` rounds.
+pub async fn submit_step_to_quorum<A, F, Fut, E>(
+    workers: &[A],
+    quorum: usize,
+    attempts: u32,
+    retry_delay: Duration,
+    mut submit: F,
+) -> Result<Vec<A>>
+where
+    A: Copy + Display,
+    F: FnMut(A) -> Fut,
+    Fut: Future<Output = std::result::Result<StepAck, E>>,
+    E: Display,
+{
+    let mut accepted = Vec::new();
+    let mut pending: Vec<A> = workers.to_vec();
+    let mut failures = Vec::new();
+    for attempt in 0..attempts.max(1) {
+        if attempt > 0 {
+            tokio::time::sleep(retry_delay).await;
+        }
+        failures.clear();
+        let mut still_pending = Vec::new();
+        for worker in pending {
+            match submit(worker).await {
+                Ok(StepAck::Accepted) => accepted.push(worker),
+                Ok(StepAck::Rejected(reason)) => {
+                    failures.push(format!("{} rejected it: {}", worker, reason));
+                    still_pending.push(worker);
+                }
+                Err(e) => {
+                    failures.push(format!("{} is unreachable: {}", worker, e));
+                    still_pending.push(worker);
+                }
+            }
+        }
+        pending = still_pending;
+        if accepted.len() >= quorum {
+            return Ok(accepted);
+        }
     }
-
-    Ok((metadata, modules))
+    bail!(
+        "only {} of {} workers accepted the step (quorum {}) after {} attempts; {}",
+        accepted.len(),
+        workers.len(),
+        quorum,
+        attempts.max(1),
+        failures.join("; ")
+    )
 }
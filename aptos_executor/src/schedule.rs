@@ -0,0 +1,114 @@
+//! Conflict analysis and parallel scheduling of the transactions of a block.
+
+use aptos_types::transaction::{SignedTransaction, TransactionPayload};
+use move_core_types::account_address::AccountAddress;
+use std::collections::{BTreeSet, HashMap};
+
+#[cfg(test)]
+#[path = "tests/schedule_tests.rs"]
+pub mod schedule_tests;
+
+/// Entry functions whose first argument is the recipient of an APT transfer.
+const TRANSFER_FUNCTIONS: &[(&str, &str)] = &[
+    ("coin", "transfer"),
+    ("aptos_account", "transfer"),
+    ("aptos_account", "transfer_coins"),
+];
+
+/// The accounts whose state a transaction may read or write, or `None` if they cannot be told
+/// from its payload (in which case the transaction conflicts with every other one).
+///
+/// Only APT transfers are analyzed: they touch their signers (sender, secondary signers and fee
+/// payer) and their recipient. The global APT supply is also updated by every transaction, but
+/// through an aggregator whose updates commute, so it is not a conflict.
+pub fn touched_accounts(txn: &SignedTransaction) -> Option<BTreeSet<AccountAddress>> {
+    let entry_function = match txn.payload() {
+        TransactionPayload::EntryFunction(entry_function) => entry_function,
+        _ => return None,
+    };
+    let module = entry_function.module();
+    let is_transfer = *module.address() == AccountAddress::ONE
+        && TRANSFER_FUNCTIONS.iter().any(|(name, function)| {
+            module.name().as_str() == *name && entry_function.function().as_str() == *function
+        });
+    if !is_transfer {
+        return None;
+    }
+    let recipient: AccountAddress = bcs::from_bytes(entry_function.args().first()?).ok()?;
+
+    let authenticator = txn.authenticator_ref();
+    let mut accounts = BTreeSet::new();
+    accounts.insert(txn.sender());
+    accounts.insert(recipient);
+    accounts.extend(authenticator.secondary_signer_addresses());
+    accounts.extend(authenticator.fee_payer_address());
+    Some(accounts)
+}
+
+/// Whether two transactions may touch the same state, in which case their relative order matters.
+pub fn conflicts(a: &SignedTransaction, b: &SignedTransaction) -> bool {
+    match (touched_accounts(a), touched_accounts(b)) {
+        (Some(a), Some(b)) => !a.is_disjoint(&b),
+        _ => true,
+    }
+}
+
+/// A block split into groups of transactions that do not conflict with each other. Groups hold
+/// indices into the planned block.
+///
+/// Executing the groups in order (and the transactions of a group in any order, possibly in
+/// parallel) yields the same result as executing the block sequentially: two conflicting
+/// transactions always end up in different groups, with the earlier one in the earlier group.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParallelPlan {
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl ParallelPlan {
+    /// Returns the indices of the transactions in execution order, group after group.
+    pub fn order(&self) -> Vec<usize> {
+        self.groups.iter().flatten().copied().collect()
+    }
+
+    /// Returns the number of transactions of the largest group.
+    pub fn max_parallelism(&self) -> usize {
+        self.groups.iter().map(Vec::len).max().unwrap_or(0)
+    }
+}
+
+/// Plans the execution of a block in groups of non-conflicting transactions. Each transaction is
+/// placed in the group right after the last group holding a transaction it conflicts with.
+pub fn plan_parallel_schedule(txns: &[SignedTransaction]) -> ParallelPlan {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    // The last group that touched each account.
+    let mut last_group: HashMap<AccountAddress, usize> = HashMap::new();
+    // The last group holding a transaction that conflicts with every other one.
+    let mut barrier: Option<usize> = None;
+
+    for (index, txn) in txns.iter().enumerate() {
+        let group = match touched_accounts(txn) {
+            Some(accounts) => {
+                let after = accounts
+                    .iter()
+                    .filter_map(|account| last_group.get(account).copied())
+                    .chain(barrier)
+                    .max();
+                let group = after.map_or(0, |x| x + 1);
+                for account in accounts {
+                    last_group.insert(account, group);
+                }
+                group
+            }
+            None => {
+                let group = groups.len();
+                barrier = Some(group);
+                group
+            }
+        };
+        if group == groups.len() {
+            groups.push(Vec::new());
+        }
+        groups[group].push(index);
+    }
+    ParallelPlan { groups }
+}
@@ -0,0 +1,86 @@
+//! Assertion helpers for tests that execute transactions through the VM.
+
+use crate::database::AptosDatabase;
+use crate::executor::TransactionResult;
+use aptos_types::{
+    transaction::{ExecutionStatus, TransactionStatus},
+    vm_status::VMStatus,
+};
+use std::fmt::Write as _;
+
+#[cfg(test)]
+#[path = "tests/test_utils_tests.rs"]
+pub mod test_utils_tests;
+
+/// Panics with a per-transaction breakdown unless every transaction executed successfully.
+pub fn assert_all_executed(results: &[TransactionResult]) {
+    let failures: Vec<_> = results
+        .iter()
+        .enumerate()
+        .filter(|(_, result)| !matches!(result.status(), VMStatus::Executed))
+        .collect();
+    if failures.is_empty() {
+        return;
+    }
+
+    let mut message = format!(
+        "{} of {} transactions did not execute:",
+        failures.len(),
+        results.len()
+    );
+    for (index, result) in failures {
+        let _ = write!(message, "\n  transaction {}: {}", index, describe(result));
+    }
+    panic!("{}", message);
+}
+
+/// Panics with a description of the result unless the transaction executed successfully.
+pub fn assert_executed(result: &TransactionResult) {
+    if !matches!(result.status(), VMStatus::Executed) {
+        panic!("Transaction did not execute: {}", describe(result));
+    }
+}
+
+/// Panics unless the APT held by the accounts of `after` is the APT held in `before`, minus what the
+/// VM burned (as recorded by the supply), plus `expected_minted`: the APT credited without going
+/// through the supply, e.g. by `publish_account_resources`. APT minted or burned through the VM moves
+/// the supply and the balances together and needs no allowance.
+pub fn assert_apt_conserved(before: &AptosDatabase, after: &AptosDatabase, expected_minted: u128) {
+    let held = |database: &AptosDatabase| -> u128 {
+        database
+            .list_accounts()
+            .expect("Failed to list the accounts")
+            .iter()
+            .map(|(_, balance)| balance)
+            .sum()
+    };
+    let supply = |database: &AptosDatabase| -> u128 {
+        database
+            .apt_supply()
+            .expect("Failed to read the APT supply")
+    };
+    let (held_before, held_after) = (held(before), held(after));
+    let (supply_before, supply_after) = (supply(before), supply(after));
+
+    // Compare the sums rather than the differences, which may be negative.
+    if held_after + supply_before != held_before + supply_after + expected_minted {
+        panic!(
+            "APT is not conserved: accounts held {} then {} octas, the supply went from {} to {}, \
+             and {} octas were expected to be minted",
+            held_before, held_after, supply_before, supply_after, expected_minted
+        );
+    }
+}
+
+fn describe(result: &TransactionResult) -> String {
+    let abort_code = match result.output.status() {
+        TransactionStatus::Keep(ExecutionStatus::MoveAbort { code, .. }) => code.to_string(),
+        _ => "none".to_string(),
+    };
+    format!(
+        "status={:?}, abort_code={}, gas_used={}",
+        result.status(),
+        abort_code,
+        result.gas_used()
+    )
+}
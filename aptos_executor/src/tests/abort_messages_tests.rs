@@ -0,0 +1,110 @@
+use super::*;
+use crate::scenarios::three_trader::{resolve_package_dir, TRADER_A_SEED};
+use crate::LocalAccount;
+use aptos_types::vm::module_metadata::{RuntimeModuleMetadataV1, APTOS_METADATA_KEY_V1};
+use move_binary_format::file_format::{empty_module, Metadata};
+use move_core_types::identifier::Identifier;
+use move_core_types::{account_address::AccountAddress, errmap::ErrorDescription};
+use std::collections::BTreeMap;
+
+// Fixture
+fn module(address: AccountAddress, name: &str) -> AbortLocation {
+    AbortLocation::Module(ModuleId::new(address, Identifier::new(name).unwrap()))
+}
+
+// Fixture: compiles an empty module `address::name` whose metadata holds the given error map, as
+// the compiler writes it.
+fn compiled_module(address: AccountAddress, name: &str, errors: &[(u64, &str, &str)]) -> Vec<u8> {
+    let error_map: BTreeMap<_, _> = errors
+        .iter()
+        .map(|(code, name, description)| {
+            let error = ErrorDescription {
+                code_name: name.to_string(),
+                code_description: description.to_string(),
+            };
+            (*code, error)
+        })
+        .collect();
+    let metadata = RuntimeModuleMetadataV1 {
+        error_map,
+        ..RuntimeModuleMetadataV1::default()
+    };
+
+    let mut module = empty_module();
+    module.address_identifiers[0] = address;
+    module.identifiers[0] = Identifier::new(name).unwrap();
+    module.metadata.push(Metadata {
+        key: APTOS_METADATA_KEY_V1.to_vec(),
+        value: bcs::to_bytes(&metadata).unwrap(),
+    });
+    let mut bytes = Vec::new();
+    module.serialize(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn constant_names_read_as_messages() {
+    assert_eq!(
+        constant_message("ESELF_MATCH_NOT_ALLOWED"),
+        "self match not allowed"
+    );
+    assert_eq!(constant_message("EORDER_NOT_FOUND"), "order not found");
+}
+
+#[test]
+fn error_maps_are_read_from_the_module_metadata() {
+    let package = AccountAddress::from_hex_literal("0xcafe").unwrap();
+    let modules = vec![
+        compiled_module(
+            package,
+            "market",
+            &[
+                (6, "EORDER_NOT_FOUND", ""),
+                (9, "ESELF_MATCH", "Self-match not allowed"),
+            ],
+        ),
+        compiled_module(package, "vault", &[(4, "EINSUFFICIENT_ESCROW", "")]),
+    ];
+    let messages = AbortMessages::from_compiled_modules(&modules).unwrap();
+
+    // The doc comment of a constant takes precedence over its name.
+    let market = module(package, "market");
+    assert_eq!(messages.message(&market, 6), Some("order not found"));
+    assert_eq!(messages.message(&market, 9), Some("Self-match not allowed"));
+    // Codes are scoped to their module.
+    assert_eq!(messages.message(&market, 4), None);
+    assert_eq!(
+        messages.message(&module(package, "vault"), 4),
+        Some("insufficient escrow")
+    );
+    // So are modules to the address of their package.
+    let elsewhere = module(AccountAddress::ONE, "market");
+    assert_eq!(messages.message(&elsewhere, 6), None);
+    assert_eq!(messages.message(&AbortLocation::Script, 6), None);
+}
+
+#[test]
+fn modules_without_error_maps_are_rejected() {
+    let modules = vec![compiled_module(AccountAddress::ONE, "empty", &[])];
+    assert!(AbortMessages::from_compiled_modules(&modules).is_err());
+    assert!(AbortMessages::from_compiled_modules(&[vec![0xde, 0xad]]).is_err());
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn market_error_maps_are_loaded_from_the_package() {
+    let package_dir = resolve_package_dir().unwrap();
+    let messages = AbortMessages::from_package_dir(&package_dir).unwrap();
+
+    let module_owner = LocalAccount::generate(TRADER_A_SEED).unwrap().address;
+    let market_setup = module(module_owner, "market_setup");
+    assert_eq!(messages.message(&market_setup, 6), Some("order not found"));
+    assert_eq!(
+        messages.message(&market_setup, 7),
+        Some("batch length mismatch")
+    );
+    assert_eq!(
+        messages.message(&module(module_owner, "vault"), 4),
+        Some("insufficient escrow")
+    );
+}
@@ -0,0 +1,414 @@
+use super::*;
+use std::sync::atomic::AtomicUsize;
+
+#[test]
+fn list_accounts_returns_bootstrapped_accounts() {
+    let database = AptosDatabase::new_with_genesis().unwrap();
+    let mut expected = Vec::new();
+    for (seed, balance) in [(1, 1_000u64), (2, 2_000), (3, 3_000)] {
+        let account = LocalAccount::generate(seed).unwrap();
+        database
+            .publish_account_resources(&account, balance)
+            .unwrap();
+        expected.push((account.address, u128::from(balance + DEFAULT_GAS_BUFFER)));
+    }
+    expected.sort_by_key(|(address, _)| *address);
+
+    // Genesis publishes the framework accounts at reserved addresses; ignore them.
+    let accounts: Vec<_> = database
+        .list_accounts()
+        .unwrap()
+        .into_iter()
+        .filter(|(address, _)| !address.is_special())
+        .collect();
+    assert_eq!(accounts, expected);
+}
+
+#[test]
+fn funding_beyond_genesis_supply_fails() {
+    let supply = GenesisSupply {
+        initial: 3 * u128::from(DEFAULT_GAS_BUFFER),
+        maximum: Some(4 * u128::from(DEFAULT_GAS_BUFFER)),
+    };
+    let database = AptosDatabase::new_with_genesis_supply(supply).unwrap();
+
+    // The first account (plus its gas buffer) fits in the supply.
+    let first = LocalAccount::generate(1).unwrap();
+    assert!(database
+        .publish_account_resources(&first, DEFAULT_GAS_BUFFER)
+        .is_ok());
+
+    // The second one would bring the total above it.
+    let second = LocalAccount::generate(2).unwrap();
+    let error = database
+        .publish_account_resources(&second, DEFAULT_GAS_BUFFER)
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("exceed the genesis APT supply"),
+        "{}",
+        error
+    );
+
+    // Only the first account was published.
+    let accounts: Vec<_> = database
+        .list_accounts()
+        .unwrap()
+        .into_iter()
+        .map(|(address, _)| address)
+        .collect();
+    assert!(accounts.contains(&first.address));
+    assert!(!accounts.contains(&second.address));
+}
+
+#[test]
+fn initial_supply_above_maximum_is_rejected() {
+    let supply = GenesisSupply {
+        initial: 10,
+        maximum: Some(5),
+    };
+    assert!(AptosDatabase::new_with_genesis_supply(supply).is_err());
+}
+
+#[test]
+fn accounts_are_published_in_parallel() {
+    const ACCOUNTS: u64 = 500;
+    let accounts: Vec<_> = (0..ACCOUNTS)
+        .map(|seed| LocalAccount::generate(seed).unwrap())
+        .collect();
+    let batch: Vec<_> = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, account)| (account, 1_000 + i as u64))
+        .collect();
+
+    let sequential = AptosDatabase::new_with_genesis().unwrap();
+    for (account, balance) in &batch {
+        sequential
+            .publish_account_resources(account, *balance)
+            .unwrap();
+    }
+
+    let parallel = AptosDatabase::new_with_genesis().unwrap();
+    let version = parallel.reader.latest_version();
+    parallel.publish_accounts(&batch).unwrap();
+
+    // Every account is funded as if published one by one, in a single version.
+    assert_eq!(parallel.reader.latest_version(), version + 1);
+    for (account, balance) in &batch {
+        assert_eq!(
+            parallel.account_balance(account.address).unwrap(),
+            u128::from(balance + DEFAULT_GAS_BUFFER)
+        );
+        let resource = parallel.account_resource(account.address).unwrap().unwrap();
+        assert_eq!(resource.sequence_number(), 0);
+    }
+    assert_eq!(
+        parallel.list_accounts().unwrap(),
+        sequential.list_accounts().unwrap()
+    );
+    assert_eq!(parallel.state_root(), sequential.state_root());
+}
+
+#[test]
+fn accounts_beyond_genesis_supply_are_not_published() {
+    let supply = GenesisSupply {
+        initial: 3 * u128::from(DEFAULT_GAS_BUFFER),
+        maximum: None,
+    };
+    let database = AptosDatabase::new_with_genesis_supply(supply).unwrap();
+    let first = LocalAccount::generate(1).unwrap();
+    let second = LocalAccount::generate(2).unwrap();
+    let version = database.reader.latest_version();
+
+    // Together, the accounts exceed the supply: neither is published.
+    let batch = [(&first, DEFAULT_GAS_BUFFER), (&second, DEFAULT_GAS_BUFFER)];
+    assert!(database.publish_accounts(&batch).is_err());
+    assert_eq!(database.reader.latest_version(), version);
+    assert!(database.account_resource(first.address).unwrap().is_none());
+}
+
+#[test]
+fn write_set_is_applied_without_the_vm() {
+    let database = AptosDatabase::new_with_genesis().unwrap();
+    let address = LocalAccount::generate(9).unwrap().address;
+    let key = StateKey::resource(&address, &AccountResource::struct_tag()).unwrap();
+    assert!(database.get_state_value(&key).unwrap().is_none());
+
+    // A hand-built write set creating the account resource of a new address.
+    let resource = AccountResource::new(
+        5,
+        vec![0; 32],
+        EventHandle::new(EventKey::new(0, address), 0),
+        EventHandle::new(EventKey::new(1, address), 0),
+    );
+    let bytes = bcs::to_bytes(&resource).unwrap();
+    let write_set = aptos_types::write_set::WriteSetMut::new(vec![(
+        key.clone(),
+        WriteOp::legacy_creation(bytes.into()),
+    )])
+    .freeze()
+    .unwrap();
+
+    let version = database.reader().latest_version();
+    database.apply_write_set(&write_set);
+    assert_eq!(database.reader().latest_version(), version + 1);
+
+    let value = database.get_state_value(&key).unwrap().unwrap();
+    let stored: AccountResource = bcs::from_bytes(value.bytes()).unwrap();
+    assert_eq!(stored.sequence_number(), 5);
+}
+
+/// A forked state counting how many values are fetched from it.
+#[derive(Default)]
+struct MockSource {
+    values: HashMap<StateKey, StateValue>,
+    fetches: AtomicUsize,
+}
+
+impl StateSource for MockSource {
+    fn fetch(&self, key: &StateKey) -> StorageResult<Option<StateValue>> {
+        self.fetches.fetch_add(1, Ordering::SeqCst);
+        Ok(self.values.get(key).cloned())
+    }
+}
+
+// Fixture
+fn raw_state(name: &str, value: &str) -> (StateKey, StateValue) {
+    (
+        StateKey::raw(name.as_bytes()),
+        StateValue::new_legacy(value.as_bytes().to_vec().into()),
+    )
+}
+
+#[test]
+fn evicted_forked_values_are_fetched_again() {
+    let mut source = MockSource::default();
+    let states: Vec<_> = ["a", "b", "c"]
+        .iter()
+        .map(|name| raw_state(name, &format!("remote {}", name)))
+        .collect();
+    source.values.extend(states.iter().cloned());
+    let source = Arc::new(source);
+    let reader = TestDbReader::forking(source.clone(), 2);
+    let fetches = || source.fetches.load(Ordering::SeqCst);
+    let (a, b, c) = (&states[0], &states[1], &states[2]);
+
+    // Cached values are not fetched again.
+    assert_eq!(reader.get_state_value(&a.0).unwrap(), Some(a.1.clone()));
+    assert_eq!(reader.get_state_value(&b.0).unwrap(), Some(b.1.clone()));
+    assert_eq!(reader.get_state_value(&a.0).unwrap(), Some(a.1.clone()));
+    assert_eq!(fetches(), 2);
+
+    // Reading a third value evicts the least recently read one, which is fetched again when read.
+    assert_eq!(reader.get_state_value(&c.0).unwrap(), Some(c.1.clone()));
+    assert_eq!(reader.get_state_value(&a.0).unwrap(), Some(a.1.clone()));
+    assert_eq!(fetches(), 3);
+    assert_eq!(reader.get_state_value(&b.0).unwrap(), Some(b.1.clone()));
+    assert_eq!(fetches(), 4);
+
+    // Local writes and deletions take precedence over the forked state, and are never evicted.
+    let (_, local) = raw_state("c", "local c");
+    reader.set_state_value(c.0.clone(), local.clone());
+    reader.remove_state_value(&a.0);
+    for (key, _) in &states {
+        reader.get_state_value(key).unwrap();
+    }
+    assert_eq!(reader.get_state_value(&c.0).unwrap(), Some(local));
+    assert_eq!(reader.get_state_value(&a.0).unwrap(), None);
+}
+
+/// A forked state that cannot be read.
+struct UnreachableSource;
+
+impl StateSource for UnreachableSource {
+    fn fetch(&self, _key: &StateKey) -> StorageResult<Option<StateValue>> {
+        Err(aptos_storage_interface::AptosDbError::Other(
+            "unreachable".to_string(),
+        ))
+    }
+}
+
+#[test]
+fn forked_state_failures_are_reported() {
+    let reader = TestDbReader::forking(Arc::new(UnreachableSource), 2);
+    let (key, value) = raw_state("a", "local a");
+    assert!(reader.get_state_value(&key).is_err());
+
+    // Values written locally are still read.
+    reader.set_state_value(key.clone(), value.clone());
+    assert_eq!(reader.get_state_value(&key).unwrap(), Some(value));
+}
+
+#[test]
+fn in_memory_state_is_never_evicted() {
+    let reader = TestDbReader::new();
+    let states: Vec<_> = (0..100)
+        .map(|i| raw_state(&i.to_string(), "value"))
+        .collect();
+    for (key, value) in &states {
+        reader.set_state_value(key.clone(), value.clone());
+    }
+    for (key, value) in &states {
+        assert_eq!(reader.get_state_value(key).unwrap(), Some(value.clone()));
+    }
+}
+
+#[test]
+fn accounts_funded_via_transactions_can_transfer() {
+    use crate::executor::{AptosVmExecutor, KeptOrDiscarded};
+    use crate::transaction_builder::apt_transfer;
+    use aptos_types::vm_status::VMStatus;
+
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut treasury = LocalAccount::generate(1).unwrap();
+    let mut sender = LocalAccount::generate(2).unwrap();
+    let recipient = LocalAccount::generate(3).unwrap();
+    let database = executor.database();
+    database
+        .publish_account_resources(&treasury, 100_000_000)
+        .unwrap();
+
+    // Neither account exists yet: the VM creates them along with their balances.
+    database
+        .fund_via_transactions(
+            &mut treasury,
+            &[(sender.address, 10_000_000), (recipient.address, 1)],
+        )
+        .unwrap();
+    assert_eq!(treasury.sequence_number, 2);
+    assert_eq!(
+        database.account_balance(sender.address).unwrap(),
+        10_000_000
+    );
+    assert_eq!(database.account_balance(recipient.address).unwrap(), 1);
+    let resource = database.account_resource(sender.address).unwrap().unwrap();
+    assert_eq!(resource.sequence_number(), 0);
+
+    // The funded account pays for its own transfer.
+    let chain_id = executor.chain_id();
+    let transfer = apt_transfer(&mut sender, recipient.address, 1_000, chain_id).unwrap();
+    let results = executor.execute_block(&[transfer]);
+    assert_eq!(results[0].keep_or_discard(), KeptOrDiscarded::Kept);
+    assert_eq!(results[0].status(), &VMStatus::Executed);
+    let database = executor.database();
+    assert_eq!(database.account_balance(recipient.address).unwrap(), 1_001);
+    assert!(database.account_balance(sender.address).unwrap() < 10_000_000 - 1_000);
+
+    // A treasury without enough APT stops at the first failed transfer.
+    let poor = LocalAccount::generate(4).unwrap();
+    assert!(database
+        .fund_via_transactions(&mut treasury, &[(poor.address, u64::MAX)])
+        .is_err());
+    assert!(database.account_resource(poor.address).unwrap().is_none());
+    // The treasury keeps the sequence number it has on chain.
+    let resource = database
+        .account_resource(treasury.address)
+        .unwrap()
+        .unwrap();
+    assert_eq!(treasury.sequence_number, resource.sequence_number());
+}
+
+#[test]
+fn databases_share_genesis_but_not_state() {
+    // The first database generates the genesis, unless another test already did.
+    let first = AptosDatabase::new_with_genesis().unwrap();
+
+    // Later databases only copy it.
+    let second = AptosDatabase::new_with_genesis().unwrap();
+
+    // Both start from the same state but evolve independently.
+    assert_eq!(first.state_root(), second.state_root());
+    let account = LocalAccount::generate(1).unwrap();
+    first.publish_account_resources(&account, 1_000).unwrap();
+    assert!(first.account_resource(account.address).unwrap().is_some());
+    assert!(second.account_resource(account.address).unwrap().is_none());
+    assert_ne!(first.state_root(), second.state_root());
+}
+
+#[test]
+fn framework_hash_is_stable_across_constructions() {
+    let first = AptosDatabase::new_with_framework(GenesisSupply::default(), GenesisFramework::Head)
+        .unwrap()
+        .framework()
+        .unwrap();
+    let second = AptosDatabase::new_with_genesis()
+        .unwrap()
+        .framework()
+        .unwrap();
+    assert_eq!(first.release, GenesisFramework::Head);
+    assert_eq!(first, second);
+
+    // The hash does not depend on the cache: generating the genesis again yields the same hash.
+    let generated = generate_genesis_change_set_for_mainnet(GenesisOptions::Head);
+    assert_eq!(first.hash, genesis_hash(generated.write_set()));
+
+    // The framework of an imported state is not known.
+    let snapshot = AptosDatabase::new_with_genesis()
+        .unwrap()
+        .export_snapshot(ChainId::test());
+    let imported = AptosDatabase::from_snapshot(snapshot, ChainId::test()).unwrap();
+    assert_eq!(imported.framework(), None);
+}
+
+#[test]
+fn snapshot_of_another_chain_is_rejected() {
+    let database = AptosDatabase::new_with_genesis().unwrap();
+    let account = LocalAccount::generate(1).unwrap();
+    database.publish_account_resources(&account, 1_000).unwrap();
+
+    // A snapshot imports on the chain it was taken on.
+    let imported =
+        AptosDatabase::from_snapshot(database.export_snapshot(ChainId::test()), ChainId::test())
+            .unwrap();
+    assert_eq!(imported.state_root(), database.state_root());
+    assert_eq!(
+        imported.account_balance(account.address).unwrap(),
+        database.account_balance(account.address).unwrap()
+    );
+
+    // But not on another chain, nor with another format.
+    assert!(AptosDatabase::from_snapshot(
+        database.export_snapshot(ChainId::test()),
+        ChainId::mainnet()
+    )
+    .is_err());
+    let mut snapshot = database.export_snapshot(ChainId::test());
+    snapshot.format = STATE_SNAPSHOT_FORMAT + 1;
+    assert!(AptosDatabase::from_snapshot(snapshot, ChainId::test()).is_err());
+}
+
+#[test]
+fn snapshot_keeps_the_version_supply_and_funding() {
+    let supply = GenesisSupply {
+        initial: 3 * u128::from(DEFAULT_GAS_BUFFER),
+        maximum: None,
+    };
+    let database = AptosDatabase::new_with_genesis_supply(supply).unwrap();
+    let first = LocalAccount::generate(1).unwrap();
+    database
+        .publish_account_resources(&first, DEFAULT_GAS_BUFFER)
+        .unwrap();
+
+    let snapshot = database.export_snapshot(ChainId::test());
+    let version = snapshot.version;
+    let imported = AptosDatabase::from_snapshot(snapshot, ChainId::test()).unwrap();
+    assert_eq!(imported.reader.latest_version(), version);
+    assert_eq!(imported.genesis_supply(), supply);
+
+    // The APT funded before the export still counts against the supply, even after a reset.
+    imported.reset_to_genesis();
+    let second = LocalAccount::generate(2).unwrap();
+    let error = imported
+        .publish_account_resources(&second, DEFAULT_GAS_BUFFER)
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("exceed the genesis APT supply"),
+        "{}",
+        error
+    );
+
+    // A snapshot funding more than its supply is rejected.
+    let mut snapshot = database.export_snapshot(ChainId::test());
+    snapshot.funded = supply.initial + 1;
+    assert!(AptosDatabase::from_snapshot(snapshot, ChainId::test()).is_err());
+}
@@ -0,0 +1,579 @@
+use super::*;
+use crate::transaction_builder::{apt_transfer, rotate_key_txn};
+use aptos_types::{
+    account_config::{primary_apt_store, NewBlockEvent},
+    on_chain_config::ValidatorSet,
+    state_store::state_key::inner::StateKeyInner,
+    transaction::authenticator::AuthenticationKey,
+};
+
+/// Whether the state key holds a resource of the account or of its primary APT store.
+fn belongs_to(change: &StateChange, address: AccountAddress) -> bool {
+    match change.state_key.inner() {
+        StateKeyInner::AccessPath(path) => {
+            path.address == address || path.address == primary_apt_store(address)
+        }
+        _ => false,
+    }
+}
+
+#[test]
+fn stale_sequence_number_is_discarded() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+
+    let chain_id = executor.chain_id();
+    let first = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+
+    // Build a second transfer reusing the sequence number of the first one.
+    sender.sequence_number = 0;
+    let stale = apt_transfer(&mut sender, recipient.address, 200, chain_id).unwrap();
+
+    let results = executor.execute_block(&[first, stale]);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].keep_or_discard(), KeptOrDiscarded::Kept);
+    assert_eq!(
+        results[1].keep_or_discard(),
+        KeptOrDiscarded::Discarded(StatusCode::SEQUENCE_NUMBER_TOO_OLD)
+    );
+}
+
+#[test]
+fn discarded_transactions_emit_no_events() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+
+    let chain_id = executor.chain_id();
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    sender.sequence_number = 0;
+    let stale = apt_transfer(&mut sender, recipient.address, 200, chain_id).unwrap();
+
+    // The transfer reports at least its fees; the stale transaction left no trace.
+    let results = executor.execute_block(&[transfer, stale]);
+    assert!(!results[0].events().is_empty());
+    assert!(results[1].events().is_empty());
+}
+
+#[test]
+fn executors_run_the_transactions_of_their_chain() {
+    let mut executor = AptosVmExecutor::new_with_chain_id(ChainId::mainnet()).unwrap();
+    assert_eq!(executor.chain_id(), ChainId::mainnet());
+    assert_eq!(executor.database().chain_id().unwrap(), ChainId::mainnet());
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+
+    // Transactions of another chain are turned away.
+    let foreign = apt_transfer(&mut sender, recipient.address, 100, ChainId::test()).unwrap();
+    sender.sequence_number = 0;
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, ChainId::mainnet()).unwrap();
+    let results = executor.execute_block(&[foreign, transfer]);
+    assert_eq!(
+        results[0].keep_or_discard(),
+        KeptOrDiscarded::Discarded(StatusCode::BAD_CHAIN_ID)
+    );
+    assert_eq!(results[1].status(), &VMStatus::Executed);
+
+    // The chain survives a reset.
+    executor.reset_to_genesis();
+    assert_eq!(executor.database().chain_id().unwrap(), ChainId::mainnet());
+}
+
+#[test]
+fn minting_beyond_the_maximum_supply_aborts() {
+    use crate::transaction_builder::{DEFAULT_GAS_UNIT_PRICE, DEFAULT_MAX_GAS_AMOUNT};
+    use aptos_cached_packages::aptos_stdlib;
+    use aptos_types::{state_store::state_value::StateValue, transaction::RawTransaction};
+    use move_core_types::identifier::Identifier;
+
+    // Leave room for 10 APT above the initial supply.
+    let headroom = 1_000_000_000;
+    let initial = GenesisSupply::default().initial;
+    let supply = GenesisSupply {
+        initial,
+        maximum: Some(initial + u128::from(headroom)),
+    };
+    let mut executor = AptosVmExecutor::with_genesis_supply(supply).unwrap();
+    let mut minter = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&minter, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+
+    // Hand the APT mint capability to the minter, as genesis does for the core resources account.
+    // `MintCapStore` only holds a `MintCapability`, which has no fields.
+    let mint_cap_store = StructTag {
+        address: AccountAddress::ONE,
+        module: Identifier::new("aptos_coin").unwrap(),
+        name: Identifier::new("MintCapStore").unwrap(),
+        type_args: vec![],
+    };
+    executor.database().reader().set_state_value(
+        StateKey::resource(&minter.address, &mint_cap_store).unwrap(),
+        StateValue::new_legacy(bcs::to_bytes(&false).unwrap().into()),
+    );
+
+    let chain_id = executor.chain_id();
+    let mut mint = |amount| {
+        let raw_txn = RawTransaction::new(
+            minter.address,
+            minter.next_sequence_number().unwrap(),
+            aptos_stdlib::aptos_coin_mint(recipient.address, amount),
+            DEFAULT_MAX_GAS_AMOUNT,
+            DEFAULT_GAS_UNIT_PRICE,
+            u64::MAX,
+            chain_id,
+        );
+        minter.sign(raw_txn).unwrap()
+    };
+    let within = mint(headroom / 2);
+    let beyond = mint(headroom);
+    let results = executor.execute_block(&[within, beyond]);
+
+    // Half of the headroom can be minted, but not the whole of it on top.
+    assert_eq!(results[0].status(), &VMStatus::Executed);
+    assert_eq!(results[1].keep_or_discard(), KeptOrDiscarded::Kept);
+    assert!(
+        matches!(results[1].status(), VMStatus::MoveAbort { .. }),
+        "{:?}",
+        results[1].status()
+    );
+    assert_eq!(
+        executor.account_balance(recipient.address).unwrap(),
+        u128::from(1_000_000 + crate::DEFAULT_GAS_BUFFER + headroom / 2)
+    );
+}
+
+#[test]
+fn simulated_transactions_leave_the_state_untouched() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+    let sender_balance = executor.account_balance(sender.address).unwrap();
+    let recipient_balance = executor.account_balance(recipient.address).unwrap();
+
+    let chain_id = executor.chain_id();
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    let result = executor.simulate(&transfer);
+    assert_eq!(result.status(), &VMStatus::Executed);
+    assert!(result.gas_used() > 0);
+
+    assert_eq!(
+        executor.account_balance(sender.address).unwrap(),
+        sender_balance
+    );
+    assert_eq!(
+        executor.account_balance(recipient.address).unwrap(),
+        recipient_balance
+    );
+    // The transaction was not committed: it can still be executed.
+    let results = executor.execute_block(&[transfer]);
+    assert_eq!(results[0].gas_used(), result.gas_used());
+}
+
+#[test]
+fn executed_blocks_update_typed_resources() {
+    use aptos_types::account_config::AccountResource;
+
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+
+    let chain_id = executor.chain_id();
+    let txns: Vec<_> = (0..3)
+        .map(|_| apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap())
+        .collect();
+    executor.execute_block(&txns);
+
+    let database = executor.database();
+    let account: AccountResource = database.get_resource(sender.address).unwrap().unwrap();
+    assert_eq!(account.sequence_number(), 3);
+    let account: AccountResource = database.get_resource(recipient.address).unwrap().unwrap();
+    assert_eq!(account.sequence_number(), 0);
+    let stranger = LocalAccount::generate(3).unwrap();
+    let missing: Option<AccountResource> = database.get_resource(stranger.address).unwrap();
+    assert!(missing.is_none());
+}
+
+#[test]
+fn execute_block_with_changes_reports_writes() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+
+    let chain_id = executor.chain_id();
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    sender.sequence_number = 0;
+    let stale = apt_transfer(&mut sender, recipient.address, 200, chain_id).unwrap();
+
+    let results = executor
+        .execute_block_with_changes(&[transfer, stale])
+        .unwrap();
+    assert_eq!(results.len(), 2);
+
+    // The transfer modifies the balances of both accounts.
+    let (result, changes) = &results[0];
+    assert_eq!(result.keep_or_discard(), KeptOrDiscarded::Kept);
+    for address in [sender.address, recipient.address] {
+        assert!(changes
+            .iter()
+            .any(|change| change.kind == StateChangeKind::Modification
+                && change.value.is_some()
+                && belongs_to(change, address)));
+    }
+
+    // The stale transfer is discarded and leaves the state untouched.
+    let (result, changes) = &results[1];
+    assert_ne!(result.keep_or_discard(), KeptOrDiscarded::Kept);
+    assert!(changes.is_empty());
+}
+
+#[test]
+fn reset_to_genesis_restores_baseline() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let bootstrap = |executor: &AptosVmExecutor| {
+        let sender = LocalAccount::generate(1).unwrap();
+        let recipient = LocalAccount::generate(2).unwrap();
+        executor.bootstrap_account(&sender, 1_000_000).unwrap();
+        executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+        (sender, recipient)
+    };
+    let balances = |executor: &AptosVmExecutor, accounts: [AccountAddress; 2]| {
+        accounts.map(|address| executor.account_balance(address).unwrap())
+    };
+
+    let (mut sender, recipient) = bootstrap(&executor);
+    let accounts = [sender.address, recipient.address];
+    let baseline = balances(&executor, accounts);
+
+    // Run a few transfers.
+    let chain_id = executor.chain_id();
+    let txns: Vec<_> = (0..3)
+        .map(|_| apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap())
+        .collect();
+    let results = executor.execute_block(&txns);
+    assert!(results
+        .iter()
+        .all(|result| result.keep_or_discard() == KeptOrDiscarded::Kept));
+    assert_ne!(balances(&executor, accounts), baseline);
+
+    // Resetting drops the bootstrapped accounts altogether.
+    executor.reset_to_genesis();
+    assert!(executor.account_balance(sender.address).is_err());
+    assert!(executor.account_balance(recipient.address).is_err());
+
+    // Bootstrapping again brings the balances back to the baseline, and the same transactions
+    // can be replayed.
+    bootstrap(&executor);
+    assert_eq!(balances(&executor, accounts), baseline);
+    let results = executor.execute_block(&txns);
+    assert!(results
+        .iter()
+        .all(|result| result.keep_or_discard() == KeptOrDiscarded::Kept));
+}
+
+#[test]
+fn expired_transaction_is_classified_and_graced() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let mut other = LocalAccount::generate(3).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+    executor.bootstrap_account(&other, 1_000_000).unwrap();
+
+    let chain_id = executor.chain_id();
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    let expiration = transfer.expiration_timestamp_secs() * 1_000_000;
+
+    // The block is committed after the expiration of the transfer, but within the grace window: the
+    // block runs that much earlier, so the transfer is executed.
+    executor.set_expiry_grace(5);
+    let results = executor
+        .execute_committed_block(&[transfer], 1, expiration + 2_000_000)
+        .unwrap();
+    assert!(!results[0].is_expired());
+    assert_eq!(results[0].keep_or_discard(), KeptOrDiscarded::Kept);
+    assert_eq!(
+        executor.database().timestamp_usecs().unwrap(),
+        expiration - 3_000_000
+    );
+
+    // Past the grace window, transactions are reported as expired rather than as any other failure.
+    let late = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    let late_expiration = late.expiration_timestamp_secs() * 1_000_000;
+    let results = executor
+        .execute_committed_block(&[late], 2, late_expiration + 5_000_000)
+        .unwrap();
+    assert!(results[0].is_expired());
+    assert_eq!(
+        results[0].keep_or_discard(),
+        KeptOrDiscarded::Discarded(StatusCode::TRANSACTION_EXPIRED)
+    );
+
+    // Without grace, the block runs at its commit time.
+    executor.set_expiry_grace(0);
+    let transfer = apt_transfer(&mut other, recipient.address, 100, chain_id).unwrap();
+    let expiration = transfer.expiration_timestamp_secs() * 1_000_000;
+    let results = executor
+        .execute_committed_block(&[transfer], 3, expiration + 5_000_000)
+        .unwrap();
+    assert!(results[0].is_expired());
+
+    // Blocks committed earlier than the on-chain time leave the clock where it is.
+    let clock = executor.database().timestamp_usecs().unwrap();
+    let transfer = apt_transfer(&mut other, recipient.address, 100, chain_id).unwrap();
+    executor
+        .execute_committed_block(&[transfer], 4, clock - 1)
+        .unwrap();
+    assert_eq!(executor.database().timestamp_usecs().unwrap(), clock);
+}
+
+#[test]
+fn account_resource_tracks_sequence_number() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+
+    let chain_id = executor.chain_id();
+    let transfers = vec![
+        apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap(),
+        apt_transfer(&mut sender, recipient.address, 200, chain_id).unwrap(),
+    ];
+    let results = executor.execute_block(&transfers);
+    assert!(results
+        .iter()
+        .all(|x| x.keep_or_discard() == KeptOrDiscarded::Kept));
+
+    let resource = executor
+        .database()
+        .account_resource(sender.address)
+        .unwrap()
+        .unwrap();
+    assert_eq!(resource.sequence_number(), 2);
+    let auth_key = AuthenticationKey::ed25519(&sender.public_key);
+    assert_eq!(resource.authentication_key(), auth_key.to_vec().as_slice());
+
+    // The recipient did not send anything.
+    let resource = executor
+        .database()
+        .account_resource(recipient.address)
+        .unwrap()
+        .unwrap();
+    assert_eq!(resource.sequence_number(), 0);
+
+    // Unknown accounts have no resource.
+    let unknown = LocalAccount::generate(9).unwrap().address;
+    assert!(executor
+        .database()
+        .account_resource(unknown)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn rotated_key_signs_for_the_same_address() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+    let chain_id = executor.chain_id();
+
+    // Rotate the key of the sender to the one of another (unfunded) account.
+    let new_private_key = LocalAccount::generate(7).unwrap().private_key;
+    let rotation = rotate_key_txn(&mut sender, &new_private_key, chain_id).unwrap();
+    let results = executor.execute_block(&[rotation]);
+    assert_eq!(results[0].keep_or_discard(), KeptOrDiscarded::Kept);
+    let address = sender.address;
+    sender.rotate_key(new_private_key);
+    assert_eq!(sender.address, address);
+
+    let resource = executor
+        .database()
+        .account_resource(address)
+        .unwrap()
+        .unwrap();
+    let auth_key = AuthenticationKey::ed25519(&sender.public_key);
+    assert_eq!(resource.authentication_key(), auth_key.to_vec().as_slice());
+    assert_eq!(sender.authentication_key(), auth_key);
+
+    // The old key is rejected...
+    let mut stale = LocalAccount::generate(1).unwrap();
+    stale.sequence_number = sender.sequence_number;
+    let transfer = apt_transfer(&mut stale, recipient.address, 100, chain_id).unwrap();
+    let results = executor.execute_block(&[transfer]);
+    assert_eq!(
+        results[0].keep_or_discard(),
+        KeptOrDiscarded::Discarded(StatusCode::INVALID_AUTH_KEY)
+    );
+
+    // ...while the new one transfers from the same address.
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    assert_eq!(transfer.sender(), address);
+    let results = executor.execute_block(&[transfer]);
+    assert_eq!(results[0].keep_or_discard(), KeptOrDiscarded::Kept);
+}
+
+/// Returns the address of a validator of the current epoch, as set up by genesis.
+fn genesis_validator(executor: &AptosVmExecutor) -> AccountAddress {
+    let key = StateKey::on_chain_config::<ValidatorSet>().unwrap();
+    let value = executor.database().get_state_value(&key).unwrap().unwrap();
+    let validators: ValidatorSet = bcs::from_bytes(value.bytes()).unwrap();
+    *validators.payload().next().unwrap().account_address()
+}
+
+#[test]
+fn block_metadata_is_visible_to_transactions() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    executor.bootstrap_account(&recipient, 1_000_000).unwrap();
+    let chain_id = executor.chain_id();
+    let proposer = genesis_validator(&executor);
+    let now = executor.database().timestamp_usecs().unwrap();
+
+    // The block prologue records the supplied round.
+    let metadata = BlockMetadata {
+        epoch: 1,
+        round: 42,
+        proposer,
+        timestamp: now + 1_000_000,
+    };
+    let result = executor.execute_block_metadata(metadata).unwrap();
+    let new_block = result
+        .events()
+        .iter()
+        .filter(|event| {
+            event
+                .type_tag()
+                .to_string()
+                .ends_with("::block::NewBlockEvent")
+        })
+        .map(|event| bcs::from_bytes::<NewBlockEvent>(event.event_data()).unwrap())
+        .next()
+        .expect("the prologue should emit a new block event");
+    assert_eq!(new_block.round(), 42);
+    assert_eq!(new_block.proposer(), proposer);
+
+    // Transactions of the next block run at the time of that block.
+    let metadata = BlockMetadata {
+        epoch: 1,
+        round: 43,
+        proposer,
+        timestamp: now + 2_000_000,
+    };
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    let results = executor
+        .execute_block_with_metadata(&[transfer], metadata)
+        .unwrap();
+    assert_eq!(results[0].keep_or_discard(), KeptOrDiscarded::Kept);
+    assert_eq!(
+        executor.database().timestamp_usecs().unwrap(),
+        now + 2_000_000
+    );
+
+    // A proposer outside of the validator set is rejected before executing anything.
+    let metadata = BlockMetadata {
+        epoch: 1,
+        round: 44,
+        proposer: recipient.address,
+        timestamp: now + 3_000_000,
+    };
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    assert!(executor
+        .execute_block_with_metadata(&[transfer], metadata)
+        .is_err());
+}
+
+#[test]
+fn current_timestamp_follows_block_metadata() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000).unwrap();
+    let chain_id = executor.chain_id();
+    let proposer = genesis_validator(&executor);
+    let before = executor.current_timestamp_usecs().unwrap();
+
+    // Executing a block at a later time advances the on-chain clock to that time.
+    let metadata = BlockMetadata {
+        epoch: 1,
+        round: 1,
+        proposer,
+        timestamp: before + 5_000_000,
+    };
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    executor
+        .execute_block_with_metadata(&[transfer], metadata)
+        .unwrap();
+    assert_eq!(
+        executor.current_timestamp_usecs().unwrap(),
+        before + 5_000_000
+    );
+
+    // Blocks without metadata leave the clock alone.
+    let transfer = apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap();
+    executor.execute_block(&[transfer]);
+    assert_eq!(
+        executor.current_timestamp_usecs().unwrap(),
+        before + 5_000_000
+    );
+}
+
+#[test]
+fn bootstrap_gas_buffer_is_configurable() {
+    let executor = AptosVmExecutor::new().unwrap();
+    let exact = LocalAccount::generate(1).unwrap();
+    let buffered = LocalAccount::generate(2).unwrap();
+    let default = LocalAccount::generate(3).unwrap();
+
+    executor
+        .bootstrap_account_with_buffer(&exact, 1_000, 0)
+        .unwrap();
+    executor
+        .bootstrap_account_with_buffer(&buffered, 1_000, 500)
+        .unwrap();
+    executor.bootstrap_account(&default, 1_000).unwrap();
+
+    assert_eq!(executor.account_balance(exact.address).unwrap(), 1_000);
+    assert_eq!(executor.account_balance(buffered.address).unwrap(), 1_500);
+    assert_eq!(
+        executor.account_balance(default.address).unwrap(),
+        1_000 + u128::from(crate::DEFAULT_GAS_BUFFER)
+    );
+}
+
+#[test]
+fn transfer_touches_sender_and_recipient() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let bystander = LocalAccount::generate(3).unwrap();
+    for account in [&sender, &recipient, &bystander] {
+        executor.bootstrap_account(account, 1_000_000).unwrap();
+    }
+
+    let txn = apt_transfer(&mut sender, recipient.address, 1_000, executor.chain_id()).unwrap();
+    let results = executor.execute_block(&[txn]);
+    assert_eq!(results[0].keep_or_discard(), KeptOrDiscarded::Kept);
+
+    let mut expected = vec![sender.address, recipient.address];
+    expected.sort();
+    assert_eq!(results[0].touched_accounts(), expected);
+}
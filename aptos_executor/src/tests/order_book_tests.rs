@@ -0,0 +1,228 @@
+use super::*;
+use crate::package::load_package_artifacts;
+use crate::scenarios::three_trader::resolve_package_dir;
+use crate::transaction_builder::{
+    create_market, mint_trader_funds, place_limit_order_with_client_id, place_orders_batch,
+    publish_package, register_trader, OrderSpec,
+};
+use crate::{AptosVmExecutor, LocalAccount};
+use aptos_types::vm_status::VMStatus;
+
+// Fixture
+fn order(price: u64, size: u64) -> RestingOrder {
+    RestingOrder {
+        order_id: 0,
+        trader: AccountAddress::ONE,
+        price,
+        size,
+    }
+}
+
+#[test]
+fn orders_are_aggregated_by_price() {
+    let bids = [order(900, 5), order(950, 1), order(900, 2)];
+    let asks = [order(1_500, 20), order(1_000, 10), order(1_500, 4)];
+    let book = OrderBookSnapshot::from_orders(&bids, &asks);
+
+    let level = |price, size, orders| PriceLevel {
+        price,
+        size,
+        orders,
+    };
+    assert_eq!(book.bids, vec![level(950, 1, 1), level(900, 7, 2)]);
+    assert_eq!(book.asks, vec![level(1_000, 10, 1), level(1_500, 24, 2)]);
+    assert_eq!(book.best_bid(), Some(&level(950, 1, 1)));
+    assert_eq!(book.best_ask(), Some(&level(1_000, 10, 1)));
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn resting_orders_are_read_from_the_market() {
+    let package_dir = resolve_package_dir().unwrap();
+    let (metadata, modules) = load_package_artifacts(&package_dir).unwrap();
+
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let chain_id = executor.chain_id();
+    let mut admin = LocalAccount::generate(1).unwrap();
+    let market = LocalAccount::generate(2).unwrap();
+    let mut trader = LocalAccount::generate(3).unwrap();
+    for account in [&admin, &market, &trader] {
+        executor
+            .bootstrap_account(account, 1_000_000_000_000)
+            .unwrap();
+    }
+    let owner = admin.address;
+    assert!(executor.order_book(owner, market.address).is_err());
+
+    let mut txns = vec![
+        publish_package(&mut admin, metadata, modules, chain_id).unwrap(),
+        create_market(&mut admin, &market, false, true, 60, chain_id).unwrap(),
+        register_trader(owner, &mut trader, chain_id).unwrap(),
+        mint_trader_funds(&mut admin, trader.address, 1_000_000, 1_000_000, chain_id).unwrap(),
+    ];
+    // Two asks at the same price, one above, and a bid that does not cross.
+    for (client_order_id, (price, size, is_bid)) in [
+        (1_000, 10, false),
+        (1_000, 5, false),
+        (1_500, 20, false),
+        (900, 8, true),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        txns.push(
+            place_limit_order_with_client_id(
+                owner,
+                &mut trader,
+                &market,
+                price,
+                size,
+                is_bid,
+                client_order_id as u64,
+                chain_id,
+            )
+            .unwrap(),
+        );
+    }
+    for result in executor.execute_block(&txns) {
+        assert_eq!(result.status(), &VMStatus::Executed);
+    }
+
+    let book = executor.order_book(owner, market.address).unwrap();
+    assert_eq!(
+        book.asks,
+        vec![
+            PriceLevel {
+                price: 1_000,
+                size: 15,
+                orders: 2
+            },
+            PriceLevel {
+                price: 1_500,
+                size: 20,
+                orders: 1
+            },
+        ]
+    );
+    assert_eq!(
+        book.bids,
+        vec![PriceLevel {
+            price: 900,
+            size: 8,
+            orders: 1
+        }]
+    );
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn batched_orders_are_placed_in_one_transaction() {
+    let package_dir = resolve_package_dir().unwrap();
+    let (metadata, modules) = load_package_artifacts(&package_dir).unwrap();
+
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let chain_id = executor.chain_id();
+    let mut admin = LocalAccount::generate(1).unwrap();
+    let market = LocalAccount::generate(2).unwrap();
+    let mut trader = LocalAccount::generate(3).unwrap();
+    for account in [&admin, &market, &trader] {
+        executor
+            .bootstrap_account(account, 1_000_000_000_000)
+            .unwrap();
+    }
+
+    let owner = admin.address;
+    let setup = vec![
+        publish_package(&mut admin, metadata, modules, chain_id).unwrap(),
+        create_market(&mut admin, &market, false, true, 60, chain_id).unwrap(),
+        register_trader(owner, &mut trader, chain_id).unwrap(),
+        mint_trader_funds(&mut admin, trader.address, 1_000_000, 1_000_000, chain_id).unwrap(),
+    ];
+    for result in executor.execute_block(&setup) {
+        assert_eq!(result.status(), &VMStatus::Executed);
+    }
+
+    // Two asks and a bid that does not cross them, in a single transaction.
+    let orders = vec![
+        OrderSpec {
+            limit_price: 1_000,
+            size: 10,
+            is_bid: false,
+            client_order_id: 1,
+        },
+        OrderSpec {
+            limit_price: 1_500,
+            size: 20,
+            is_bid: false,
+            client_order_id: 2,
+        },
+        OrderSpec {
+            limit_price: 900,
+            size: 8,
+            is_bid: true,
+            client_order_id: 3,
+        },
+    ];
+    let txn = place_orders_batch(owner, &mut trader, &market, orders, chain_id).unwrap();
+    let results = executor.execute_block(&[txn]);
+    assert_eq!(results[0].status(), &VMStatus::Executed);
+
+    let book = executor.order_book(owner, market.address).unwrap();
+    let level = |price, size| PriceLevel {
+        price,
+        size,
+        orders: 1,
+    };
+    assert_eq!(book.asks, vec![level(1_000, 10), level(1_500, 20)]);
+    assert_eq!(book.bids, vec![level(900, 8)]);
+
+    // The three orders escrowed the funds of the trader.
+    let base = executor
+        .coin_balance(trader.address, &base_coin(owner).unwrap())
+        .unwrap();
+    let quote = executor
+        .coin_balance(trader.address, &quote_coin(owner).unwrap())
+        .unwrap();
+    assert_eq!(base, 1_000_000 - 10 - 20);
+    assert_eq!(quote, 1_000_000 - 900 * 8);
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn traders_are_registered_by_register_trader() {
+    let package_dir = resolve_package_dir().unwrap();
+    let (metadata, modules) = load_package_artifacts(&package_dir).unwrap();
+
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let chain_id = executor.chain_id();
+    let mut admin = LocalAccount::generate(1).unwrap();
+    let market = LocalAccount::generate(2).unwrap();
+    let mut trader = LocalAccount::generate(3).unwrap();
+    for account in [&admin, &market, &trader] {
+        executor
+            .bootstrap_account(account, 1_000_000_000_000)
+            .unwrap();
+    }
+    let owner = admin.address;
+    assert!(executor
+        .is_registered(owner, market.address, trader.address)
+        .is_err());
+
+    let setup = vec![
+        publish_package(&mut admin, metadata, modules, chain_id).unwrap(),
+        create_market(&mut admin, &market, false, true, 60, chain_id).unwrap(),
+    ];
+    for result in executor.execute_block(&setup) {
+        assert_eq!(result.status(), &VMStatus::Executed);
+    }
+    assert!(!executor
+        .is_registered(owner, market.address, trader.address)
+        .unwrap());
+
+    let txn = register_trader(owner, &mut trader, chain_id).unwrap();
+    let results = executor.execute_block(&[txn]);
+    assert_eq!(results[0].status(), &VMStatus::Executed);
+    assert!(executor
+        .is_registered(owner, market.address, trader.address)
+        .unwrap());
+}
@@ -0,0 +1,65 @@
+use super::*;
+use std::fs;
+
+/// Writes a package whose metadata declares `declared` and whose bytecode directory holds `present`.
+fn write_package(dir: &Path, declared: &[&str], present: &[&str]) {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir.join("bytecode_modules")).unwrap();
+    let metadata = PackageMetadata {
+        name: "simple_market".to_string(),
+        upgrade_policy: UpgradePolicy { policy: 1 },
+        upgrade_number: 0,
+        source_digest: String::new(),
+        manifest: Vec::new(),
+        modules: declared
+            .iter()
+            .map(|name| ModuleMetadata {
+                name: name.to_string(),
+                source: Vec::new(),
+                source_map: Vec::new(),
+                extension: None,
+            })
+            .collect(),
+        deps: Vec::new(),
+        extension: None,
+    };
+    fs::write(
+        dir.join("package-metadata.bcs"),
+        bcs::to_bytes(&metadata).unwrap(),
+    )
+    .unwrap();
+    for name in present {
+        let path = dir.join("bytecode_modules").join(format!("{}.mv", name));
+        fs::write(path, [0xa1, 0x1c, 0xeb, 0x0b]).unwrap();
+    }
+}
+
+#[test]
+fn matching_package_is_loaded() {
+    let dir = Path::new(".test_matching_package_is_loaded");
+    write_package(dir, &["coins", "market_setup"], &["coins", "market_setup"]);
+    let loaded = load_package_artifacts(dir);
+    fs::remove_dir_all(dir).unwrap();
+    let (_, modules) = loaded.unwrap();
+    assert_eq!(modules.len(), 2);
+}
+
+#[test]
+fn missing_module_is_reported() {
+    let dir = Path::new(".test_missing_module_is_reported");
+    write_package(
+        dir,
+        &["coins", "market_setup", "vault"],
+        &["coins", "market_setup"],
+    );
+    let loaded = load_package_artifacts(dir);
+    fs::remove_dir_all(dir).unwrap();
+    let error = loaded.unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(
+        message.contains("declared but missing [\"vault\"]"),
+        "{}",
+        message
+    );
+    assert!(message.contains("simple_market"), "{}", message);
+}
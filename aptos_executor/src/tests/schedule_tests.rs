@@ -0,0 +1,61 @@
+use super::*;
+use crate::transaction_builder::{apt_transfer, publish_package};
+use crate::LocalAccount;
+use aptos_types::chain_id::ChainId;
+
+// Fixture
+fn accounts(count: u64) -> Vec<LocalAccount> {
+    (1..=count)
+        .map(|seed| LocalAccount::generate(seed).unwrap())
+        .collect()
+}
+
+fn transfer(accounts: &mut [LocalAccount], sender: usize, recipient: usize) -> SignedTransaction {
+    let recipient = accounts[recipient].address;
+    apt_transfer(&mut accounts[sender], recipient, 100, ChainId::test()).unwrap()
+}
+
+#[test]
+fn independent_transfers_are_grouped_and_conflicting_ones_ordered() {
+    let mut accounts = accounts(6);
+    let (a, b, c, d, e, f) = (0, 1, 2, 3, 4, 5);
+    let txns = vec![
+        transfer(&mut accounts, a, b),
+        transfer(&mut accounts, c, d),
+        // Conflicts with the first transfer (same sender).
+        transfer(&mut accounts, a, e),
+        // Conflicts with the previous transfer (its recipient).
+        transfer(&mut accounts, e, f),
+        // Conflicts with the second transfer only.
+        transfer(&mut accounts, d, c),
+    ];
+
+    let plan = plan_parallel_schedule(&txns);
+    assert_eq!(plan.groups, vec![vec![0, 1], vec![2, 4], vec![3]]);
+    assert_eq!(plan.order(), vec![0, 1, 2, 4, 3]);
+    assert_eq!(plan.max_parallelism(), 2);
+
+    // No group holds two conflicting transactions.
+    for group in &plan.groups {
+        for (i, x) in group.iter().enumerate() {
+            for y in &group[i + 1..] {
+                assert!(!conflicts(&txns[*x], &txns[*y]));
+            }
+        }
+    }
+}
+
+#[test]
+fn unknown_transactions_are_barriers() {
+    let mut accounts = accounts(5);
+    let txns = vec![
+        transfer(&mut accounts, 0, 1),
+        publish_package(&mut accounts[2], vec![], vec![], ChainId::test()).unwrap(),
+        transfer(&mut accounts, 3, 4),
+    ];
+    assert!(touched_accounts(&txns[1]).is_none());
+
+    // The publish transaction conflicts with everything, even transfers between other accounts.
+    let plan = plan_parallel_schedule(&txns);
+    assert_eq!(plan.groups, vec![vec![0], vec![1], vec![2]]);
+}
@@ -0,0 +1,93 @@
+use super::*;
+use crate::database::AptosDatabase;
+use crate::transaction_builder::apt_transfer;
+use crate::{AptosVmExecutor, LocalAccount};
+
+/// Executes a transfer of `amount` from a freshly bootstrapped account holding `balance`.
+fn execute_transfer(balance: u64, amount: u64) -> Vec<TransactionResult> {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, balance).unwrap();
+    executor.bootstrap_account(&recipient, balance).unwrap();
+
+    let chain_id = executor.chain_id();
+    let txn = apt_transfer(&mut sender, recipient.address, amount, chain_id).unwrap();
+    executor.execute_block(&[txn])
+}
+
+#[test]
+fn assert_all_executed_accepts_successful_transactions() {
+    let results = execute_transfer(1_000, 100);
+    assert_all_executed(&results);
+    assert_executed(&results[0]);
+}
+
+#[test]
+#[should_panic(
+    expected = "1 of 1 transactions did not execute:\n  transaction 0: status=MoveAbort"
+)]
+fn assert_all_executed_reports_aborted_transactions() {
+    // Transfer more than the balance (including the gas buffer) to make the transfer abort.
+    let results = execute_transfer(1_000, u64::MAX / 2);
+    assert_all_executed(&results);
+}
+
+#[test]
+#[should_panic(expected = "abort_code=")]
+fn assert_executed_reports_aborted_transaction() {
+    let results = execute_transfer(1_000, u64::MAX / 2);
+    assert_executed(&results[0]);
+}
+
+/// Copies the current state of the executor, to compare it with the state after a block.
+fn snapshot(executor: &AptosVmExecutor) -> AptosDatabase {
+    let chain_id = executor.chain_id();
+    let snapshot = executor.database().export_snapshot(chain_id);
+    AptosDatabase::from_snapshot(snapshot, chain_id).unwrap()
+}
+
+#[test]
+fn transfers_conserve_apt() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 10_000).unwrap();
+    executor.bootstrap_account(&recipient, 10_000).unwrap();
+    let before = snapshot(&executor);
+
+    // The gas of the block is burned: both the balances and the supply shrink.
+    let chain_id = executor.chain_id();
+    let txns: Vec<_> = (0..3)
+        .map(|_| apt_transfer(&mut sender, recipient.address, 100, chain_id).unwrap())
+        .collect();
+    let results = executor.execute_block(&txns);
+    assert_all_executed(&results);
+    assert_apt_conserved(&before, executor.database(), 0);
+}
+
+#[test]
+#[should_panic(expected = "APT is not conserved")]
+fn apt_credited_outside_the_vm_is_reported() {
+    let executor = AptosVmExecutor::new().unwrap();
+    let before = snapshot(&executor);
+
+    // Publishing an account credits its balance without touching the supply.
+    let account = LocalAccount::generate(1).unwrap();
+    executor
+        .bootstrap_account_with_buffer(&account, 1_000, 0)
+        .unwrap();
+    assert_apt_conserved(&before, executor.database(), 0);
+}
+
+#[test]
+fn declared_mints_are_allowed() {
+    let executor = AptosVmExecutor::new().unwrap();
+    let before = snapshot(&executor);
+
+    let account = LocalAccount::generate(1).unwrap();
+    executor
+        .bootstrap_account_with_buffer(&account, 1_000, 0)
+        .unwrap();
+    assert_apt_conserved(&before, executor.database(), 1_000);
+}
@@ -0,0 +1,276 @@
+use super::*;
+
+#[tokio::test]
+async fn step_rejected_by_a_worker_is_resubmitted() {
+    // Worker 3 is overloaded the first time it gets the step, the others accept it right away.
+    let mut submissions = Vec::new();
+    let accepted = submit_step_to_quorum(&[1, 2, 3, 4], 4, 3, Duration::ZERO, |worker| {
+        let ack = match worker == 3 && !submissions.contains(&3) {
+            true => StepAck::Rejected("The worker is overloaded, retry later".to_string()),
+            false => StepAck::Accepted,
+        };
+        submissions.push(worker);
+        async move { Ok::<_, String>(ack) }
+    })
+    .await
+    .unwrap();
+    assert_eq!(accepted, vec![1, 2, 4, 3]);
+    assert_eq!(submissions, vec![1, 2, 3, 4, 3]);
+}
+
+#[tokio::test]
+async fn step_without_quorum_fails_clearly() {
+    // Two of the four workers never accept the step, so it cannot reach a quorum of three.
+    let result = submit_step_to_quorum(
+        &[1, 2, 3, 4],
+        worker_quorum(4),
+        3,
+        Duration::ZERO,
+        |worker| async move {
+            match worker {
+                3 => Ok(StepAck::Rejected(
+                    "The batch maker is not running".to_string(),
+                )),
+                4 => Err("connection refused"),
+                _ => Ok(StepAck::Accepted),
+            }
+        },
+    )
+    .await;
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("only 2 of 4 workers accepted the step (quorum 3)"));
+    assert!(error.contains("3 rejected it: The batch maker is not running"));
+    assert!(error.contains("4 is unreachable: connection refused"));
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn scenario_reaches_the_expected_outcome() {
+    let package_dir = resolve_package_dir().unwrap();
+    let mut executor = AptosVmExecutor::new().unwrap();
+    for seed in [TRADER_A_SEED, TRADER_B_SEED, TRADER_C_SEED, TRADER_D_SEED] {
+        let account = LocalAccount::generate(seed).unwrap();
+        executor
+            .bootstrap_account(&account, 1_000_000_000_000)
+            .unwrap();
+    }
+
+    let scenario = build_three_trader_transactions(&package_dir, executor.chain_id()).unwrap();
+    assert_eq!(scenario.len(), EXPECTED_SCENARIO_TXNS);
+    for step in scenario {
+        let results = executor.execute_block(&[step.txn]);
+        assert_eq!(
+            results[0].status(),
+            &aptos_types::vm_status::VMStatus::Executed,
+            "{}",
+            step.label
+        );
+    }
+
+    let accounts = ScenarioAccounts::generate().unwrap();
+    let outcome = ScenarioOutcome::from_executor(&executor, &accounts).unwrap();
+    assert_eq!(outcome, ScenarioOutcome::expected());
+
+    // The same outcome is decoded from the raw values a node serves.
+    let values: Vec<_> = ScenarioOutcome::state_keys(&accounts)
+        .unwrap()
+        .iter()
+        .map(|key| {
+            executor
+                .database()
+                .get_state_value(key)
+                .unwrap()
+                .unwrap()
+                .bytes()
+                .to_vec()
+        })
+        .collect();
+    assert_eq!(ScenarioOutcome::decode(&values).unwrap(), outcome);
+}
+
+#[test]
+fn unexpected_outcome_is_reported() {
+    assert!(ScenarioOutcome::expected().check().is_ok());
+
+    let mut outcome = ScenarioOutcome::expected();
+    outcome.book.bids.clear();
+    let error = outcome.check().unwrap_err().to_string();
+    assert!(error.contains("the market did not reach the expected state"));
+
+    let error = ScenarioOutcome::decode(&[Vec::new()])
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("got 1"), "{}", error);
+}
+
+#[test]
+fn settled_crossing_trade_is_accepted() {
+    let quote = CROSSING_FILL_SIZE * CROSSING_FILL_PRICE;
+    let before = [(100, 50_000), (200, 60_000)];
+    let after = [
+        (100, 50_000 + quote),
+        (200 + CROSSING_FILL_SIZE, 60_000 - quote),
+    ];
+    assert!(check_crossing_trade(&before, &after).is_ok());
+}
+
+#[test]
+fn unsettled_crossing_trade_is_reported() {
+    let quote = CROSSING_FILL_SIZE * CROSSING_FILL_PRICE;
+    let before = [(100, 50_000), (200, 60_000)];
+
+    // Nothing moved, e.g. the bid rested instead of matching.
+    let error = check_crossing_trade(&before, &before)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("did not match trader B's ask"), "{}", error);
+
+    // Trader C paid, but received no base.
+    let after = [(100, 50_000 + quote), (200, 60_000 - quote)];
+    assert!(check_crossing_trade(&before, &after).is_err());
+
+    // Trader C could not have paid for the trade.
+    let poor = [(100, 50_000), (200, quote - 1)];
+    let error = check_crossing_trade(&poor, &after).unwrap_err().to_string();
+    assert!(error.contains("cannot pay"), "{}", error);
+
+    // The balances of both traders are needed.
+    assert!(check_crossing_trade(&before[..1], &after).is_err());
+}
+
+// Fixture: an executor with funded traders, and the transactions of the scenario.
+fn scenario_executor(package_dir: &Path) -> (AptosVmExecutor, Vec<ScenarioTxn>) {
+    let executor = AptosVmExecutor::new().unwrap();
+    for seed in [TRADER_A_SEED, TRADER_B_SEED, TRADER_C_SEED, TRADER_D_SEED] {
+        let account = LocalAccount::generate(seed).unwrap();
+        executor
+            .bootstrap_account(&account, 1_000_000_000_000)
+            .unwrap();
+    }
+    let scenario = build_three_trader_transactions(package_dir, executor.chain_id()).unwrap();
+    (executor, scenario)
+}
+
+// Fixture: runs the scenario on a fresh executor, either as one block or as a block per step, and
+// returns its outcome.
+fn run_scenario(package_dir: &Path, single_block: bool) -> ScenarioOutcome {
+    let (mut executor, scenario) = scenario_executor(package_dir);
+    let labels: Vec<_> = scenario.iter().map(|step| step.label.clone()).collect();
+    let txns: Vec<_> = scenario.into_iter().map(|step| step.txn).collect();
+
+    let results = match single_block {
+        true => executor.execute_block(&txns),
+        false => txns
+            .iter()
+            .flat_map(|txn| executor.execute_block(std::slice::from_ref(txn)))
+            .collect(),
+    };
+    for (result, label) in results.iter().zip(&labels) {
+        assert_eq!(
+            result.status(),
+            &aptos_types::vm_status::VMStatus::Executed,
+            "{}",
+            label
+        );
+    }
+
+    let accounts = ScenarioAccounts::generate().unwrap();
+    ScenarioOutcome::from_executor(&executor, &accounts).unwrap()
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn scenario_runs_as_a_single_block() {
+    let package_dir = resolve_package_dir().unwrap();
+
+    // The VM is shared by all the transactions of the block, including the ones calling the modules
+    // published earlier in the block.
+    assert_eq!(
+        run_scenario(&package_dir, true),
+        ScenarioOutcome::expected()
+    );
+    assert_eq!(
+        run_scenario(&package_dir, false),
+        ScenarioOutcome::expected()
+    );
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn simulated_scenario_predicts_its_execution() {
+    let package_dir = resolve_package_dir().unwrap();
+    let (mut executor, scenario) = scenario_executor(&package_dir);
+    let txns: Vec<_> = scenario.into_iter().map(|step| step.txn).collect();
+    let accounts = ScenarioAccounts::generate().unwrap();
+
+    // The simulation leaves the database untouched.
+    let root = executor.state_root();
+    let simulated = executor.simulate_block(&txns);
+    assert_eq!(executor.state_root(), root);
+
+    // The predicted outcome is read from a copy of the database to which the simulated outputs are
+    // applied, and fails if one of the values it is read from is missing. The order book, for one,
+    // is only created by the scenario.
+    let predicted_state = executor.database().copy();
+    for result in &simulated {
+        assert_eq!(result.status(), &aptos_types::vm_status::VMStatus::Executed);
+        predicted_state.apply_vm_output(&result.output);
+    }
+    let book = order_book_key(accounts.module_owner, accounts.market).unwrap();
+    assert!(executor
+        .database()
+        .get_state_value(&book)
+        .unwrap()
+        .is_none());
+    assert!(predicted_state.get_state_value(&book).unwrap().is_some());
+    let predicted = ScenarioOutcome::from_database(&predicted_state, &accounts).unwrap();
+    assert_eq!(predicted, ScenarioOutcome::expected());
+
+    let executed = executor.execute_block(&txns);
+    for (simulated, executed) in simulated.iter().zip(&executed) {
+        assert_eq!(simulated.status(), executed.status());
+        assert_eq!(
+            simulated.transaction_output().write_set(),
+            executed.transaction_output().write_set()
+        );
+    }
+    let outcome = ScenarioOutcome::from_executor(&executor, &accounts).unwrap();
+    assert_eq!(outcome, predicted);
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn matched_trades_emit_order_events() {
+    let package_dir = resolve_package_dir().unwrap();
+    for allow_events_emission in [true, false] {
+        let (mut executor, _) = scenario_executor(&package_dir);
+        let scenario = build_three_trader_transactions_with_events(
+            &package_dir,
+            executor.chain_id(),
+            allow_events_emission,
+        )
+        .unwrap();
+        let txns: Vec<_> = scenario.into_iter().map(|step| step.txn).collect();
+        let results = executor.execute_block(&txns);
+        assert!(results
+            .iter()
+            .all(|result| result.status() == &aptos_types::vm_status::VMStatus::Executed));
+
+        let resting = market_events(&results[TRADER_A_ASK_STEP]);
+        let crossing = market_events(&results[TRADER_C_BUY_STEP]);
+        match allow_events_emission {
+            // The crossing bid also reports the fill of the ask it matched.
+            true => {
+                assert_eq!(resting.len(), 1);
+                assert!(crossing.len() > resting.len());
+            }
+            false => {
+                assert!(results
+                    .iter()
+                    .all(|result| market_events(result).is_empty()));
+                // The framework still emits its own events, e.g. the fees.
+                assert!(!results[TRADER_C_BUY_STEP].events().is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use super::*;
+use crate::accounts::{OnChainSequence, SequenceSource};
+use crate::package::load_package_artifacts;
+use crate::scenarios::three_trader::{resolve_package_dir, TRADER_A_SEED};
+use crate::AptosVmExecutor;
+use aptos_types::vm_status::VMStatus;
+use std::sync::Arc;
+
+#[test]
+fn publish_gas_budget_grows_with_package_size() {
+    let small = publish_gas_budget(&[0; 10], &[vec![0; 10]]);
+    let large = publish_gas_budget(&[0; 10], &[vec![0; 10], vec![0; 10_000]]);
+    assert_eq!(small, DEFAULT_MAX_GAS_AMOUNT + 20 * PUBLISH_GAS_PER_BYTE);
+    assert!(large > small);
+}
+
+#[test]
+fn publish_package_with_options_overrides_gas() {
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let options = TxnOptions {
+        max_gas_amount: 42,
+        gas_unit_price: 7,
+        expiration_timestamp_secs: Some(1_000),
+    };
+    let txn = publish_package_with_options(&mut sender, vec![], vec![], ChainId::test(), options)
+        .unwrap();
+    assert_eq!(txn.max_gas_amount(), 42);
+    assert_eq!(txn.gas_unit_price(), 7);
+    assert_eq!(txn.expiration_timestamp_secs(), 1_000);
+    assert_eq!(sender.sequence_number, 1);
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn publish_multi_module_package() {
+    let package_dir = resolve_package_dir().unwrap();
+    let (metadata, modules) = load_package_artifacts(&package_dir).unwrap();
+    assert!(modules.len() > 1, "Expected a multi-module package");
+
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    executor
+        .bootstrap_account(&sender, 1_000_000_000_000)
+        .unwrap();
+
+    let txn = publish_package(&mut sender, metadata, modules, executor.chain_id()).unwrap();
+    let results = executor.execute_block(&[txn]);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].status(), &VMStatus::Executed);
+}
+
+// Fixture
+fn market_context<'a>(
+    admin: &'a mut LocalAccount,
+    market_signer: &'a LocalAccount,
+) -> BuilderContext<'a> {
+    BuilderContext {
+        sender: admin,
+        secondary_signers: vec![market_signer],
+        module_owner: AccountAddress::ONE,
+        chain_id: ChainId::test(),
+    }
+}
+
+#[test]
+fn build_create_market_by_name() {
+    let registry = TxnBuilderRegistry::with_market_builders();
+    assert!(registry.names().any(|x| x == "create_market"));
+
+    let mut admin = LocalAccount::generate(1).unwrap();
+    let market_signer = LocalAccount::generate(2).unwrap();
+    let args = vec![
+        bcs::to_bytes(&true).unwrap(),
+        bcs::to_bytes(&false).unwrap(),
+        bcs::to_bytes(&30u64).unwrap(),
+    ];
+    let context = market_context(&mut admin, &market_signer);
+    let txn = registry.build("create_market", context, &args).unwrap();
+    assert_eq!(admin.sequence_number, 1);
+
+    // Same transaction as when calling the builder directly.
+    let mut expected_admin = LocalAccount::generate(1).unwrap();
+    let expected = create_market(
+        &mut expected_admin,
+        &market_signer,
+        true,
+        false,
+        30,
+        ChainId::test(),
+    )
+    .unwrap();
+    assert_eq!(txn.sender(), expected.sender());
+    assert_eq!(txn.sequence_number(), expected.sequence_number());
+    assert_eq!(txn.payload(), expected.payload());
+    assert_eq!(
+        txn.authenticator_ref().secondary_signer_addresses(),
+        vec![market_signer.address]
+    );
+}
+
+#[test]
+fn build_orders_batch_by_name() {
+    let registry = TxnBuilderRegistry::with_market_builders();
+    let mut trader = LocalAccount::generate(1).unwrap();
+    let market_signer = LocalAccount::generate(2).unwrap();
+    let orders = vec![
+        OrderSpec {
+            limit_price: 1_000,
+            size: 10,
+            is_bid: false,
+            client_order_id: 1,
+        },
+        OrderSpec {
+            limit_price: 900,
+            size: 8,
+            is_bid: true,
+            client_order_id: 2,
+        },
+    ];
+
+    // The orders are a single BCS-encoded argument.
+    let args = vec![bcs::to_bytes(&orders).unwrap()];
+    let context = market_context(&mut trader, &market_signer);
+    let txn = registry
+        .build("place_orders_batch", context, &args)
+        .unwrap();
+    let mut expected_trader = LocalAccount::generate(1).unwrap();
+    let expected = place_orders_batch(
+        AccountAddress::ONE,
+        &mut expected_trader,
+        &market_signer,
+        orders,
+        ChainId::test(),
+    )
+    .unwrap();
+    assert_eq!(txn.payload(), expected.payload());
+
+    // An empty batch is rejected without consuming a sequence number.
+    let args = vec![bcs::to_bytes(&Vec::<OrderSpec>::new()).unwrap()];
+    let context = market_context(&mut trader, &market_signer);
+    assert!(registry
+        .build("place_orders_batch", context, &args)
+        .is_err());
+    assert_eq!(trader.sequence_number, 1);
+}
+
+#[test]
+fn build_rejects_bad_arguments() {
+    let registry = TxnBuilderRegistry::with_market_builders();
+    let mut admin = LocalAccount::generate(1).unwrap();
+    let market_signer = LocalAccount::generate(2).unwrap();
+
+    // Unknown builder, missing argument, and argument of the wrong type.
+    assert!(registry
+        .build("unknown", market_context(&mut admin, &market_signer), &[])
+        .is_err());
+    let args = vec![
+        bcs::to_bytes(&true).unwrap(),
+        bcs::to_bytes(&false).unwrap(),
+    ];
+    assert!(registry
+        .build(
+            "create_market",
+            market_context(&mut admin, &market_signer),
+            &args
+        )
+        .is_err());
+    let args = vec![vec![], vec![], vec![]];
+    assert!(registry
+        .build(
+            "create_market",
+            market_context(&mut admin, &market_signer),
+            &args
+        )
+        .is_err());
+    assert_eq!(admin.sequence_number, 0);
+}
+
+#[test]
+#[ignore = "needs the compiled simple_market package, see resolve_package_dir"]
+fn fungible_transfer_moves_assets_between_primary_stores() {
+    let package_dir = resolve_package_dir().unwrap();
+    let (package_metadata, modules) = load_package_artifacts(&package_dir).unwrap();
+
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut module_owner = LocalAccount::generate(TRADER_A_SEED).unwrap();
+    let mut sender = LocalAccount::generate(2).unwrap();
+    let recipient = LocalAccount::generate(3).unwrap();
+    for account in [&module_owner, &sender, &recipient] {
+        executor.bootstrap_account(account, 1_000_000_000).unwrap();
+    }
+
+    // Publish the package, then create a fungible asset held by the sender.
+    let chain_id = executor.chain_id();
+    let txns = vec![
+        publish_package(&mut module_owner, package_metadata, modules, chain_id).unwrap(),
+        create_test_asset(module_owner.address, &mut sender, 10_000, chain_id).unwrap(),
+    ];
+    let results = executor.execute_block(&txns);
+    assert!(results.iter().all(|x| x.status() == &VMStatus::Executed));
+
+    let metadata = test_asset_metadata(sender.address);
+    assert_eq!(
+        executor.fungible_balance(sender.address, metadata).unwrap(),
+        10_000
+    );
+    assert_eq!(
+        executor
+            .fungible_balance(recipient.address, metadata)
+            .unwrap(),
+        0
+    );
+
+    let txn = fungible_transfer(&mut sender, recipient.address, metadata, 4_000, chain_id).unwrap();
+    let results = executor.execute_block(&[txn]);
+    assert_eq!(results[0].status(), &VMStatus::Executed);
+
+    // The gas is paid in APT, so only the transferred amount leaves the store of the sender.
+    assert_eq!(
+        executor.fungible_balance(sender.address, metadata).unwrap(),
+        6_000
+    );
+    assert_eq!(
+        executor
+            .fungible_balance(recipient.address, metadata)
+            .unwrap(),
+        4_000
+    );
+}
+
+#[test]
+fn builders_take_the_sequence_number_from_the_source() {
+    struct Mock;
+    impl SequenceSource for Mock {
+        fn sequence_number(&self, _address: AccountAddress) -> Result<u64> {
+            Ok(5)
+        }
+    }
+
+    let mut sender = LocalAccount::generate(1)
+        .unwrap()
+        .with_sequence_source(Arc::new(Mock));
+    let recipient = LocalAccount::generate(2).unwrap();
+    let txn = apt_transfer(&mut sender, recipient.address, 10, ChainId::test()).unwrap();
+    assert_eq!(txn.sequence_number(), 5);
+    // The source lags behind the transaction signed but not executed yet: the local counter wins.
+    let txn = aptos_account_transfer(&mut sender, recipient.address, 10, ChainId::test()).unwrap();
+    assert_eq!(txn.sequence_number(), 6);
+
+    // Without a source, the local counter is used and incremented.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    sender.sequence_number = 3;
+    let txn = apt_transfer(&mut sender, recipient.address, 10, ChainId::test()).unwrap();
+    assert_eq!(txn.sequence_number(), 3);
+    assert_eq!(sender.sequence_number, 4);
+}
+
+#[test]
+fn on_chain_sequence_follows_executed_transactions() {
+    let mut executor = AptosVmExecutor::new().unwrap();
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    executor.bootstrap_account(&sender, 1_000_000_000).unwrap();
+
+    // The local counter of another copy of the account knows nothing of the executed transfer.
+    let txn = aptos_account_transfer(&mut sender, recipient.address, 10, ChainId::test()).unwrap();
+    for result in executor.execute_block(&[txn]) {
+        assert_eq!(result.status(), &VMStatus::Executed);
+    }
+    let source = Arc::new(OnChainSequence(executor.database().reader()));
+    let mut copy = LocalAccount::generate(1)
+        .unwrap()
+        .with_sequence_source(source);
+    let txn = apt_transfer(&mut copy, recipient.address, 10, ChainId::test()).unwrap();
+    assert_eq!(txn.sequence_number(), 1);
+
+    // Accounts that do not exist yet start at zero.
+    let source = OnChainSequence(executor.database().reader());
+    assert_eq!(source.sequence_number(AccountAddress::TWO).unwrap(), 0);
+}
@@ -1,10 +1,13 @@
 //! Helpers for constructing Aptos transactions used by tests and clients.
 
 use crate::accounts::LocalAccount;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context as _, Result};
 use aptos_cached_packages::aptos_stdlib;
-use aptos_crypto::SigningKey;
+use aptos_crypto::ed25519::Ed25519PrivateKey;
+use aptos_crypto::{PrivateKey as _, SigningKey};
 use aptos_types::{
+    account_address::create_object_address,
+    account_config::{RotationProofChallenge, CORE_CODE_ADDRESS},
     chain_id::ChainId,
     transaction::{
         authenticator::AccountAuthenticator, EntryFunction, RawTransaction, RawTransactionWithData,
@@ -16,8 +19,41 @@ use move_core_types::{
     identifier::Identifier,
     language_storage::{ModuleId, StructTag, TypeTag},
 };
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+#[cfg(test)]
+#[path = "tests/transaction_builder_tests.rs"]
+pub mod transaction_builder_tests;
+
+/// The gas budget of transactions built by this module, unless stated otherwise.
+pub const DEFAULT_MAX_GAS_AMOUNT: u64 = 2_000_000;
+/// The gas unit price of transactions built by this module, unless stated otherwise.
+pub const DEFAULT_GAS_UNIT_PRICE: u64 = 100;
+/// Extra gas budgeted by `publish_package` for every byte of metadata and bytecode.
+pub const PUBLISH_GAS_PER_BYTE: u64 = 20;
+
+/// Overrides for the gas and expiration settings of a transaction.
+#[derive(Clone, Copy, Debug)]
+pub struct TxnOptions {
+    pub max_gas_amount: u64,
+    pub gas_unit_price: u64,
+    /// Expiration timestamp in seconds; defaults to ten minutes from now when unset.
+    pub expiration_timestamp_secs: Option<u64>,
+}
+
+impl Default for TxnOptions {
+    fn default() -> Self {
+        Self {
+            max_gas_amount: DEFAULT_MAX_GAS_AMOUNT,
+            gas_unit_price: DEFAULT_GAS_UNIT_PRICE,
+            expiration_timestamp_secs: None,
+        }
+    }
+}
+
 /// Builds a signed transaction that transfers APT from `sender` to `recipient`.
 pub fn apt_transfer(
     sender: &mut LocalAccount,
@@ -46,10 +82,10 @@ pub fn apt_transfer(
 
     let raw_txn = RawTransaction::new(
         sender.address,
-        sender.sequence_number,
+        sender.next_sequence_number()?,
         payload,
-        2_000_000,
-        100,
+        DEFAULT_MAX_GAS_AMOUNT,
+        DEFAULT_GAS_UNIT_PRICE,
         expiration_secs,
         chain_id,
     );
@@ -57,6 +93,110 @@ pub fn apt_transfer(
     sender.sign(raw_txn)
 }
 
+/// Builds a signed transaction that transfers APT from `sender` to `recipient` via
+/// `aptos_account::transfer`, which creates the account of the recipient if it does not exist yet.
+pub fn aptos_account_transfer(
+    sender: &mut LocalAccount,
+    recipient: AccountAddress,
+    amount: u64,
+    chain_id: ChainId,
+) -> Result<SignedTransaction> {
+    let payload = aptos_stdlib::aptos_account_transfer(recipient, amount);
+    let raw_txn = RawTransaction::new(
+        sender.address,
+        sender.next_sequence_number()?,
+        payload,
+        DEFAULT_MAX_GAS_AMOUNT,
+        DEFAULT_GAS_UNIT_PRICE,
+        default_expiration_secs(),
+        chain_id,
+    );
+
+    sender.sign(raw_txn)
+}
+
+/// Builds a signed transaction that moves `amount` units of a fungible asset from the primary store
+/// of `sender` to that of `recipient` via `0x1::primary_fungible_store::transfer`, creating the store
+/// of the recipient if needed. `metadata` is the address of the metadata object of the asset (e.g.
+/// `0xa` for APT).
+pub fn fungible_transfer(
+    sender: &mut LocalAccount,
+    recipient: AccountAddress,
+    metadata: AccountAddress,
+    amount: u64,
+    chain_id: ChainId,
+) -> Result<SignedTransaction> {
+    let module = ModuleId::new(
+        AccountAddress::ONE,
+        Identifier::new("primary_fungible_store")?,
+    );
+    let function = Identifier::new("transfer")?;
+    let metadata_type = TypeTag::Struct(Box::new(StructTag {
+        address: AccountAddress::ONE,
+        module: Identifier::new("fungible_asset")?,
+        name: Identifier::new("Metadata")?,
+        type_args: vec![],
+    }));
+
+    // An `Object<T>` is serialized as the address of the object.
+    let entry_function = EntryFunction::new(
+        module,
+        function,
+        vec![metadata_type],
+        vec![
+            bcs::to_bytes(&metadata)?,
+            bcs::to_bytes(&recipient)?,
+            bcs::to_bytes(&amount)?,
+        ],
+    );
+
+    let raw_txn = RawTransaction::new(
+        sender.address,
+        sender.next_sequence_number()?,
+        TransactionPayload::EntryFunction(entry_function),
+        DEFAULT_MAX_GAS_AMOUNT,
+        DEFAULT_GAS_UNIT_PRICE,
+        default_expiration_secs(),
+        chain_id,
+    );
+
+    sender.sign(raw_txn)
+}
+
+/// Seed of the metadata object created by `simple_market::test_asset::create_and_mint`.
+pub const TEST_ASSET_SEED: &[u8] = b"TEST_ASSET";
+
+/// Returns the address of the metadata object of the test asset created by `creator`.
+pub fn test_asset_metadata(creator: AccountAddress) -> AccountAddress {
+    create_object_address(creator, TEST_ASSET_SEED)
+}
+
+/// Builds a signed transaction that creates the test fungible asset of `creator` and mints `amount`
+/// of it into their primary store. The metadata of the asset lives at `test_asset_metadata`.
+pub fn create_test_asset(
+    module_owner: AccountAddress,
+    creator: &mut LocalAccount,
+    amount: u64,
+    chain_id: ChainId,
+) -> Result<SignedTransaction> {
+    let module = ModuleId::new(module_owner, Identifier::new("test_asset")?);
+    let function = Identifier::new("create_and_mint")?;
+    let entry_function =
+        EntryFunction::new(module, function, vec![], vec![bcs::to_bytes(&amount)?]);
+
+    let raw_txn = RawTransaction::new(
+        creator.address,
+        creator.next_sequence_number()?,
+        TransactionPayload::EntryFunction(entry_function),
+        DEFAULT_MAX_GAS_AMOUNT,
+        DEFAULT_GAS_UNIT_PRICE,
+        default_expiration_secs(),
+        chain_id,
+    );
+
+    creator.sign(raw_txn)
+}
+
 fn default_expiration_secs() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -65,27 +205,98 @@ fn default_expiration_secs() -> u64 {
         .as_secs()
 }
 
+/// Builds a signed transaction that rotates the authentication key of `account` to the one of
+/// `new_private_key` via `account::rotate_authentication_key`, proving knowledge of both keys. The
+/// account keeps signing with its current key until `LocalAccount::rotate_key` is called, once the
+/// transaction is executed.
+pub fn rotate_key_txn(
+    account: &mut LocalAccount,
+    new_private_key: &Ed25519PrivateKey,
+    chain_id: ChainId,
+) -> Result<SignedTransaction> {
+    let sequence_number = account.next_sequence_number()?;
+    let new_public_key = new_private_key.public_key();
+    let challenge = RotationProofChallenge {
+        account_address: CORE_CODE_ADDRESS,
+        module_name: String::from("account"),
+        struct_name: String::from("RotationProofChallenge"),
+        sequence_number,
+        originator: account.address,
+        current_auth_key: account.authentication_key().account_address(),
+        new_public_key: new_public_key.to_bytes().to_vec(),
+    };
+    let message = bcs::to_bytes(&challenge)?;
+    let current_signature = account.private_key.sign_arbitrary_message(&message);
+    let new_signature = new_private_key.sign_arbitrary_message(&message);
+
+    // Scheme 0 is Ed25519.
+    let payload = aptos_stdlib::account_rotate_authentication_key(
+        0,
+        account.public_key.to_bytes().to_vec(),
+        0,
+        new_public_key.to_bytes().to_vec(),
+        current_signature.to_bytes().to_vec(),
+        new_signature.to_bytes().to_vec(),
+    );
+    let raw_txn = RawTransaction::new(
+        account.address,
+        sequence_number,
+        payload,
+        DEFAULT_MAX_GAS_AMOUNT,
+        DEFAULT_GAS_UNIT_PRICE,
+        default_expiration_secs(),
+        chain_id,
+    );
+
+    account.sign(raw_txn)
+}
+
 /// Builds a signed transaction that publishes a Move package via `code::publish_package_txn`.
+/// The gas budget grows with the size of the package, see `publish_gas_budget`.
 pub fn publish_package(
     sender: &mut LocalAccount,
     metadata: Vec<u8>,
     modules: Vec<Vec<u8>>,
     chain_id: ChainId,
+) -> Result<SignedTransaction> {
+    let options = TxnOptions {
+        max_gas_amount: publish_gas_budget(&metadata, &modules),
+        ..TxnOptions::default()
+    };
+    publish_package_with_options(sender, metadata, modules, chain_id, options)
+}
+
+/// Same as `publish_package` but with explicit gas and expiration settings.
+pub fn publish_package_with_options(
+    sender: &mut LocalAccount,
+    metadata: Vec<u8>,
+    modules: Vec<Vec<u8>>,
+    chain_id: ChainId,
+    options: TxnOptions,
 ) -> Result<SignedTransaction> {
     let payload = aptos_stdlib::code_publish_package_txn(metadata, modules);
     let raw_txn = RawTransaction::new(
         sender.address,
-        sender.sequence_number,
+        sender.next_sequence_number()?,
         payload,
-        2_000_000,
-        100,
-        default_expiration_secs(),
+        options.max_gas_amount,
+        options.gas_unit_price,
+        options
+            .expiration_timestamp_secs
+            .unwrap_or_else(default_expiration_secs),
         chain_id,
     );
 
     sender.sign(raw_txn)
 }
 
+/// Returns the default gas budget to publish a package: the regular budget plus
+/// `PUBLISH_GAS_PER_BYTE` for every byte of metadata and bytecode.
+pub fn publish_gas_budget(metadata: &[u8], modules: &[Vec<u8>]) -> u64 {
+    let bytes = metadata.len() + modules.iter().map(Vec::len).sum::<usize>();
+    DEFAULT_MAX_GAS_AMOUNT.saturating_add(PUBLISH_GAS_PER_BYTE.saturating_mul(bytes as u64))
+}
+
 /// Builds a multi-agent transaction that invokes `simple_market::market_setup::create_market`.
 pub fn create_market(
     admin: &mut LocalAccount,
@@ -124,10 +335,10 @@ pub fn register_trader(
     let payload = TransactionPayload::EntryFunction(entry_function);
     let raw_txn = RawTransaction::new(
         trader.address,
-        trader.sequence_number,
+        trader.next_sequence_number()?,
         payload,
-        2_000_000,
-        100,
+        DEFAULT_MAX_GAS_AMOUNT,
+        DEFAULT_GAS_UNIT_PRICE,
         default_expiration_secs(),
         chain_id,
     );
@@ -159,10 +370,10 @@ pub fn mint_trader_funds(
     let payload = TransactionPayload::EntryFunction(entry_function);
     let raw_txn = RawTransaction::new(
         admin.address,
-        admin.sequence_number,
+        admin.next_sequence_number()?,
         payload,
-        2_000_000,
-        100,
+        DEFAULT_MAX_GAS_AMOUNT,
+        DEFAULT_GAS_UNIT_PRICE,
         default_expiration_secs(),
         chain_id,
     );
@@ -198,6 +409,49 @@ pub fn place_limit_order_with_client_id(
     build_multi_agent_market_txn(trader, market_signer, entry_function, chain_id)
 }
 
+/// A limit order placed by `place_orders_batch`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderSpec {
+    pub limit_price: u64,
+    pub size: u64,
+    pub is_bid: bool,
+    pub client_order_id: u64,
+}
+
+/// Builds a multi-agent transaction that invokes `place_limit_orders_with_client_ids`, placing all
+/// `orders` atomically: if one of them cannot be placed, none is.
+pub fn place_orders_batch(
+    module_owner: AccountAddress,
+    trader: &mut LocalAccount,
+    market_signer: &LocalAccount,
+    orders: Vec<OrderSpec>,
+    chain_id: ChainId,
+) -> Result<SignedTransaction> {
+    if orders.is_empty() {
+        bail!("a batch must contain at least one order");
+    }
+    let limit_prices: Vec<_> = orders.iter().map(|x| x.limit_price).collect();
+    let sizes: Vec<_> = orders.iter().map(|x| x.size).collect();
+    let is_bids: Vec<_> = orders.iter().map(|x| x.is_bid).collect();
+    let client_order_ids: Vec<_> = orders.iter().map(|x| x.client_order_id).collect();
+
+    let module = ModuleId::new(module_owner, Identifier::new("market_setup")?);
+    let function = Identifier::new("place_limit_orders_with_client_ids")?;
+    let entry_function = EntryFunction::new(
+        module,
+        function,
+        vec![],
+        vec![
+            bcs::to_bytes(&limit_prices)?,
+            bcs::to_bytes(&sizes)?,
+            bcs::to_bytes(&is_bids)?,
+            bcs::to_bytes(&client_order_ids)?,
+        ],
+    );
+
+    build_multi_agent_market_txn(trader, market_signer, entry_function, chain_id)
+}
+
 /// Builds a multi-agent transaction that cancels an order by client order ID.
 pub fn cancel_order_by_client_id(
     module_owner: AccountAddress,
@@ -279,10 +533,10 @@ fn build_multi_agent_market_txn(
     let payload = TransactionPayload::EntryFunction(entry_function);
     let raw_txn = RawTransaction::new(
         primary.address,
-        primary.sequence_number,
+        primary.next_sequence_number()?,
         payload,
-        2_000_000,
-        100,
+        DEFAULT_MAX_GAS_AMOUNT,
+        DEFAULT_GAS_UNIT_PRICE,
         default_expiration_secs(),
         chain_id,
     );
@@ -308,3 +562,179 @@ fn build_multi_agent_market_txn(
         vec![market_authenticator],
     ))
 }
+
+/// The accounts and chain of a transaction built through a `TxnBuilderRegistry`.
+pub struct BuilderContext<'a> {
+    /// The sender of the transaction, whose sequence number is bumped.
+    pub sender: &'a mut LocalAccount,
+    /// The other signers of multi-agent transactions, e.g. the market signer.
+    pub secondary_signers: Vec<&'a LocalAccount>,
+    /// The account that published the Move modules the transaction invokes.
+    pub module_owner: AccountAddress,
+    pub chain_id: ChainId,
+}
+
+impl<'a> BuilderContext<'a> {
+    /// Returns the secondary signer at `index`.
+    fn secondary_signer(&self, index: usize) -> Result<&'a LocalAccount> {
+        self.secondary_signers
+            .get(index)
+            .copied()
+            .ok_or_else(|| anyhow!("missing secondary signer {}", index))
+    }
+}
+
+/// Builds a transaction from its context and its BCS-encoded arguments.
+pub type TxnBuilder =
+    Box<dyn Fn(BuilderContext<'_>, &[Vec<u8>]) -> Result<SignedTransaction> + Send + Sync>;
+
+/// Maps names to transaction builders, so that scenarios can be described in data rather than code.
+/// Arguments are BCS-encoded, in the order of the parameters of the underlying builder.
+#[derive(Default)]
+pub struct TxnBuilderRegistry {
+    builders: HashMap<String, TxnBuilder>,
+}
+
+impl TxnBuilderRegistry {
+    /// Returns a registry holding the APT transfer and the builders of the market scenario, named
+    /// after their functions in this module.
+    pub fn with_market_builders() -> Self {
+        let mut registry = Self::default();
+        registry.register("apt_transfer", |context, args| {
+            expect_args(args, 2)?;
+            apt_transfer(
+                context.sender,
+                decode_arg(args, 0)?,
+                decode_arg(args, 1)?,
+                context.chain_id,
+            )
+        });
+        registry.register("create_market", |context, args| {
+            expect_args(args, 3)?;
+            let market_signer = context.secondary_signer(0)?;
+            create_market(
+                context.sender,
+                market_signer,
+                decode_arg(args, 0)?,
+                decode_arg(args, 1)?,
+                decode_arg(args, 2)?,
+                context.chain_id,
+            )
+        });
+        registry.register("register_trader", |context, args| {
+            expect_args(args, 0)?;
+            register_trader(context.module_owner, context.sender, context.chain_id)
+        });
+        registry.register("mint_trader_funds", |context, args| {
+            expect_args(args, 3)?;
+            mint_trader_funds(
+                context.sender,
+                decode_arg(args, 0)?,
+                decode_arg(args, 1)?,
+                decode_arg(args, 2)?,
+                context.chain_id,
+            )
+        });
+        registry.register("place_limit_order_with_client_id", |context, args| {
+            expect_args(args, 4)?;
+            let market_signer = context.secondary_signer(0)?;
+            place_limit_order_with_client_id(
+                context.module_owner,
+                context.sender,
+                market_signer,
+                decode_arg(args, 0)?,
+                decode_arg(args, 1)?,
+                decode_arg(args, 2)?,
+                decode_arg(args, 3)?,
+                context.chain_id,
+            )
+        });
+        registry.register("place_orders_batch", |context, args| {
+            expect_args(args, 1)?;
+            let market_signer = context.secondary_signer(0)?;
+            place_orders_batch(
+                context.module_owner,
+                context.sender,
+                market_signer,
+                decode_arg(args, 0)?,
+                context.chain_id,
+            )
+        });
+        registry.register("cancel_order_by_client_id", |context, args| {
+            expect_args(args, 1)?;
+            let market_signer = context.secondary_signer(0)?;
+            cancel_order_by_client_id(
+                context.module_owner,
+                context.sender,
+                market_signer,
+                decode_arg(args, 0)?,
+                context.chain_id,
+            )
+        });
+        registry.register("decrease_order_size_by_client_id", |context, args| {
+            expect_args(args, 2)?;
+            let market_signer = context.secondary_signer(0)?;
+            decrease_order_size_by_client_id(
+                context.module_owner,
+                context.sender,
+                market_signer,
+                decode_arg(args, 0)?,
+                decode_arg(args, 1)?,
+                context.chain_id,
+            )
+        });
+        registry.register("replace_order_by_client_id", |context, args| {
+            expect_args(args, 4)?;
+            let market_signer = context.secondary_signer(0)?;
+            replace_order_by_client_id(
+                context.module_owner,
+                context.sender,
+                market_signer,
+                decode_arg(args, 0)?,
+                decode_arg(args, 1)?,
+                decode_arg(args, 2)?,
+                decode_arg(args, 3)?,
+                context.chain_id,
+            )
+        });
+        registry
+    }
+
+    /// Registers a builder under `name`, replacing any builder of the same name.
+    pub fn register<F>(&mut self, name: &str, builder: F)
+    where
+        F: Fn(BuilderContext<'_>, &[Vec<u8>]) -> Result<SignedTransaction> + Send + Sync + 'static,
+    {
+        self.builders.insert(name.to_string(), Box::new(builder));
+    }
+
+    /// Returns the names of the registered builders, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.builders.keys().map(String::as_str)
+    }
+
+    /// Builds a transaction with the builder registered under `name`.
+    pub fn build(
+        &self,
+        name: &str,
+        context: BuilderContext<'_>,
+        args: &[Vec<u8>],
+    ) -> Result<SignedTransaction> {
+        let builder = self
+            .builders
+            .get(name)
+            .ok_or_else(|| anyhow!("unknown transaction builder {}", name))?;
+        builder(context, args).with_context(|| format!("failed to build {}", name))
+    }
+}
+
+fn expect_args(args: &[Vec<u8>], expected: usize) -> Result<()> {
+    if args.len() != expected {
+        bail!("expected {} arguments, got {}", expected, args.len());
+    }
+    Ok(())
+}
+
+fn decode_arg<T: DeserializeOwned>(args: &[Vec<u8>], index: usize) -> Result<T> {
+    bcs::from_bytes(&args[index]).with_context(|| format!("malformed argument {}", index))
+}
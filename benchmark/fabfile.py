@@ -152,6 +152,7 @@ def remote(ctx, burst=50, debug=False, consensus_only=True, update=True, aggrega
         'sync_retry_nodes': 3,  # number of nodes
         'batch_size': 1024_000,  # bytes
         'max_batch_delay': 2000,  # ms
+        'worker_listen_ip': '0.0.0.0',  # the hosts do not own their public IPs
         'use_vote_aggregator': aggregate,
         # FailureBestCase | FailureMidCase | FailureWorstCase | FairSuccession | Simple
         'leader_elector': 'Simple',
@@ -0,0 +1,154 @@
+//! Committees built in memory, for tests running several authorities in one process.
+
+use crate::{
+    Authority, Committee, ConfigError, ConsensusAddresses, Parameters, PrimaryAddresses, Stake,
+    WorkerAddresses, WorkerId,
+};
+use blsttc::{SecretKeySet, SecretKeyShare};
+use crypto::{generate_production_keypair, PublicKey, SecretKey};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+
+/// The keys of an authority of a committee made by `CommitteeBuilder`.
+pub struct AuthorityKeys {
+    /// The name of the authority.
+    pub name: PublicKey,
+    /// The secret key signing headers and votes.
+    pub secret: SecretKey,
+    /// The BLS key share signing votes for certificates.
+    pub bls_secret: SecretKeyShare,
+}
+
+/// A committee made by `CommitteeBuilder`, with parameters matching its size and the keys of its
+/// authorities (in the order of their ids).
+pub struct TestCommittee {
+    pub committee: Committee,
+    pub parameters: Parameters,
+    pub keys: Vec<AuthorityKeys>,
+}
+
+/// Builds a committee and its parameters without any key or committee file. Every authority gets
+/// fresh keys and its own range of ports on the loopback interface, starting at the base port.
+pub struct CommitteeBuilder {
+    stakes: Vec<Stake>,
+    workers: u32,
+    base_port: u16,
+    parameters: Parameters,
+}
+
+impl CommitteeBuilder {
+    /// Starts a committee of `size` authorities with one unit of stake each and no workers.
+    pub fn new(size: usize) -> Self {
+        Self {
+            stakes: vec![1; size],
+            workers: 0,
+            base_port: 10_000,
+            parameters: Parameters::default(),
+        }
+    }
+
+    /// Sets the stake of every authority; the committee has one authority per stake.
+    pub fn stakes(mut self, stakes: Vec<Stake>) -> Self {
+        self.stakes = stakes;
+        self
+    }
+
+    /// Sets the number of workers of every authority.
+    pub fn workers(mut self, workers: u32) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Sets the first port of the committee. Tests running concurrently need disjoint ranges.
+    pub fn base_port(mut self, base_port: u16) -> Self {
+        self.base_port = base_port;
+        self
+    }
+
+    /// Sets the parameters to start from. `build` overrides their `n`, `f`, `c` and `k`.
+    pub fn parameters(mut self, parameters: Parameters) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// Generates the keys and addresses of the authorities. The committee tolerates the largest
+    /// `f` with `n >= 3f + 1`, and uses the slow path only (`c` and `k` are zero). Fails if the
+    /// ports of the committee do not fit above the base port.
+    pub fn build(self) -> Result<TestCommittee, ConfigError> {
+        let size = self.stakes.len();
+        let n = size as u32;
+        let f = n.saturating_sub(1) / 3;
+        let bls = SecretKeySet::random(f as usize, &mut blsttc::rand::rngs::OsRng);
+
+        // Every authority uses the ports of its consensus, its primary, and each of its workers.
+        let ports_per_authority = 3 + 3 * self.workers as usize;
+        let (base_port, workers_per_authority) = (self.base_port, self.workers);
+        let address = |offset: usize| -> Result<SocketAddr, ConfigError> {
+            u16::try_from(offset)
+                .ok()
+                .and_then(|offset| base_port.checked_add(offset))
+                .map(|port| SocketAddr::from(([127, 0, 0, 1], port)))
+                .ok_or_else(|| {
+                    ConfigError::InvalidCommittee(format!(
+                        "The ports of {} authorities with {} workers each overflow from port {}",
+                        size, workers_per_authority, base_port
+                    ))
+                })
+        };
+
+        let mut authorities = BTreeMap::new();
+        let mut keys = Vec::with_capacity(size);
+        for (i, stake) in self.stakes.into_iter().enumerate() {
+            let (name, secret) = generate_production_keypair();
+            let offset = i * ports_per_authority;
+            let workers = (0..self.workers)
+                .map(|id| {
+                    let offset = offset + 3 + 3 * id as usize;
+                    let addresses = WorkerAddresses {
+                        transactions: address(offset)?,
+                        worker_to_worker: address(offset + 1)?,
+                        primary_to_worker: address(offset + 2)?,
+                        api: None,
+                    };
+                    Ok((id, addresses))
+                })
+                .collect::<Result<HashMap<WorkerId, WorkerAddresses>, ConfigError>>()?;
+            let authority = Authority {
+                id: i as u32,
+                bls_pubkey_g1: bls.public_keys().public_key_share(i),
+                bls_pubkey_g2: bls.public_keys_g2().public_key_share(i),
+                is_honest: true,
+                stake,
+                consensus: ConsensusAddresses {
+                    consensus_to_consensus: address(offset)?,
+                },
+                primary: PrimaryAddresses {
+                    primary_to_primary: address(offset + 1)?,
+                    worker_to_primary: address(offset + 2)?,
+                },
+                workers,
+            };
+            authorities.insert(name, authority);
+            keys.push(AuthorityKeys {
+                name,
+                secret,
+                bls_secret: bls.secret_key_share(i),
+            });
+        }
+
+        let parameters = Parameters {
+            n,
+            f,
+            c: 0,
+            k: 0,
+            ..self.parameters
+        };
+        let committee = Committee::new(authorities, n, f, 0, 0);
+        Ok(TestCommittee {
+            committee,
+            parameters,
+            keys,
+        })
+    }
+}
@@ -1,16 +1,25 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use blsttc::{PublicKeyShareG1, PublicKeyShareG2, SecretKeyShare};
 use crypto::{combine_keys, generate_production_keypair, PublicKey, SecretKey};
-use log::info;
-use serde::de::DeserializeOwned;
-use serde::{Deserialize, Serialize};
+use log::{info, warn};
+use serde::de::{DeserializeOwned, Error as _, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs::{self, OpenOptions};
 use std::io::BufWriter;
 use std::io::Write as _;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 use thiserror::Error;
 
+#[cfg(test)]
+#[path = "tests/config_tests.rs"]
+pub mod config_tests;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod committee_builder;
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("Node {0} is not in the committee")]
@@ -24,6 +33,12 @@ pub enum ConfigError {
 
     #[error("Failed to write config file '{file}': {message}")]
     ExportError { file: String, message: String },
+
+    #[error("Invalid parameter '{name}': {message}")]
+    InvalidParameter { name: String, message: String },
+
+    #[error("Invalid committee: {0}")]
+    InvalidCommittee(String),
 }
 
 pub trait Import: DeserializeOwned {
@@ -68,6 +83,78 @@ pub enum LeaderElectorKind {
     Simple,
 }
 
+/// Where the node keeps the data shared by the primary and the consensus.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum StoreBackend {
+    /// Kept in memory and lost when the node stops; for tests and experiments.
+    Memory,
+    /// A RocksDB database at the given path.
+    RocksDb { path: String },
+}
+
+/// The serialization format of the headers, certificates and blocks the primary, the consensus and
+/// the committer keep in the store. Every node sharing a store must use the same codec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum StoreCodec {
+    #[default]
+    Bincode,
+    /// The format of the transactions, for tooling reading the store with BCS only.
+    Bcs,
+}
+
+/// The release of the Aptos framework installed by the genesis of the executor. `Head` is the framework
+/// compiled into the Aptos dependency, which changes whenever the dependency is updated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum GenesisFramework {
+    #[default]
+    Head,
+    Testnet,
+    Mainnet,
+}
+
+#[derive(Error, Debug)]
+pub enum CodecError {
+    #[error("Bincode error: {0}")]
+    Bincode(#[from] Box<bincode::ErrorKind>),
+
+    #[error("BCS error: {0}")]
+    Bcs(#[from] bcs::Error),
+}
+
+impl StoreCodec {
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Self::Bincode => Ok(bincode::serialize(value)?),
+            Self::Bcs => Ok(bcs::to_bytes(value)?),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        match self {
+            Self::Bincode => Ok(bincode::deserialize(bytes)?),
+            Self::Bcs => Ok(bcs::from_bytes(bytes)?),
+        }
+    }
+}
+
+/// Faults injected into the messages a primary sends to the other primaries, to test the protocol
+/// under adverse network conditions. Only honoured by nodes built with the `fault-injection` feature.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FaultConfig {
+    /// The fraction of outgoing messages that are silently dropped, between 0 and 1.
+    #[serde(default)]
+    pub drop_rate: f64,
+    /// Outgoing messages are delayed by a random duration of up to this many milliseconds.
+    #[serde(default)]
+    pub max_delay: u64,
+    /// Whether to send conflicting headers to different peers for each of our rounds.
+    #[serde(default)]
+    pub equivocate: bool,
+    /// Seeds the faults, so that a run can be reproduced.
+    #[serde(default)]
+    pub seed: u64,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct Parameters {
     /// Runs the consensus module in isolation if true.
@@ -82,7 +169,9 @@ pub struct Parameters {
     /// The maximum delay that the primary waits between generating two headers, even if the header
     /// did not reach `max_header_size`. Denominated in ms.
     pub max_header_delay: u64,
-    /// The depth of the garbage collection (Denominated in number of rounds).
+    /// The depth of the garbage collection (Denominated in number of rounds). The primary may read
+    /// back from the store the headers and certificates of rounds down to this depth, so the store
+    /// must retain at least as many rounds (see `store_retention`).
     pub gc_depth: u64,
     /// The delay after which the synchronizer retries to send sync requests. Denominated in ms.
     pub sync_retry_delay: u64,
@@ -99,6 +188,133 @@ pub struct Parameters {
     pub use_vote_aggregator: bool,
     /// The type of leader election function to use. See leader.rs.
     pub leader_elector: LeaderElectorKind,
+    /// Causes primaries to send headers and certificates to high-stake peers first.
+    #[serde(default)]
+    pub prioritize_by_stake: bool,
+    /// Causes workers to deliver a batch to their primary only once a quorum of workers
+    /// acknowledged it.
+    #[serde(default)]
+    pub batch_quorum_ack: bool,
+    /// Causes workers to cluster the transactions of each sender contiguously within a batch, in
+    /// sequence number order.
+    #[serde(default)]
+    pub group_batches_by_sender: bool,
+    /// The maximum number of client connections a worker accepts at the same time on its transactions
+    /// address. Further connections are closed straight away. No limit if unset.
+    #[serde(default)]
+    pub max_client_connections: Option<usize>,
+    /// The initial delay after which the primary retries to connect to an unreachable peer. The delay
+    /// doubles after each failed attempt. Denominated in ms.
+    #[serde(default = "default_network_retry_delay")]
+    pub network_retry_delay: u64,
+    /// The number of consecutive failed connection attempts after which the primary drops the
+    /// messages pending for an unreachable peer. The primary retries forever if unset.
+    #[serde(default)]
+    pub network_max_retries: Option<u64>,
+    /// The maximum number of transactions from a single sender that the primary includes in a header.
+    /// The excess is deferred to the next headers. No limit if unset.
+    #[serde(default)]
+    pub max_txns_per_sender_per_header: Option<usize>,
+    /// Causes the primary to shuffle the transactions of its headers with a seed derived from their
+    /// content, rather than ordering them first-in first-out. Only the order of the senders changes:
+    /// the transactions of a sender stay ordered by sequence number. Other nodes check the order.
+    #[serde(default)]
+    pub shuffle_header_payload: bool,
+    /// The number of execution receipts kept for clients to query. The oldest receipts are pruned.
+    #[serde(default = "default_receipts_capacity")]
+    pub receipts_capacity: usize,
+    /// The number of rounds for which execution receipts are kept, pruned as blocks are executed.
+    /// Defaults to `gc_depth`.
+    #[serde(default)]
+    pub receipts_retention: Option<u64>,
+    /// Causes the committer to check that the rounds of the committed certificates advance without
+    /// gaps, and to report the skipped rounds in its logs and in the `/status` of the transaction API:
+    /// they may hold transactions that were never executed.
+    #[serde(default)]
+    pub detect_commit_gaps: bool,
+    /// The store backend of the node. Defaults to a RocksDB database at the path given on the command
+    /// line.
+    #[serde(default)]
+    pub store_backend: Option<StoreBackend>,
+    /// The serialization format of the headers and certificates in the store.
+    #[serde(default)]
+    pub store_codec: StoreCodec,
+    /// If set, the primary deletes from the store the headers and certificates more than this many
+    /// rounds below the last committed round. Must be at least `gc_depth`: the primary would
+    /// otherwise look up data that is gone, while retaining more only costs disk space. It should
+    /// also cover the rounds the committer lags behind the consensus, whose headers it still reads.
+    /// The store retains everything if unset.
+    #[serde(default)]
+    pub store_retention: Option<u64>,
+    /// Faults to inject into the messages of the primary, for testing only.
+    #[serde(default)]
+    pub fault_injection: Option<FaultConfig>,
+    /// If set, the committer appends a JSON summary of every executed block to this file.
+    #[serde(default)]
+    pub block_output: Option<PathBuf>,
+    /// If set, the committer appends the output of every executed transaction to this file, in the
+    /// Aptos `TransactionOutput` format, for indexers and other Aptos tooling.
+    #[serde(default)]
+    pub transaction_output: Option<PathBuf>,
+    /// Above this many committed blocks waiting to be executed, the primary stops creating headers as
+    /// soon as it has enough transactions and backs off its inter-header delay instead, so that the
+    /// backlog of the executor stops growing. Never throttled if unset.
+    #[serde(default)]
+    pub execution_pressure_threshold: Option<usize>,
+    /// If set, the primary proposes an empty header after this long (in ms) without proposing any, so
+    /// that the dag (and thus garbage collection and liveness monitoring) keeps advancing while no
+    /// transactions arrive. Headers are never closer than `max_header_delay`.
+    #[serde(default)]
+    pub heartbeat_delay: Option<u64>,
+    /// If set, the primary stops proposing headers while its round is more than this many rounds
+    /// ahead of the last committed round, and resumes once commits catch up. This bounds the depth of
+    /// the dag (and the memory it holds) when consensus falls behind.
+    #[serde(default)]
+    pub max_round_lead: Option<u64>,
+    /// If set, the primary deprioritizes the senders aborting more than this fraction of their recent
+    /// transactions: it includes a single one of their transactions per header, after all the
+    /// others, so that a buggy contract does not waste the capacity of the dag.
+    #[serde(default)]
+    pub max_sender_abort_rate: Option<f64>,
+    /// If set, up to this many certificates wait in a buffer between the primary's core and the
+    /// consensus, so that a burst of certificates does not hold up the processing of headers and
+    /// votes while the consensus catches up.
+    #[serde(default)]
+    pub certificate_buffer_capacity: Option<usize>,
+    /// The number of threads verifying the certificates received from other primaries. If unset, one
+    /// thread per authority, bounded by the number of CPUs and `MAX_VERIFICATION_THREADS`.
+    #[serde(default)]
+    pub verification_threads: Option<usize>,
+    /// The number of client transactions the worker queues for its batch maker. Transactions arriving
+    /// while the queue is full are dropped.
+    #[serde(default = "default_worker_queue_capacity")]
+    pub transactions_queue_capacity: usize,
+    /// The number of sealed batches the worker queues while waiting for a quorum of workers to
+    /// acknowledge them (only with `batch_quorum_ack`).
+    #[serde(default = "default_worker_queue_capacity")]
+    pub batches_queue_capacity: usize,
+    /// The number of sealed batches the worker buffers while the primary (or the quorum waiter) does
+    /// not keep up, after which it stops accepting client transactions until they catch up.
+    #[serde(default = "default_max_pending_batches")]
+    pub max_pending_batches: usize,
+    /// If set, the worker coalesces the batches sealed within this long (in ms) of each other into a
+    /// single delivery to the primary, so that the proposer wakes up less often under load. A batch
+    /// is delayed by at most this long.
+    #[serde(default)]
+    pub digest_coalesce_delay: Option<u64>,
+    /// The worker rejects the client transactions offering a lower gas unit price, so that zero-fee
+    /// spam does not consume the bandwidth of the dag. Zero admits every transaction.
+    #[serde(default = "default_min_gas_unit_price")]
+    pub min_gas_unit_price: u64,
+    /// The worker rejects the multi-agent transactions (e.g. market orders co-signed by the market)
+    /// missing the signature of a secondary signer, which would otherwise only fail at execution.
+    #[serde(default)]
+    pub reject_missing_secondary_signatures: bool,
+    /// The IP the workers listen on for client transactions (over TCP and HTTP) and for the messages
+    /// of other workers, e.g. `0.0.0.0` to listen on every interface. The workers listen on the IP the
+    /// committee advertises for them if unset.
+    #[serde(default)]
+    pub worker_listen_ip: Option<IpAddr>,
 
     pub n: u32,
     pub f: u32,
@@ -117,10 +333,40 @@ impl Default for Parameters {
             gc_depth: 50,
             sync_retry_delay: 5_000,
             sync_retry_nodes: 3,
+            worker_listen_ip: None,
             batch_size: 500_000,
             max_batch_delay: 100,
             use_vote_aggregator: false,
             leader_elector: LeaderElectorKind::Simple,
+            prioritize_by_stake: false,
+            batch_quorum_ack: false,
+            group_batches_by_sender: false,
+            max_client_connections: None,
+            network_retry_delay: default_network_retry_delay(),
+            network_max_retries: None,
+            max_txns_per_sender_per_header: None,
+            shuffle_header_payload: false,
+            receipts_capacity: default_receipts_capacity(),
+            receipts_retention: None,
+            detect_commit_gaps: false,
+            store_backend: None,
+            store_codec: StoreCodec::default(),
+            store_retention: None,
+            fault_injection: None,
+            block_output: None,
+            transaction_output: None,
+            execution_pressure_threshold: None,
+            heartbeat_delay: None,
+            max_round_lead: None,
+            max_sender_abort_rate: None,
+            certificate_buffer_capacity: None,
+            verification_threads: None,
+            transactions_queue_capacity: default_worker_queue_capacity(),
+            batches_queue_capacity: default_worker_queue_capacity(),
+            max_pending_batches: default_max_pending_batches(),
+            digest_coalesce_delay: None,
+            min_gas_unit_price: default_min_gas_unit_price(),
+            reject_missing_secondary_signatures: false,
             n: 15,
             f: 3,
             c: 2,
@@ -129,15 +375,199 @@ impl Default for Parameters {
     }
 }
 
+fn default_network_retry_delay() -> u64 {
+    200
+}
+
+fn default_receipts_capacity() -> usize {
+    100_000
+}
+
+fn default_worker_queue_capacity() -> usize {
+    1_000
+}
+
+fn default_max_pending_batches() -> usize {
+    100
+}
+
+/// The gas unit price set by the transaction builders of `aptos_executor`.
+fn default_min_gas_unit_price() -> u64 {
+    100
+}
+
 impl Import for Parameters {}
 
 impl Parameters {
+    /// The smallest batch delay (in ms) we accept. Smaller values make the workers spin on their timer.
+    pub const MIN_BATCH_DELAY: u64 = 10;
+
+    /// The largest certificate-verification pool sized automatically. More threads rarely help: each
+    /// authority only sends one certificate per round.
+    pub const MAX_VERIFICATION_THREADS: usize = 16;
+
+    /// Returns the number of threads verifying certificates for a committee of `committee_size`
+    /// authorities, unless overridden by `verification_threads`.
+    pub fn verification_threads(&self, committee_size: usize) -> usize {
+        if let Some(threads) = self.verification_threads {
+            return threads;
+        }
+        let cpus = std::thread::available_parallelism().map_or(1, |x| x.get());
+        committee_size
+            .min(cpus)
+            .clamp(1, Self::MAX_VERIFICATION_THREADS)
+    }
+
+    /// Checks the parameters before the node boots. Degenerate values are rejected and delays that are
+    /// too small are clamped to a sane minimum.
+    pub fn validate(&mut self) -> Result<(), ConfigError> {
+        if self.batch_size == 0 {
+            return Err(ConfigError::InvalidParameter {
+                name: "batch_size".to_string(),
+                message: "must be greater than 0 B, otherwise batches only seal on the batch delay"
+                    .to_string(),
+            });
+        }
+        if self.network_retry_delay == 0 {
+            return Err(ConfigError::InvalidParameter {
+                name: "network_retry_delay".to_string(),
+                message: "must be greater than 0 ms, otherwise unreachable peers are retried in a busy loop"
+                    .to_string(),
+            });
+        }
+        if self.network_max_retries == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "network_max_retries".to_string(),
+                message: "must be greater than 0, leave it unset to retry forever".to_string(),
+            });
+        }
+        if let Some(retention) = self.store_retention {
+            if retention < self.gc_depth {
+                return Err(ConfigError::InvalidParameter {
+                    name: "store_retention".to_string(),
+                    message: format!(
+                        "the store must retain at least gc_depth ({}) rounds, got {}",
+                        self.gc_depth, retention
+                    ),
+                });
+            }
+        }
+        if let Some(StoreBackend::RocksDb { path }) = &self.store_backend {
+            if path.is_empty() {
+                return Err(ConfigError::InvalidParameter {
+                    name: "store_backend".to_string(),
+                    message: "the path of the RocksDB store must not be empty".to_string(),
+                });
+            }
+        }
+        if let Some(faults) = &self.fault_injection {
+            if !(0.0..1.0).contains(&faults.drop_rate) {
+                return Err(ConfigError::InvalidParameter {
+                    name: "fault_injection".to_string(),
+                    message: "the drop rate must be at least 0 and smaller than 1".to_string(),
+                });
+            }
+        }
+        if self.max_client_connections == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "max_client_connections".to_string(),
+                message: "must be greater than 0, leave it unset to disable the limit".to_string(),
+            });
+        }
+        if self.receipts_retention == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "receipts_retention".to_string(),
+                message: "must be greater than 0 rounds, otherwise receipts are pruned as soon as they are recorded"
+                    .to_string(),
+            });
+        }
+        if self.heartbeat_delay == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "heartbeat_delay".to_string(),
+                message: "must be greater than 0 ms, leave it unset to disable heartbeats"
+                    .to_string(),
+            });
+        }
+        if self.max_round_lead == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "max_round_lead".to_string(),
+                message: "must be greater than 0 rounds, leave it unset to never pause proposing"
+                    .to_string(),
+            });
+        }
+        if let Some(rate) = self.max_sender_abort_rate {
+            if !(0.0..1.0).contains(&rate) {
+                return Err(ConfigError::InvalidParameter {
+                    name: "max_sender_abort_rate".to_string(),
+                    message: "must be at least 0 and smaller than 1, leave it unset to never deprioritize senders"
+                        .to_string(),
+                });
+            }
+        }
+        if self.certificate_buffer_capacity == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "certificate_buffer_capacity".to_string(),
+                message: "must be greater than 0, leave it unset to forward certificates directly"
+                    .to_string(),
+            });
+        }
+        if self.verification_threads == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "verification_threads".to_string(),
+                message: "must be greater than 0, leave it unset to size the pool automatically"
+                    .to_string(),
+            });
+        }
+        if self.transactions_queue_capacity == 0 {
+            return Err(ConfigError::InvalidParameter {
+                name: "transactions_queue_capacity".to_string(),
+                message: "must be greater than 0 transactions".to_string(),
+            });
+        }
+        if self.batches_queue_capacity == 0 {
+            return Err(ConfigError::InvalidParameter {
+                name: "batches_queue_capacity".to_string(),
+                message: "must be greater than 0 batches".to_string(),
+            });
+        }
+        if self.max_pending_batches == 0 {
+            return Err(ConfigError::InvalidParameter {
+                name: "max_pending_batches".to_string(),
+                message: "must be greater than 0 batches".to_string(),
+            });
+        }
+        if self.digest_coalesce_delay == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "digest_coalesce_delay".to_string(),
+                message:
+                    "must be greater than 0 ms, leave it unset to deliver every batch on its own"
+                        .to_string(),
+            });
+        }
+        if self.max_txns_per_sender_per_header == Some(0) {
+            return Err(ConfigError::InvalidParameter {
+                name: "max_txns_per_sender_per_header".to_string(),
+                message: "must be greater than 0, leave it unset to disable the limit".to_string(),
+            });
+        }
+        if self.max_batch_delay < Self::MIN_BATCH_DELAY {
+            warn!(
+                "Max batch delay of {} ms is too small, clamping it to {} ms",
+                self.max_batch_delay,
+                Self::MIN_BATCH_DELAY
+            );
+            self.max_batch_delay = Self::MIN_BATCH_DELAY;
+        }
+        Ok(())
+    }
+
     pub fn log(&self, committee: &Committee) {
         // NOTE: These log entries are needed to compute performance.
         if self.consensus_only {
             info!("Running consensus in isolation");
         }
 
+        committee.execution.log();
         let faults = committee.get_byzantine_ids().len();
         info!("With {} faulty nodes in the network", faults);
         info!("Using {:?} leader elector", self.leader_elector);
@@ -147,12 +577,120 @@ impl Parameters {
         info!("C value set to {}", self.c);
         info!("K value set to {}", self.k);
         info!("Max header delay set to {} ms", self.max_header_delay);
+        if let Some(delay) = self.heartbeat_delay {
+            info!("Heartbeat delay set to {} ms", delay);
+        }
+        if let Some(lead) = self.max_round_lead {
+            info!("Pausing headers more than {} rounds ahead of commits", lead);
+        }
+        if let Some(capacity) = self.certificate_buffer_capacity {
+            info!(
+                "Buffering up to {} certificates for the consensus",
+                capacity
+            );
+        }
+        if let Some(rate) = self.max_sender_abort_rate {
+            info!(
+                "Deprioritizing senders aborting more than {} of their transactions",
+                rate
+            );
+        }
+        if let Some(threshold) = self.execution_pressure_threshold {
+            info!(
+                "Throttling headers above {} blocks waiting to be executed",
+                threshold
+            );
+        }
         info!("Garbage collection depth set to {} rounds", self.gc_depth);
+        match self.store_retention {
+            Some(retention) => info!("Store retention set to {} rounds", retention),
+            None => info!("Store retention set to unlimited"),
+        }
+        info!(
+            "Verifying certificates with {} threads",
+            self.verification_threads(committee.size())
+        );
         info!("Sync retry delay set to {} ms", self.sync_retry_delay);
         info!("Sync retry nodes set to {} nodes", self.sync_retry_nodes);
+        match self.worker_listen_ip {
+            Some(ip) => info!("Worker listen IP set to {}", ip),
+            None => info!("Workers listen on the IPs of the committee"),
+        }
         info!("Batch size set to {} B", self.batch_size);
         info!("Block size set to {} Certificates", self.max_block_size);
         info!("Max batch delay set to {} ms", self.max_batch_delay);
+        info!(
+            "Worker queues set to {} transactions and {} batches",
+            self.transactions_queue_capacity, self.batches_queue_capacity
+        );
+        info!(
+            "Max pending batches set to {} batches",
+            self.max_pending_batches
+        );
+        if let Some(delay) = self.digest_coalesce_delay {
+            info!(
+                "Coalescing the batches delivered to the primary over {} ms",
+                delay
+            );
+        }
+        info!("Min gas unit price set to {}", self.min_gas_unit_price);
+        if self.reject_missing_secondary_signatures {
+            info!("Rejecting multi-agent transactions missing secondary signatures");
+        }
+        info!("Network retry delay set to {} ms", self.network_retry_delay);
+        match self.network_max_retries {
+            Some(retries) => info!("Network max retries set to {} attempts", retries),
+            None => info!("Network max retries set to unlimited"),
+        }
+        if let Some(max) = self.max_txns_per_sender_per_header {
+            info!("Max transactions per sender per header set to {}", max);
+        }
+        if self.shuffle_header_payload {
+            info!("Shuffling the transactions of each header");
+        }
+        info!(
+            "Execution receipts capacity set to {} receipts",
+            self.receipts_capacity
+        );
+        info!(
+            "Execution receipts retained for {} rounds",
+            self.receipts_retention.unwrap_or(self.gc_depth)
+        );
+        if self.detect_commit_gaps {
+            info!("Checking that committed rounds advance without gaps");
+        }
+        match &self.store_backend {
+            Some(StoreBackend::Memory) => info!("Store backend set to memory"),
+            Some(StoreBackend::RocksDb { path }) => {
+                info!("Store backend set to RocksDB at {}", path)
+            }
+            None => (),
+        }
+        info!("Store codec set to {:?}", self.store_codec);
+        if let Some(faults) = &self.fault_injection {
+            warn!(
+                "Injecting faults: drop rate {}, max delay {} ms, equivocate {}, seed {}",
+                faults.drop_rate, faults.max_delay, faults.equivocate, faults.seed
+            );
+        }
+        if let Some(path) = &self.block_output {
+            info!("Writing executed blocks to {}", path.display());
+        }
+        if let Some(path) = &self.transaction_output {
+            info!("Writing transaction outputs to {}", path.display());
+        }
+        if self.prioritize_by_stake {
+            info!("Prioritizing high-stake primaries when broadcasting");
+        }
+        if self.group_batches_by_sender {
+            info!("Grouping the transactions of each batch by sender");
+        }
+        if let Some(max) = self.max_client_connections {
+            info!("Max client connections per worker set to {}", max);
+        }
+        if self.batch_quorum_ack {
+            info!("Waiting for a quorum of workers to acknowledge each batch");
+        }
     }
 }
 
@@ -178,6 +716,9 @@ pub struct WorkerAddresses {
     pub worker_to_worker: SocketAddr,
     /// Address to receive messages from our primary (LAN).
     pub primary_to_worker: SocketAddr,
+    /// Address to receive client transactions over HTTP (WAN). The HTTP API is disabled if unset.
+    #[serde(default)]
+    pub api: Option<SocketAddr>,
 }
 
 #[derive(Clone, Deserialize)]
@@ -197,14 +738,198 @@ pub struct Authority {
     pub workers: HashMap<WorkerId, WorkerAddresses>,
 }
 
+/// Deserializes the authorities of a committee, failing if two of them share a public key (a map
+/// would otherwise silently keep only the last one).
+fn unique_authorities<'de, D>(deserializer: D) -> Result<BTreeMap<PublicKey, Authority>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct AuthoritiesVisitor;
+
+    impl<'de> Visitor<'de> for AuthoritiesVisitor {
+        type Value = BTreeMap<PublicKey, Authority>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a map of authorities by public key")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut authorities = BTreeMap::new();
+            while let Some((name, authority)) = map.next_entry::<PublicKey, Authority>()? {
+                if authorities.insert(name, authority).is_some() {
+                    return Err(A::Error::custom(format!(
+                        "duplicate authority {} in the committee",
+                        name
+                    )));
+                }
+            }
+            Ok(authorities)
+        }
+    }
+
+    deserializer.deserialize_map(AuthoritiesVisitor)
+}
+
+/// The settings changing the result of execution. They are shared by the whole committee (and read
+/// from the committee file rather than the parameters of each node): nodes executing the same blocks
+/// with different settings would diverge.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExecutionConfig {
+    /// Causes the committer to reorder each block in groups of non-conflicting transactions. The
+    /// result of the block is unchanged.
+    #[serde(default)]
+    pub parallel_schedule: bool,
+    /// Causes the committer to order the certificates of each committed block round by round, each
+    /// round starting with the certificate of its leader (picked by the leader elector of the
+    /// consensus).
+    #[serde(default)]
+    pub order_commits_by_leader: bool,
+    /// Causes the committer to drop the committed transactions that were already committed. Only
+    /// worth disabling when duplicates are filtered upstream, or to benchmark raw execution.
+    #[serde(default = "default_deduplicate_transactions")]
+    pub deduplicate_transactions: bool,
+    /// For how long past their expiration committed transactions are still executed, since they may
+    /// expire while waiting to be ordered: the on-chain time of every committed block lags this far
+    /// behind the time of its headers. Denominated in seconds.
+    #[serde(default)]
+    pub execution_expiry_grace: u64,
+    /// If set, the committer imports its state from this snapshot file (as written by the
+    /// `export_state` command) instead of generating genesis, which speeds up the boot of test
+    /// clusters. Every node must have the same snapshot at this path.
+    #[serde(default)]
+    pub state_snapshot: Option<PathBuf>,
+    /// The release of the Aptos framework installed at genesis.
+    #[serde(default)]
+    pub genesis_framework: GenesisFramework,
+    /// The id of the chain, recorded at genesis and carried by every transaction so that it cannot be
+    /// replayed on another chain. Defaults to the id of the Aptos test chains.
+    #[serde(default = "default_chain_id")]
+    pub chain_id: u8,
+    /// If set, the node refuses to start unless its genesis has this hash (as logged at startup and
+    /// reported by the status endpoint), so that an update of the Aptos dependency cannot silently
+    /// change the framework. Cannot be combined with `state_snapshot`, whose genesis is not known.
+    #[serde(default)]
+    pub framework_hash: Option<String>,
+    /// If set, the compiled Move package (built with `aptos move compile --save-metadata`) whose error
+    /// maps explain the aborts of its modules in the logs and results of the committer.
+    #[serde(default)]
+    pub abort_messages_package: Option<PathBuf>,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            parallel_schedule: false,
+            order_commits_by_leader: false,
+            deduplicate_transactions: default_deduplicate_transactions(),
+            execution_expiry_grace: 0,
+            state_snapshot: None,
+            genesis_framework: GenesisFramework::default(),
+            chain_id: default_chain_id(),
+            framework_hash: None,
+            abort_messages_package: None,
+        }
+    }
+}
+
+fn default_deduplicate_transactions() -> bool {
+    true
+}
+
+/// The id of the Aptos test chains (`ChainId::test()`).
+fn default_chain_id() -> u8 {
+    4
+}
+
+impl ExecutionConfig {
+    /// Checks the execution settings before the node boots.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::InvalidCommittee(message));
+        if self.chain_id == 0 {
+            return invalid("the chain id must not be 0".to_string());
+        }
+        if let Some(path) = &self.state_snapshot {
+            if !path.is_file() {
+                return invalid(format!("no state snapshot at {}", path.display()));
+            }
+        }
+        if let Some(path) = &self.abort_messages_package {
+            if !path.is_dir() {
+                return invalid(format!("no compiled package at {}", path.display()));
+            }
+        }
+        if let Some(hash) = &self.framework_hash {
+            let digits = hash.strip_prefix("0x").unwrap_or_default();
+            if digits.len() != 64 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return invalid(format!(
+                    "the framework hash {} is not a 0x-prefixed 32-byte hex hash",
+                    hash
+                ));
+            }
+            if self.state_snapshot.is_some() {
+                return invalid(
+                    "the framework hash of a state snapshot cannot be checked".to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn log(&self) {
+        if self.parallel_schedule {
+            info!("Scheduling committed blocks in groups of non-conflicting transactions");
+        }
+        if self.order_commits_by_leader {
+            info!("Ordering committed certificates by round leader");
+        }
+        if !self.deduplicate_transactions {
+            info!("Executing committed transactions without deduplication");
+        }
+        if let Some(path) = &self.state_snapshot {
+            info!("Importing the state from {}", path.display());
+        }
+        info!("Genesis framework set to {:?}", self.genesis_framework);
+        info!("Chain id set to {}", self.chain_id);
+        if let Some(hash) = &self.framework_hash {
+            info!("Framework pinned to genesis hash {}", hash);
+        }
+        if let Some(path) = &self.abort_messages_package {
+            info!("Explaining the aborts of the package at {}", path.display());
+        }
+        if self.execution_expiry_grace > 0 {
+            info!(
+                "Execution expiry grace set to {} s",
+                self.execution_expiry_grace
+            );
+        }
+    }
+}
+
 #[derive(Clone, Deserialize)]
 pub struct Comm {
+    #[serde(deserialize_with = "unique_authorities")]
     pub authorities: BTreeMap<PublicKey, Authority>,
+    /// The execution settings of the committee.
+    #[serde(default)]
+    pub execution: ExecutionConfig,
 }
 impl Import for Comm {}
 
+impl Comm {
+    /// Returns the addresses on which the specified worker of every authority receives client
+    /// transactions. Authorities without such a worker are skipped.
+    pub fn transactions_addresses(&self, id: &WorkerId) -> Vec<SocketAddr> {
+        self.authorities
+            .values()
+            .filter_map(|authority| authority.workers.get(id))
+            .map(|worker| worker.transactions)
+            .collect()
+    }
+}
+
 #[derive(Clone, Deserialize)]
 pub struct Committee {
+    #[serde(deserialize_with = "unique_authorities")]
     pub authorities: BTreeMap<PublicKey, Authority>,
     pub sorted_keys: Vec<PublicKeyShareG2>,
     pub combined_pubkey: PublicKeyShareG2,
@@ -217,6 +942,9 @@ pub struct Committee {
     pub slow_commit_threshold: u32,
     pub fast_commit_threshold: u32,
     pub view_change_threshold: u32,
+    /// The execution settings of the committee.
+    #[serde(default)]
+    pub execution: ExecutionConfig,
 }
 
 impl Import for Committee {}
@@ -252,10 +980,79 @@ impl Committee {
             slow_commit_threshold,
             fast_commit_threshold,
             view_change_threshold,
+            execution: ExecutionConfig::default(),
         };
         committee
     }
 
+    /// The maximum number of authorities: the signers of a certificate are recorded in a `u128`.
+    pub const MAX_SIZE: usize = 128;
+
+    /// Checks that the committee can make progress before the node boots: every authority must have
+    /// its own BLS key and some stake, and the authorities together must reach the thresholds without
+    /// any of them forming a quorum alone.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.execution.validate()?;
+        let invalid = |message: String| Err(ConfigError::InvalidCommittee(message));
+        if self.authorities.is_empty() {
+            return invalid("the committee has no authority".to_string());
+        }
+        if self.size() > Self::MAX_SIZE {
+            return invalid(format!(
+                "the committee has {} authorities, at most {} are supported",
+                self.size(),
+                Self::MAX_SIZE
+            ));
+        }
+        // The signers of aggregated signatures are told apart by their BLS key.
+        let mut bls_keys = BTreeMap::new();
+        for (name, authority) in &self.authorities {
+            if let Some(other) = bls_keys.insert(authority.bls_pubkey_g2, name) {
+                return invalid(format!(
+                    "authorities {} and {} share the same BLS key",
+                    other, name
+                ));
+            }
+        }
+        if let Some((name, _)) = self.authorities.iter().find(|(_, x)| x.stake == 0) {
+            return invalid(format!("authority {} has zero stake", name));
+        }
+        let total: u64 = self.authorities.values().map(|x| u64::from(x.stake)).sum();
+        if total > u64::from(Stake::MAX) {
+            return invalid(format!(
+                "the total stake {} overflows the stake type (at most {})",
+                total,
+                Stake::MAX
+            ));
+        }
+        for (name, threshold) in [
+            ("quorum", self.quorum_threshold()),
+            ("validity", self.validity_threshold()),
+        ] {
+            if u64::from(threshold) > total {
+                return invalid(format!(
+                    "the {} threshold {} exceeds the total stake {}",
+                    name, threshold, total
+                ));
+            }
+        }
+        if self.size() > 1 {
+            if let Some((name, x)) = self
+                .authorities
+                .iter()
+                .find(|(_, x)| x.stake >= self.quorum_threshold())
+            {
+                return invalid(format!(
+                    "authority {} forms a quorum alone with a stake of {} (quorum threshold {})",
+                    name,
+                    x.stake,
+                    self.quorum_threshold()
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_byzantine_ids(&self) -> Vec<PublicKey> {
         self.authorities
             .iter()
@@ -362,6 +1159,17 @@ impl Committee {
             .collect()
     }
 
+    /// Returns the addresses of all primaries except `myself`, ordered by descending stake. Ties
+    /// are broken by public key so that the order is deterministic.
+    pub fn others_primaries_by_stake(
+        &self,
+        myself: &PublicKey,
+    ) -> Vec<(PublicKey, PrimaryAddresses)> {
+        let mut primaries = self.others_primaries(myself);
+        primaries.sort_by(|(a, _), (b, _)| self.stake(b).cmp(&self.stake(a)).then(a.cmp(b)));
+        primaries
+    }
+
     /// Returns the addresses of a specific worker (`id`) of a specific authority (`to`).
     pub fn worker(&self, to: &PublicKey, id: &WorkerId) -> Result<WorkerAddresses, ConfigError> {
         self.authorities
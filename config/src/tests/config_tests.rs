@@ -0,0 +1,448 @@
+use super::*;
+use blsttc::SecretKeySet;
+
+#[test]
+fn default_parameters_are_valid() {
+    let mut parameters = Parameters::default();
+    assert!(parameters.validate().is_ok());
+}
+
+#[test]
+fn zero_batch_size_is_rejected() {
+    let mut parameters = Parameters {
+        batch_size: 0,
+        ..Parameters::default()
+    };
+    match parameters.validate() {
+        Err(e) => {
+            let message = e.to_string();
+            assert!(message.contains("batch_size"), "{}", message);
+            assert!(message.contains("greater than 0"), "{}", message);
+        }
+        Ok(()) => panic!("A zero batch size should be rejected"),
+    }
+}
+
+#[test]
+fn small_batch_delay_is_clamped() {
+    let mut parameters = Parameters {
+        max_batch_delay: 0,
+        ..Parameters::default()
+    };
+    assert!(parameters.validate().is_ok());
+    assert_eq!(parameters.max_batch_delay, Parameters::MIN_BATCH_DELAY);
+}
+
+#[test]
+fn zero_network_retry_delay_is_rejected() {
+    let mut parameters = Parameters {
+        network_retry_delay: 0,
+        ..Parameters::default()
+    };
+    match parameters.validate() {
+        Err(e) => assert!(e.to_string().contains("network_retry_delay")),
+        Ok(()) => panic!("A zero network retry delay should be rejected"),
+    }
+}
+
+#[test]
+fn store_retention_shorter_than_gc_depth_is_rejected() {
+    let mut parameters = Parameters {
+        gc_depth: 50,
+        store_retention: Some(49),
+        ..Parameters::default()
+    };
+    match parameters.validate() {
+        Err(e) => {
+            let message = e.to_string();
+            assert!(message.contains("store_retention"), "{}", message);
+            assert!(message.contains("gc_depth (50)"), "{}", message);
+        }
+        Ok(()) => panic!("A store retention shorter than the gc depth should be rejected"),
+    }
+
+    // Retaining exactly the gc depth is enough.
+    parameters.store_retention = Some(50);
+    assert!(parameters.validate().is_ok());
+}
+
+#[test]
+fn network_retry_policy_defaults_when_missing() {
+    let parameters: Parameters = serde_json::from_str(
+        r#"{
+            "consensus_only": false,
+            "timeout_delay": 5000,
+            "header_size": 1000,
+            "max_block_size": 1,
+            "max_header_delay": 100,
+            "gc_depth": 50,
+            "sync_retry_delay": 5000,
+            "sync_retry_nodes": 3,
+            "batch_size": 500000,
+            "max_batch_delay": 100,
+            "use_vote_aggregator": false,
+            "leader_elector": "Simple",
+            "n": 15,
+            "f": 3,
+            "c": 2,
+            "k": 1
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        parameters.network_retry_delay,
+        Parameters::default().network_retry_delay
+    );
+    assert_eq!(parameters.network_max_retries, None);
+}
+
+// Fixture
+fn committee_with_stakes(stakes: &[Stake]) -> Committee {
+    let bls = SecretKeySet::random(0, &mut blsttc::rand::rngs::OsRng);
+    let authorities: BTreeMap<_, _> = stakes
+        .iter()
+        .enumerate()
+        .map(|(i, stake)| {
+            let (name, _) = generate_production_keypair();
+            let authority = Authority {
+                id: i as u32,
+                bls_pubkey_g1: bls.public_keys().public_key_share(i),
+                bls_pubkey_g2: bls.public_keys_g2().public_key_share(i),
+                is_honest: true,
+                stake: *stake,
+                consensus: ConsensusAddresses {
+                    consensus_to_consensus: format!("127.0.0.1:{}", 100 + i).parse().unwrap(),
+                },
+                primary: PrimaryAddresses {
+                    primary_to_primary: format!("127.0.0.1:{}", 200 + i).parse().unwrap(),
+                    worker_to_primary: format!("127.0.0.1:{}", 300 + i).parse().unwrap(),
+                },
+                workers: HashMap::new(),
+            };
+            (name, authority)
+        })
+        .collect();
+    Committee {
+        authorities,
+        sorted_keys: Vec::new(),
+        combined_pubkey: PublicKeyShareG2::default(),
+        n: stakes.len() as u32,
+        f: 0,
+        c: 0,
+        k: 0,
+        p: 0,
+        quorum_threshold: 0,
+        slow_commit_threshold: 0,
+        fast_commit_threshold: 0,
+        view_change_threshold: 0,
+        execution: ExecutionConfig::default(),
+    }
+}
+
+#[test]
+fn committee_with_zero_stake_authority_is_rejected() {
+    let mut committee = committee_with_stakes(&[1, 1, 1, 1]);
+    committee.f = 1;
+    committee.quorum_threshold = 3;
+    assert!(committee.validate().is_ok());
+
+    let zero = committee.authorities.values_mut().nth(1).unwrap();
+    zero.stake = 0;
+    let error = committee.validate().unwrap_err();
+    assert!(
+        matches!(&error, ConfigError::InvalidCommittee(message) if message.contains("zero stake")),
+        "{}",
+        error
+    );
+
+    // An authority reaching the quorum on its own is rejected as well.
+    let mut committee = committee_with_stakes(&[1, 5, 1, 1]);
+    committee.quorum_threshold = 3;
+    assert!(committee
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .contains("forms a quorum alone"));
+}
+
+#[test]
+fn authorities_sharing_a_bls_key_are_rejected() {
+    let mut committee = committee_with_stakes(&[1, 1, 1, 1]);
+    committee.f = 1;
+    committee.quorum_threshold = 3;
+    assert!(committee.validate().is_ok());
+
+    let key = committee.authorities.values().next().unwrap().bls_pubkey_g2;
+    committee
+        .authorities
+        .values_mut()
+        .nth(2)
+        .unwrap()
+        .bls_pubkey_g2 = key;
+    let error = committee.validate().unwrap_err();
+    assert!(
+        matches!(&error, ConfigError::InvalidCommittee(message) if message.contains("share the same BLS key")),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn others_primaries_by_stake_is_ordered_by_descending_stake() {
+    let committee = committee_with_stakes(&[1, 10, 3, 7, 5]);
+    let myself = committee
+        .authorities
+        .iter()
+        .find(|(_, authority)| authority.stake == 5)
+        .map(|(name, _)| *name)
+        .unwrap();
+
+    let stakes: Vec<_> = committee
+        .others_primaries_by_stake(&myself)
+        .iter()
+        .map(|(name, _)| committee.stake(name))
+        .collect();
+    assert_eq!(stakes, vec![10, 7, 3, 1]);
+}
+
+#[test]
+fn transactions_addresses_from_committee_file() {
+    let (a, _) = generate_production_keypair();
+    let (b, _) = generate_production_keypair();
+    let authority = |id: u32, base: u16| {
+        serde_json::json!({
+            "id": id,
+            "bls_pubkey_g1": PublicKeyShareG1::default(),
+            "bls_pubkey_g2": PublicKeyShareG2::default(),
+            "is_honest": true,
+            "stake": 1,
+            "consensus": { "consensus_to_consensus": format!("127.0.0.1:{}", base) },
+            "primary": {
+                "primary_to_primary": format!("127.0.0.1:{}", base + 1),
+                "worker_to_primary": format!("127.0.0.1:{}", base + 2)
+            },
+            "workers": {
+                "0": {
+                    "primary_to_worker": format!("127.0.0.1:{}", base + 3),
+                    "transactions": format!("127.0.0.1:{}", base + 4),
+                    "worker_to_worker": format!("127.0.0.1:{}", base + 5)
+                }
+            }
+        })
+    };
+    let mut authorities = serde_json::Map::new();
+    authorities.insert(a.encode_base64(), authority(0, 100));
+    authorities.insert(b.encode_base64(), authority(1, 200));
+    let committee = serde_json::json!({ "authorities": authorities });
+
+    let path = ".test_transactions_addresses_from_committee_file.json";
+    std::fs::write(path, committee.to_string()).unwrap();
+    let comm = Comm::import(path).unwrap();
+    let _ = std::fs::remove_file(path);
+
+    let mut addresses = comm.transactions_addresses(&0);
+    addresses.sort();
+    let expected: Vec<SocketAddr> = vec![
+        "127.0.0.1:104".parse().unwrap(),
+        "127.0.0.1:204".parse().unwrap(),
+    ];
+    assert_eq!(addresses, expected);
+    assert!(comm.transactions_addresses(&1).is_empty());
+}
+
+#[test]
+fn committee_file_with_duplicate_authorities_is_rejected() {
+    let (name, _) = generate_production_keypair();
+    let authority = |id: u32, base: u16| {
+        serde_json::json!({
+            "id": id,
+            "bls_pubkey_g1": PublicKeyShareG1::default(),
+            "bls_pubkey_g2": PublicKeyShareG2::default(),
+            "is_honest": true,
+            "stake": 1,
+            "consensus": { "consensus_to_consensus": format!("127.0.0.1:{}", base) },
+            "primary": {
+                "primary_to_primary": format!("127.0.0.1:{}", base + 1),
+                "worker_to_primary": format!("127.0.0.1:{}", base + 2)
+            },
+            "workers": {}
+        })
+    };
+    // A JSON map cannot be built with the same key twice: write the file by hand.
+    let committee = format!(
+        r#"{{ "authorities": {{ "{name}": {}, "{name}": {} }} }}"#,
+        authority(0, 100),
+        authority(1, 200),
+        name = name.encode_base64()
+    );
+
+    let path = ".test_committee_file_with_duplicate_authorities_is_rejected.json";
+    std::fs::write(path, committee).unwrap();
+    let result = Comm::import(path);
+    let _ = std::fs::remove_file(path);
+
+    let error = match result {
+        Ok(_) => panic!("A committee with duplicate authorities should be rejected"),
+        Err(e) => e.to_string(),
+    };
+    assert!(
+        error.contains(&format!("duplicate authority {}", name)),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn verification_threads_follow_committee_size() {
+    let parameters = Parameters::default();
+    let cpus = std::thread::available_parallelism().map_or(1, |x| x.get());
+    assert_eq!(parameters.verification_threads(1), 1);
+    assert_eq!(parameters.verification_threads(0), 1);
+    assert!(parameters.verification_threads(1_000) <= Parameters::MAX_VERIFICATION_THREADS);
+    assert!(parameters.verification_threads(1_000) <= cpus);
+
+    // The override ignores both the committee size and the number of CPUs.
+    let mut parameters = Parameters {
+        verification_threads: Some(64),
+        ..Parameters::default()
+    };
+    assert!(parameters.validate().is_ok());
+    assert_eq!(parameters.verification_threads(4), 64);
+
+    parameters.verification_threads = Some(0);
+    assert!(parameters.validate().is_err());
+}
+
+#[test]
+fn built_committees_are_valid_and_use_distinct_ports() {
+    use crate::committee_builder::CommitteeBuilder;
+    use std::collections::HashSet;
+
+    let mut test = CommitteeBuilder::new(7)
+        .stakes(vec![1, 1, 2, 1, 1, 3, 1])
+        .workers(2)
+        .base_port(20_000)
+        .build()
+        .unwrap();
+    assert!(test.committee.validate().is_ok());
+    assert!(test.parameters.validate().is_ok());
+    assert_eq!((test.committee.n, test.committee.f), (7, 2));
+    assert_eq!(test.keys.len(), 7);
+    assert_eq!(test.committee.stake(&test.keys[5].name), 3);
+
+    let mut ports = HashSet::new();
+    for authority in test.committee.authorities.values() {
+        assert!(ports.insert(authority.consensus.consensus_to_consensus.port()));
+        assert!(ports.insert(authority.primary.primary_to_primary.port()));
+        assert!(ports.insert(authority.primary.worker_to_primary.port()));
+        for worker in authority.workers.values() {
+            assert!(ports.insert(worker.transactions.port()));
+            assert!(ports.insert(worker.worker_to_worker.port()));
+            assert!(ports.insert(worker.primary_to_worker.port()));
+        }
+    }
+    assert_eq!(ports.len(), 7 * (3 + 2 * 3));
+}
+
+#[test]
+fn built_committees_fail_when_their_ports_overflow() {
+    use crate::committee_builder::CommitteeBuilder;
+
+    // Four authorities with two workers each need 36 ports.
+    let builder = || CommitteeBuilder::new(4).workers(2);
+    assert!(builder().base_port(u16::MAX - 36).build().is_ok());
+    assert!(matches!(
+        builder().base_port(u16::MAX - 34).build(),
+        Err(ConfigError::InvalidCommittee(..))
+    ));
+}
+
+#[test]
+fn sender_abort_rate_must_be_a_fraction() {
+    for rate in [0.0, 0.5] {
+        let mut parameters = Parameters {
+            max_sender_abort_rate: Some(rate),
+            ..Parameters::default()
+        };
+        assert!(parameters.validate().is_ok(), "{}", rate);
+    }
+    for rate in [-0.1, 1.0, f64::NAN] {
+        let mut parameters = Parameters {
+            max_sender_abort_rate: Some(rate),
+            ..Parameters::default()
+        };
+        assert!(parameters.validate().is_err(), "{}", rate);
+    }
+}
+
+#[test]
+fn pinned_framework_hash_must_be_a_hash() {
+    let hash = format!("0x{}", "ab".repeat(32));
+    let execution = ExecutionConfig {
+        framework_hash: Some(hash.clone()),
+        ..ExecutionConfig::default()
+    };
+    assert!(execution.validate().is_ok());
+
+    for invalid in ["", "0x1234", &hash[2..]] {
+        let execution = ExecutionConfig {
+            framework_hash: Some(invalid.to_string()),
+            ..ExecutionConfig::default()
+        };
+        assert!(execution.validate().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn abort_messages_package_must_exist() {
+    let execution = ExecutionConfig {
+        abort_messages_package: Some(".".into()),
+        ..ExecutionConfig::default()
+    };
+    assert!(execution.validate().is_ok());
+
+    let execution = ExecutionConfig {
+        abort_messages_package: Some(".test_abort_messages_package_must_exist".into()),
+        ..ExecutionConfig::default()
+    };
+    assert!(execution.validate().is_err());
+}
+
+#[test]
+fn chain_id_must_not_be_zero() {
+    assert!(ExecutionConfig::default().validate().is_ok());
+
+    let execution = ExecutionConfig {
+        chain_id: 0,
+        ..ExecutionConfig::default()
+    };
+    assert!(execution.validate().is_err());
+}
+
+#[test]
+fn execution_settings_are_read_from_the_committee_file() {
+    let path = ".test_execution_settings_are_read_from_the_committee_file.json";
+    let committee = serde_json::json!({
+        "authorities": {},
+        "execution": { "parallel_schedule": true, "execution_expiry_grace": 5, "chain_id": 2 }
+    });
+    std::fs::write(path, committee.to_string()).unwrap();
+    let comm = Comm::import(path).unwrap();
+    let _ = std::fs::remove_file(path);
+
+    assert!(comm.execution.parallel_schedule);
+    assert_eq!(comm.execution.execution_expiry_grace, 5);
+    assert_eq!(comm.execution.chain_id, 2);
+    // The settings left out keep their defaults.
+    assert!(comm.execution.deduplicate_transactions);
+    assert_eq!(comm.execution.framework_hash, None);
+
+    // A committee file without execution settings uses the defaults.
+    let path = ".test_execution_settings_default_without_a_section.json";
+    std::fs::write(path, serde_json::json!({ "authorities": {} }).to_string()).unwrap();
+    let comm = Comm::import(path).unwrap();
+    let _ = std::fs::remove_file(path);
+    assert!(!comm.execution.parallel_schedule);
+    assert!(comm.execution.deduplicate_transactions);
+    assert_eq!(comm.execution.chain_id, 4);
+}
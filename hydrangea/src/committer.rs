@@ -1,140 +1,900 @@
-use aptos_executor::{AptosVmExecutor, LocalAccount, TransactionResult};
+use crate::error::{ConsensusError, ConsensusResult};
+use crate::leader::LeaderSchedule;
+use aptos_crypto::HashValue;
+use aptos_executor::database::{account_resource_key, TestDbReader};
+use aptos_executor::{
+    plan_parallel_schedule, AptosVmExecutor, KeptOrDiscarded, LocalAccount, TransactionResult,
+};
+use aptos_types::account_address::AccountAddress;
+use aptos_types::account_config::AccountResource;
+use aptos_types::state_store::state_key::StateKey;
 use aptos_types::transaction::SignedTransaction;
-use log::{error, info, warn};
-use primary::{Certificate, Header};
-use std::collections::HashSet;
+use aptos_types::vm_status::VMStatus;
+use config::StoreCodec;
+use crypto::Hash as _;
+use crypto::{Digest, PublicKey, SecretKey, SignatureService};
+use log::{debug, error, info, log, warn, Level};
+use primary::{
+    AbortStats, Certificate, ChainHead, CommitAttestation, ExecutionPressure, ExecutionReceipt,
+    ExecutionReceipts, Header, Round, StateReader, TraceId, TraceIds,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use store::Store;
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::task::JoinHandle;
+
+#[cfg(test)]
+#[path = "tests/committer_tests.rs"]
+pub mod committer_tests;
 
 const PRE_FUNDED_ACCOUNT_SEEDS: &[u64] = &[1, 2, 3, 4];
 const INITIAL_ACCOUNT_BALANCE: u64 = 1_000_000_000_000;
 
+/// How the committer orders and reports the committed blocks.
+pub struct CommitterConfig {
+    /// Whether to reorder each block in groups of non-conflicting transactions.
+    pub parallel_schedule: bool,
+    /// If set, the certificates of each block are ordered round by round, leader first.
+    pub leader_schedule: Option<Box<dyn LeaderSchedule>>,
+    /// If set, a summary of every executed block is appended to this file as a line of JSON.
+    pub block_output: Option<PathBuf>,
+    /// If set, the output of every executed transaction is appended to this file as a line of JSON,
+    /// in the Aptos `TransactionOutput` format (see `TransactionOutputRecord`).
+    pub transaction_output: Option<PathBuf>,
+    /// Lowered every time a block is executed.
+    pub execution_pressure: ExecutionPressure,
+    /// If set, the receipts of the transactions executed more than this many rounds before the last
+    /// block are pruned.
+    pub receipts_retention: Option<Round>,
+    /// Whether to count the rounds skipped by the committed certificates in the receipts.
+    pub detect_commit_gaps: bool,
+    /// Whether to stop at the first committed transaction that is not executed successfully, e.g.
+    /// to check that a known-good set of transactions runs as expected.
+    pub strict: bool,
+    /// The serialization format of the headers in the store, shared with the primary's `Core`.
+    pub store_codec: StoreCodec,
+    /// Counts the stored headers that could not be decoded.
+    pub header_decode_failures: HeaderDecodeFailures,
+    /// If set, the heads of the chain of executed blocks are attested with this key.
+    pub attester: Option<CommitAttester>,
+    /// If set, the outcomes of the transactions of every executed block are reported here, by sender.
+    pub tx_abort_stats: Option<Sender<AbortStats>>,
+    /// The depth of the garbage collection of the consensus: certificates more than this many rounds
+    /// below the last committed block are never delivered again.
+    pub gc_depth: Round,
+    /// Whether to drop the committed transactions that were already committed, in the same block or
+    /// an earlier one. Disabling it saves serializing every transaction, e.g. to benchmark raw
+    /// execution or when duplicates are filtered upstream: duplicates are then executed (and fail).
+    pub deduplicate: bool,
+}
+
+impl Default for CommitterConfig {
+    fn default() -> Self {
+        Self {
+            parallel_schedule: false,
+            leader_schedule: None,
+            block_output: None,
+            transaction_output: None,
+            execution_pressure: ExecutionPressure::default(),
+            receipts_retention: None,
+            detect_commit_gaps: false,
+            strict: false,
+            store_codec: StoreCodec::default(),
+            header_decode_failures: HeaderDecodeFailures::default(),
+            attester: None,
+            tx_abort_stats: None,
+            gc_depth: 50,
+            deduplicate: true,
+        }
+    }
+}
+
+/// Signs the heads of the chain of executed blocks, so that clients can hold the node to the blocks
+/// it claims to have executed. Its key is separate from the consensus keys of the node, so that it
+/// can be rotated without changing the committee.
+#[derive(Clone)]
+pub struct CommitAttester {
+    name: PublicKey,
+    signature_service: SignatureService,
+}
+
+impl CommitAttester {
+    pub fn new(name: PublicKey, secret: SecretKey) -> Self {
+        Self {
+            name,
+            signature_service: SignatureService::new(secret),
+        }
+    }
+
+    /// Returns the public attestation key.
+    pub fn name(&self) -> PublicKey {
+        self.name
+    }
+
+    async fn attest(&mut self, head: &ChainHead) -> CommitAttestation {
+        let signature = self
+            .signature_service
+            .request_signature(head.digest())
+            .await;
+        CommitAttestation {
+            signer: self.name,
+            signature,
+        }
+    }
+}
+
+/// The certificates executed since the committer started, by round, so that the certificates the
+/// consensus delivers again are not executed twice. They are not persisted since the state of the
+/// executor is not either: after a restart, the certificates the consensus replays are executed again
+/// on top of the state the executor boots with.
+#[derive(Default)]
+struct ExecutedCertificates(BTreeMap<Round, HashSet<Digest>>);
+
+impl ExecutedCertificates {
+    fn contains(&self, certificate: &Certificate) -> bool {
+        self.0
+            .get(&certificate.round)
+            .map_or(false, |digests| digests.contains(&certificate.digest()))
+    }
+
+    fn insert(&mut self, certificate: &Certificate) {
+        self.0
+            .entry(certificate.round)
+            .or_default()
+            .insert(certificate.digest());
+    }
+
+    /// Forgets the certificates of the rounds below `round`.
+    fn prune_below(&mut self, round: Round) {
+        self.0 = self.0.split_off(&round);
+    }
+
+    fn len(&self) -> usize {
+        self.0.values().map(HashSet::len).sum()
+    }
+}
+
+/// Counts the headers the committer found in the store but could not decode, e.g. because they were
+/// written with another codec. The transactions of these headers are never executed. Clones share
+/// the same counter.
+#[derive(Clone, Default)]
+pub struct HeaderDecodeFailures {
+    inner: Arc<AtomicU64>,
+}
+
+impl HeaderDecodeFailures {
+    /// Records a header that could not be decoded and returns the number of failures so far.
+    fn record(&self) -> u64 {
+        self.inner.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Returns the number of headers that could not be decoded so far.
+    pub fn count(&self) -> u64 {
+        self.inner.load(Ordering::Relaxed)
+    }
+}
+
+/// The summary of an executed block, as written to the block output.
+#[derive(Serialize)]
+struct BlockSummary {
+    /// The highest round of the certificates of the block.
+    round: Round,
+    transactions: Vec<TransactionSummary>,
+    /// The digest of the writes of the block (see `write_set_root`).
+    write_set_root: String,
+    /// The hash of the previous executed block.
+    prev_block_hash: String,
+    /// The hash of the block (see `block_hash`).
+    block_hash: String,
+}
+
+/// The output of an executed transaction, as written to the transaction output. Aptos tooling decodes
+/// the output with `bcs::from_bytes::<TransactionOutput>`.
+#[derive(Serialize)]
+struct TransactionOutputRecord {
+    /// The highest round of the certificates of the block of the transaction.
+    round: Round,
+    hash: String,
+    /// The hex-encoded BCS bytes of the `TransactionOutput` of the transaction.
+    output: String,
+}
+
+impl TransactionOutputRecord {
+    fn new(round: Round, txn: &SignedTransaction, result: &TransactionResult) -> Self {
+        let output = bcs::to_bytes(&result.transaction_output())
+            .expect("transaction outputs should serialize");
+        Self {
+            round,
+            hash: txn.committed_hash().to_hex_literal(),
+            output: hex::encode(output),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TransactionSummary {
+    hash: String,
+    /// The id correlating the log entries of the transaction across subsystems (see `TraceId`).
+    trace_id: String,
+    status: String,
+    kept: bool,
+    gas_used: u64,
+}
+
 pub struct Committer {
     store: Store,
     executor: AptosVmExecutor,
     rx_commit: Receiver<Vec<Certificate>>,
+    /// The receipts of the committed transactions, queried by clients through the transaction API.
+    receipts: ExecutionReceipts,
+    /// Whether to reorder each block in groups of non-conflicting transactions.
+    parallel_schedule: bool,
+    /// If set, the certificates of each block are ordered round by round, leader first.
+    leader_schedule: Option<Box<dyn LeaderSchedule>>,
+    /// The file to which the summaries of the executed blocks are appended, if any.
+    block_output: Option<File>,
+    /// The file to which the outputs of the executed transactions are appended, if any.
+    transaction_output: Option<File>,
+    /// The hash of the last executed block, zero before the first block.
+    chain_head: HashValue,
+    /// The number of committed blocks waiting to be executed.
+    execution_pressure: ExecutionPressure,
+    /// For how many rounds the receipts of the executed transactions are retained, if bounded.
+    receipts_retention: Option<Round>,
+    /// Where to record the rounds skipped by the committed certificates, if we check for gaps.
+    detect_commit_gaps: bool,
+    /// The highest round of the certificates committed so far, unknown before the first commit.
+    last_committed_round: Option<Round>,
+    /// Whether to stop at the first committed transaction that is not executed successfully.
+    strict: bool,
+    /// Whether to drop the committed transactions that were already committed.
+    deduplicate: bool,
+    /// The serialization format of the headers in the store.
+    store_codec: StoreCodec,
+    /// Counts the stored headers that could not be decoded.
+    header_decode_failures: HeaderDecodeFailures,
+    /// Attests the heads of the chain of executed blocks, if we do.
+    attester: Option<CommitAttester>,
+    /// Reports the outcomes of the transactions of every executed block to the proposer, if set.
+    tx_abort_stats: Option<Sender<AbortStats>>,
+    /// The certificates executed since the committer started.
+    executed: ExecutedCertificates,
+    /// The depth of the garbage collection of the consensus.
+    gc_depth: Round,
 }
 
 impl Committer {
-    pub fn spawn(store: Store, rx_commit: Receiver<Vec<Certificate>>) {
+    /// Spawns the committer with an executor built by `init_executor`, so that a failure to build the
+    /// genesis is reported before the node starts rather than killing this task. The returned handle
+    /// resolves to the error that stopped the committer, if any.
+    pub fn spawn(
+        store: Store,
+        executor: AptosVmExecutor,
+        receipts: ExecutionReceipts,
+        config: CommitterConfig,
+        rx_commit: Receiver<Vec<Certificate>>,
+    ) -> JoinHandle<ConsensusResult<()>> {
+        receipts.set_state_reader(Arc::new(ExecutedState(executor.database().reader())));
+        if let Some(attester) = &config.attester {
+            receipts.set_attestation_key(attester.name());
+        }
+        if let Some(framework) = executor.framework() {
+            receipts.set_framework(framework.release, framework.hash.to_hex_literal());
+        }
+        if config.detect_commit_gaps {
+            receipts.count_commit_gaps();
+        }
+        let block_output = config
+            .block_output
+            .and_then(|path| open_output(&path, "block output"));
+        let transaction_output = config
+            .transaction_output
+            .and_then(|path| open_output(&path, "transaction output"));
         tokio::spawn(async move {
-            let executor = match AptosVmExecutor::new() {
-                Ok(executor) => executor,
-                Err(e) => {
-                    error!("Failed to initialize Aptos VM executor: {}", e);
-                    return;
-                }
-            };
-
-            bootstrap_accounts(&executor);
-
             let mut committer = Self {
                 store,
                 executor,
                 rx_commit,
+                receipts,
+                parallel_schedule: config.parallel_schedule,
+                leader_schedule: config.leader_schedule,
+                block_output,
+                transaction_output,
+                chain_head: HashValue::zero(),
+                execution_pressure: config.execution_pressure,
+                receipts_retention: config.receipts_retention,
+                detect_commit_gaps: config.detect_commit_gaps,
+                last_committed_round: None,
+                strict: config.strict,
+                deduplicate: config.deduplicate,
+                store_codec: config.store_codec,
+                header_decode_failures: config.header_decode_failures,
+                attester: config.attester,
+                tx_abort_stats: config.tx_abort_stats,
+                executed: ExecutedCertificates::default(),
+                gc_depth: config.gc_depth,
             };
-            committer.run().await;
-        });
+            let result = committer.run().await;
+            if let Err(e) = &result {
+                error!("The committer stopped: {}", e);
+            }
+            result
+        })
     }
 
-    async fn run(&mut self) {
+    async fn run(&mut self) -> ConsensusResult<()> {
         while let Some(certificates) = self.rx_commit.recv().await {
-            #[cfg(feature = "benchmark")]
+            self.detect_gaps(&certificates);
+            let certificates = self.skip_executed(certificates);
+            let round = certificates.iter().map(|x| x.round).max();
             for certificate in &certificates {
-                info!("Committed Header {:?}", certificate.id);
+                self.executed.insert(certificate);
             }
-
-            let mut transactions: Vec<SignedTransaction> = Vec::new();
-            for certificate in certificates {
-                match self.load_header(&certificate).await {
-                    Some(header) => transactions.extend(header.payload),
-                    None => warn!(
-                        "Missing header for certificate {:?} (round {})",
-                        certificate.id, certificate.round
-                    ),
-                }
+            if !certificates.is_empty() {
+                self.commit(certificates).await?;
             }
 
-            if transactions.is_empty() {
-                continue;
+            // The consensus does not deliver the certificates below its gc round again.
+            if let Some(round) = round {
+                self.executed
+                    .prune_below(round.saturating_sub(self.gc_depth));
             }
+            self.execution_pressure.block_executed();
+        }
+        Ok(())
+    }
 
-            let transactions = deduplicate_transactions(transactions);
-            if transactions.is_empty() {
-                continue;
+    /// Reports the rounds above the last committed round that are missing from a committed block.
+    /// Certificates of older rounds may still be committed late, so only rounds above it count. The
+    /// first commit sets the baseline: the committer cannot tell which rounds were committed before.
+    fn detect_gaps(&mut self, certificates: &[Certificate]) {
+        if !self.detect_commit_gaps {
+            return;
+        }
+        let rounds: BTreeSet<_> = certificates.iter().map(|x| x.round).collect();
+        let (lowest, highest) = match (rounds.iter().next(), rounds.iter().next_back()) {
+            (Some(lowest), Some(highest)) => (*lowest, *highest),
+            _ => return,
+        };
+
+        let mut expected = self.last_committed_round.map_or(lowest, |x| x + 1);
+        for round in rounds.range(expected..) {
+            if *round > expected {
+                warn!(
+                    "Committed round {} without committing rounds {} to {}",
+                    round,
+                    expected,
+                    round - 1
+                );
+                self.receipts.record_commit_gap(expected, round - 1);
             }
+            expected = round + 1;
+        }
+        self.last_committed_round = self.last_committed_round.max(Some(highest));
+    }
 
-            let results = self.executor.execute_block(&transactions);
-            log_execution_results(&transactions, &results);
+    /// Hands the head of the chain of executed blocks to the transaction API, attested if we have an
+    /// attestation key.
+    async fn publish_chain_head(&mut self, round: Round) {
+        let mut head = ChainHead {
+            round,
+            block_hash: self.chain_head.to_hex_literal(),
+            attestation: None,
+        };
+        if let Some(attester) = self.attester.as_mut() {
+            head.attestation = Some(attester.attest(&head).await);
         }
+        self.receipts.set_chain_head(head);
     }
 
-    async fn load_header(&self, certificate: &Certificate) -> Option<Header> {
-        let mut store = self.store.clone();
-        match store.read(certificate.id.to_vec()).await {
-            Ok(Some(bytes)) => match bincode::deserialize::<Header>(&bytes) {
-                Ok(header) => Some(header),
-                Err(e) => {
-                    warn!(
-                        "Failed to deserialize header for certificate {:?}: {}",
-                        certificate.id, e
+    /// Extends the chain of executed blocks with a block and returns the hash of the block.
+    async fn extend_chain(
+        &mut self,
+        round: Round,
+        transactions: &[SignedTransaction],
+        write_set_root: &HashValue,
+    ) -> HashValue {
+        let hashes: Vec<_> = transactions.iter().map(|x| x.committed_hash()).collect();
+        self.chain_head = block_hash(&self.chain_head, round, &hashes, write_set_root);
+        self.publish_chain_head(round).await;
+        self.chain_head
+    }
+
+    /// Filters out the certificates we already executed.
+    fn skip_executed(&self, certificates: Vec<Certificate>) -> Vec<Certificate> {
+        certificates
+            .into_iter()
+            .filter(|certificate| {
+                let executed = self.executed.contains(certificate);
+                if executed {
+                    debug!(
+                        "Skipping certificate {:?} (round {}): already executed",
+                        certificate.id, certificate.round
                     );
-                    None
                 }
-            },
-            Ok(None) => {
-                warn!(
-                    "No header found in storage for certificate {:?}",
-                    certificate.id
-                );
-                None
+                !executed
+            })
+            .collect()
+    }
+
+    /// Executes a committed block. In strict mode, fails once the block is executed if one of its
+    /// transactions was not executed successfully.
+    async fn commit(&mut self, mut certificates: Vec<Certificate>) -> ConsensusResult<()> {
+        if let Some(schedule) = &self.leader_schedule {
+            certificates = order_by_leader(certificates, schedule.as_ref());
+        }
+
+        #[cfg(feature = "benchmark")]
+        for certificate in &certificates {
+            info!("Committed Header {:?}", certificate.id);
+        }
+
+        let round = certificates
+            .iter()
+            .map(|x| x.round)
+            .max()
+            .unwrap_or_default();
+        let mut transactions: Vec<SignedTransaction> = Vec::new();
+        let mut timestamps = Vec::new();
+        for certificate in certificates {
+            let header = load_header(
+                &mut self.store,
+                &certificate,
+                self.store_codec,
+                &self.header_decode_failures,
+            )
+            .await;
+            match header {
+                Some(header) => {
+                    timestamps.push(header.timestamp);
+                    transactions.extend(header.payload);
+                }
+                None => warn!(
+                    "Missing header for certificate {:?} (round {})",
+                    certificate.id, certificate.round
+                ),
             }
-            Err(e) => {
+        }
+
+        // Commits without transactions to execute leave the chain as is, but still advance its round
+        // so that clients can tell the node keeps committing.
+        if transactions.is_empty() {
+            self.publish_chain_head(round).await;
+            return Ok(());
+        }
+
+        if self.deduplicate {
+            let database = self.executor.database();
+            let trace_ids = self.receipts.trace_ids();
+            transactions =
+                deduplicate_transactions(transactions, trace_ids, |sender| {
+                    match database.account_resource(sender) {
+                        Ok(account) => account.map_or(0, |x| x.sequence_number()),
+                        Err(e) => {
+                            warn!("Failed to read the sequence number of {}: {}", sender, e);
+                            0
+                        }
+                    }
+                });
+            if transactions.is_empty() {
+                self.publish_chain_head(round).await;
+                return Ok(());
+            }
+        }
+        if self.parallel_schedule {
+            transactions = schedule_in_groups(transactions);
+        }
+
+        let results = self
+            .executor
+            .execute_committed_block(&transactions, round, block_timestamp_usecs(timestamps))
+            .map_err(|e| ConsensusError::BlockNotExecuted {
+                round,
+                message: e.to_string(),
+            })?;
+        log_execution_results(&transactions, &results, self.receipts.trace_ids());
+        self.report_aborts(&transactions, &results);
+        let write_set_root = write_set_root(&results);
+        let prev_block_hash = self.chain_head;
+        let block_hash = self
+            .extend_chain(round, &transactions, &write_set_root)
+            .await;
+        if let Some(file) = &mut self.block_output {
+            let summary = BlockSummary {
+                round,
+                transactions: transaction_summaries(
+                    &transactions,
+                    &results,
+                    self.receipts.trace_ids(),
+                ),
+                write_set_root: write_set_root.to_hex_literal(),
+                prev_block_hash: prev_block_hash.to_hex_literal(),
+                block_hash: block_hash.to_hex_literal(),
+            };
+            if let Err(e) = write_json_line(file, &summary) {
                 warn!(
-                    "Store read failure for certificate {:?}: {}",
-                    certificate.id, e
+                    "Failed to write the summary of the block of round {}: {}",
+                    round, e
                 );
-                None
             }
         }
+        if let Some(file) = &mut self.transaction_output {
+            for (txn, result) in transactions.iter().zip(results.iter()) {
+                let record = TransactionOutputRecord::new(round, txn, result);
+                if let Err(e) = write_json_line(file, &record) {
+                    warn!(
+                        "Failed to write the output of transaction {}: {}",
+                        record.hash, e
+                    );
+                    break;
+                }
+            }
+        }
+
+        for (txn, result) in transactions.iter().zip(results.iter()) {
+            self.receipts.insert(round, execution_receipt(txn, result));
+        }
+        self.garbage_collect(round);
+
+        if self.strict {
+            let failed = transactions
+                .iter()
+                .zip(results.iter())
+                .find(|(_, result)| *result.status() != VMStatus::Executed);
+            if let Some((txn, result)) = failed {
+                bail!(ConsensusError::TransactionNotExecuted {
+                    hash: txn.committed_hash().to_hex_literal(),
+                    status: format!("{:?}", result.status()),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the outcomes of the transactions of a block to the proposer. The statistics are only a
+    /// hint: they are dropped rather than holding up execution if the proposer lags behind.
+    fn report_aborts(&self, transactions: &[SignedTransaction], results: &[TransactionResult]) {
+        let Some(tx_abort_stats) = &self.tx_abort_stats else {
+            return;
+        };
+        let mut stats = AbortStats::default();
+        for (txn, result) in transactions.iter().zip(results.iter()) {
+            stats.record(txn.sender(), *result.status() == VMStatus::Executed);
+        }
+        match tx_abort_stats.try_send(stats) {
+            Ok(()) | Err(TrySendError::Closed(_)) => (),
+            Err(TrySendError::Full(_)) => debug!("Dropping the abort statistics of a block"),
+        }
+    }
+
+    /// Prunes the receipts of the transactions executed before the retained rounds, `round` being the
+    /// round of the last executed block.
+    fn garbage_collect(&self, round: Round) {
+        if let Some(retention) = self.receipts_retention {
+            self.receipts
+                .prune_below((round + 1).saturating_sub(retention));
+        }
+    }
+}
+
+/// Builds the executor of the committer (including its genesis, or the state it imports) and funds
+/// the pre-funded accounts that do not exist yet. The node must not start if this fails: it would
+/// otherwise accept transactions it cannot execute.
+pub fn init_executor<F, E>(make_executor: F) -> ConsensusResult<AptosVmExecutor>
+where
+    F: FnOnce() -> Result<AptosVmExecutor, E>,
+    E: Display,
+{
+    let executor = make_executor().map_err(|e| ConsensusError::ExecutorInit(e.to_string()))?;
+    bootstrap_accounts(&executor);
+    Ok(executor)
+}
+
+/// Returns the time of a block, in microseconds: the median of the times of its headers, so that no
+/// single author can move the on-chain clock.
+fn block_timestamp_usecs(mut timestamps: Vec<u64>) -> u64 {
+    timestamps.sort_unstable();
+    timestamps
+        .get(timestamps.len() / 2)
+        .map_or(0, |millis| millis.saturating_mul(1_000))
+}
+
+/// Loads and decodes the header referenced by a committed certificate, recording in `failures` the
+/// headers found in the store that cannot be decoded. The header was written by the primary's `Core`,
+/// so the committer must share its store.
+async fn load_header(
+    store: &mut Store,
+    certificate: &Certificate,
+    codec: StoreCodec,
+    failures: &HeaderDecodeFailures,
+) -> Option<Header> {
+    let bytes = match store.read(certificate.id.to_vec()).await {
+        Ok(Some(bytes)) => bytes,
+        Ok(None) => {
+            error!(
+                "No header found in store '{}' for certificate {:?}; the committer and the primary must share the same store",
+                store.path(),
+                certificate.id
+            );
+            return None;
+        }
+        Err(e) => {
+            warn!(
+                "Failed to load header for certificate {:?}: {}",
+                certificate.id, e
+            );
+            return None;
+        }
+    };
+    match codec.decode(&bytes) {
+        Ok(header) => Some(header),
+        Err(e) => {
+            let count = failures.record();
+            error!(
+                "Failed to decode the header of certificate {:?} ({} B) with the {:?} codec: {}; the committer and the primary must use the same store codec ({} undecodable headers so far)",
+                certificate.id,
+                bytes.len(),
+                codec,
+                e,
+                count
+            );
+            None
+        }
     }
 }
 
+/// Funds the pre-funded accounts. An account already in the state, e.g. imported from a snapshot,
+/// keeps its balance and sequence number.
 fn bootstrap_accounts(executor: &AptosVmExecutor) {
     for seed in PRE_FUNDED_ACCOUNT_SEEDS {
-        match LocalAccount::generate(*seed) {
-            Ok(account) => {
-                executor.bootstrap_account(&account, INITIAL_ACCOUNT_BALANCE);
-                info!("Bootstrapped Aptos account {:?}", account.address);
+        let account = match LocalAccount::generate(*seed) {
+            Ok(account) => account,
+            Err(e) => {
+                warn!("Failed to generate deterministic account {}: {}", seed, e);
+                continue;
             }
-            Err(e) => warn!("Failed to generate deterministic account {}: {}", seed, e),
+        };
+        if let Ok(Some(_)) = executor.database().account_resource(account.address) {
+            info!("Aptos account {:?} already exists", account.address);
+            continue;
+        }
+        match executor.bootstrap_account(&account, INITIAL_ACCOUNT_BALANCE) {
+            Ok(()) => info!("Bootstrapped Aptos account {:?}", account.address),
+            Err(e) => warn!(
+                "Failed to bootstrap Aptos account {:?}: {}",
+                account.address, e
+            ),
         }
     }
 }
 
-fn log_execution_results(transactions: &[SignedTransaction], results: &[TransactionResult]) {
+fn log_execution_results(
+    transactions: &[SignedTransaction],
+    results: &[TransactionResult],
+    trace_ids: &TraceIds,
+) {
     for (index, (txn, result)) in transactions.iter().zip(results.iter()).enumerate() {
-        let status_display = format!("{:?}", result.status());
-        let gas_used = result.gas_used();
-        info!(
-            "Executed transaction {} ({} BCS bytes): status={}, gas_used={}",
+        let (level, message) = execution_log(index, txn, trace_ids.resolve(txn), result);
+        log!(level, "{}", message);
+    }
+}
+
+/// Returns how to log the result of the transaction at `index` of a block: successful transactions
+/// are logged as executed, while failed (kept but not successful), discarded and expired
+/// transactions are logged as warnings. The transaction is traced under `trace_id`.
+fn execution_log(
+    index: usize,
+    txn: &SignedTransaction,
+    trace_id: TraceId,
+    result: &TransactionResult,
+) -> (Level, String) {
+    let status_display = format!("{:?}", result.status());
+    if result.is_expired() {
+        let message = format!(
+            "Expired transaction {} (trace {}, {} BCS bytes): expiration={}",
             index,
+            trace_id,
             serialized_len(txn),
-            status_display,
-            gas_used
+            txn.expiration_timestamp_secs()
         );
+        return (Level::Warn, message);
+    }
+    match result.keep_or_discard() {
+        KeptOrDiscarded::Kept if *result.status() == VMStatus::Executed => {
+            let message = format!(
+                "Executed transaction {} (trace {}, {} BCS bytes): status={}, gas_used={}",
+                index,
+                trace_id,
+                serialized_len(txn),
+                status_display,
+                result.gas_used()
+            );
+            (Level::Info, message)
+        }
+        KeptOrDiscarded::Kept => {
+            let mut message = format!(
+                "Failed transaction {} (trace {}, {} BCS bytes): status={}, gas_used={}",
+                index,
+                trace_id,
+                serialized_len(txn),
+                status_display,
+                result.gas_used()
+            );
+            if let Some(abort) = result.abort_message() {
+                message.push_str(&format!(", abort={:?}", abort));
+            }
+            (Level::Warn, message)
+        }
+        KeptOrDiscarded::Discarded(code) => {
+            let message = format!(
+                "Discarded transaction {} (trace {}, {} BCS bytes): status={}, code={:?}",
+                index,
+                trace_id,
+                serialized_len(txn),
+                status_display,
+                code
+            );
+            (Level::Warn, message)
+        }
+    }
+}
+
+/// Serves the state of the executor to the transaction API. The reader is shared with the executor, so
+/// that the API reads the state of the last executed block without waiting for the committer.
+struct ExecutedState(Arc<TestDbReader>);
+
+impl StateReader for ExecutedState {
+    fn state_value(&self, key: &StateKey) -> Result<Option<Vec<u8>>, String> {
+        self.0
+            .get_state_value(key)
+            .map(|value| value.map(|value| value.bytes().to_vec()))
+            .map_err(|e| e.to_string())
+    }
+
+    fn sequence_number(&self, address: &AccountAddress) -> Result<u64, String> {
+        let key = account_resource_key(*address).map_err(|e| e.to_string())?;
+        match self.0.get_state_value(&key).map_err(|e| e.to_string())? {
+            Some(value) => bcs::from_bytes::<AccountResource>(value.bytes())
+                .map(|account| account.sequence_number())
+                .map_err(|e| e.to_string()),
+            None => Ok(0),
+        }
     }
 }
 
+fn execution_receipt(txn: &SignedTransaction, result: &TransactionResult) -> ExecutionReceipt {
+    ExecutionReceipt {
+        hash: txn.committed_hash().to_hex_literal(),
+        status: format!("{:?}", result.status()),
+        kept: result.keep_or_discard() == KeptOrDiscarded::Kept,
+        gas_used: result.gas_used(),
+        events: result.events().to_vec(),
+    }
+}
+
+fn transaction_summaries(
+    transactions: &[SignedTransaction],
+    results: &[TransactionResult],
+    trace_ids: &TraceIds,
+) -> Vec<TransactionSummary> {
+    transactions
+        .iter()
+        .zip(results.iter())
+        .map(|(txn, result)| TransactionSummary {
+            hash: txn.committed_hash().to_hex_literal(),
+            trace_id: trace_ids.resolve(txn).to_string(),
+            status: format!("{:?}", result.status()),
+            kept: result.keep_or_discard() == KeptOrDiscarded::Kept,
+            gas_used: result.gas_used(),
+        })
+        .collect()
+}
+
+/// Opens a file to append the outputs of the committer to, reporting (and ignoring) failures.
+fn open_output(path: &Path, what: &str) -> Option<File> {
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            error!("Failed to open {} {}: {}", what, path.display(), e);
+            None
+        }
+    }
+}
+
+/// Returns the hash of an executed block, chained to the hash of the previous block (zero for the
+/// first block). It commits to the transactions of the block, in execution order, and to their
+/// writes; chained from the genesis, the writes of all blocks determine the state, so that a light
+/// client can verify a sequence of blocks without the state.
+pub fn block_hash(
+    prev_block_hash: &HashValue,
+    round: Round,
+    transaction_hashes: &[HashValue],
+    write_set_root: &HashValue,
+) -> HashValue {
+    let bytes = bcs::to_bytes(&(prev_block_hash, round, transaction_hashes, write_set_root))
+        .expect("Failed to serialize block");
+    HashValue::sha3_256_of(&bytes)
+}
+
+/// Returns the digest of the write sets of the transactions of a block, in execution order. Unlike
+/// a root of the whole state, it costs time proportional to the block rather than to the state.
+fn write_set_root(results: &[TransactionResult]) -> HashValue {
+    let write_sets: Vec<_> = results
+        .iter()
+        .map(|result| result.transaction_output().write_set().clone())
+        .collect();
+    let bytes = bcs::to_bytes(&write_sets).expect("Failed to serialize write sets");
+    HashValue::sha3_256_of(&bytes)
+}
+
+/// Appends the value to the file as a single line of JSON.
+fn write_json_line<T: Serialize>(file: &mut File, value: &T) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    file.write_all(&line)
+}
+
 fn serialized_len(tx: &SignedTransaction) -> usize {
     bcs::serialized_size(tx).expect("failed to compute serialized transaction size") as usize
 }
 
-fn deduplicate_transactions(transactions: Vec<SignedTransaction>) -> Vec<SignedTransaction> {
+/// Reorders the transactions group after group of non-conflicting transactions. Conflicting
+/// transactions keep their relative order, so the result of the block is unchanged.
+fn schedule_in_groups(transactions: Vec<SignedTransaction>) -> Vec<SignedTransaction> {
+    let plan = plan_parallel_schedule(&transactions);
+    debug!(
+        "Scheduled {} transactions in {} groups (at most {} in parallel)",
+        transactions.len(),
+        plan.groups.len(),
+        plan.max_parallelism()
+    );
+    let mut transactions: Vec<_> = transactions.into_iter().map(Some).collect();
+    plan.order()
+        .into_iter()
+        .filter_map(|index| transactions[index].take())
+        .collect()
+}
+
+/// Orders the certificates round by round. Within a round, the certificate of the round's leader (the
+/// anchor) comes first and the others keep their relative order.
+fn order_by_leader(
+    mut certificates: Vec<Certificate>,
+    schedule: &dyn LeaderSchedule,
+) -> Vec<Certificate> {
+    certificates.sort_by_key(|x| (x.round, x.origin != schedule.leader(x.round)));
+    certificates
+}
+
+/// Drops duplicates within `transactions` as well as the transactions whose sequence number their
+/// sender already used, i.e. that were executed in an earlier block (or superseded by another
+/// transaction of the sender). `next_sequence_number` reads the sequence number of an account from
+/// the state, so that nothing has to be remembered across blocks.
+fn deduplicate_transactions(
+    transactions: Vec<SignedTransaction>,
+    trace_ids: &TraceIds,
+    next_sequence_number: impl Fn(AccountAddress) -> u64,
+) -> Vec<SignedTransaction> {
     let mut seen: HashSet<Vec<u8>> = HashSet::with_capacity(transactions.len());
     let mut unique = Vec::with_capacity(transactions.len());
 
     for txn in transactions {
+        if txn.sequence_number() < next_sequence_number(txn.sender()) {
+            debug!(
+                "Dropping transaction {} (trace {}): sequence number {} already used",
+                txn.committed_hash(),
+                trace_ids.resolve(&txn),
+                txn.sequence_number()
+            );
+            continue;
+        }
         match bcs::to_bytes(&txn) {
             Ok(bytes) => {
                 if seen.insert(bytes) {
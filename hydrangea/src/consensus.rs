@@ -1,12 +1,13 @@
-use crate::committer::Committer;
+use crate::committer::{CommitAttester, Committer, CommitterConfig, HeaderDecodeFailures};
 use crate::core::Core;
 use crate::error::ConsensusError;
 use crate::helper::Helper;
-use crate::leader::LeaderElector;
+use crate::leader::{LeaderElector, LeaderSchedule};
 use crate::mempool::MempoolDriver;
 use crate::messages::{Block, FallbackRecoveryProposal, NormalProposal, Timeout, Vote, QC, TC};
 use crate::proposer::Proposer;
 use crate::synchronizer::Synchronizer;
+use aptos_executor::AptosVmExecutor;
 use async_trait::async_trait;
 use bytes::Bytes;
 use config::{Committee, Parameters};
@@ -14,7 +15,7 @@ use crypto::{BlsSignatureService, Digest, PublicKey, SignatureService};
 use futures::SinkExt as _;
 use log::{debug, info};
 use network::{MessageHandler, Receiver as NetworkReceiver, Writer};
-use primary::Certificate;
+use primary::{AbortStats, Certificate, ExecutionPressure, ExecutionReceipts};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use store::Store;
@@ -59,6 +60,12 @@ impl Consensus {
         signature_service: SignatureService,
         bls_signature_service: BlsSignatureService,
         store: Store,
+        executor: Option<AptosVmExecutor>,
+        receipts: ExecutionReceipts,
+        execution_pressure: ExecutionPressure,
+        header_decode_failures: HeaderDecodeFailures,
+        attester: Option<CommitAttester>,
+        tx_abort_stats: Sender<AbortStats>,
         rx_mempool: Receiver<Certificate>,
         tx_mempool: Sender<Certificate>,
         tx_output: Sender<Block>,
@@ -94,7 +101,9 @@ impl Consensus {
         );
 
         // Make the leader election module.
-        let leader_elector = LeaderElector::new(parameters.leader_elector, committee.clone());
+        let leader_elector =
+            LeaderElector::new(parameters.leader_elector.clone(), committee.clone())
+                .expect("Failed to make the leader elector");
 
         // Make the mempool driver.
         let mempool_driver = MempoolDriver::new(committee.clone(), tx_mempool);
@@ -104,6 +113,7 @@ impl Consensus {
             name,
             committee.clone(),
             store.clone(),
+            parameters.store_codec,
             tx_sync_core.clone(),
             parameters.sync_retry_delay,
         );
@@ -116,6 +126,7 @@ impl Consensus {
             signature_service.clone(),
             bls_signature_service,
             store.clone(),
+            parameters.store_codec,
             leader_elector,
             mempool_driver,
             synchronizer,
@@ -125,12 +136,44 @@ impl Consensus {
             rx_sync_core,
             tx_core_proposer,
             tx_commit,
+            execution_pressure.clone(),
             tx_output,
         );
 
-        if !parameters.consensus_only {
-            // Commits the mempool certificates and their sub-dag.
-            Committer::spawn(store.clone(), rx_commit);
+        // Commits the mempool certificates and their sub-dag (unless we run consensus in isolation).
+        if let Some(executor) = executor {
+            Committer::spawn(
+                store.clone(),
+                executor,
+                receipts,
+                CommitterConfig {
+                    parallel_schedule: committee.execution.parallel_schedule,
+                    leader_schedule: committee.execution.order_commits_by_leader.then(|| {
+                        Box::new(
+                            LeaderElector::new(
+                                parameters.leader_elector.clone(),
+                                committee.clone(),
+                            )
+                            .expect("Failed to make the leader elector"),
+                        ) as Box<dyn LeaderSchedule>
+                    }),
+                    block_output: parameters.block_output.clone(),
+                    transaction_output: parameters.transaction_output.clone(),
+                    execution_pressure,
+                    receipts_retention: Some(
+                        parameters.receipts_retention.unwrap_or(parameters.gc_depth),
+                    ),
+                    detect_commit_gaps: parameters.detect_commit_gaps,
+                    strict: false,
+                    store_codec: parameters.store_codec,
+                    header_decode_failures,
+                    attester,
+                    tx_abort_stats: Some(tx_abort_stats),
+                    gc_depth: parameters.gc_depth,
+                    deduplicate: committee.execution.deduplicate_transactions,
+                },
+                rx_commit,
+            );
         }
 
         // Spawn the block proposer.
@@ -146,7 +189,12 @@ impl Consensus {
         );
 
         // Spawn the helper module.
-        Helper::spawn(committee, store, /* rx_requests */ rx_helper);
+        Helper::spawn(
+            committee,
+            store,
+            parameters.store_codec,
+            /* rx_requests */ rx_helper,
+        );
     }
 }
 
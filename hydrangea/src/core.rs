@@ -12,12 +12,12 @@ use crate::timer::Timer;
 use async_recursion::async_recursion;
 use blsttc::SignatureShareG1;
 use bytes::Bytes;
-use config::Committee;
+use config::{Committee, StoreCodec};
 use crypto::{BlsSignatureService, Digest, Hash as _};
 use crypto::{PublicKey, SignatureService};
 use log::{debug, error, info, warn};
 use network::SimpleSender;
-use primary::Certificate;
+use primary::{Certificate, ExecutionPressure};
 use std::cmp::max;
 use std::collections::{HashMap, HashSet};
 use store::Store;
@@ -63,6 +63,8 @@ pub struct Core {
     signature_service: SignatureService,
     bls_signature_service: BlsSignatureService,
     store: Store,
+    /// The serialization format of the blocks in the store.
+    store_codec: StoreCodec,
     synchronizer: Synchronizer,
     sync_requests: HashSet<Digest>,
     timeout_delay: u64,
@@ -70,6 +72,8 @@ pub struct Core {
     timeout_syncs: HashSet<Round>,
     timer: Timer,
     tx_commit: Sender<Vec<Certificate>>,
+    /// The number of blocks sent to the committer and not executed yet.
+    execution_pressure: ExecutionPressure,
     tx_output: Sender<Block>,
     tx_proposer: Sender<ProposerMessage>,
     // Index of uncommitted blocks by Digest.
@@ -90,6 +94,7 @@ impl Core {
         signature_service: SignatureService,
         bls_signature_service: BlsSignatureService,
         store: Store,
+        store_codec: StoreCodec,
         leader_elector: LeaderElector,
         mempool_driver: MempoolDriver,
         synchronizer: Synchronizer,
@@ -99,6 +104,7 @@ impl Core {
         rx_synchronizer: Receiver<Block>,
         tx_proposer: Sender<ProposerMessage>,
         tx_commit: Sender<Vec<Certificate>>,
+        execution_pressure: ExecutionPressure,
         tx_output: Sender<Block>,
     ) {
         tokio::spawn(async move {
@@ -142,12 +148,14 @@ impl Core {
                 signature_service,
                 bls_signature_service,
                 store,
+                store_codec,
                 synchronizer,
                 sync_requests: HashSet::new(),
                 timeout_delay,
                 timeout_syncs: HashSet::new(),
                 timer: Timer::new(timeout_delay),
                 tx_commit,
+                execution_pressure,
                 tx_output,
                 tx_proposer,
                 pending_proposals,
@@ -354,7 +362,10 @@ impl Core {
         let _ = self.observe_payload(block).await;
         // Write to disk
         let key = block.digest().to_vec();
-        let value = bincode::serialize(block).expect("Failed to serialize block");
+        let value = self
+            .store_codec
+            .encode(block)
+            .expect("Failed to serialize block");
         self.store.write(key, value).await;
         debug!("Stored block {:?}", block);
     }
@@ -405,7 +416,9 @@ impl Core {
 
             if !self.consensus_only {
                 let payload = committing.payload.clone();
-                // Send the payload to the committer.
+                // Send the payload to the committer. The block is counted first so that the committer
+                // never sees it executed before it is counted.
+                self.execution_pressure.block_committed();
                 self.tx_commit
                     .send(payload.clone())
                     .await
@@ -1,4 +1,5 @@
 use crate::{consensus::Round, QC};
+use config::CodecError;
 use crypto::{BlsError, CryptoError, Digest, PublicKey};
 use primary::DagError;
 use store::StoreError;
@@ -30,6 +31,9 @@ pub enum ConsensusError {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] Box<bincode::ErrorKind>),
 
+    #[error("Serialization error: {0}")]
+    CodecError(#[from] CodecError),
+
     #[error("Store error: {0}")]
     StoreError(#[from] StoreError),
 
@@ -101,4 +105,13 @@ pub enum ConsensusError {
 
     #[error(transparent)]
     DagError(#[from] DagError),
+
+    #[error("Failed to initialize the Aptos VM executor: {0}")]
+    ExecutorInit(String),
+
+    #[error("Failed to execute the block of round {round}: {message}")]
+    BlockNotExecuted { round: Round, message: String },
+
+    #[error("Committed transaction {hash} was not executed: {status}")]
+    TransactionNotExecuted { hash: String, status: String },
 }
@@ -1,6 +1,7 @@
 use crate::consensus::ConsensusMessage;
+use crate::messages::Block;
 use bytes::Bytes;
-use config::Committee;
+use config::{Committee, StoreCodec};
 use crypto::{Digest, PublicKey};
 use log::{debug, warn};
 use network::SimpleSender;
@@ -17,6 +18,8 @@ pub struct Helper {
     committee: Committee,
     /// The persistent storage.
     store: Store,
+    /// The serialization format of the blocks in the store.
+    store_codec: StoreCodec,
     /// Input channel to receive sync requests.
     rx_requests: Receiver<(Digest, PublicKey)>,
     /// A network sender to reply to the sync requests.
@@ -24,11 +27,17 @@ pub struct Helper {
 }
 
 impl Helper {
-    pub fn spawn(committee: Committee, store: Store, rx_requests: Receiver<(Digest, PublicKey)>) {
+    pub fn spawn(
+        committee: Committee,
+        store: Store,
+        store_codec: StoreCodec,
+        rx_requests: Receiver<(Digest, PublicKey)>,
+    ) {
         tokio::spawn(async move {
             Self {
                 committee,
                 store,
+                store_codec,
                 rx_requests,
                 network: SimpleSender::new(),
             }
@@ -63,8 +72,10 @@ impl Helper {
                 .await
                 .expect("Failed to read from storage")
             {
-                let block =
-                    bincode::deserialize(&bytes).expect("Failed to deserialize our own block");
+                let block: Block = self
+                    .store_codec
+                    .decode(&bytes)
+                    .expect("Failed to deserialize our own block");
                 let message = bincode::serialize(&ConsensusMessage::SyncResponse(block))
                     .expect("Failed to serialize block");
                 debug!("Serving {} to {}", digest, address);
@@ -1,8 +1,18 @@
 use crate::consensus::Round;
-use config::{Committee, LeaderElectorKind};
+use config::{Committee, ConfigError, LeaderElectorKind};
 use crypto::PublicKey;
 use std::collections::{HashMap, VecDeque};
 
+#[cfg(test)]
+#[path = "tests/leader_tests.rs"]
+pub mod leader_tests;
+
+/// Picks the leader of each round of the DAG, whose certificate anchors the round when ordering
+/// committed certificates.
+pub trait LeaderSchedule: Send + Sync + 'static {
+    fn leader(&self, round: Round) -> PublicKey;
+}
+
 enum LeaderElectorInner {
     Simple(SimpleLeaderElector),
     Fair(DeterministicFairSuccessionLeaderElector),
@@ -15,7 +25,13 @@ pub struct LeaderElector {
 }
 
 impl LeaderElector {
-    pub fn new(kind: LeaderElectorKind, committee: Committee) -> Self {
+    /// Fails if the committee has no authority to elect.
+    pub fn new(kind: LeaderElectorKind, committee: Committee) -> Result<Self, ConfigError> {
+        if committee.authorities.is_empty() {
+            return Err(ConfigError::InvalidCommittee(
+                "no authority to elect leaders from".to_string(),
+            ));
+        }
         let inner = match kind {
             LeaderElectorKind::Simple => {
                 LeaderElectorInner::Simple(SimpleLeaderElector::new(committee))
@@ -34,7 +50,7 @@ impl LeaderElector {
             ),
         };
 
-        Self { inner }
+        Ok(Self { inner })
     }
 
     pub fn get_leader(&self, round: Round) -> PublicKey {
@@ -46,6 +62,13 @@ impl LeaderElector {
     }
 }
 
+/// The committer orders the certificates of a round starting with the leader of the consensus.
+impl LeaderSchedule for LeaderElector {
+    fn leader(&self, round: Round) -> PublicKey {
+        self.get_leader(round)
+    }
+}
+
 #[derive(Clone)]
 pub struct DeterministicFairSuccessionLeaderElector {
     nodes_ids: Vec<PublicKey>,
@@ -16,5 +16,8 @@ mod timer;
 // #[path = "tests/common.rs"]
 // mod common;
 
+pub use crate::committer::{block_hash, init_executor, CommitAttester, HeaderDecodeFailures};
 pub use crate::consensus::Consensus;
+pub use crate::error::ConsensusError;
+pub use crate::leader::{LeaderElector, LeaderSchedule};
 pub use crate::messages::{Block, QC, TC};
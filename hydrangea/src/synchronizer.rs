@@ -2,7 +2,7 @@ use crate::consensus::{ConsensusMessage, CHANNEL_CAPACITY};
 use crate::error::ConsensusResult;
 use crate::messages::{Block, QC};
 use bytes::Bytes;
-use config::Committee;
+use config::{Committee, StoreCodec};
 use crypto::{Digest, Hash, PublicKey};
 use futures::stream::futures_unordered::FuturesUnordered;
 use futures::stream::StreamExt as _;
@@ -22,6 +22,8 @@ const TIMER_ACCURACY: u64 = 5_000;
 
 pub struct Synchronizer {
     store: Store,
+    /// The serialization format of the blocks in the store.
+    store_codec: StoreCodec,
     inner_channel: Sender<(Digest, PublicKey, Option<Block>)>,
 }
 
@@ -30,6 +32,7 @@ impl Synchronizer {
         name: PublicKey,
         committee: Committee,
         store: Store,
+        store_codec: StoreCodec,
         tx_sync_core: Sender<Block>,
         sync_retry_delay: u64,
     ) -> Self {
@@ -150,6 +153,7 @@ impl Synchronizer {
         });
         Self {
             store,
+            store_codec,
             inner_channel: tx_inner,
         }
     }
@@ -178,7 +182,7 @@ impl Synchronizer {
             Ok(Some(Block::genesis()))
         } else {
             match self.store.read(digest.to_vec()).await? {
-                Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+                Some(bytes) => Ok(Some(self.store_codec.decode(&bytes)?)),
                 None => {
                     debug!("Sending request to Synchronizer for {}", digest);
                     if let Err(e) = self
@@ -0,0 +1,1029 @@
+use super::*;
+use crypto::{Digest, PublicKey};
+use primary::{CommitGaps, SenderOutcomes};
+use std::fs;
+use tokio::sync::mpsc::channel;
+
+// Fixture: a committer running on a fresh store named after the test, fed with the certificates of
+// the headers written to that store.
+struct TestCommitter {
+    store: Store,
+    store_codec: StoreCodec,
+    tx_commit: Sender<Vec<Certificate>>,
+    handle: JoinHandle<ConsensusResult<()>>,
+    next_id: u8,
+}
+
+impl TestCommitter {
+    fn spawn(
+        name: &str,
+        executor: AptosVmExecutor,
+        receipts: ExecutionReceipts,
+        config: CommitterConfig,
+    ) -> Self {
+        let path = format!(".db_test_{}", name);
+        let _ = fs::remove_dir_all(&path);
+        let store = Store::new(&path).unwrap();
+        let store_codec = config.store_codec;
+        let (tx_commit, rx_commit) = channel(1);
+        let handle = Committer::spawn(store.clone(), executor, receipts, config, rx_commit);
+        Self {
+            store,
+            store_codec,
+            tx_commit,
+            handle,
+            next_id: 1,
+        }
+    }
+
+    /// Replaces the committer by a new one running on the same store, as after a restart.
+    fn restart(
+        &mut self,
+        executor: AptosVmExecutor,
+        receipts: ExecutionReceipts,
+        config: CommitterConfig,
+    ) {
+        self.store_codec = config.store_codec;
+        let (tx_commit, rx_commit) = channel(1);
+        self.handle = Committer::spawn(self.store.clone(), executor, receipts, config, rx_commit);
+        self.tx_commit = tx_commit;
+    }
+
+    /// Writes a header of `round` holding `payload` and returns its certificate, without committing it.
+    async fn write(&mut self, round: Round, payload: Vec<SignedTransaction>) -> Certificate {
+        let header = Header {
+            round,
+            payload,
+            id: Digest([self.next_id; 32]),
+            ..Header::default()
+        };
+        self.next_id += 1;
+        header.write(&mut self.store, self.store_codec).await;
+        Certificate {
+            id: header.id,
+            round,
+            ..Certificate::default()
+        }
+    }
+
+    /// Commits `certificates` as one sequence.
+    async fn send(&self, certificates: Vec<Certificate>) {
+        self.tx_commit.send(certificates).await.unwrap();
+    }
+
+    /// Writes a header of `round` holding `payload` and commits its certificate.
+    async fn commit(&mut self, round: Round, payload: Vec<SignedTransaction>) {
+        let certificate = self.write(round, payload).await;
+        self.send(vec![certificate]).await;
+    }
+
+    /// Waits for the committer to stop, failing after 60 s.
+    async fn stopped(self) -> ConsensusResult<()> {
+        tokio::time::timeout(std::time::Duration::from_secs(60), self.handle)
+            .await
+            .expect("The committer did not stop in time")
+            .unwrap()
+    }
+}
+
+// Fixture: polls `poll` every 50 ms until it returns a value, failing after 60 s.
+async fn wait_for<T>(what: &str, mut poll: impl FnMut() -> Option<T>) -> T {
+    tokio::time::timeout(std::time::Duration::from_secs(60), async {
+        loop {
+            match poll() {
+                Some(value) => break value,
+                None => tokio::time::sleep(std::time::Duration::from_millis(50)).await,
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("Timed out waiting for {}", what))
+}
+
+// Fixture: the first line written to a file, if any.
+fn first_line(path: &str) -> Option<String> {
+    first_lines(path, 1)?.pop()
+}
+
+// Fixture: the first `count` lines written to a file, once there are that many.
+fn first_lines(path: &str, count: usize) -> Option<Vec<String>> {
+    let content = fs::read_to_string(path).ok()?;
+    let lines: Vec<_> = content.lines().take(count).map(str::to_string).collect();
+    (lines.len() == count).then_some(lines)
+}
+
+// Fixture: a transfer of `amount` octas from `sender` to `recipient`.
+fn transfer(
+    sender: &mut LocalAccount,
+    recipient: AccountAddress,
+    amount: u64,
+) -> SignedTransaction {
+    aptos_executor::transaction_builder::apt_transfer(
+        sender,
+        recipient,
+        amount,
+        aptos_types::chain_id::ChainId::test(),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn committer_reads_headers_written_by_core() {
+    let path = ".db_test_committer_reads_headers_written_by_core";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    // The core and the committer hold clones of the same store.
+    let mut core_store = store.clone();
+    let mut committer_store = store;
+
+    // The core persists the header it votes on.
+    let header = Header {
+        round: 3,
+        id: Digest([7; 32]),
+        ..Header::default()
+    };
+    header.write(&mut core_store, StoreCodec::default()).await;
+
+    // The committer loads it back from the certificate.
+    let certificate = Certificate {
+        id: header.id.clone(),
+        round: header.round,
+        ..Certificate::default()
+    };
+    let loaded = load_header(
+        &mut committer_store,
+        &certificate,
+        StoreCodec::default(),
+        &HeaderDecodeFailures::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(loaded.id, header.id);
+    assert_eq!(loaded.round, header.round);
+}
+
+#[tokio::test]
+async fn committer_does_not_see_headers_of_another_store() {
+    let core_path = ".db_test_committer_other_store_core";
+    let committer_path = ".db_test_committer_other_store_committer";
+    let _ = fs::remove_dir_all(core_path);
+    let _ = fs::remove_dir_all(committer_path);
+    let mut core_store = Store::new(core_path).unwrap();
+    let mut committer_store = Store::new(committer_path).unwrap();
+
+    let header = Header {
+        id: Digest([9; 32]),
+        ..Header::default()
+    };
+    header.write(&mut core_store, StoreCodec::default()).await;
+
+    let certificate = Certificate {
+        id: header.id.clone(),
+        ..Certificate::default()
+    };
+    assert!(load_header(
+        &mut committer_store,
+        &certificate,
+        StoreCodec::default(),
+        &HeaderDecodeFailures::default(),
+    )
+    .await
+    .is_none());
+}
+
+#[tokio::test]
+async fn undecodable_headers_are_counted() {
+    let path = ".db_test_undecodable_headers_are_counted";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // The bytes stored for the certificate are not a header.
+    let certificate = Certificate {
+        id: Digest([12; 32]),
+        ..Certificate::default()
+    };
+    store.write(certificate.id.to_vec(), vec![0xff; 3]).await;
+
+    let failures = HeaderDecodeFailures::default();
+    for codec in [StoreCodec::Bincode, StoreCodec::Bcs] {
+        assert!(load_header(&mut store, &certificate, codec, &failures)
+            .await
+            .is_none());
+    }
+    assert_eq!(failures.count(), 2);
+
+    // A missing header is not a decoding failure.
+    let missing = Certificate {
+        id: Digest([13; 32]),
+        ..Certificate::default()
+    };
+    assert!(
+        load_header(&mut store, &missing, StoreCodec::default(), &failures)
+            .await
+            .is_none()
+    );
+    assert_eq!(failures.count(), 2);
+}
+
+#[tokio::test]
+async fn committed_transaction_result_can_be_fetched_by_hash() {
+    // Commit a transfer between two pre-funded accounts.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let txn = transfer(&mut sender, recipient.address, 100);
+    let hash = txn.committed_hash().to_hex_literal();
+    let receipts = ExecutionReceipts::new(10);
+    let mut committer = TestCommitter::spawn(
+        "committed_transaction_result_can_be_fetched_by_hash",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        CommitterConfig::default(),
+    );
+    committer.commit(0, vec![txn]).await;
+
+    // Wait for the commit and fetch the result by hash.
+    let receipt = wait_for("the transaction to commit", || receipts.get(&hash)).await;
+    assert_eq!(receipt.hash, hash);
+    assert!(receipt.kept);
+    assert!(receipt.gas_used > 0);
+    assert!(!receipt.events.is_empty());
+
+    // The state left by the transaction is served too.
+    let key = aptos_executor::database::account_resource_key(sender.address).unwrap();
+    let account = match receipts.state_value(&key) {
+        primary::StateLookup::Found(bytes) => {
+            bcs::from_bytes::<aptos_types::account_config::AccountResource>(&bytes).unwrap()
+        }
+        lookup => panic!("The account of the sender is not served: {:?}", lookup),
+    };
+    assert_eq!(account.sequence_number(), 1);
+    assert_eq!(receipts.sequence_number(&sender.address), Some(Ok(1)));
+}
+
+#[tokio::test]
+async fn replayed_certificates_are_executed_once() {
+    let receipts = ExecutionReceipts::new(10);
+    let mut committer = TestCommitter::spawn(
+        "replayed_certificates_are_executed_once",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        CommitterConfig::default(),
+    );
+
+    // Two transfers from the same sender, in two headers.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let mut certificates = Vec::new();
+    let mut hashes = Vec::new();
+    for round in 1..=2 {
+        let txn = transfer(&mut sender, recipient.address, 100);
+        hashes.push(txn.committed_hash().to_hex_literal());
+        certificates.push(committer.write(round, vec![txn]).await);
+    }
+
+    // Commit the first certificate, then both of them: the first one is not executed again.
+    committer.send(vec![certificates[0].clone()]).await;
+    let receipt = wait_for("the first transfer", || receipts.get(&hashes[0])).await;
+    assert!(receipt.kept);
+    committer.send(certificates.clone()).await;
+    let receipt = wait_for("the second transfer", || receipts.get(&hashes[1])).await;
+    assert!(receipt.kept);
+    assert!(receipts.get(&hashes[0]).unwrap().kept);
+    assert_eq!(receipts.sequence_number(&sender.address), Some(Ok(2)));
+
+    // The state of the executor is not persisted: after a restart, the committer executes the
+    // certificates the consensus replays again, on top of the genesis.
+    let receipts = ExecutionReceipts::new(10);
+    committer.restart(
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        CommitterConfig::default(),
+    );
+    committer.send(certificates).await;
+    let receipt = wait_for("the second transfer", || receipts.get(&hashes[1])).await;
+    assert!(receipt.kept);
+    assert!(receipts.get(&hashes[0]).unwrap().kept);
+    assert_eq!(receipts.sequence_number(&sender.address), Some(Ok(2)));
+}
+
+#[test]
+fn executed_certificates_are_pruned_below_the_gc_round() {
+    let certificate = |round, id| Certificate {
+        id: Digest([id; 32]),
+        round,
+        ..Certificate::default()
+    };
+    let mut executed = ExecutedCertificates::default();
+    for round in 1..=5 {
+        executed.insert(&certificate(round, round as u8));
+    }
+    assert!(executed.contains(&certificate(2, 2)));
+    assert!(!executed.contains(&certificate(2, 3)));
+
+    executed.prune_below(3);
+    assert_eq!(executed.len(), 3);
+    assert!(!executed.contains(&certificate(2, 2)));
+    assert!(executed.contains(&certificate(3, 3)));
+}
+
+#[test]
+fn executor_init_failure_is_reported() {
+    match init_executor(|| Err::<AptosVmExecutor, _>("genesis failed")) {
+        Err(ConsensusError::ExecutorInit(message)) => assert!(message.contains("genesis failed")),
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("A failing executor constructor should be reported"),
+    }
+}
+
+/// Alternates the leadership between two authorities.
+struct Alternating(PublicKey, PublicKey);
+
+impl LeaderSchedule for Alternating {
+    fn leader(&self, round: u64) -> PublicKey {
+        match round % 2 {
+            0 => self.0,
+            _ => self.1,
+        }
+    }
+}
+
+#[test]
+fn leader_certificate_is_committed_first_in_each_round() {
+    let first = PublicKey([1; 32]);
+    let second = PublicKey([2; 32]);
+    let certificate = |origin, round| Certificate {
+        origin,
+        round,
+        ..Certificate::default()
+    };
+    let schedule = Alternating(first, second);
+
+    // Round 2 is led by the first authority and round 3 by the second one.
+    let certificates = vec![
+        certificate(second, 3),
+        certificate(second, 2),
+        certificate(first, 3),
+        certificate(first, 2),
+    ];
+    let ordered: Vec<_> = order_by_leader(certificates, &schedule)
+        .into_iter()
+        .map(|x| (x.origin, x.round))
+        .collect();
+    assert_eq!(
+        ordered,
+        vec![(first, 2), (second, 2), (second, 3), (first, 3)]
+    );
+}
+
+#[tokio::test]
+async fn executed_blocks_are_written_as_json_lines() {
+    let output = ".test_executed_blocks_are_written_as_json_lines.jsonl";
+    let _ = fs::remove_file(output);
+    let config = CommitterConfig {
+        block_output: Some(output.into()),
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "executed_blocks_are_written_as_json_lines",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        ExecutionReceipts::new(10),
+        config,
+    );
+
+    // Commit two blocks, each with a transfer between two pre-funded accounts.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let mut hashes = Vec::new();
+    for round in 1..=2 {
+        let txn = transfer(&mut sender, recipient.address, 100);
+        hashes.push(txn.committed_hash().to_hex_literal());
+        committer.commit(round, vec![txn]).await;
+    }
+
+    // Wait for both summaries.
+    let lines = wait_for("the blocks to execute", || first_lines(output, 2)).await;
+    let summaries: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(summaries.len(), 2);
+    for (i, summary) in summaries.iter().enumerate() {
+        assert_eq!(summary["round"], i as u64 + 1);
+        let transactions = summary["transactions"].as_array().unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0]["hash"], hashes[i].as_str());
+        assert_eq!(transactions[0]["kept"], true);
+        assert!(transactions[0]["gas_used"].as_u64().unwrap() > 0);
+        assert!(transactions[0]["status"].is_string());
+        assert!(summary["write_set_root"].is_string());
+    }
+    // Each transfer writes different balances.
+    assert_ne!(
+        summaries[0]["write_set_root"],
+        summaries[1]["write_set_root"]
+    );
+}
+
+#[tokio::test]
+async fn transaction_outputs_are_exported_in_the_aptos_format() {
+    use aptos_types::transaction::{ExecutionStatus, TransactionOutput, TransactionStatus};
+
+    let output = ".test_transaction_outputs_are_exported_in_the_aptos_format.jsonl";
+    let _ = fs::remove_file(output);
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let txn = transfer(&mut sender, recipient.address, 100);
+    let config = CommitterConfig {
+        transaction_output: Some(output.into()),
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "transaction_outputs_are_exported_in_the_aptos_format",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        ExecutionReceipts::new(10),
+        config,
+    );
+    committer.commit(1, vec![txn.clone()]).await;
+
+    let line = wait_for("the transaction output", || first_line(output)).await;
+    let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(record["round"], 1);
+    assert_eq!(
+        record["hash"],
+        txn.committed_hash().to_hex_literal().as_str()
+    );
+    let bytes = hex::decode(record["output"].as_str().unwrap()).unwrap();
+    let exported: TransactionOutput = bcs::from_bytes(&bytes).unwrap();
+
+    // The export matches the output of the same transfer executed directly.
+    let mut executor = init_executor(AptosVmExecutor::new).unwrap();
+    let expected = executor.execute_block(&[txn])[0].transaction_output();
+    assert_eq!(
+        exported.status(),
+        &TransactionStatus::Keep(ExecutionStatus::Success)
+    );
+    assert_eq!(exported.write_set(), expected.write_set());
+    assert!(exported.write_set().write_op_iter().next().is_some());
+    assert_eq!(exported.gas_used(), expected.gas_used());
+}
+
+#[tokio::test]
+async fn executed_blocks_form_a_hash_chain() {
+    let output = ".test_executed_blocks_form_a_hash_chain.jsonl";
+    let _ = fs::remove_file(output);
+    let receipts = ExecutionReceipts::new(10);
+    let config = CommitterConfig {
+        block_output: Some(output.into()),
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "executed_blocks_form_a_hash_chain",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        config,
+    );
+
+    // Commit three blocks, each with a transfer between two pre-funded accounts.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    for round in 1..=3 {
+        let txn = transfer(&mut sender, recipient.address, 100);
+        committer.commit(round, vec![txn]).await;
+    }
+    let lines = wait_for("the blocks to execute", || first_lines(output, 3)).await;
+
+    // Every block links to the previous one and its hash can be recomputed from the summary alone.
+    let hash =
+        |value: &serde_json::Value| HashValue::from_hex_literal(value.as_str().unwrap()).unwrap();
+    let mut head = HashValue::zero();
+    for line in &lines {
+        let summary: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(hash(&summary["prev_block_hash"]), head);
+        let transaction_hashes: Vec<_> = summary["transactions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| hash(&x["hash"]))
+            .collect();
+        let recomputed = block_hash(
+            &head,
+            summary["round"].as_u64().unwrap(),
+            &transaction_hashes,
+            &hash(&summary["write_set_root"]),
+        );
+        assert_eq!(hash(&summary["block_hash"]), recomputed);
+        head = recomputed;
+    }
+
+    // The committer exposes the same head.
+    let chain_head = receipts.chain_head().unwrap();
+    assert_eq!(chain_head.round, 3);
+    assert_eq!(chain_head.block_hash, head.to_hex_literal());
+}
+
+#[tokio::test]
+async fn receipts_older_than_retention_are_pruned() {
+    use primary::ReceiptLookup;
+
+    // Only the receipts of the last two rounds are retained.
+    let receipts = ExecutionReceipts::new(10);
+    let config = CommitterConfig {
+        receipts_retention: Some(2),
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "receipts_older_than_retention_are_pruned",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        config,
+    );
+
+    // Commit four blocks of consecutive rounds, each with a transfer between two pre-funded
+    // accounts.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let mut hashes = Vec::new();
+    for round in 1..=4 {
+        let txn = transfer(&mut sender, recipient.address, 100);
+        hashes.push(txn.committed_hash().to_hex_literal());
+        committer.commit(round, vec![txn]).await;
+    }
+
+    // The receipts of a block are pruned right after those of the next block are recorded.
+    wait_for("the blocks to execute", || {
+        (receipts.lookup(&hashes[1]) == ReceiptLookup::Pruned).then_some(())
+    })
+    .await;
+
+    assert_eq!(receipts.lookup(&hashes[0]), ReceiptLookup::Pruned);
+    assert_eq!(receipts.lookup(&hashes[1]), ReceiptLookup::Pruned);
+    assert!(matches!(
+        receipts.lookup(&hashes[2]),
+        ReceiptLookup::Found(_)
+    ));
+    assert!(matches!(
+        receipts.lookup(&hashes[3]),
+        ReceiptLookup::Found(_)
+    ));
+    assert_eq!(receipts.len(), 2);
+    assert_eq!(receipts.lookup("0x1234"), ReceiptLookup::Unknown);
+}
+
+#[tokio::test]
+async fn skipped_rounds_are_reported() {
+    let receipts = ExecutionReceipts::new(10);
+    let (tx_abort_stats, mut rx_abort_stats) = channel(10);
+    let config = CommitterConfig {
+        detect_commit_gaps: true,
+        tx_abort_stats: Some(tx_abort_stats),
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "skipped_rounds_are_reported",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        config,
+    );
+
+    // Commit empty headers of rounds 1, 2, and 4, skipping round 3.
+    for round in [1, 2, 4] {
+        committer.commit(round, Vec::new()).await;
+    }
+    let gaps = wait_for("the gap", || {
+        receipts.commit_gaps().filter(|gaps| gaps.gaps > 0)
+    })
+    .await;
+    let expected = CommitGaps {
+        gaps: 1,
+        skipped_rounds: 1,
+        last_gap: Some((3, 3)),
+    };
+    assert_eq!(gaps, expected);
+
+    // Late certificates of old rounds are not gaps, and neither are contiguous rounds. Each of them
+    // holds a transfer, so that the committer reports the block once it processed it.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    for round in [3, 5] {
+        let txn = transfer(&mut sender, recipient.address, 100);
+        committer.commit(round, vec![txn]).await;
+        let stats = tokio::time::timeout(std::time::Duration::from_secs(60), rx_abort_stats.recv())
+            .await
+            .unwrap_or_else(|_| panic!("Timed out waiting for the block of round {}", round))
+            .unwrap();
+        assert_eq!(stats.senders.len(), 1);
+        assert!(stats.senders.contains_key(&sender.address));
+    }
+    assert_eq!(receipts.commit_gaps(), Some(expected));
+}
+
+#[tokio::test]
+async fn strict_committer_stops_on_aborted_transaction() {
+    let config = CommitterConfig {
+        strict: true,
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "strict_committer_stops_on_aborted_transaction",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        ExecutionReceipts::new(10),
+        config,
+    );
+
+    // A transfer of more APT than the sender holds aborts.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let txn = transfer(&mut sender, recipient.address, u64::MAX);
+    let hash = txn.committed_hash().to_hex_literal();
+    committer.commit(0, vec![txn]).await;
+
+    // The committer stops and reports the aborted transaction.
+    match committer.stopped().await {
+        Err(ConsensusError::TransactionNotExecuted { hash: failed, .. }) => {
+            assert_eq!(failed, hash)
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn executed_transactions_keep_their_trace_id() {
+    let output = ".test_executed_transactions_keep_their_trace_id.jsonl";
+    let _ = fs::remove_file(output);
+
+    // The client submitted the transaction under its own trace id.
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let txn = transfer(&mut sender, recipient.address, 100);
+    let receipts = ExecutionReceipts::new(10);
+    let trace_id: TraceId = "00000000deadbeef".parse().unwrap();
+    assert_ne!(trace_id, TraceId::of(&txn));
+    receipts.trace_ids().assign(&txn, trace_id);
+
+    let config = CommitterConfig {
+        block_output: Some(output.into()),
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "executed_transactions_keep_their_trace_id",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts,
+        config,
+    );
+    committer.commit(0, vec![txn]).await;
+
+    // The execution log of the committer references the same id.
+    let line = wait_for("the block summary", || first_line(output)).await;
+    let summary: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(summary["transactions"][0]["trace_id"], "00000000deadbeef");
+}
+
+#[tokio::test]
+async fn headers_round_trip_through_the_bcs_codec() {
+    let receipts = ExecutionReceipts::new(10);
+    let config = CommitterConfig {
+        store_codec: StoreCodec::Bcs,
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "headers_round_trip_through_the_bcs_codec",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        config,
+    );
+
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let txn = transfer(&mut sender, recipient.address, 100);
+    let hash = txn.committed_hash().to_hex_literal();
+    let certificate = committer.write(4, vec![txn.clone()]).await;
+
+    // The header is stored as BCS and reads back identical.
+    let mut store = committer.store.clone();
+    let bytes = store.read(certificate.id.to_vec()).await.unwrap().unwrap();
+    let loaded = Header::read(&mut store, &certificate.id, StoreCodec::Bcs)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(bytes, bcs::to_bytes(&loaded).unwrap());
+    assert_eq!(loaded.id, certificate.id);
+    assert_eq!(loaded.round, 4);
+    assert_eq!(loaded.payload, vec![txn]);
+
+    // The committer reads it back with the same codec.
+    committer.send(vec![certificate]).await;
+    let receipt = wait_for("the transaction to commit", || receipts.get(&hash)).await;
+    assert!(receipt.kept);
+}
+
+#[tokio::test]
+async fn committer_boots_from_an_imported_snapshot() {
+    let snapshot = ".test_committer_boots_from_an_imported_snapshot.bcs";
+    let _ = fs::remove_file(snapshot);
+
+    // Export the state of a node that funded an account the committer does not pre-fund.
+    let mut sender = LocalAccount::generate(9).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let exporter = init_executor(AptosVmExecutor::new).unwrap();
+    exporter.bootstrap_account(&sender, 5_000).unwrap();
+    let balance = exporter.account_balance(sender.address).unwrap();
+    exporter
+        .export_snapshot(std::path::Path::new(snapshot))
+        .unwrap();
+
+    // Boot another executor from the snapshot: the imported balance is kept.
+    let executor = init_executor(|| {
+        AptosVmExecutor::from_snapshot(std::path::Path::new(snapshot), exporter.chain_id())
+    })
+    .unwrap();
+    assert_eq!(executor.account_balance(sender.address).unwrap(), balance);
+    assert_eq!(executor.state_root(), exporter.state_root());
+
+    // The account only exists in the imported state, yet its transfer executes.
+    let txn = transfer(&mut sender, recipient.address, 1_000);
+    let hash = txn.committed_hash().to_hex_literal();
+    let receipts = ExecutionReceipts::new(10);
+    let mut committer = TestCommitter::spawn(
+        "committer_boots_from_an_imported_snapshot",
+        executor,
+        receipts.clone(),
+        CommitterConfig::default(),
+    );
+    committer.commit(0, vec![txn]).await;
+
+    let receipt = wait_for("the transaction to commit", || receipts.get(&hash)).await;
+    assert!(receipt.kept);
+    assert!(receipt.status.contains("Executed"), "{}", receipt.status);
+}
+
+#[tokio::test]
+async fn injected_abort_is_reported_as_a_failed_transaction() {
+    // Two transfers that would both succeed, the second of which is forced to abort.
+    let recipient = LocalAccount::generate(2).unwrap();
+    let transactions: Vec<_> = [1, 3]
+        .into_iter()
+        .map(|seed| {
+            let mut sender = LocalAccount::generate(seed).unwrap();
+            transfer(&mut sender, recipient.address, 100)
+        })
+        .collect();
+    let hashes: Vec<_> = transactions
+        .iter()
+        .map(|txn| txn.committed_hash().to_hex_literal())
+        .collect();
+    let abort = VMStatus::MoveAbort {
+        location: aptos_types::vm_status::AbortLocation::Script,
+        code: 7,
+        message: None,
+    };
+    let failures = || aptos_executor::InjectedFailures::default().fail(1, abort.clone());
+
+    // The committer logs the first transaction as executed and the second as failed.
+    let mut executor = init_executor(AptosVmExecutor::new).unwrap();
+    executor.inject_failures(failures());
+    let results = executor.execute_block(&transactions);
+    let (level, message) = execution_log(
+        0,
+        &transactions[0],
+        TraceId::of(&transactions[0]),
+        &results[0],
+    );
+    assert_eq!(level, Level::Info);
+    assert!(message.starts_with("Executed transaction 0"), "{}", message);
+    let (level, message) = execution_log(
+        1,
+        &transactions[1],
+        TraceId::of(&transactions[1]),
+        &results[1],
+    );
+    assert_eq!(level, Level::Warn);
+    assert!(message.starts_with("Failed transaction 1"), "{}", message);
+    assert!(message.contains("MoveAbort"), "{}", message);
+
+    // Once committed, the forced abort is kept on chain and shows in the receipt.
+    let receipts = ExecutionReceipts::new(10);
+    let mut executor = init_executor(AptosVmExecutor::new).unwrap();
+    executor.inject_failures(failures());
+    let mut committer = TestCommitter::spawn(
+        "injected_abort_is_reported_as_a_failed_transaction",
+        executor,
+        receipts.clone(),
+        CommitterConfig::default(),
+    );
+    committer.commit(0, transactions).await;
+
+    let (executed, aborted) = wait_for("the transactions to commit", || {
+        Some((receipts.get(&hashes[0])?, receipts.get(&hashes[1])?))
+    })
+    .await;
+    assert!(executed.kept);
+    assert_eq!(executed.status, format!("{:?}", VMStatus::Executed));
+    assert!(aborted.kept);
+    assert_eq!(aborted.status, format!("{:?}", abort));
+}
+
+#[test]
+fn market_aborts_are_logged_with_their_message() {
+    use aptos_executor::AbortMessages;
+    use aptos_types::vm_status::AbortLocation;
+    use move_core_types::{account_address::AccountAddress, ident_str, language_storage::ModuleId};
+
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let transactions = vec![transfer(&mut sender, recipient.address, 100)];
+
+    // The transaction is forced to abort with `EORDER_NOT_FOUND` of the market package.
+    let package = AccountAddress::from_hex_literal("0xcafe").unwrap();
+    let market_setup = ModuleId::new(package, ident_str!("market_setup").to_owned());
+    let abort = VMStatus::MoveAbort {
+        location: AbortLocation::Module(market_setup.clone()),
+        code: 6,
+        message: None,
+    };
+    let mut executor = init_executor(AptosVmExecutor::new).unwrap();
+    executor.inject_failures(aptos_executor::InjectedFailures::default().fail(0, abort));
+    let mut messages = AbortMessages::default();
+    messages.insert(market_setup, 6, "order not found");
+    executor.register_abort_messages(messages);
+
+    let results = executor.execute_block(&transactions);
+    assert_eq!(results[0].abort_message(), Some("order not found"));
+    let (level, message) = execution_log(
+        0,
+        &transactions[0],
+        TraceId::of(&transactions[0]),
+        &results[0],
+    );
+    assert_eq!(level, Level::Warn);
+    assert!(message.contains("abort=\"order not found\""), "{}", message);
+}
+
+#[tokio::test]
+async fn chain_heads_are_attested_with_the_attestation_key() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng as _;
+
+    // The node has a consensus key and a separate attestation key.
+    let mut rng = StdRng::from_seed([0; 32]);
+    let (consensus_key, _) = crypto::generate_keypair(&mut rng);
+    let (attestation_key, attestation_secret) = crypto::generate_keypair(&mut rng);
+
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let receipts = ExecutionReceipts::new(10);
+    let config = CommitterConfig {
+        attester: Some(CommitAttester::new(attestation_key, attestation_secret)),
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "chain_heads_are_attested_with_the_attestation_key",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        config,
+    );
+    assert_eq!(receipts.attestation_key(), Some(attestation_key));
+    committer
+        .commit(1, vec![transfer(&mut sender, recipient.address, 100)])
+        .await;
+
+    let head = wait_for("the block to execute", || receipts.chain_head()).await;
+
+    // The head verifies against the attestation key only.
+    let attestation = head.attestation.clone().expect("The head is not attested");
+    assert_eq!(attestation.signer, attestation_key);
+    assert!(attestation.verify(&head, &attestation_key).is_ok());
+    assert!(attestation.verify(&head, &consensus_key).is_err());
+
+    // It does not verify for another head either.
+    let forged = ChainHead {
+        round: 2,
+        ..head.clone()
+    };
+    assert!(attestation.verify(&forged, &attestation_key).is_err());
+}
+
+#[tokio::test]
+async fn aborts_are_reported_by_sender() {
+    // One transfer of more APT than its sender holds, which aborts, and a valid one.
+    let mut aborting = LocalAccount::generate(1).unwrap();
+    let mut honest = LocalAccount::generate(2).unwrap();
+    let recipient = LocalAccount::generate(3).unwrap();
+    let payload = vec![
+        transfer(&mut aborting, recipient.address, u64::MAX),
+        transfer(&mut honest, recipient.address, 100),
+    ];
+
+    let (tx_abort_stats, mut rx_abort_stats) = channel(1);
+    let config = CommitterConfig {
+        tx_abort_stats: Some(tx_abort_stats),
+        ..CommitterConfig::default()
+    };
+    let mut committer = TestCommitter::spawn(
+        "aborts_are_reported_by_sender",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        ExecutionReceipts::new(10),
+        config,
+    );
+    committer.commit(0, payload).await;
+
+    // The proposer learns which sender aborted.
+    let stats = rx_abort_stats.recv().await.unwrap();
+    assert_eq!(stats.senders.len(), 2);
+    assert_eq!(
+        stats.senders[&aborting.address],
+        SenderOutcomes {
+            executed: 0,
+            aborted: 1
+        }
+    );
+    assert_eq!(
+        stats.senders[&honest.address],
+        SenderOutcomes {
+            executed: 1,
+            aborted: 0
+        }
+    );
+}
+
+/// Commits a header holding the same transfer twice and returns the number of transactions of the
+/// executed block, as written to the block output, along with the receipt of the transfer.
+async fn executed_block_size(name: &str, deduplicate: bool) -> (usize, ExecutionReceipt) {
+    let output = format!(".test_{}.jsonl", name);
+    let _ = fs::remove_file(&output);
+    let mut sender = LocalAccount::generate(1).unwrap();
+    let recipient = LocalAccount::generate(2).unwrap();
+    let txn = transfer(&mut sender, recipient.address, 100);
+    let hash = txn.committed_hash().to_hex_literal();
+
+    let config = CommitterConfig {
+        block_output: Some(output.clone().into()),
+        deduplicate,
+        ..CommitterConfig::default()
+    };
+    let receipts = ExecutionReceipts::new(10);
+    let mut committer = TestCommitter::spawn(
+        name,
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        config,
+    );
+    committer.commit(0, vec![txn.clone(), txn]).await;
+
+    // The summary of the block is written before the receipts are recorded: wait for both.
+    let (line, receipt) = wait_for("the block to execute", || {
+        Some((first_line(&output)?, receipts.get(&hash)?))
+    })
+    .await;
+    let summary: serde_json::Value = serde_json::from_str(&line).unwrap();
+    (summary["transactions"].as_array().unwrap().len(), receipt)
+}
+
+#[tokio::test]
+async fn duplicates_are_collapsed_when_deduplicating() {
+    assert!(CommitterConfig::default().deduplicate);
+    let (size, receipt) =
+        executed_block_size("duplicates_are_collapsed_when_deduplicating", true).await;
+    assert_eq!(size, 1);
+    assert!(receipt.kept);
+    assert_eq!(receipt.status, "Executed");
+}
+
+#[tokio::test]
+async fn duplicates_are_executed_without_deduplication() {
+    let (size, receipt) =
+        executed_block_size("duplicates_are_executed_without_deduplication", false).await;
+    assert_eq!(size, 2);
+    // The duplicate is discarded, but the receipt of the transfer is still the executed one.
+    assert!(receipt.kept);
+    assert_eq!(receipt.status, "Executed");
+}
+
+#[test]
+fn blocks_run_at_the_median_time_of_their_headers() {
+    // An author far ahead of (or behind) the others does not move the clock.
+    assert_eq!(
+        block_timestamp_usecs(vec![1_000, u64::MAX / 1_000, 2]),
+        1_000_000
+    );
+    assert_eq!(block_timestamp_usecs(vec![5]), 5_000);
+    assert_eq!(block_timestamp_usecs(Vec::new()), 0);
+}
+
+#[tokio::test]
+async fn empty_commits_advance_the_chain_head() {
+    // A header without transactions, committed at round 2.
+    let receipts = ExecutionReceipts::new(10);
+    let mut committer = TestCommitter::spawn(
+        "empty_commits_advance_the_chain_head",
+        init_executor(AptosVmExecutor::new).unwrap(),
+        receipts.clone(),
+        CommitterConfig::default(),
+    );
+    committer.commit(2, Vec::new()).await;
+
+    // The round of the head advances, but the chain is not extended.
+    let head = wait_for("the commit", || receipts.chain_head()).await;
+    assert_eq!(head.round, 2);
+    assert_eq!(head.block_hash, HashValue::zero().to_hex_literal());
+}
@@ -0,0 +1,73 @@
+use super::*;
+use config::{Authority, ConsensusAddresses, PrimaryAddresses};
+use crypto::generate_keypair;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use std::collections::{BTreeMap, HashSet};
+
+// Fixture
+fn committee() -> Committee {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let authorities: BTreeMap<_, _> = (0..4)
+        .map(|i| {
+            let (name, _) = generate_keypair(&mut rng);
+            let authority = Authority {
+                id: i,
+                bls_pubkey_g1: Default::default(),
+                bls_pubkey_g2: Default::default(),
+                is_honest: true,
+                stake: 1,
+                consensus: ConsensusAddresses {
+                    consensus_to_consensus: format!("127.0.0.1:{}", 100 + i).parse().unwrap(),
+                },
+                primary: PrimaryAddresses {
+                    primary_to_primary: format!("127.0.0.1:{}", 200 + i).parse().unwrap(),
+                    worker_to_primary: format!("127.0.0.1:{}", 300 + i).parse().unwrap(),
+                },
+                workers: HashMap::new(),
+            };
+            (name, authority)
+        })
+        .collect();
+    Committee {
+        authorities,
+        sorted_keys: Vec::new(),
+        combined_pubkey: Default::default(),
+        n: 4,
+        f: 1,
+        c: 0,
+        k: 0,
+        p: 0,
+        quorum_threshold: 3,
+        slow_commit_threshold: 3,
+        fast_commit_threshold: 4,
+        view_change_threshold: 3,
+        execution: Default::default(),
+    }
+}
+
+#[test]
+fn simple_elector_cycles_through_all_authorities() {
+    let committee = committee();
+    let schedule = LeaderElector::new(LeaderElectorKind::Simple, committee.clone()).unwrap();
+
+    // Every authority leads exactly once in every window of `n` consecutive rounds.
+    let n = committee.authorities.len() as Round;
+    let leaders: Vec<_> = (1..=n).map(|round| schedule.leader(round)).collect();
+    let unique: HashSet<_> = leaders.iter().collect();
+    assert_eq!(unique.len(), committee.authorities.len());
+
+    // The schedule then repeats itself.
+    for round in 1..=3 * n {
+        assert_eq!(schedule.leader(round), schedule.leader(round + n));
+    }
+}
+
+#[test]
+fn empty_committee_has_no_leader() {
+    let committee = Committee {
+        authorities: BTreeMap::new(),
+        ..committee()
+    };
+    assert!(LeaderElector::new(LeaderElectorKind::Simple, committee).is_err());
+}
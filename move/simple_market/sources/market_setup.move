@@ -16,6 +16,7 @@ module simple_market::market_setup {
     use aptos_experimental::market;
     use aptos_experimental::market_types;
     use aptos_experimental::order_book_types::{OrderIdType, TriggerCondition};
+    use simple_market::resting_orders;
     use simple_market::vault;
     use simple_market::coins::{BaseCoin, QuoteCoin};
 
@@ -24,6 +25,7 @@ module simple_market::market_setup {
     const EMARKET_NOT_FOUND: u64 = 3;
     const EPRICE_OVERFLOW: u64 = 5;
     const EORDER_NOT_FOUND: u64 = 6;
+    const EBATCH_LENGTH_MISMATCH: u64 = 7;
 
     struct OrderMetadata has store, copy, drop {
         market: address,
@@ -107,6 +109,7 @@ module simple_market::market_setup {
             },
         );
         vault::initialize(market_signer);
+        resting_orders::initialize(market_signer);
     }
 
     public entry fun place_limit_order(
@@ -152,6 +155,41 @@ module simple_market::market_setup {
         );
     }
 
+    /// Places several limit orders at once: either all of them are placed, or the transaction aborts.
+    /// The i-th order is described by the i-th element of every vector.
+    public entry fun place_limit_orders_with_client_ids(
+        trader: &signer,
+        market_signer: &signer,
+        limit_prices: vector<u64>,
+        sizes: vector<u64>,
+        is_bids: vector<bool>,
+        client_order_ids: vector<u64>,
+    ) acquires MarketStore {
+        let count = vector::length(&limit_prices);
+        assert!(
+            vector::length(&sizes) == count
+                && vector::length(&is_bids) == count
+                && vector::length(&client_order_ids) == count,
+            EBATCH_LENGTH_MISMATCH,
+        );
+        let market_address = signer::address_of(market_signer);
+        assert!(exists<MarketStore>(market_address), EMARKET_NOT_FOUND);
+        let market_store = borrow_global_mut<MarketStore>(market_address);
+        let i = 0;
+        while (i < count) {
+            place_limit_order_internal(
+                market_address,
+                market_store,
+                trader,
+                *vector::borrow(&limit_prices, i),
+                *vector::borrow(&sizes, i),
+                *vector::borrow(&is_bids, i),
+                option::some(*vector::borrow(&client_order_ids, i)),
+            );
+            i = i + 1;
+        };
+    }
+
     public entry fun cancel_order_by_client_id(
         trader: &signer,
         market_signer: &signer,
@@ -160,13 +198,7 @@ module simple_market::market_setup {
         let market_address = signer::address_of(market_signer);
         assert!(exists<MarketStore>(market_address), EMARKET_NOT_FOUND);
         let market_store = borrow_global_mut<MarketStore>(market_address);
-        let callbacks = new_demo_callbacks();
-        market::cancel_order_with_client_id(
-            &mut market_store.market,
-            trader,
-            client_order_id,
-            &callbacks,
-        );
+        cancel_resting_order(market_address, market_store, trader, client_order_id);
     }
 
     public entry fun decrease_order_size_by_client_id(
@@ -194,6 +226,29 @@ module simple_market::market_setup {
             size_delta,
             &callbacks,
         );
+        resting_orders::decrease(market_address, order_id, size_delta);
+    }
+
+    fun cancel_resting_order(
+        market_address: address,
+        market_store: &mut MarketStore,
+        trader: &signer,
+        client_order_id: u64,
+    ) {
+        let order_id_option = market_store
+            .market
+            .get_order_book()
+            .get_order_id_by_client_id(signer::address_of(trader), client_order_id);
+        let callbacks = new_demo_callbacks();
+        market::cancel_order_with_client_id(
+            &mut market_store.market,
+            trader,
+            client_order_id,
+            &callbacks,
+        );
+        if (option::is_some(&order_id_option)) {
+            resting_orders::remove(market_address, option::destroy_some(order_id_option));
+        };
     }
 
     public entry fun replace_order_by_client_id(
@@ -207,13 +262,7 @@ module simple_market::market_setup {
         let market_address = signer::address_of(market_signer);
         assert!(exists<MarketStore>(market_address), EMARKET_NOT_FOUND);
         let market_store = borrow_global_mut<MarketStore>(market_address);
-        let callbacks = new_demo_callbacks();
-        market::cancel_order_with_client_id(
-            &mut market_store.market,
-            trader,
-            client_order_id,
-            &callbacks,
-        );
+        cancel_resting_order(market_address, market_store, trader, client_order_id);
         place_limit_order_internal(
             market_address,
             market_store,
@@ -275,7 +324,7 @@ module simple_market::market_setup {
         taker: address,
         _taker_order_id: OrderIdType,
         maker: address,
-        _maker_order_id: OrderIdType,
+        maker_order_id: OrderIdType,
         _fill_id: u64,
         is_taker_long: bool,
         price: u64,
@@ -309,6 +358,7 @@ module simple_market::market_setup {
             coin::deposit<BaseCoin>(maker, base_payment);
             coin::deposit<QuoteCoin>(taker, quote_delivery);
         };
+        resting_orders::decrease(market_addr, maker_order_id, size);
 
         market_types::new_settle_trade_result(
             size,
@@ -330,13 +380,15 @@ module simple_market::market_setup {
     }
 
     fun place_maker_order_callback(
-        _account: address,
-        _order_id: OrderIdType,
-        _is_bid: bool,
-        _price: u64,
-        _size: u64,
-        _metadata: OrderMetadata,
-    ) {}
+        account: address,
+        order_id: OrderIdType,
+        is_bid: bool,
+        price: u64,
+        size: u64,
+        metadata: OrderMetadata,
+    ) {
+        resting_orders::add(metadata.market, order_id, account, is_bid, price, size);
+    }
 
     fun cleanup_order_callback(
         _account: address,
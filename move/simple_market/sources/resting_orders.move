@@ -0,0 +1,106 @@
+module simple_market::resting_orders {
+    use std::signer;
+    use std::vector;
+    use aptos_experimental::order_book_types::OrderIdType;
+
+    friend simple_market::market_setup;
+
+    /// An order resting on the book of a market.
+    struct Order has store, copy, drop {
+        order_id: OrderIdType,
+        trader: address,
+        price: u64,
+        /// The remaining size of the order.
+        size: u64,
+    }
+
+    /// The resting orders of a market, in the order they were placed. The market keeps its book in
+    /// tables; this copy lets clients read the whole book from a single resource.
+    struct OrderBook has key {
+        bids: vector<Order>,
+        asks: vector<Order>,
+    }
+
+    public(friend) fun initialize(market_signer: &signer) {
+        if (!exists<OrderBook>(signer::address_of(market_signer))) {
+            move_to(
+                market_signer,
+                OrderBook {
+                    bids: vector::empty<Order>(),
+                    asks: vector::empty<Order>(),
+                },
+            );
+        };
+    }
+
+    /// Records an order placed on the book with its remaining size.
+    public(friend) fun add(
+        market_addr: address,
+        order_id: OrderIdType,
+        trader: address,
+        is_bid: bool,
+        price: u64,
+        size: u64,
+    ) acquires OrderBook {
+        let book = borrow_global_mut<OrderBook>(market_addr);
+        let order = Order { order_id, trader, price, size };
+        if (is_bid) {
+            vector::push_back(&mut book.bids, order);
+        } else {
+            vector::push_back(&mut book.asks, order);
+        };
+    }
+
+    /// Decreases the size of a resting order (e.g. once it is partially filled), removing it once
+    /// nothing is left. Orders that are not resting are ignored.
+    public(friend) fun decrease(
+        market_addr: address,
+        order_id: OrderIdType,
+        size_delta: u64,
+    ) acquires OrderBook {
+        let book = borrow_global_mut<OrderBook>(market_addr);
+        if (!decrease_in(&mut book.bids, order_id, size_delta)) {
+            decrease_in(&mut book.asks, order_id, size_delta);
+        };
+    }
+
+    /// Removes a resting order, e.g. once it is cancelled. Orders that are not resting are ignored.
+    public(friend) fun remove(market_addr: address, order_id: OrderIdType) acquires OrderBook {
+        let book = borrow_global_mut<OrderBook>(market_addr);
+        let (found, i) = find(&book.bids, order_id);
+        if (found) {
+            vector::remove(&mut book.bids, i);
+            return
+        };
+        let (found, i) = find(&book.asks, order_id);
+        if (found) {
+            vector::remove(&mut book.asks, i);
+        };
+    }
+
+    fun decrease_in(orders: &mut vector<Order>, order_id: OrderIdType, size_delta: u64): bool {
+        let (found, i) = find(orders, order_id);
+        if (!found) {
+            return false
+        };
+        let size = vector::borrow(orders, i).size;
+        if (size <= size_delta) {
+            vector::remove(orders, i);
+        } else {
+            vector::borrow_mut(orders, i).size = size - size_delta;
+        };
+        true
+    }
+
+    fun find(orders: &vector<Order>, order_id: OrderIdType): (bool, u64) {
+        let i = 0;
+        let count = vector::length(orders);
+        while (i < count) {
+            if (vector::borrow(orders, i).order_id == order_id) {
+                return (true, i)
+            };
+            i = i + 1;
+        };
+        (false, 0)
+    }
+}
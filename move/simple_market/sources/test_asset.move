@@ -0,0 +1,27 @@
+module simple_market::test_asset {
+    use std::option;
+    use std::signer;
+    use std::string;
+    use aptos_framework::fungible_asset;
+    use aptos_framework::object;
+    use aptos_framework::primary_fungible_store;
+
+    /// Seed of the named object holding the metadata of the asset, under its creator.
+    const ASSET_SEED: vector<u8> = b"TEST_ASSET";
+
+    /// Creates a fungible asset owned by `creator` and mints `amount` of it into their primary store.
+    public entry fun create_and_mint(creator: &signer, amount: u64) {
+        let constructor_ref = &object::create_named_object(creator, ASSET_SEED);
+        primary_fungible_store::create_primary_store_enabled_fungible_asset(
+            constructor_ref,
+            option::none(),
+            string::utf8(b"Test Asset"),
+            string::utf8(b"TEST"),
+            6,
+            string::utf8(b""),
+            string::utf8(b""),
+        );
+        let mint_ref = fungible_asset::generate_mint_ref(constructor_ref);
+        primary_fungible_store::mint(&mint_ref, signer::address_of(creator), amount);
+    }
+}
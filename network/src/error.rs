@@ -11,6 +11,9 @@ pub enum NetworkError {
     #[error("Failed to accept connection: {0}")]
     FailedToListen(std::io::Error),
 
+    #[error("Refusing connection from {0}: already handling {1} connections")]
+    TooManyConnections(SocketAddr, usize),
+
     #[error("Failed to send message to {0}: {1}")]
     FailedToSendMessage(SocketAddr, std::io::Error),
 
@@ -7,6 +7,9 @@ use futures::stream::StreamExt as _;
 use log::{debug, info, warn};
 use std::error::Error;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt as _;
 use tokio::net::{TcpListener, TcpStream};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
@@ -26,6 +29,32 @@ pub trait MessageHandler: Clone + Send + Sync + 'static {
     async fn dispatch(&self, writer: &mut Writer, message: Bytes) -> Result<(), Box<dyn Error>>;
 }
 
+/// The number of connections currently handled by a `Receiver`. Clones share the same counter.
+#[derive(Clone, Default)]
+pub struct ActiveConnections(Arc<AtomicUsize>);
+
+impl ActiveConnections {
+    /// Returns the number of open connections.
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Counts a new connection until the returned guard is dropped.
+    fn open(&self) -> ConnectionGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard(self.clone())
+    }
+}
+
+/// Stops counting a connection once its runner exits.
+struct ConnectionGuard(ActiveConnections);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        (self.0).0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 /// For each incoming request, we spawn a new runner responsible to receive messages and forward them
 /// through the provided deliver channel.
 pub struct Receiver<Handler: MessageHandler> {
@@ -33,14 +62,36 @@ pub struct Receiver<Handler: MessageHandler> {
     address: SocketAddr,
     /// Struct responsible to define how to handle received messages.
     handler: Handler,
+    /// The maximum number of connections handled at the same time, if any.
+    max_connections: Option<usize>,
+    /// The connections currently handled.
+    active_connections: ActiveConnections,
 }
 
 impl<Handler: MessageHandler> Receiver<Handler> {
     /// Spawn a new network receiver handling connections from any incoming peer.
     pub fn spawn(address: SocketAddr, handler: Handler) {
+        Self::spawn_with_connection_limit(address, handler, None);
+    }
+
+    /// Spawn a new network receiver that closes incoming connections straight away once it already
+    /// handles `max_connections` connections. Returns a handle on the number of open connections.
+    pub fn spawn_with_connection_limit(
+        address: SocketAddr,
+        handler: Handler,
+        max_connections: Option<usize>,
+    ) -> ActiveConnections {
+        let active_connections = ActiveConnections::default();
+        let receiver = Self {
+            address,
+            handler,
+            max_connections,
+            active_connections: active_connections.clone(),
+        };
         tokio::spawn(async move {
-            Self { address, handler }.run().await;
+            receiver.run().await;
         });
+        active_connections
     }
 
     /// Main loop responsible to accept incoming connections and spawn a new runner to handle it.
@@ -51,22 +102,39 @@ impl<Handler: MessageHandler> Receiver<Handler> {
 
         debug!("Listening on {}", self.address);
         loop {
-            let (socket, peer) = match listener.accept().await {
+            let (mut socket, peer) = match listener.accept().await {
                 Ok(value) => value,
                 Err(e) => {
                     warn!("{}", NetworkError::FailedToListen(e));
                     continue;
                 }
             };
-            info!("Incoming connection established with {}", peer);
-            Self::spawn_runner(socket, peer, self.handler.clone()).await;
+            let active = self.active_connections.get();
+            if self.max_connections.map_or(false, |max| active >= max) {
+                warn!("{}", NetworkError::TooManyConnections(peer, active));
+                let _ = socket.shutdown().await;
+                continue;
+            }
+            let guard = self.active_connections.open();
+            info!(
+                "Incoming connection established with {} ({} active connections)",
+                peer,
+                active + 1
+            );
+            Self::spawn_runner(socket, peer, self.handler.clone(), guard).await;
         }
     }
 
     /// Spawn a new runner to handle a specific TCP connection. It receives messages and process them
     /// using the provided handler.
-    async fn spawn_runner(socket: TcpStream, peer: SocketAddr, handler: Handler) {
+    async fn spawn_runner(
+        socket: TcpStream,
+        peer: SocketAddr,
+        handler: Handler,
+        guard: ConnectionGuard,
+    ) {
         tokio::spawn(async move {
+            let _guard = guard;
             let _ = socket.set_nodelay(true);
             let mut codec = LengthDelimitedCodec::new();
             codec.set_max_frame_length(320 * 1000 * 1000);
@@ -24,6 +24,26 @@ pub mod reliable_sender_tests;
 /// Convenient alias for cancel handlers returned to the caller task.
 pub type CancelHandler = oneshot::Receiver<Bytes>;
 
+/// How a `ReliableSender` retries to connect to unreachable peers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The initial delay to wait before re-attempting a connection (in ms). The delay doubles after
+    /// each failed attempt.
+    pub retry_delay: u64,
+    /// The number of consecutive failed connection attempts after which the messages pending for the
+    /// peer are dropped (which closes their cancel handlers). Retries forever if `None`.
+    pub max_retries: Option<u64>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retry_delay: 200,
+            max_retries: None,
+        }
+    }
+}
+
 /// We keep alive one TCP connection per peer, each connection is handled by a separate task (called `Connection`).
 /// We communicate with our 'connections' through a dedicated channel kept by the HashMap called `connections`.
 /// This sender is 'reliable' in the sense that it keeps trying to re-transmit messages for which it didn't
@@ -34,6 +54,8 @@ pub struct ReliableSender {
     connections: HashMap<SocketAddr, Sender<InnerMessage>>,
     /// Small RNG just used to shuffle nodes and randomize connections (not crypto related).
     rng: SmallRng,
+    /// How our connections retry to reach their peer.
+    retry_policy: RetryPolicy,
     // TODO: Remove
     sent: u64,
 }
@@ -46,18 +68,23 @@ impl std::default::Default for ReliableSender {
 
 impl ReliableSender {
     pub fn new() -> Self {
+        Self::with_retry_policy(RetryPolicy::default())
+    }
+
+    pub fn with_retry_policy(retry_policy: RetryPolicy) -> Self {
         Self {
             connections: HashMap::new(),
             rng: SmallRng::from_entropy(),
+            retry_policy,
             // TODO: Remove
             sent: 0,
         }
     }
 
     /// Helper function to spawn a new connection.
-    fn spawn_connection(address: SocketAddr) -> Sender<InnerMessage> {
+    fn spawn_connection(address: SocketAddr, retry_policy: RetryPolicy) -> Sender<InnerMessage> {
         let (tx, rx) = channel(1_000);
-        Connection::spawn(address, rx);
+        Connection::spawn(address, rx, retry_policy);
         tx
     }
 
@@ -67,9 +94,10 @@ impl ReliableSender {
         self.sent += 1;
 
         let (sender, receiver) = oneshot::channel();
+        let retry_policy = self.retry_policy;
         self.connections
             .entry(address)
-            .or_insert_with(|| Self::spawn_connection(address))
+            .or_insert_with(|| Self::spawn_connection(address, retry_policy))
             .send(InnerMessage {
                 data,
                 cancel_handler: sender,
@@ -133,17 +161,20 @@ struct Connection {
     receiver: Receiver<InnerMessage>,
     /// The initial delay to wait before re-attempting a connection (in ms).
     retry_delay: u64,
+    /// The number of failed connection attempts after which we drop the buffered messages.
+    max_retries: Option<u64>,
     /// Buffer keeping all messages that need to be re-transmitted.
     buffer: VecDeque<(Bytes, oneshot::Sender<Bytes>)>,
 }
 
 impl Connection {
-    fn spawn(address: SocketAddr, receiver: Receiver<InnerMessage>) {
+    fn spawn(address: SocketAddr, receiver: Receiver<InnerMessage>, retry_policy: RetryPolicy) {
         tokio::spawn(async move {
             Self {
                 address,
                 receiver,
-                retry_delay: 200,
+                retry_delay: retry_policy.retry_delay,
+                max_retries: retry_policy.max_retries,
                 buffer: VecDeque::new(),
             }
             .run()
@@ -181,6 +212,11 @@ impl Connection {
                             () = &mut timer => {
                                 delay = min(2*delay, 60_000);
                                 retry +=1;
+                                if self.max_retries.map_or(false, |max| u64::from(retry) >= max) {
+                                    self.give_up(retry);
+                                    delay = self.retry_delay;
+                                    retry = 0;
+                                }
                                 break 'waiter;
                             },
 
@@ -197,6 +233,20 @@ impl Connection {
         }
     }
 
+    /// Drop all the messages we failed to deliver. Their cancel handlers are closed, notifying the caller
+    /// task. We still try to deliver the messages we receive later on.
+    fn give_up(&mut self, retry: u16) {
+        if !self.buffer.is_empty() {
+            warn!(
+                "Dropping {} messages for {} after {} failed connection attempts",
+                self.buffer.len(),
+                self.address,
+                retry
+            );
+            self.buffer.clear();
+        }
+    }
+
     /// Transmit messages once we have established a connection.
     async fn keep_alive(&mut self, stream: TcpStream) -> NetworkError {
         // This buffer keeps all messages and handlers that we have successfully transmitted but for
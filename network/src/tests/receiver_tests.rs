@@ -46,3 +46,50 @@ async fn receive() {
     let received = message.unwrap();
     assert_eq!(received, sent);
 }
+
+#[tokio::test]
+async fn refuse_connections_beyond_limit() {
+    // Make the network receiver.
+    let address = "127.0.0.1:4100".parse::<SocketAddr>().unwrap();
+    let (tx, mut rx) = channel(1);
+    let active =
+        Receiver::spawn_with_connection_limit(address, TestHandler { deliver: tx }, Some(2));
+    sleep(Duration::from_millis(50)).await;
+
+    // Open as many connections as allowed.
+    let sent = "Hello, world!";
+    let bytes = Bytes::from(bincode::serialize(sent).unwrap());
+    let mut transports = Vec::new();
+    for _ in 0..2 {
+        let stream = TcpStream::connect(address).await.unwrap();
+        let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+        transport.send(bytes.clone()).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap(), sent);
+        transports.push(transport);
+    }
+    assert_eq!(active.get(), 2);
+
+    // The next connection is closed without being served.
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut extra = Framed::new(stream, LengthDelimitedCodec::new());
+    let _ = extra.send(bytes.clone()).await;
+    assert!(matches!(extra.next().await, None | Some(Err(_))));
+    assert_eq!(active.get(), 2);
+
+    // The existing connections keep working.
+    for transport in &mut transports {
+        transport.send(bytes.clone()).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap(), sent);
+    }
+
+    // Closed connections stop being counted, and free a slot for a new one.
+    drop(transports.pop());
+    while active.get() != 1 {
+        sleep(Duration::from_millis(10)).await;
+    }
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+    transport.send(bytes.clone()).await.unwrap();
+    assert_eq!(rx.recv().await.unwrap(), sent);
+    assert_eq!(active.get(), 2);
+}
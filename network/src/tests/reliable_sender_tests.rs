@@ -65,3 +65,19 @@ async fn retry() {
     // Ensure the server received the message (ie. it did not panic).
     assert!(handle.await.is_ok());
 }
+
+#[tokio::test]
+async fn give_up() {
+    // Make a network sender that quickly gives up on unreachable peers (no listeners are running).
+    let address = "127.0.0.1:5400".parse::<SocketAddr>().unwrap();
+    let retry_policy = RetryPolicy {
+        retry_delay: 10,
+        max_retries: Some(2),
+    };
+    let mut sender = ReliableSender::with_retry_policy(retry_policy);
+    let cancel_handler = sender.send(address, Bytes::from("Hello, world!")).await;
+
+    // Ensure the message is dropped rather than retried forever.
+    let result = tokio::time::timeout(Duration::from_millis(1_000), cancel_handler).await;
+    assert!(matches!(result, Ok(Err(_))));
+}
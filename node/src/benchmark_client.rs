@@ -1,29 +1,68 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use aptos_executor::{transaction_builder::apt_transfer, LocalAccount};
+use aptos_types::account_address::AccountAddress;
 use aptos_types::chain_id::ChainId;
+use aptos_types::transaction::SignedTransaction;
 use bytes::Bytes;
 use clap::{crate_name, crate_version, App, AppSettings};
+use config::{Comm, ExecutionConfig, Import as _, WorkerId};
 use env_logger::Env;
-use futures::future::join_all;
+use futures::future::{join_all, try_join_all};
 use futures::sink::SinkExt as _;
-use log::{info, warn};
+use hyper::{body, Client as HttpClient, StatusCode};
+use log::{debug, info, warn};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use std::cmp::max;
 use std::net::SocketAddr;
+use std::path::Path;
 use tokio::net::TcpStream;
-use tokio::time::{interval, sleep, Duration, Instant};
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::time::{interval, sleep, sleep_until, timeout, Duration, Instant};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
+/// The worker whose transactions addresses are targeted when loading a committee file.
+const WORKER_ID: WorkerId = 0;
+
+#[cfg(test)]
+#[path = "tests/benchmark_client_tests.rs"]
+mod benchmark_client_tests;
+
+/// The number of submissions waiting for their artificial delay to elapse, per target.
+const DELAYED_SUBMISSIONS_CAPACITY: usize = 10_000;
+
+/// How often the status of a node is polled while waiting for it to be ready.
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The reply of the transaction API to an account query.
+#[derive(Deserialize)]
+struct AccountResponse {
+    sequence_number: u64,
+}
+
+/// The reply of the transaction API to a status query.
+#[derive(Deserialize)]
+struct StatusResponse {
+    executed_round: Option<u64>,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let matches = App::new(crate_name!())
         .version(crate_version!())
         .about("Benchmark client for Narwhal and Tusk.")
-        .args_from_usage("<ADDR> 'The network address of the node where to send txs'")
+        .args_from_usage("[ADDR] 'The network address of the node where to send txs'")
+        .args_from_usage("--committee=[FILE] 'A committee file; txs are sent to the transactions address of every worker, for the chain of the committee'")
         .args_from_usage("--size=<INT> 'The size of each transaction in bytes'")
         .args_from_usage("--burst=<INT> 'Burst duration (in ms)'")
         .args_from_usage("--rate=<INT> 'The rate (txs/s) at which to send the transactions'")
         .args_from_usage("--nodes=[ADDR]... 'Network addresses that must be reachable before starting the benchmark.'")
+        .args_from_usage("--api=[ADDR]... 'Transaction API addresses from which to fetch the sequence number of the sender'")
+        .args_from_usage("--target-latency-ms=[INT] 'Artificial latency (in ms) added to the submissions to every target'")
+        .args_from_usage("--jitter-ms=[INT] 'Maximum deviation (in ms) from the artificial latency, drawn uniformly for every submission'")
+        .args_from_usage("--wait-timeout-secs=[INT] 'How long (in s) to wait for the nodes to be ready before giving up (default 120)'")
         .setting(AppSettings::ArgRequiredElseHelp)
         .get_matches();
 
@@ -31,11 +70,25 @@ async fn main() -> Result<()> {
         .format_timestamp_millis()
         .init();
 
-    let target = matches
-        .value_of("ADDR")
-        .unwrap()
-        .parse::<SocketAddr>()
-        .context("Invalid socket address format")?;
+    // Without a committee file, the nodes are assumed to run the default chain.
+    let (mut targets, mut apis, chain_id) = match matches.value_of("committee") {
+        Some(path) => load_committee(Path::new(path))?,
+        None => (
+            Vec::new(),
+            Vec::new(),
+            ChainId::new(ExecutionConfig::default().chain_id),
+        ),
+    };
+    if let Some(target) = matches.value_of("ADDR") {
+        targets.push(
+            target
+                .parse::<SocketAddr>()
+                .context("Invalid socket address format")?,
+        );
+    }
+    if targets.is_empty() {
+        bail!("No target: specify the address of a node or a committee file");
+    }
     let size = matches
         .value_of("size")
         .unwrap()
@@ -51,15 +104,50 @@ async fn main() -> Result<()> {
         .unwrap()
         .parse::<u64>()
         .context("The rate of transactions must be a non-negative integer")?;
-    let nodes = matches
+    let mut nodes = matches
         .values_of("nodes")
         .unwrap_or_default()
         .into_iter()
         .map(|x| x.parse::<SocketAddr>())
         .collect::<Result<Vec<_>, _>>()
         .context("Invalid socket address format")?;
+    let link_delay = LinkDelay {
+        latency: Duration::from_millis(
+            matches
+                .value_of("target-latency-ms")
+                .unwrap_or("0")
+                .parse::<u64>()
+                .context("The target latency must be a non-negative integer")?,
+        ),
+        jitter: Duration::from_millis(
+            matches
+                .value_of("jitter-ms")
+                .unwrap_or("0")
+                .parse::<u64>()
+                .context("The jitter must be a non-negative integer")?,
+        ),
+    };
+    let wait_timeout = Duration::from_secs(
+        matches
+            .value_of("wait-timeout-secs")
+            .unwrap_or("120")
+            .parse::<u64>()
+            .context("The wait timeout must be a non-negative integer")?,
+    );
+    // Every target must be reachable before starting the benchmark.
+    nodes.extend(targets.iter().copied());
+    apis.extend(
+        matches
+            .values_of("api")
+            .unwrap_or_default()
+            .map(|x| x.parse::<SocketAddr>())
+            .collect::<Result<Vec<_>, _>>()
+            .context("Invalid socket address format")?,
+    );
 
-    info!("Node address: {}", target);
+    for target in &targets {
+        info!("Node address: {}", target);
+    }
 
     // NOTE: This log entry is used to compute performance.
     info!("Requested transaction size: {} B", size);
@@ -67,7 +155,14 @@ async fn main() -> Result<()> {
     // NOTE: This log entry is used to compute performance.
     info!("Transactions rate: {} tx/s", rate);
 
-    let chain_id = ChainId::test();
+    if !link_delay.is_zero() {
+        info!(
+            "Delaying submissions by {:?} (jitter {:?})",
+            link_delay.latency, link_delay.jitter
+        );
+    }
+
+    info!("Chain id: {}", chain_id);
     let transfer_amount = 1u64;
 
     let recipient = LocalAccount::generate(2).context("failed to create recipient account")?;
@@ -91,7 +186,7 @@ async fn main() -> Result<()> {
     let sender = LocalAccount::generate(1).context("failed to create sender account")?;
 
     let mut client = Client {
-        target,
+        targets,
         rate,
         nodes,
         burst_duration,
@@ -100,17 +195,136 @@ async fn main() -> Result<()> {
         chain_id,
         transfer_amount,
         tx_size_bytes,
+        link_delay,
+        wait_timeout,
     };
 
-    // Wait for all nodes to be online and synchronized.
-    client.wait().await;
+    // Wait for all nodes to be online and serving their transaction API.
+    client.wait(&apis).await?;
+
+    // Resume from the sequence number of the sender, which may have sent transactions before a restart.
+    client.resume(&apis).await?;
 
     // Start the benchmark.
     client.send().await.context("Failed to submit transactions")
 }
 
+/// Loads the addresses on which the workers of the committee receive client transactions, the
+/// addresses of their transaction API (if any), and the id of the chain they run.
+fn load_committee(path: &Path) -> Result<(Vec<SocketAddr>, Vec<SocketAddr>, ChainId)> {
+    let comm = Comm::import(path.to_str().unwrap())
+        .with_context(|| format!("failed to import committee from {}", path.display()))?;
+    let apis = comm
+        .authorities
+        .values()
+        .filter_map(|authority| authority.workers.get(&WORKER_ID).and_then(|x| x.api))
+        .collect();
+    if comm.execution.chain_id == 0 {
+        bail!("invalid chain id 0 in {}", path.display());
+    }
+    Ok((
+        comm.transactions_addresses(&WORKER_ID),
+        apis,
+        ChainId::new(comm.execution.chain_id),
+    ))
+}
+
+/// Fetches the sequence number of the next transaction of an account from a transaction API.
+async fn fetch_sequence_number(api: SocketAddr, address: AccountAddress) -> Result<u64> {
+    let uri = format!("http://{}/accounts/{}", api, address.to_hex_literal());
+    let account: AccountResponse = get_json(&uri).await?;
+    Ok(account.sequence_number)
+}
+
+/// Fetches the round of the last block executed by a node from its transaction API.
+async fn fetch_executed_round(api: SocketAddr) -> Result<Option<u64>> {
+    let uri = format!("http://{}/status", api);
+    let status: StatusResponse = get_json(&uri).await?;
+    Ok(status.executed_round)
+}
+
+/// Polls the status of a node until its transaction API answers. The executed round is only logged:
+/// an idle node does not execute blocks, so waiting for the round to advance could hang forever.
+async fn wait_until_ready(api: SocketAddr) {
+    loop {
+        match fetch_executed_round(api).await {
+            Ok(round) => {
+                debug!("Node {} is ready (executed round {:?})", api, round);
+                return;
+            }
+            Err(e) => debug!("Node {} is not ready: {:#}", api, e),
+        }
+        sleep(READINESS_POLL_INTERVAL).await;
+    }
+}
+
+/// Sends a GET request to the transaction API and decodes its JSON reply.
+async fn get_json<T: DeserializeOwned>(uri: &str) -> Result<T> {
+    let response = HttpClient::new()
+        .get(uri.parse().context("invalid URI")?)
+        .await
+        .context("failed to query the transaction API")?;
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body())
+        .await
+        .context("failed to read response body")?;
+    if status != StatusCode::OK {
+        bail!(
+            "unexpected HTTP status {}: {}",
+            status,
+            String::from_utf8_lossy(&bytes)
+        );
+    }
+    serde_json::from_slice(&bytes).context("malformed response")
+}
+
+/// Artificial network conditions between the client and every target, to emulate geo-distributed
+/// nodes.
+#[derive(Clone, Copy, Debug, Default)]
+struct LinkDelay {
+    latency: Duration,
+    /// The delay of every submission deviates from the latency by at most this much.
+    jitter: Duration,
+}
+
+impl LinkDelay {
+    fn is_zero(&self) -> bool {
+        self.latency.as_nanos() == 0 && self.jitter.as_nanos() == 0
+    }
+
+    /// Draws the delay of a submission, uniformly within the jitter around the latency.
+    fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        let jitter = self.jitter.as_micros() as i64;
+        if jitter == 0 {
+            return self.latency;
+        }
+        let delay = self.latency.as_micros() as i64 + rng.gen_range(-jitter, jitter + 1);
+        Duration::from_micros(delay.max(0) as u64)
+    }
+}
+
+/// Sends every submission to the target once its due time is reached. Submissions are sent in order,
+/// so a submission with a short delay may wait for the previous one, as on a TCP connection. The
+/// returned channel closes once the target is unreachable.
+fn spawn_delayed_transport(
+    mut transport: Framed<TcpStream, LengthDelimitedCodec>,
+) -> Sender<(Bytes, Instant)> {
+    let (tx, mut rx) = channel::<(Bytes, Instant)>(DELAYED_SUBMISSIONS_CAPACITY);
+    tokio::spawn(async move {
+        while let Some((bytes, due)) = rx.recv().await {
+            sleep_until(due).await;
+            if let Err(e) = transport.send(bytes).await {
+                warn!("Failed to send transaction: {}", e);
+                break;
+            }
+        }
+    });
+    tx
+}
+
 struct Client {
-    target: SocketAddr,
+    /// Every transaction is sent to all of these addresses.
+    targets: Vec<SocketAddr>,
     rate: u64,
     nodes: Vec<SocketAddr>,
     burst_duration: u64,
@@ -119,9 +333,54 @@ struct Client {
     chain_id: ChainId,
     transfer_amount: u64,
     tx_size_bytes: usize,
+    /// The artificial delay of the submissions to every target.
+    link_delay: LinkDelay,
+    /// How long to wait for the nodes to be ready before giving up.
+    wait_timeout: Duration,
 }
 
 impl Client {
+    /// Starts from the sequence number the nodes expect from the sender, rather than from zero. Nodes
+    /// may lag behind, so the highest sequence number reported by the APIs is used.
+    pub async fn resume(&mut self, apis: &[SocketAddr]) -> Result<()> {
+        if apis.is_empty() {
+            warn!(
+                "No transaction API to fetch the sequence number of the sender from, starting at 0"
+            );
+            return Ok(());
+        }
+        let mut sequence = None;
+        for api in apis {
+            match fetch_sequence_number(*api, self.sender.address).await {
+                Ok(x) => sequence = sequence.max(Some(x)),
+                Err(e) => warn!(
+                    "Failed to fetch the sequence number of the sender from {}: {}",
+                    api, e
+                ),
+            }
+        }
+        match sequence {
+            Some(sequence) => {
+                info!("Resuming at sequence number {}", sequence);
+                self.sender.sequence_number = sequence;
+                Ok(())
+            }
+            None => {
+                bail!("Failed to fetch the sequence number of the sender from any transaction API")
+            }
+        }
+    }
+
+    /// Builds the next transaction of the sender, bumping its sequence number.
+    fn next_transaction(&mut self) -> Result<SignedTransaction> {
+        apt_transfer(
+            &mut self.sender,
+            self.recipient.address,
+            self.transfer_amount,
+            self.chain_id,
+        )
+    }
+
     pub async fn send(&mut self) -> Result<()> {
         const PRECISION: u64 = 20; // Sample precision.
         info!("Burst duration {:?}", self.burst_duration);
@@ -131,15 +390,24 @@ impl Client {
             return Ok(());
         }
 
-        // Connect to the mempool.
-        let stream = TcpStream::connect(self.target)
-            .await
-            .context(format!("failed to connect to {}", self.target))?;
+        // Connect to the mempool of every target.
+        let mut transports = try_join_all(self.targets.iter().map(|target| async move {
+            TcpStream::connect(*target)
+                .await
+                .map(|stream| Framed::new(stream, LengthDelimitedCodec::new()))
+                .context(format!("failed to connect to {}", target))
+        }))
+        .await?;
+        let delayed_transports: Vec<_> = if self.link_delay.is_zero() {
+            Vec::new()
+        } else {
+            transports.drain(..).map(spawn_delayed_transport).collect()
+        };
+        let mut rng = rand::thread_rng();
 
         // Submit all transactions.
         let burst = max(1, self.rate / PRECISION);
         let mut counter: u64 = 0;
-        let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
         let interval = interval(Duration::from_millis(self.burst_duration));
         tokio::pin!(interval);
 
@@ -161,16 +429,19 @@ impl Client {
                     );
                 }
 
-                let txn = apt_transfer(
-                    &mut self.sender,
-                    self.recipient.address,
-                    self.transfer_amount,
-                    self.chain_id,
-                )?;
-                let bytes = bcs::to_bytes(&txn)?;
-                if let Err(e) = transport.send(Bytes::from(bytes)).await {
-                    warn!("Failed to send transaction: {}", e);
-                    break 'main;
+                let txn = self.next_transaction()?;
+                let bytes = Bytes::from(bcs::to_bytes(&txn)?);
+                for transport in &mut transports {
+                    if let Err(e) = transport.send(bytes.clone()).await {
+                        warn!("Failed to send transaction: {}", e);
+                        break 'main;
+                    }
+                }
+                for transport in &delayed_transports {
+                    let due = Instant::now() + self.link_delay.sample(&mut rng);
+                    if transport.send((bytes.clone(), due)).await.is_err() {
+                        break 'main;
+                    }
                 }
                 counter = counter.wrapping_add(1);
             }
@@ -183,16 +454,21 @@ impl Client {
         Ok(())
     }
 
-    pub async fn wait(&self) {
-        // Wait for all nodes to be online.
-        info!("Waiting for all nodes to be online...");
-        join_all(self.nodes.iter().cloned().map(|address| {
-            tokio::spawn(async move {
-                while TcpStream::connect(address).await.is_err() {
-                    sleep(Duration::from_millis(10)).await;
-                }
-            })
-        }))
-        .await;
+    /// Waits until every node accepts connections and every node with a transaction API answers
+    /// status queries. Fails if they are not ready within `wait_timeout`.
+    pub async fn wait(&self, apis: &[SocketAddr]) -> Result<()> {
+        info!("Waiting for all nodes to be ready...");
+        let online = join_all(self.nodes.iter().cloned().map(|address| async move {
+            while TcpStream::connect(address).await.is_err() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }));
+        let ready = join_all(apis.iter().cloned().map(wait_until_ready));
+        timeout(self.wait_timeout, async {
+            online.await;
+            ready.await;
+        })
+        .await
+        .map_err(|_| anyhow!("Nodes not ready after {:?}", self.wait_timeout))
     }
 }
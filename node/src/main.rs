@@ -1,20 +1,33 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use aptos_executor::{AbortMessages, AptosVmExecutor, GenesisSupply};
+use aptos_types::chain_id::ChainId;
 use clap::{crate_name, crate_version, App, AppSettings, ArgMatches, SubCommand};
 use config::Export as _;
 use config::Import as _;
-use config::{BlsKeyPair, Comm, Committee, KeyPair, Parameters};
+use config::{BlsKeyPair, Comm, Committee, KeyPair, Parameters, StoreBackend};
 use crypto::{BlsSignatureService, SignatureService};
 use env_logger::Env;
-use hydrangea::{Block, Consensus};
-use log::debug;
-use primary::Primary;
+use hydrangea::{init_executor, Block, CommitAttester, Consensus, HeaderDecodeFailures};
+use log::{debug, info, warn};
+use primary::{
+    load_start_round, CertifiedRounds, DroppedCertificates, ExecutionPressure, ExecutionReceipts,
+    Primary,
+};
+use std::path::Path;
 use store::Store;
 use tokio::sync::mpsc::{channel, Receiver};
 
+#[cfg(test)]
+#[path = "tests/store_backend_tests.rs"]
+mod store_backend_tests;
+
 /// The default channel capacity.
 pub const CHANNEL_CAPACITY: usize = 1_000;
 
+/// Authorities whose highest certified round is this far behind the others are reported.
+const LAGGING_ROUNDS_THRESHOLD: u64 = 10;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let matches = App::new(crate_name!())
@@ -33,6 +46,12 @@ async fn main() -> Result<()> {
                 .arg_from_usage("--threshold=<INT>  'threshold number of keys require to verify'")
                 .arg_from_usage("--path=<String>  'Path for storing blskeys'"),
         )
+        .subcommand(
+            SubCommand::with_name("export_state")
+                .about("Print the state after genesis to file, to boot nodes from it")
+                .args_from_usage("--filename=<FILE> 'The file where to print the state snapshot'")
+                .args_from_usage("--chain-id=[INT] 'The chain id of the committee booting from the snapshot (default: the Aptos test chain)'"),
+        )
         .subcommand(
             SubCommand::with_name("run")
                 .about("Run a node")
@@ -41,6 +60,7 @@ async fn main() -> Result<()> {
                 .args_from_usage("--committee=<FILE> 'The file containing committee information'")
                 .args_from_usage("--parameters=[FILE] 'The file containing the node parameters'")
                 .args_from_usage("--store=<PATH> 'The path where to create the data store'")
+                .args_from_usage("--attestation-key=[FILE] 'The file containing the key attesting executed blocks'")
                 .subcommand(SubCommand::with_name("primary").about("Run a single primary"))
                 .subcommand(
                     SubCommand::with_name("worker")
@@ -92,6 +112,19 @@ async fn main() -> Result<()> {
                 .parse::<String>()
                 .unwrap(),
         ),
+        ("export_state", Some(sub_matches)) => {
+            let chain_id = match sub_matches.value_of("chain-id") {
+                Some(id) => match id.parse::<u8>() {
+                    Ok(id) if id > 0 => ChainId::new(id),
+                    _ => bail!("The chain id must be an integer between 1 and 255"),
+                },
+                None => ChainId::test(),
+            };
+            init_executor(|| AptosVmExecutor::new_with_chain_id(chain_id))
+                .context("Failed to generate the genesis")?
+                .export_snapshot(Path::new(sub_matches.value_of("filename").unwrap()))
+                .context("Failed to export the state")?
+        }
         ("run", Some(sub_matches)) => run(sub_matches).await?,
         _ => unreachable!(),
     }
@@ -102,6 +135,7 @@ async fn main() -> Result<()> {
 async fn run(matches: &ArgMatches<'_>) -> Result<()> {
     let ed_key_file = matches.value_of("edkeys").unwrap();
     let bls_key_file = matches.value_of("blskeys").unwrap();
+    let attestation_key_file = matches.value_of("attestation-key");
     let committee_file = matches.value_of("committee").unwrap();
     let parameters_file = matches.value_of("parameters");
     let store_path = matches.value_of("store").unwrap();
@@ -111,41 +145,112 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
     let bls_keypair =
         BlsKeyPair::import(bls_key_file).context("Failed to load the node's keypair")?;
     let name = ed_keypair.name;
+    // The heads of the chain of executed blocks are only attested with a key of their own.
+    let attester = match attestation_key_file {
+        Some(file) => {
+            let keypair =
+                KeyPair::import(file).context("Failed to load the node's attestation key")?;
+            Some(CommitAttester::new(keypair.name, keypair.secret))
+        }
+        None => None,
+    };
     let comm = Comm::import(committee_file).context("Failed to load the committee information")?;
 
     // Load default parameters if none are specified.
-    let parameters = match parameters_file {
+    let mut parameters = match parameters_file {
         Some(filename) => {
             Parameters::import(filename).context("Failed to load the node's parameters")?
         }
         None => Parameters::default(),
     };
+    parameters.validate().context("Invalid node parameters")?;
 
-    let committee = Committee::new(
-        comm.authorities,
-        parameters.n,
-        parameters.f,
-        parameters.c,
-        parameters.k,
-    );
+    let committee = Committee {
+        execution: comm.execution,
+        ..Committee::new(
+            comm.authorities,
+            parameters.n,
+            parameters.f,
+            parameters.c,
+            parameters.k,
+        )
+    };
+    committee.validate().context("Invalid committee")?;
 
     // The `SignatureService` provides signatures on input digests.
     let signature_service = SignatureService::new(ed_keypair.secret);
     let bls_signature_service = BlsSignatureService::new(bls_keypair.secret);
-    // Make the data store.
-    let store = Store::new(store_path).context("Failed to create a store")?;
+    // Make the data store. This single store is shared by the primary and the consensus: the
+    // `Committer` reads back the headers written by the primary's `Core`.
+    let store = open_store(parameters.store_backend.as_ref(), store_path)?;
 
     // Channels the sequence of certificates.
     let (tx_output, rx_output) = channel(CHANNEL_CAPACITY);
 
+    // Diagnostics shared with the primary.
+    let certified_rounds = CertifiedRounds::default();
+    let dropped_certificates = DroppedCertificates::default();
+    let header_decode_failures = HeaderDecodeFailures::default();
+
+    // The receipts of the committed transactions, written by the consensus and served by the primary.
+    let receipts = ExecutionReceipts::new(parameters.receipts_capacity);
+    // Feeds the backlog of the committer back to the proposer.
+    let execution_pressure = ExecutionPressure::default();
+
+    // Build the executor (and its genesis) before starting any task, so that the node never accepts
+    // transactions it cannot execute.
+    let executor = match parameters.consensus_only {
+        true => None,
+        false => {
+            let execution = &committee.execution;
+            let chain_id = ChainId::new(execution.chain_id);
+            let mut executor = match &execution.state_snapshot {
+                Some(path) => init_executor(|| AptosVmExecutor::from_snapshot(path, chain_id)),
+                None => init_executor(|| {
+                    AptosVmExecutor::with_framework(
+                        GenesisSupply::default(),
+                        execution.genesis_framework,
+                        chain_id,
+                    )
+                }),
+            }
+            .context("Failed to start the committer")?;
+            if let Some(framework) = executor.framework() {
+                let hash = framework.hash.to_hex_literal();
+                info!(
+                    "Genesis installs the {:?} framework (hash {})",
+                    framework.release, hash
+                );
+                match &execution.framework_hash {
+                    Some(pinned) if !pinned.eq_ignore_ascii_case(&hash) => bail!(
+                        "The genesis hash {} does not match the pinned framework hash {}",
+                        hash,
+                        pinned
+                    ),
+                    _ => (),
+                }
+            }
+            executor.set_expiry_grace(execution.execution_expiry_grace);
+            if let Some(path) = &execution.abort_messages_package {
+                let messages = AbortMessages::from_package_dir(path)
+                    .context("Failed to load the abort messages")?;
+                executor.register_abort_messages(messages);
+            }
+            Some(executor)
+        }
+    };
+
     // Check whether to run a primary, a worker, or an entire authority.
     match matches.subcommand() {
         // Spawn the primary and consensus core.
         ("primary", _) => {
             let (tx_new_certificates, rx_new_certificates) = channel(CHANNEL_CAPACITY);
             let (tx_feedback, rx_feedback) = channel(CHANNEL_CAPACITY);
+            let (tx_abort_stats, rx_abort_stats) = channel(CHANNEL_CAPACITY);
 
             if !parameters.consensus_only {
+                // Resume after the last header we proposed before a restart, if any.
+                let start_round = load_start_round(&mut store.clone()).await;
                 Primary::spawn(
                     name,
                     committee.clone(),
@@ -153,8 +258,15 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
                     signature_service.clone(),
                     bls_signature_service.clone(),
                     store.clone(),
+                    start_round,
+                    certified_rounds.clone(),
+                    dropped_certificates.clone(),
+                    receipts.clone(),
+                    execution_pressure.clone(),
+                    rx_abort_stats,
                     /* tx_consensus */ tx_new_certificates,
                     /* rx_consensus */ rx_feedback,
+                    /* tx_assembled */ None,
                 );
             }
 
@@ -165,6 +277,12 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
                 signature_service,
                 bls_signature_service,
                 store,
+                executor,
+                receipts,
+                execution_pressure,
+                header_decode_failures.clone(),
+                attester,
+                tx_abort_stats,
                 /* rx_mempool */ rx_new_certificates,
                 /* tx_mempool */ tx_feedback,
                 tx_output,
@@ -184,15 +302,66 @@ async fn run(matches: &ArgMatches<'_>) -> Result<()> {
     }
 
     // Analyze the consensus' output.
-    analyze(rx_output).await;
+    analyze(
+        rx_output,
+        certified_rounds,
+        dropped_certificates,
+        header_decode_failures,
+    )
+    .await;
 
     // If this expression is reached, the program ends and all other tasks terminate.
     unreachable!();
 }
 
+/// Opens the store of the node with the configured backend, by default a RocksDB database at
+/// `default_path`.
+fn open_store(backend: Option<&StoreBackend>, default_path: &str) -> Result<Store> {
+    match backend {
+        Some(StoreBackend::Memory) => Ok(Store::new_in_memory()),
+        Some(StoreBackend::RocksDb { path }) => {
+            Store::new(path).with_context(|| format!("Failed to create a store at {}", path))
+        }
+        None => Store::new(default_path).context("Failed to create a store"),
+    }
+}
+
 /// Receives an ordered list of certificates and apply any application-specific logic.
-async fn analyze(mut rx_output: Receiver<Block>) {
+async fn analyze(
+    mut rx_output: Receiver<Block>,
+    certified_rounds: CertifiedRounds,
+    dropped_certificates: DroppedCertificates,
+    header_decode_failures: HeaderDecodeFailures,
+) {
+    let mut dropped = 0;
+    let mut undecodable = 0;
     while let Some(_block) = rx_output.recv().await {
         // NOTE: Here goes the application logic.
+
+        for (name, round) in certified_rounds.lagging(LAGGING_ROUNDS_THRESHOLD) {
+            debug!(
+                "Authority {} is lagging behind: highest certified round {}",
+                name, round
+            );
+        }
+
+        let count = dropped_certificates.count();
+        if count > dropped {
+            debug!(
+                "{} certificates dropped below the garbage collection round so far",
+                count
+            );
+            dropped = count;
+        }
+
+        // The transactions of these headers were never executed.
+        let count = header_decode_failures.count();
+        if count > undecodable {
+            warn!(
+                "{} committed headers could not be decoded from the store so far",
+                count
+            );
+            undecodable = count;
+        }
     }
 }
@@ -0,0 +1,210 @@
+use super::*;
+use futures::stream::StreamExt as _;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::TcpListener;
+
+/// Serves a single account query, replying that the next sequence number is `sequence_number`.
+async fn mock_api(address: SocketAddr, sequence_number: u64) {
+    let listener = TcpListener::bind(address).await.unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buffer = vec![0; 1_024];
+        let n = socket.read(&mut buffer).await.unwrap();
+        let request = String::from_utf8_lossy(&buffer[..n]);
+        assert!(request.starts_with("GET /accounts/0x"), "{}", request);
+
+        let body = format!(
+            r#"{{"address":"0x1","sequence_number":{}}}"#,
+            sequence_number
+        );
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+    });
+}
+
+/// Serves the status queries of a node, replying with the round in `executed_round`.
+async fn mock_status(address: SocketAddr, executed_round: Arc<Mutex<Option<u64>>>) {
+    let listener = TcpListener::bind(address).await.unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0; 1_024];
+            let n = socket.read(&mut buffer).await.unwrap();
+            if n == 0 {
+                // A bare connection check.
+                continue;
+            }
+            let request = String::from_utf8_lossy(&buffer[..n]);
+            assert!(request.starts_with("GET /status"), "{}", request);
+
+            let round = *executed_round.lock().unwrap();
+            let body = match round {
+                Some(round) => format!(r#"{{"attestation_key":null,"executed_round":{}}}"#, round),
+                None => r#"{"attestation_key":null,"executed_round":null}"#.to_string(),
+            };
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+}
+
+// Fixture
+fn client() -> Client {
+    Client {
+        targets: Vec::new(),
+        rate: 1,
+        nodes: Vec::new(),
+        burst_duration: 1,
+        sender: LocalAccount::generate(1).unwrap(),
+        recipient: LocalAccount::generate(2).unwrap(),
+        chain_id: ChainId::test(),
+        transfer_amount: 1,
+        tx_size_bytes: 0,
+        link_delay: LinkDelay::default(),
+        wait_timeout: Duration::from_secs(10),
+    }
+}
+
+#[tokio::test]
+async fn resume_from_on_chain_sequence_number() {
+    let api = "127.0.0.1:4300".parse().unwrap();
+    mock_api(api, 7).await;
+
+    let mut client = client();
+    client.resume(&[api]).await.unwrap();
+
+    let txn = client.next_transaction().unwrap();
+    assert_eq!(txn.sequence_number(), 7);
+    let txn = client.next_transaction().unwrap();
+    assert_eq!(txn.sequence_number(), 8);
+}
+
+#[tokio::test]
+async fn resume_fails_without_reachable_api() {
+    let api = "127.0.0.1:4301".parse().unwrap();
+    let mut client = client();
+    assert!(client.resume(&[api]).await.is_err());
+    assert_eq!(client.sender.sequence_number, 0);
+}
+
+#[test]
+fn delays_stay_within_jitter() {
+    let delay = LinkDelay {
+        latency: Duration::from_millis(50),
+        jitter: Duration::from_millis(10),
+    };
+    let mut rng = StdRng::from_seed([0; 32]);
+    let samples: Vec<_> = (0..1_000).map(|_| delay.sample(&mut rng)).collect();
+    assert!(samples
+        .iter()
+        .all(|x| (Duration::from_millis(40)..=Duration::from_millis(60)).contains(x)));
+    assert!(samples.iter().any(|x| *x < Duration::from_millis(50)));
+    assert!(samples.iter().any(|x| *x > Duration::from_millis(50)));
+
+    // Without jitter, every submission is delayed by exactly the latency.
+    let delay = LinkDelay {
+        latency: Duration::from_millis(50),
+        jitter: Duration::default(),
+    };
+    assert_eq!(delay.sample(&mut rng), Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn submissions_are_delayed_by_the_latency() {
+    const SUBMISSIONS: usize = 10;
+    let latency = Duration::from_millis(50);
+
+    // Record the arrival time of every submission at the target.
+    let address: SocketAddr = "127.0.0.1:4302".parse().unwrap();
+    let listener = TcpListener::bind(address).await.unwrap();
+    let arrivals = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut transport = Framed::new(socket, LengthDelimitedCodec::new());
+        let mut arrivals = Vec::new();
+        while arrivals.len() < SUBMISSIONS {
+            let message = transport.next().await.unwrap().unwrap();
+            arrivals.push((message.freeze(), Instant::now()));
+        }
+        arrivals
+    });
+
+    let stream = TcpStream::connect(address).await.unwrap();
+    let transport = spawn_delayed_transport(Framed::new(stream, LengthDelimitedCodec::new()));
+    let delay = LinkDelay {
+        latency,
+        jitter: Duration::default(),
+    };
+    let mut rng = StdRng::from_seed([0; 32]);
+    let mut submissions = Vec::new();
+    for i in 0..SUBMISSIONS {
+        let now = Instant::now();
+        submissions.push(now);
+        let due = now + delay.sample(&mut rng);
+        transport
+            .send((Bytes::from(format!("tx{}", i)), due))
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    // Every submission arrives after the latency, in the order it was submitted.
+    let arrivals = arrivals.await.unwrap();
+    for (i, (submitted, (message, arrived))) in submissions.iter().zip(&arrivals).enumerate() {
+        assert_eq!(message, &Bytes::from(format!("tx{}", i)));
+        let elapsed = arrived.duration_since(*submitted);
+        assert!(elapsed >= latency, "arrived after {:?}", elapsed);
+    }
+}
+
+#[tokio::test]
+async fn wait_until_the_api_answers() {
+    let api: SocketAddr = "127.0.0.1:4303".parse().unwrap();
+
+    // The node does not serve its transaction API yet.
+    let mut client = client();
+    let mut waiting = tokio::spawn(async move { client.wait(&[api]).await });
+    assert!(timeout(Duration::from_millis(500), &mut waiting)
+        .await
+        .is_err());
+
+    // The node is ready once it answers status queries, even before executing a block.
+    mock_status(api, Arc::new(Mutex::new(None))).await;
+    timeout(Duration::from_secs(5), waiting)
+        .await
+        .expect("The client kept waiting for a ready node")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn idle_nodes_are_ready() {
+    // The node executed blocks before, but has no transactions to commit: its round does not advance.
+    let api: SocketAddr = "127.0.0.1:4304".parse().unwrap();
+    mock_status(api, Arc::new(Mutex::new(Some(3)))).await;
+
+    let mut client = client();
+    client.nodes = vec![api];
+    client.wait_timeout = Duration::from_secs(5);
+    client.wait(&[api]).await.unwrap();
+}
+
+#[tokio::test]
+async fn wait_gives_up_on_nodes_that_are_never_ready() {
+    // Nothing ever serves the transaction API of the node.
+    let api: SocketAddr = "127.0.0.1:4305".parse().unwrap();
+
+    let mut client = client();
+    client.wait_timeout = Duration::from_millis(500);
+    assert!(client.wait(&[api]).await.is_err());
+}
@@ -0,0 +1,64 @@
+use super::*;
+use config::StoreCodec;
+use crypto::Digest;
+use primary::Header;
+use std::fs;
+use tokio::time::{sleep, Duration};
+
+// Fixture
+fn header() -> Header {
+    Header {
+        round: 3,
+        id: Digest([7; 32]),
+        ..Header::default()
+    }
+}
+
+#[tokio::test]
+async fn memory_store_keeps_headers() {
+    let mut store = open_store(Some(&StoreBackend::Memory), "unused").unwrap();
+    header().write(&mut store, StoreCodec::default()).await;
+
+    // Another handle on the same store (e.g. the committer's) sees the header.
+    let loaded = Header::read(&mut store.clone(), &header().id, StoreCodec::default())
+        .await
+        .unwrap();
+    assert_eq!(loaded.map(|x| x.round), Some(3));
+}
+
+#[tokio::test]
+async fn rocksdb_store_keeps_headers_across_reopen() {
+    let path = ".db_test_rocksdb_store_keeps_headers_across_reopen";
+    let _ = fs::remove_dir_all(path);
+    let backend = StoreBackend::RocksDb {
+        path: path.to_string(),
+    };
+
+    let mut store = open_store(Some(&backend), "unused").unwrap();
+    header().write(&mut store, StoreCodec::default()).await;
+    // Reading back ensures the write was processed before closing the store.
+    assert!(
+        Header::read(&mut store, &header().id, StoreCodec::default())
+            .await
+            .unwrap()
+            .is_some()
+    );
+    drop(store);
+
+    // The database is released once the task of the previous store notices it was dropped.
+    let mut reopened = None;
+    for _ in 0..50 {
+        match open_store(Some(&backend), "unused") {
+            Ok(store) => {
+                reopened = Some(store);
+                break;
+            }
+            Err(_) => sleep(Duration::from_millis(20)).await,
+        }
+    }
+    let mut store = reopened.expect("Failed to reopen the store");
+    let loaded = Header::read(&mut store, &header().id, StoreCodec::default())
+        .await
+        .unwrap();
+    assert_eq!(loaded.map(|x| x.round), Some(3));
+}
@@ -0,0 +1,48 @@
+//! Measures how the throughput of certificate verification scales with the number of verification
+//! threads (see the `verification_threads` parameter). Run with
+//! `cargo bench -p primary --bench verification_throughput`.
+use config::committee_builder::CommitteeBuilder;
+use primary::Certificate;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::time::Instant;
+use threadpool::ThreadPool;
+
+const CERTIFICATES: u64 = 400;
+
+fn main() {
+    let test = CommitteeBuilder::new(4).build().unwrap();
+    let committee = Arc::new(test.committee);
+    let name = test.keys[0].name;
+
+    // Verification costs the same whether the signature is valid or not.
+    let certificates: Vec<Certificate> = (1..=CERTIFICATES)
+        .map(|round| Certificate {
+            origin: name,
+            round,
+            votes: (0b111, Default::default()),
+            ..Certificate::default()
+        })
+        .collect();
+
+    let cpus = std::thread::available_parallelism().map_or(1, |x| x.get());
+    println!("{} CPUs available", cpus);
+    for threads in [1, 2, 4] {
+        let pool = ThreadPool::new(threads);
+        let (tx_verified, rx_verified) = channel();
+        let start = Instant::now();
+        for certificate in &certificates {
+            let certificate = certificate.clone();
+            let committee = Arc::clone(&committee);
+            let tx_verified = tx_verified.clone();
+            pool.execute(move || {
+                let _ = tx_verified.send(certificate.verify(&committee).is_ok());
+            });
+        }
+        for _ in &certificates {
+            rx_verified.recv().unwrap();
+        }
+        let throughput = certificates.len() as f64 / start.elapsed().as_secs_f64();
+        println!("{} threads: {:.0} certificates/s", threads, throughput);
+    }
+}
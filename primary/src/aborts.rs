@@ -0,0 +1,86 @@
+use aptos_types::account_address::AccountAddress;
+use std::collections::HashMap;
+
+#[cfg(test)]
+#[path = "tests/aborts_tests.rs"]
+pub mod aborts_tests;
+
+/// The weight kept by the outcomes of the earlier blocks every time a block is recorded, so that a
+/// sender whose transactions stop aborting recovers.
+const DECAY: f64 = 0.9;
+
+/// A sender is judged on at least this many (decayed) transactions.
+const MIN_SAMPLES: f64 = 5.0;
+
+/// Senders are forgotten once their (decayed) transactions fall below this.
+const FORGET_BELOW: f64 = 0.5;
+
+/// How the transactions of a sender fared in a block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SenderOutcomes {
+    /// The transactions that executed successfully.
+    pub executed: u64,
+    /// The transactions that aborted or were otherwise not executed.
+    pub aborted: u64,
+}
+
+/// The outcomes of the transactions of an executed block, by sender. Sent by the committer to the
+/// proposer, so that it can deprioritize the senders whose transactions keep aborting.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AbortStats {
+    pub senders: HashMap<AccountAddress, SenderOutcomes>,
+}
+
+impl AbortStats {
+    /// Records the outcome of a transaction of `sender`.
+    pub fn record(&mut self, sender: AccountAddress, executed: bool) {
+        let outcomes = self.senders.entry(sender).or_default();
+        match executed {
+            true => outcomes.executed += 1,
+            false => outcomes.aborted += 1,
+        }
+    }
+}
+
+/// Tracks the recent abort rate of every sender and tells which ones to deprioritize.
+pub struct AbortTracker {
+    /// Senders aborting more than this fraction of their transactions are deprioritized. Nobody is
+    /// deprioritized if unset.
+    max_abort_rate: Option<f64>,
+    /// The decayed number of aborted and total transactions of every sender.
+    senders: HashMap<AccountAddress, (f64, f64)>,
+}
+
+impl AbortTracker {
+    pub fn new(max_abort_rate: Option<f64>) -> Self {
+        Self {
+            max_abort_rate,
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Records the outcomes of an executed block.
+    pub fn record(&mut self, stats: &AbortStats) {
+        if self.max_abort_rate.is_none() {
+            return;
+        }
+        for (aborted, total) in self.senders.values_mut() {
+            *aborted *= DECAY;
+            *total *= DECAY;
+        }
+        for (sender, outcomes) in &stats.senders {
+            let (aborted, total) = self.senders.entry(*sender).or_default();
+            *aborted += outcomes.aborted as f64;
+            *total += (outcomes.executed + outcomes.aborted) as f64;
+        }
+        self.senders.retain(|_, (_, total)| *total >= FORGET_BELOW);
+    }
+
+    /// Returns whether the transactions of `sender` abort too often to be given priority.
+    pub fn is_deprioritized(&self, sender: &AccountAddress) -> bool {
+        match (self.max_abort_rate, self.senders.get(sender)) {
+            (Some(max), Some((aborted, total))) => *total >= MIN_SAMPLES && aborted / total > max,
+            _ => false,
+        }
+    }
+}
@@ -1,28 +1,32 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use crate::error::{DagError, DagResult};
 use crate::messages::{Certificate, Header, Vote};
-use blsttc::{PublicKeyShareG2, SignatureShareG1};
+use crate::scheme::{Blsttc, ThresholdSigScheme};
 use config::{Committee, Stake};
-use crypto::{aggregate_sign, Hash, PublicKey};
+use crypto::{Hash, PublicKey};
 use std::collections::HashSet;
 
+#[cfg(test)]
+#[path = "tests/aggregators_tests.rs"]
+pub mod aggregators_tests;
+
 /// Aggregates votes for a particular header into a certificate.
-pub struct VotesAggregator {
+pub struct VotesAggregator<S: ThresholdSigScheme = Blsttc> {
     weight: Stake,
-    votes: Vec<(PublicKeyShareG2, SignatureShareG1)>,
+    votes: Vec<(S::PublicKeyShare, S::SignatureShare)>,
     used: HashSet<PublicKey>,
-    agg_sign: SignatureShareG1,
+    agg_sign: S::SignatureShare,
     pk_bit_vec: u128,
     is_qc_sent: bool,
 }
 
-impl VotesAggregator {
+impl<S: ThresholdSigScheme> VotesAggregator<S> {
     pub fn new() -> Self {
         Self {
             weight: 0,
             votes: Vec::new(),
             used: HashSet::new(),
-            agg_sign: SignatureShareG1::default(),
+            agg_sign: S::SignatureShare::default(),
             pk_bit_vec: 0,
             is_qc_sent: false,
         }
@@ -30,36 +34,43 @@ impl VotesAggregator {
 
     pub fn append(
         &mut self,
-        vote: Vote,
+        vote: Vote<S>,
         committee: &Committee,
         header: &Header,
-    ) -> DagResult<Option<Certificate>> {
+    ) -> DagResult<Option<Certificate<S>>> {
         let author = vote.author;
-        let author_bls_g2 = committee.get_bls_public_g2(&vote.author);
+        // The author may have left the committee, e.g. after a reconfiguration.
+        let author_bls_g2 =
+            S::public_key_share(committee, &author).ok_or(DagError::UnknownAuthority(author))?;
+        let signer_index =
+            S::signer_index(committee, &author_bls_g2).ok_or(DagError::UnknownAuthority(author))?;
+
+        // A vote only counts with a valid signature share, which no longer matters once we certified
+        // the header.
+        if !self.is_qc_sent {
+            vote.verify(committee)?;
+        }
 
         // Ensure it is the first time this authority votes.
         ensure!(self.used.insert(author), DagError::AuthorityReuse(author));
 
-        self.votes.push((author_bls_g2, vote.signature.clone()));
+        self.votes
+            .push((author_bls_g2.clone(), vote.signature.clone()));
         self.weight += committee.stake(&author);
 
         if !self.is_qc_sent {
             // info!("verified vote for {}", vote.id);
-            vote.verify(committee)?;
-
             if self.votes.len() == 1 {
                 self.agg_sign = vote.signature;
 
                 //adding it to bitvec
-                self.pk_bit_vec |=
-                    1 << committee.sorted_keys.binary_search(&author_bls_g2).unwrap();
+                self.pk_bit_vec |= 1 << signer_index;
             } else if self.votes.len() >= 2 {
-                let new_agg_sign = aggregate_sign(&self.agg_sign, &vote.signature);
+                let new_agg_sign = S::aggregate(&self.agg_sign, &vote.signature);
                 self.agg_sign = new_agg_sign;
 
                 //adding node id to bitvec
-                self.pk_bit_vec |=
-                    1 << committee.sorted_keys.binary_search(&author_bls_g2).unwrap();
+                self.pk_bit_vec |= 1 << signer_index;
             }
 
             if self.weight >= committee.validity_threshold() {
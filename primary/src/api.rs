@@ -0,0 +1,465 @@
+use crate::batch_maker::{BatchParameters, Transaction};
+use crate::primary::Round;
+use crate::receipts::{CommitGaps, ExecutionReceipts, ReceiptLookup, StateLookup};
+use crate::trace::{TraceId, TraceIds, TRACE_ID_HEADER};
+use crate::worker::{decode_transaction, Admission, RejectReason};
+use aptos_types::account_address::AccountAddress;
+use aptos_types::state_store::state_key::StateKey;
+use config::{GenesisFramework, Parameters};
+use crypto::PublicKey;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{debug, error, info};
+use network::ActiveConnections;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr as _;
+use tokio::sync::mpsc::Sender;
+
+/// The path on which clients submit transactions. Clients fetch the result of a committed
+/// transaction at `/transactions/<hash>`.
+const TRANSACTIONS_PATH: &str = "/transactions";
+
+/// Clients fetch the next sequence number of an account at `/accounts/<address>`.
+const ACCOUNTS_PATH: &str = "/accounts";
+
+/// Clients fetch the head of the chain of executed blocks at `/chain/head`.
+const CHAIN_HEAD_PATH: &str = "/chain/head";
+
+/// Clients fetch the status of the node, e.g. the key attesting its chain heads, at `/status`.
+const STATUS_PATH: &str = "/status";
+
+/// Clients read the value stored at a state key at `/state/<key>`, where the key is BCS-encoded and
+/// hex-encoded.
+const STATE_PATH: &str = "/state";
+
+/// Operators re-tune the batching of the worker at `/batching`, without dropping the transactions
+/// of the batch being built.
+const BATCHING_PATH: &str = "/batching";
+
+/// The reply to a query of the next sequence number of an account.
+#[derive(Debug, Serialize)]
+pub struct AccountResponse {
+    /// The address of the account.
+    pub address: String,
+    /// The sequence number of the next transaction of the account.
+    pub sequence_number: u64,
+}
+
+/// The reply to a query of the status of the node.
+#[derive(Debug, Serialize)]
+pub struct StatusResponse {
+    /// The key with which the node attests the heads of its chain of executed blocks, if it does.
+    /// It is separate from the consensus keys of the node.
+    pub attestation_key: Option<PublicKey>,
+    /// The highest committed round executed, if any (see `ChainHead::round`).
+    pub executed_round: Option<Round>,
+    /// The release of the framework installed at genesis, unless the state was imported.
+    pub genesis_framework: Option<GenesisFramework>,
+    /// The hash of the genesis, pinned with the `framework_hash` execution setting of the committee.
+    pub framework_hash: Option<String>,
+    /// The number of client connections open on the transactions address of the worker.
+    pub client_connections: usize,
+    /// The rounds skipped by the committed certificates, if the node checks them.
+    pub commit_gaps: Option<CommitGaps>,
+}
+
+/// The reply to a read of the executed state.
+#[derive(Debug, Serialize)]
+pub struct StateResponse {
+    /// The hex-encoded state key, as queried.
+    pub key: String,
+    /// The hex-encoded BCS value stored at the key.
+    pub value: String,
+}
+
+/// The reply sent back to clients submitting a transaction over HTTP.
+#[derive(Debug, Serialize)]
+pub struct SubmissionResponse {
+    /// The hash of the submitted transaction, if it could be decoded.
+    pub hash: Option<String>,
+    /// Either `accepted` or `rejected`.
+    pub status: &'static str,
+    /// The reason why the transaction was rejected, if any.
+    pub reason: Option<String>,
+}
+
+impl SubmissionResponse {
+    fn accepted(hash: String) -> Self {
+        Self {
+            hash: Some(hash),
+            status: "accepted",
+            reason: None,
+        }
+    }
+
+    fn rejected(hash: Option<String>, reason: String) -> Self {
+        Self {
+            hash,
+            status: "rejected",
+            reason: Some(reason),
+        }
+    }
+}
+
+/// Receives client transactions over HTTP and forwards them to the `BatchMaker`. Transactions
+/// may be BCS-encoded (the default) or JSON-encoded (`Content-Type: application/json`), and clients
+/// may trace them under their own id (see `TRACE_ID_HEADER`). It also
+/// serves the execution receipts of committed transactions, the sequence numbers of accounts, the
+/// head of the chain of executed blocks, the executed state, and the status of the node.
+pub struct ApiServer;
+
+impl ApiServer {
+    pub fn spawn(
+        address: SocketAddr,
+        tx_batch_maker: Sender<Transaction>,
+        tx_reload: Sender<BatchParameters>,
+        receipts: ExecutionReceipts,
+        admission: Admission,
+        client_connections: ActiveConnections,
+    ) {
+        tokio::spawn(async move {
+            let make_service = make_service_fn(move |_| {
+                let tx_batch_maker = tx_batch_maker.clone();
+                let tx_reload = tx_reload.clone();
+                let receipts = receipts.clone();
+                let client_connections = client_connections.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        Self::handle(
+                            request,
+                            tx_batch_maker.clone(),
+                            tx_reload.clone(),
+                            receipts.clone(),
+                            admission,
+                            client_connections.clone(),
+                        )
+                    }))
+                }
+            });
+
+            info!("Transaction API listening on {}", address);
+            if let Err(e) = Server::bind(&address).serve(make_service).await {
+                error!("Transaction API on {} failed: {}", address, e);
+            }
+        });
+    }
+
+    async fn handle(
+        request: Request<Body>,
+        tx_batch_maker: Sender<Transaction>,
+        tx_reload: Sender<BatchParameters>,
+        receipts: ExecutionReceipts,
+        admission: Admission,
+        client_connections: ActiveConnections,
+    ) -> Result<Response<Body>, Infallible> {
+        let path = request.uri().path().to_string();
+        let hash = resource(&path, TRANSACTIONS_PATH);
+        let account = resource(&path, ACCOUNTS_PATH);
+        let state_key = resource(&path, STATE_PATH);
+        match (request.method(), hash, account, state_key) {
+            (&Method::POST, None, None, None) if path == TRANSACTIONS_PATH => {
+                let trace_ids = receipts.trace_ids().clone();
+                Self::submit_transaction(request, tx_batch_maker, admission, trace_ids).await
+            }
+            (&Method::GET, Some(hash), _, _) => Ok(Self::get_transaction_result(hash, &receipts)),
+            (&Method::GET, _, Some(address), _) => Ok(Self::get_account(address, &receipts)),
+            (&Method::GET, _, _, Some(key)) => Ok(Self::get_state_value(key, &receipts)),
+            (&Method::GET, None, None, None) if path == CHAIN_HEAD_PATH => {
+                Ok(Self::get_chain_head(&receipts))
+            }
+            (&Method::GET, None, None, None) if path == STATUS_PATH => {
+                Ok(Self::get_status(&receipts, &client_connections))
+            }
+            (&Method::PUT, None, None, None) if path == BATCHING_PATH => {
+                Ok(Self::update_batching(request, tx_reload).await)
+            }
+            (method, _, _, _) => Ok(unknown_route(method, &path)),
+        }
+    }
+
+    /// Returns the value stored at a state key by the executed blocks, from which clients read the
+    /// resources of accounts (e.g. their balances).
+    fn get_state_value(key: &str, receipts: &ExecutionReceipts) -> Response<Body> {
+        let state_key = hex::decode(key.trim_start_matches("0x"))
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| bcs::from_bytes::<StateKey>(&bytes).map_err(|e| e.to_string()));
+        let state_key = match state_key {
+            Ok(state_key) => state_key,
+            Err(e) => {
+                let reason = format!("Invalid state key {}: {}", key, e);
+                return reply(
+                    StatusCode::BAD_REQUEST,
+                    &SubmissionResponse::rejected(None, reason),
+                );
+            }
+        };
+        let (status, reason) = match receipts.state_value(&state_key) {
+            StateLookup::Found(value) => {
+                let response = StateResponse {
+                    key: key.to_string(),
+                    value: hex::encode(value),
+                };
+                return reply(StatusCode::OK, &response);
+            }
+            StateLookup::Absent => (StatusCode::NOT_FOUND, format!("Nothing stored at {}", key)),
+            StateLookup::Unavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "This node does not execute transactions".to_string(),
+            ),
+            StateLookup::Failed(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read {}: {}", key, e),
+            ),
+        };
+        reply(status, &SubmissionResponse::rejected(None, reason))
+    }
+
+    /// Returns the sequence number of the next transaction of an account, so that clients can resume
+    /// sending transactions after a restart.
+    fn get_account(address: &str, receipts: &ExecutionReceipts) -> Response<Body> {
+        let address = match AccountAddress::from_str(address) {
+            Ok(address) => address,
+            Err(e) => {
+                let reason = format!("Invalid account address {}: {}", address, e);
+                return reply(
+                    StatusCode::BAD_REQUEST,
+                    &SubmissionResponse::rejected(None, reason),
+                );
+            }
+        };
+        let (status, reason) = match receipts.sequence_number(&address) {
+            Some(Ok(sequence_number)) => {
+                let response = AccountResponse {
+                    address: address.to_hex_literal(),
+                    sequence_number,
+                };
+                return reply(StatusCode::OK, &response);
+            }
+            Some(Err(e)) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read the account {}: {}", address, e),
+            ),
+            None => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "This node does not execute transactions".to_string(),
+            ),
+        };
+        reply(status, &SubmissionResponse::rejected(None, reason))
+    }
+
+    /// Returns the last executed block, from which light clients verify the chain of blocks.
+    fn get_chain_head(receipts: &ExecutionReceipts) -> Response<Body> {
+        match receipts.chain_head() {
+            Some(head) => reply(StatusCode::OK, &head),
+            None => reply(
+                StatusCode::NOT_FOUND,
+                &SubmissionResponse::rejected(None, "No block executed yet".to_string()),
+            ),
+        }
+    }
+
+    /// Applies new batching parameters to the batch maker of the worker. The transactions of the
+    /// batch being built are kept and batched with the new parameters.
+    async fn update_batching(
+        request: Request<Body>,
+        tx_reload: Sender<BatchParameters>,
+    ) -> Response<Body> {
+        let parameters = match hyper::body::to_bytes(request.into_body()).await {
+            Ok(body) => serde_json::from_slice::<BatchParameters>(&body).map_err(|e| e.to_string()),
+            Err(e) => Err(format!("Failed to read request body: {}", e)),
+        };
+        let reason = match parameters {
+            Ok(parameters) if parameters.batch_size == 0 => {
+                "The batch size must be greater than 0".to_string()
+            }
+            Ok(parameters) if parameters.max_batch_delay < Parameters::MIN_BATCH_DELAY => format!(
+                "The max batch delay must be at least {} ms",
+                Parameters::MIN_BATCH_DELAY
+            ),
+            Ok(parameters) => match tx_reload.send(parameters).await {
+                Ok(()) => return reply(StatusCode::OK, &parameters),
+                Err(_) => {
+                    return reply(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        &SubmissionResponse::rejected(None, RejectReason::NotRunning.to_string()),
+                    )
+                }
+            },
+            Err(e) => format!("Invalid batching parameters: {}", e),
+        };
+        reply(
+            StatusCode::BAD_REQUEST,
+            &SubmissionResponse::rejected(None, reason),
+        )
+    }
+
+    /// Returns the status of the node, from which clients learn the key to check attestations with.
+    fn get_status(
+        receipts: &ExecutionReceipts,
+        client_connections: &ActiveConnections,
+    ) -> Response<Body> {
+        let framework = receipts.framework();
+        let response = StatusResponse {
+            attestation_key: receipts.attestation_key(),
+            executed_round: receipts.chain_head().map(|head| head.round),
+            genesis_framework: framework.as_ref().map(|(release, _)| *release),
+            framework_hash: framework.map(|(_, hash)| hash),
+            client_connections: client_connections.get(),
+            commit_gaps: receipts.commit_gaps(),
+        };
+        reply(StatusCode::OK, &response)
+    }
+
+    /// Returns the execution receipt of a committed transaction (`GetTransactionResult`).
+    fn get_transaction_result(hash: &str, receipts: &ExecutionReceipts) -> Response<Body> {
+        match receipts.lookup(hash) {
+            ReceiptLookup::Found(receipt) => reply(StatusCode::OK, &receipt),
+            ReceiptLookup::Pruned => {
+                let reason = format!("The execution result of transaction {} was pruned", hash);
+                reply(
+                    StatusCode::GONE,
+                    &SubmissionResponse::rejected(Some(hash.to_string()), reason),
+                )
+            }
+            ReceiptLookup::Unknown => {
+                let reason = format!("No execution result for transaction {}", hash);
+                reply(
+                    StatusCode::NOT_FOUND,
+                    &SubmissionResponse::rejected(Some(hash.to_string()), reason),
+                )
+            }
+        }
+    }
+
+    async fn submit_transaction(
+        request: Request<Body>,
+        tx_batch_maker: Sender<Transaction>,
+        admission: Admission,
+        trace_ids: TraceIds,
+    ) -> Result<Response<Body>, Infallible> {
+        let is_json = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| value.starts_with("application/json"));
+        let trace_id = match request.headers().get(TRACE_ID_HEADER) {
+            Some(value) => {
+                let parsed = value
+                    .to_str()
+                    .map_err(|e| e.to_string())
+                    .and_then(TraceId::from_str);
+                match parsed {
+                    Ok(trace_id) => Some(trace_id),
+                    Err(e) => return Ok(invalid_trace_id(e)),
+                }
+            }
+            None => None,
+        };
+
+        let body = match hyper::body::to_bytes(request.into_body()).await {
+            Ok(body) => body,
+            Err(e) => {
+                let reason = format!("Failed to read request body: {}", e);
+                return Ok(reply(
+                    StatusCode::BAD_REQUEST,
+                    &SubmissionResponse::rejected(None, reason),
+                ));
+            }
+        };
+
+        let decoded = match is_json {
+            true => serde_json::from_slice::<Transaction>(&body).map_err(|e| e.to_string()),
+            false => decode_transaction(&body).map_err(|e| e.to_string()),
+        };
+        let txn = match decoded {
+            Ok(txn) => txn,
+            Err(e) => {
+                let reason = RejectReason::Malformed(e);
+                debug!("Rejected transaction submitted over HTTP: {}", reason);
+                return Ok(reply(
+                    rejection_status(&reason),
+                    &SubmissionResponse::rejected(None, reason.to_string()),
+                ));
+            }
+        };
+
+        // The id is assigned before the transaction is batched, so that every subsystem traces it
+        // under the same id.
+        if let Some(trace_id) = trace_id {
+            trace_ids.assign(&txn, trace_id);
+        }
+        let hash = txn.committed_hash().to_hex_literal();
+        debug!(
+            "Received transaction {} over HTTP (trace {})",
+            hash,
+            trace_ids.resolve(&txn)
+        );
+        // The batch maker stops accepting transactions when the primary does not keep up: tell the
+        // client to retry later rather than holding its request.
+        let result = admission
+            .check(&txn)
+            .and_then(|()| tx_batch_maker.try_send(txn).map_err(RejectReason::from));
+        match result {
+            Ok(()) => Ok(reply(StatusCode::OK, &SubmissionResponse::accepted(hash))),
+            Err(reason) => {
+                debug!(
+                    "Rejected transaction {} submitted over HTTP: {}",
+                    hash, reason
+                );
+                Ok(reply(
+                    rejection_status(&reason),
+                    &SubmissionResponse::rejected(Some(hash), reason.to_string()),
+                ))
+            }
+        }
+    }
+}
+
+/// Rejects a transaction submitted with a malformed trace id.
+fn invalid_trace_id(reason: String) -> Response<Body> {
+    let reason = format!("Invalid {} header: {}", TRACE_ID_HEADER, reason);
+    debug!("Rejected transaction submitted over HTTP: {}", reason);
+    reply(
+        StatusCode::BAD_REQUEST,
+        &SubmissionResponse::rejected(None, reason),
+    )
+}
+
+/// Returns the status with which the API rejects a transaction: client errors for the transactions
+/// that will never be accepted, and a retryable error otherwise.
+fn rejection_status(reason: &RejectReason) -> StatusCode {
+    match reason {
+        RejectReason::Malformed(_)
+        | RejectReason::GasPriceTooLow { .. }
+        | RejectReason::MissingSecondarySignatures { .. } => StatusCode::BAD_REQUEST,
+        RejectReason::Overloaded | RejectReason::NotRunning => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Returns the part of the path following `prefix/`, if any.
+fn resource<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    path.strip_prefix(prefix)
+        .and_then(|x| x.strip_prefix('/'))
+        .filter(|x| !x.is_empty())
+}
+
+fn unknown_route(method: &Method, path: &str) -> Response<Body> {
+    let reason = format!("Unknown route {} {}", method, path);
+    reply(
+        StatusCode::NOT_FOUND,
+        &SubmissionResponse::rejected(None, reason),
+    )
+}
+
+fn reply<T: Serialize>(status: StatusCode, response: &T) -> Response<Body> {
+    let body = serde_json::to_vec(response).expect("Failed to serialize API response");
+    let mut reply = Response::new(Body::from(body));
+    *reply.status_mut() = status;
+    reply
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    reply
+}
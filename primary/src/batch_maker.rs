@@ -1,12 +1,35 @@
+use crate::quorum_waiter::QuorumWaiterMessage;
+use crate::trace::TraceIds;
+use crate::worker::WorkerMessage;
+use aptos_types::account_address::AccountAddress;
 use aptos_types::transaction::SignedTransaction;
-use log::debug;
-use tokio::sync::mpsc::Receiver;
-use tokio::sync::mpsc::Sender;
+use bytes::Bytes;
+use crypto::PublicKey;
+use log::{debug, info, log_enabled, warn, Level};
+use network::ReliableSender;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::mpsc::{Permit, Receiver, Sender};
 use tokio::time::{sleep, Duration, Instant};
 
 pub type Transaction = SignedTransaction;
 pub type Batch = Vec<Transaction>;
 
+#[cfg(test)]
+#[path = "tests/batch_maker_tests.rs"]
+pub mod batch_maker_tests;
+
+/// Batching parameters applied to a running batch maker, e.g. when they are re-tuned live.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchParameters {
+    /// The preferred batch size (in bytes).
+    pub batch_size: usize,
+    /// The maximum delay after which to seal the batch (in ms).
+    pub max_batch_delay: u64,
+}
+
 /// Assemble clients transactions into batches.
 pub struct BatchMaker {
     /// The preferred batch size (in bytes).
@@ -19,15 +42,45 @@ pub struct BatchMaker {
     current_batch: Batch,
     /// Holds the size of the current batch (in bytes).
     current_batch_size: usize,
+    /// Channel to deliver sealed batches to the primary.
     tx_digests: Sender<Vec<Transaction>>,
+    /// Sealed batches waiting for room in `tx_digests`, in order.
+    pending: VecDeque<Batch>,
+    /// Broadcast batches waiting for room in `tx_quorum_waiter`, in order.
+    pending_quorum: VecDeque<QuorumWaiterMessage>,
+    /// Once this many batches are pending, we stop accepting transactions until the primary (or the
+    /// `QuorumWaiter`) catches up. Clients then see the transactions channel fill up rather than a
+    /// silent stall.
+    max_pending_batches: usize,
+    /// The network addresses of the other workers sharing our id, with their authority's name.
+    workers_addresses: Vec<(PublicKey, SocketAddr)>,
+    /// If set, sealed batches are broadcast to the other workers and handed to the `QuorumWaiter`
+    /// (which delivers them to the primary) rather than being sent to the primary directly.
+    tx_quorum_waiter: Option<Sender<QuorumWaiterMessage>>,
+    /// A network sender to broadcast the batches to the other workers.
+    network: ReliableSender,
+    /// Whether to cluster the transactions of each sender contiguously when sealing a batch.
+    group_by_sender: bool,
+    /// The trace ids under which the transactions are logged.
+    trace_ids: TraceIds,
+    /// Receives new batching parameters. The transactions of the current batch are kept across a
+    /// reload and batched again with the new parameters.
+    rx_reload: Receiver<BatchParameters>,
 }
 
 impl BatchMaker {
+    #[allow(clippy::too_many_arguments)]
     pub fn spawn(
         batch_size: usize,
         max_batch_delay: u64,
         rx_transaction: Receiver<Transaction>,
         tx_digests: Sender<Vec<Transaction>>,
+        max_pending_batches: usize,
+        workers_addresses: Vec<(PublicKey, SocketAddr)>,
+        tx_quorum_waiter: Option<Sender<QuorumWaiterMessage>>,
+        group_by_sender: bool,
+        trace_ids: TraceIds,
+        rx_reload: Receiver<BatchParameters>,
     ) {
         tokio::spawn(async move {
             Self {
@@ -37,6 +90,15 @@ impl BatchMaker {
                 current_batch: Batch::with_capacity(batch_size * 2),
                 current_batch_size: 0,
                 tx_digests,
+                pending: VecDeque::new(),
+                pending_quorum: VecDeque::new(),
+                max_pending_batches,
+                workers_addresses,
+                tx_quorum_waiter,
+                network: ReliableSender::new(),
+                group_by_sender,
+                trace_ids,
+                rx_reload,
             }
             .run()
             .await;
@@ -49,25 +111,41 @@ impl BatchMaker {
         tokio::pin!(timer);
 
         loop {
+            let throttled = self.throttled();
             tokio::select! {
-                // Assemble client transactions into batches of preset size.
-                Some(transaction) = self.rx_transaction.recv() => {
-                    self.current_batch_size += serialized_len(&transaction);
-                    self.current_batch.push(transaction);
-                    debug!(
-                        "Worker collected transaction; batch_size={} bytes, count={}",
-                        self.current_batch_size,
-                        self.current_batch.len()
-                    );
-                    if self.current_batch_size >= self.batch_size {
-                        self.seal().await;
+                // Assemble client transactions into batches of preset size, unless too many batches
+                // are waiting for the primary.
+                Some(transaction) = self.rx_transaction.recv(), if !throttled => {
+                    if self.ingest(transaction).await {
                         timer.as_mut().reset(Instant::now() + Duration::from_millis(self.max_batch_delay));
                     }
                 },
 
+                // Apply new batching parameters without dropping the transactions of the current batch.
+                Some(parameters) = self.rx_reload.recv() => {
+                    self.reload(parameters).await;
+                    timer.as_mut().reset(Instant::now() + Duration::from_millis(self.max_batch_delay));
+                },
+
+                // Hand the pending batches to the primary as soon as it has room for them.
+                Ok(permit) = self.tx_digests.reserve(), if !self.pending.is_empty() => {
+                    permit.send(self.pending.pop_front().unwrap());
+                    if throttled && !self.throttled() {
+                        info!("The primary caught up with our batches, accepting transactions again");
+                    }
+                },
+
+                // Same for the batches waiting for the `QuorumWaiter`.
+                Ok(permit) = reserve(self.tx_quorum_waiter.as_ref()), if !self.pending_quorum.is_empty() => {
+                    permit.send(self.pending_quorum.pop_front().unwrap());
+                    if throttled && !self.throttled() {
+                        info!("The quorum waiter caught up with our batches, accepting transactions again");
+                    }
+                },
+
                 // If the timer triggers, seal the batch even if it contains few transactions.
                 () = &mut timer => {
-                    if !self.current_batch.is_empty() {
+                    if !self.current_batch.is_empty() && !throttled {
                         self.seal().await;
                     }
                     timer.as_mut().reset(Instant::now() + Duration::from_millis(self.max_batch_delay));
@@ -79,21 +157,148 @@ impl BatchMaker {
         }
     }
 
+    /// Adds a transaction to the current batch, sealing the batch once it is full. Returns whether
+    /// the batch was sealed.
+    async fn ingest(&mut self, transaction: Transaction) -> bool {
+        self.current_batch_size += serialized_len(&transaction);
+        self.current_batch.push(transaction);
+        debug!(
+            "Worker collected transaction; batch_size={} bytes, count={}",
+            self.current_batch_size,
+            self.current_batch.len()
+        );
+        if self.current_batch_size < self.batch_size {
+            return false;
+        }
+        self.seal().await;
+        true
+    }
+
+    /// Switches to new batching parameters and ingests the transactions of the current batch again,
+    /// so that they are batched as if they arrived after the reload.
+    async fn reload(&mut self, parameters: BatchParameters) {
+        info!(
+            "Reloading the batch maker with a batch size of {} B and a max batch delay of {} ms ({} transactions in flight)",
+            parameters.batch_size,
+            parameters.max_batch_delay,
+            self.current_batch.len()
+        );
+        self.batch_size = parameters.batch_size;
+        self.max_batch_delay = parameters.max_batch_delay;
+        let in_flight: Batch = self.current_batch.drain(..).collect();
+        self.current_batch_size = 0;
+        for transaction in in_flight {
+            self.ingest(transaction).await;
+        }
+    }
+
+    /// Whether too many batches are waiting downstream to accept more transactions.
+    fn throttled(&self) -> bool {
+        self.pending.len() + self.pending_quorum.len() >= self.max_pending_batches
+    }
+
+    /// Warns that we stop accepting transactions if the last pending batch throttled us.
+    fn warn_if_throttled(&self) {
+        if self.throttled() {
+            warn!(
+                "The primary does not keep up with our batches, not accepting transactions until it \
+                catches up ({} batches pending)",
+                self.pending.len() + self.pending_quorum.len()
+            );
+        }
+    }
+
     /// Seal and broadcast the current batch.
     async fn seal(&mut self) {
-        let batch: Vec<Transaction> = self.current_batch.drain(..).collect();
+        let mut batch: Vec<Transaction> = self.current_batch.drain(..).collect();
         self.current_batch_size = 0;
+        if self.group_by_sender {
+            batch = group_by_sender(batch);
+        }
         if !batch.is_empty() {
             debug!(
                 "Worker sealing batch containing {} transactions",
                 batch.len()
             );
         }
-        self.tx_digests
-            .send(batch)
-            .await
-            .expect("Failed to send digest");
+        if log_enabled!(Level::Debug) {
+            for transaction in &batch {
+                debug!(
+                    "Batched transaction (trace {})",
+                    self.trace_ids.resolve(transaction)
+                );
+            }
+        }
+
+        let tx_quorum_waiter = match self.tx_quorum_waiter.clone() {
+            Some(tx_quorum_waiter) => tx_quorum_waiter,
+            None => {
+                send_or_queue(&self.tx_digests, &mut self.pending, batch);
+                self.warn_if_throttled();
+                return;
+            }
+        };
+
+        // Broadcast the batch to the other workers and let the `QuorumWaiter` deliver it once
+        // enough of them acknowledged it.
+        let message = WorkerMessage::Batch(batch.clone());
+        let bytes = bincode::serialize(&message).expect("Failed to serialize our own batch");
+        let (names, addresses): (Vec<_>, _) = self.workers_addresses.iter().cloned().unzip();
+        let handlers = self.network.broadcast(addresses, Bytes::from(bytes)).await;
+        let message = QuorumWaiterMessage {
+            batch,
+            handlers: names.into_iter().zip(handlers.into_iter()).collect(),
+        };
+        send_or_queue(&tx_quorum_waiter, &mut self.pending_quorum, message);
+        self.warn_if_throttled();
+    }
+}
+
+/// Sends a message without blocking, queuing it in `pending` if the receiver has no room for it.
+/// Messages are delivered in order, so a message also waits behind the ones already pending.
+fn send_or_queue<T>(sender: &Sender<T>, pending: &mut VecDeque<T>, message: T) {
+    let message = match pending.is_empty() {
+        true => match sender.try_send(message) {
+            Ok(()) => return,
+            Err(TrySendError::Full(message)) => message,
+            Err(TrySendError::Closed(_)) => panic!("Failed to deliver batch"),
+        },
+        false => message,
+    };
+    pending.push_back(message);
+}
+
+/// Waits for room in the channel, forever if there is no channel.
+async fn reserve<T>(sender: Option<&Sender<T>>) -> Result<Permit<'_, T>, SendError<()>> {
+    match sender {
+        Some(sender) => sender.reserve().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Clusters the transactions of each sender contiguously, senders ordered by their first transaction
+/// in the batch and the transactions of a sender by sequence number.
+fn group_by_sender(batch: Batch) -> Batch {
+    let mut senders: Vec<AccountAddress> = Vec::new();
+    let mut groups: HashMap<AccountAddress, Batch> = HashMap::new();
+    for transaction in batch {
+        let sender = transaction.sender();
+        groups
+            .entry(sender)
+            .or_insert_with(|| {
+                senders.push(sender);
+                Batch::new()
+            })
+            .push(transaction);
     }
+    senders
+        .into_iter()
+        .flat_map(|sender| {
+            let mut group = groups.remove(&sender).unwrap_or_default();
+            group.sort_by_key(|x| x.sequence_number());
+            group
+        })
+        .collect()
 }
 
 fn serialized_len(tx: &Transaction) -> usize {
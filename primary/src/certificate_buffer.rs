@@ -0,0 +1,69 @@
+use crate::messages::Certificate;
+use log::{debug, warn};
+use std::collections::VecDeque;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+#[cfg(test)]
+#[path = "tests/certificate_buffer_tests.rs"]
+pub mod certificate_buffer_tests;
+
+/// Sits between the `Core` and the consensus, holding up to `capacity` certificates the consensus is
+/// not ready to take yet, so that a burst of certificates does not block the `Core` (and thus the
+/// processing of headers and votes) on a full channel. Certificates are forwarded in order. Once the
+/// buffer is full, it stops taking certificates from the `Core`, which then slows down.
+pub struct CertificateBuffer {
+    /// The maximum number of certificates held.
+    capacity: usize,
+    /// Receives the certificates processed by the `Core`.
+    rx_core: Receiver<Certificate>,
+    /// Forwards the certificates to the consensus.
+    tx_consensus: Sender<Certificate>,
+    /// The certificates waiting for the consensus, oldest first.
+    pending: VecDeque<Certificate>,
+}
+
+impl CertificateBuffer {
+    pub fn spawn(
+        capacity: usize,
+        rx_core: Receiver<Certificate>,
+        tx_consensus: Sender<Certificate>,
+    ) {
+        tokio::spawn(async move {
+            Self {
+                capacity,
+                rx_core,
+                tx_consensus,
+                pending: VecDeque::with_capacity(capacity),
+            }
+            .run()
+            .await;
+        });
+    }
+
+    /// Main loop taking certificates from the `Core` while there is room, and handing them to the
+    /// consensus as soon as it accepts them.
+    async fn run(&mut self) {
+        loop {
+            let full = self.pending.len() >= self.capacity;
+            tokio::select! {
+                Some(certificate) = self.rx_core.recv(), if !full => {
+                    self.pending.push_back(certificate);
+                    if self.pending.len() == self.capacity {
+                        debug!("Certificate buffer full ({} certificates)", self.capacity);
+                    }
+                },
+                permit = self.tx_consensus.reserve(), if !self.pending.is_empty() => match permit {
+                    Ok(permit) => permit.send(self.pending.pop_front().unwrap()),
+                    Err(_) => {
+                        warn!(
+                            "Dropping {} buffered certificates: the consensus stopped",
+                            self.pending.len()
+                        );
+                        return;
+                    }
+                },
+                else => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::messages::Certificate;
+use crate::primary::Round;
+use crypto::PublicKey;
+use std::collections::{BTreeMap, HashMap};
+
+#[cfg(test)]
+#[path = "tests/certificate_orderer_tests.rs"]
+pub mod certificate_orderer_tests;
+
+/// Certificates are verified concurrently, so their verification may complete out of round order.
+/// The `CertificateOrderer` buffers verified certificates until all the lower-round certificates of
+/// the same authority finished their verification, so that they are processed in round order.
+#[derive(Default)]
+pub struct CertificateOrderer {
+    /// The number of certificates of each authority and round that are still being verified.
+    verifying: HashMap<PublicKey, BTreeMap<Round, usize>>,
+    /// Verified certificates waiting for a lower-round certificate of the same authority.
+    buffered: HashMap<PublicKey, BTreeMap<Round, Vec<Certificate>>>,
+}
+
+impl CertificateOrderer {
+    /// Records that a certificate was sent for verification.
+    pub fn submit(&mut self, certificate: &Certificate) {
+        *self
+            .verifying
+            .entry(certificate.origin)
+            .or_default()
+            .entry(certificate.round)
+            .or_insert(0) += 1;
+    }
+
+    /// Records that a certificate finished its verification. Returns the certificates of the same
+    /// authority that are now ready to be processed, in round order.
+    pub fn verified(&mut self, certificate: Certificate) -> Vec<Certificate> {
+        let origin = certificate.origin;
+        self.finish(&certificate);
+        self.buffered
+            .entry(origin)
+            .or_default()
+            .entry(certificate.round)
+            .or_default()
+            .push(certificate);
+        self.release(origin)
+    }
+
+    /// Records that a certificate failed its verification. Returns the certificates of the same
+    /// authority that no longer wait for it, in round order.
+    pub fn rejected(&mut self, certificate: &Certificate) -> Vec<Certificate> {
+        self.finish(certificate);
+        self.release(certificate.origin)
+    }
+
+    /// Records that a certificate is no longer being verified.
+    fn finish(&mut self, certificate: &Certificate) {
+        if let Some(rounds) = self.verifying.get_mut(&certificate.origin) {
+            if let Some(count) = rounds.get_mut(&certificate.round) {
+                *count -= 1;
+                if *count == 0 {
+                    rounds.remove(&certificate.round);
+                }
+            }
+            if rounds.is_empty() {
+                self.verifying.remove(&certificate.origin);
+            }
+        }
+    }
+
+    /// Returns the buffered certificates of an authority that are ready to be processed.
+    fn release(&mut self, origin: PublicKey) -> Vec<Certificate> {
+        let buffered = self.buffered.entry(origin).or_default();
+
+        // Release every certificate that does not have a lower-round certificate still being verified.
+        let lowest_verifying = self
+            .verifying
+            .get(&origin)
+            .and_then(|rounds| rounds.keys().next().copied());
+        let waiting = match lowest_verifying {
+            Some(round) => buffered.split_off(&(round + 1)),
+            None => BTreeMap::new(),
+        };
+        let ready = std::mem::replace(buffered, waiting);
+        if buffered.is_empty() {
+            self.buffered.remove(&origin);
+        }
+        ready.into_values().flatten().collect()
+    }
+
+    /// Forgets the certificates below the garbage collection round.
+    pub fn cleanup(&mut self, gc_round: Round) {
+        self.verifying.retain(|_, rounds| {
+            rounds.retain(|round, _| round >= &gc_round);
+            !rounds.is_empty()
+        });
+        self.buffered.retain(|_, rounds| {
+            rounds.retain(|round, _| round >= &gc_round);
+            !rounds.is_empty()
+        });
+    }
+}
@@ -1,30 +1,49 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use crate::aggregators::VotesAggregator;
+use crate::certificate_orderer::CertificateOrderer;
+use crate::diagnostics::{CertifiedRounds, DroppedCertificates};
 use crate::error::{DagError, DagResult};
+use crate::faults::{Fault, FaultInjector};
 use crate::messages::{Certificate, Header, Vote};
 use crate::primary::{PrimaryMessage, Round};
 // use crate::synchronizer::Synchronizer;
 use async_recursion::async_recursion;
 use bytes::Bytes;
-use config::Committee;
+use config::{Committee, StoreCodec};
 use crypto::Hash as _;
 use crypto::{BlsSignatureService, Digest, PublicKey};
 #[cfg(feature = "benchmark")]
 use log::{debug, error, info, warn};
 #[cfg(not(feature = "benchmark"))]
 use log::{debug, error, warn};
-use network::{CancelHandler, ReliableSender};
-use std::collections::{HashMap, HashSet};
+use network::{CancelHandler, ReliableSender, RetryPolicy};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use store::Store;
 use threadpool::ThreadPool;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::oneshot::error::TryRecvError;
+use tokio::time::{sleep, Duration};
 
 // #[cfg(test)]
 // #[path = "tests/core_tests.rs"]
 // pub mod core_tests;
 
+#[cfg(test)]
+#[path = "tests/cancel_handlers_tests.rs"]
+pub mod cancel_handlers_tests;
+
+#[cfg(test)]
+#[path = "tests/processed_certificates_tests.rs"]
+pub mod processed_certificates_tests;
+
+/// How deep the processing of headers, votes, and certificates may nest. Processing a header nests at
+/// most the processing of our vote and of the certificate it completes.
+const MAX_PROCESSING_DEPTH: usize = 8;
+
 pub struct Core {
     /// The public key of this primary.
     name: PublicKey,
@@ -32,6 +51,8 @@ pub struct Core {
     committee: Committee,
     /// The persistent storage.
     store: Store,
+    /// The serialization format of the headers and certificates we store.
+    store_codec: StoreCodec,
     /// Handles synchronization with other nodes and our workers.
     // synchronizer: Synchronizer,
     /// Service to sign headers.
@@ -40,6 +61,17 @@ pub struct Core {
     consensus_round: Arc<AtomicU64>,
     /// The depth of the garbage collector.
     gc_depth: Round,
+    /// How many rounds below the consensus round the headers and certificates we store are kept, if
+    /// they are ever deleted.
+    store_retention: Option<Round>,
+    /// Whether to send our headers and certificates to high-stake primaries first.
+    prioritize_by_stake: bool,
+    /// The number of threads verifying the certificates of other primaries.
+    verification_threads: usize,
+    /// The highest round at which we saw a certificate from each authority.
+    certified_rounds: CertifiedRounds,
+    /// Counts the certificates dropped because they are below the garbage collection round.
+    dropped_certificates: DroppedCertificates,
 
     /// Receiver for dag messages (headers, votes, certificates).
     rx_primaries: Receiver<PrimaryMessage>,
@@ -51,18 +83,32 @@ pub struct Core {
     rx_proposer: Receiver<Header>,
     /// Output all certificates to the consensus layer.
     tx_consensus: Sender<Certificate>,
+    /// Notifies the subscribers (if any) of every certificate we assemble from the votes on our headers.
+    tx_assembled: Option<broadcast::Sender<Certificate>>,
     /// The last garbage collected round.
     gc_round: Round,
     /// The authors of the last voted headers.
     last_voted: HashMap<Round, HashSet<PublicKey>>,
     /// A network sender to send the batches to the other workers.
     network: ReliableSender,
-    /// Keeps the cancel handlers of the messages we sent.
+    /// Keeps the cancel handlers of the messages we sent and that are not yet acknowledged.
     cancel_handlers: HashMap<Round, Vec<CancelHandler>>,
     /// Active set of headers we are currenting waiting for votes
     processing_headers: HashMap<Digest, Header>,
     processing_vote_aggregators: HashMap<Digest, VotesAggregator>,
+    /// Ensures the certificates of each authority are processed in round order once verified.
+    certificate_orderer: CertificateOrderer,
+    /// The digests of the certificates we already stored and sent to consensus, per round. The same
+    /// certificate may reach us from several peers.
+    processed_certificates: HashMap<Round, HashSet<Digest>>,
+    /// The store keys of the headers and certificates we stored, per round, while they are retained.
+    /// Only tracked if `store_retention` is set.
+    stored_keys: BTreeMap<Round, Vec<Vec<u8>>>,
+    /// How many headers, votes, and certificates are being processed, each nested in the previous one.
+    processing_depth: usize,
     tx_primaries: Sender<PrimaryMessage>,
+    /// Drops, delays, or equivocates our messages, for testing only.
+    faults: Option<FaultInjector>,
 }
 
 impl Core {
@@ -71,44 +117,185 @@ impl Core {
         name: PublicKey,
         committee: Committee,
         store: Store,
+        store_codec: StoreCodec,
         // synchronizer: Synchronizer,
         bls_signature_service: BlsSignatureService,
         consensus_round: Arc<AtomicU64>,
         gc_depth: Round,
+        store_retention: Option<Round>,
+        prioritize_by_stake: bool,
+        verification_threads: usize,
+        retry_policy: RetryPolicy,
+        certified_rounds: CertifiedRounds,
+        dropped_certificates: DroppedCertificates,
         rx_primaries: Receiver<PrimaryMessage>,
         rx_header_waiter: Receiver<Header>,
         rx_certificate_waiter: Receiver<Certificate>,
         rx_proposer: Receiver<Header>,
         tx_consensus: Sender<Certificate>,
+        tx_assembled: Option<broadcast::Sender<Certificate>>,
         tx_primaries: Sender<PrimaryMessage>,
+        faults: Option<FaultInjector>,
     ) {
+        let mut core = Self::new(
+            name,
+            committee,
+            store,
+            store_codec,
+            bls_signature_service,
+            consensus_round,
+            gc_depth,
+            store_retention,
+            prioritize_by_stake,
+            verification_threads,
+            retry_policy,
+            certified_rounds,
+            dropped_certificates,
+            rx_primaries,
+            rx_header_waiter,
+            rx_certificate_waiter,
+            rx_proposer,
+            tx_consensus,
+            tx_assembled,
+            tx_primaries,
+            faults,
+        );
         tokio::spawn(async move {
-            Self {
-                name,
-                committee,
-                store,
-                // synchronizer,
-                bls_signature_service,
-                consensus_round,
-                gc_depth,
-                rx_primaries,
-                rx_header_waiter,
-                rx_certificate_waiter,
-                rx_proposer,
-                tx_consensus,
-                gc_round: 0,
-                last_voted: HashMap::with_capacity(2 * gc_depth as usize),
-                network: ReliableSender::new(),
-                cancel_handlers: HashMap::with_capacity(2 * gc_depth as usize),
-                processing_headers: HashMap::new(),
-                processing_vote_aggregators: HashMap::new(),
-                tx_primaries,
-            }
-            .run()
-            .await;
+            core.run().await;
         });
     }
 
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        name: PublicKey,
+        committee: Committee,
+        store: Store,
+        store_codec: StoreCodec,
+        bls_signature_service: BlsSignatureService,
+        consensus_round: Arc<AtomicU64>,
+        gc_depth: Round,
+        store_retention: Option<Round>,
+        prioritize_by_stake: bool,
+        verification_threads: usize,
+        retry_policy: RetryPolicy,
+        certified_rounds: CertifiedRounds,
+        dropped_certificates: DroppedCertificates,
+        rx_primaries: Receiver<PrimaryMessage>,
+        rx_header_waiter: Receiver<Header>,
+        rx_certificate_waiter: Receiver<Certificate>,
+        rx_proposer: Receiver<Header>,
+        tx_consensus: Sender<Certificate>,
+        tx_assembled: Option<broadcast::Sender<Certificate>>,
+        tx_primaries: Sender<PrimaryMessage>,
+        faults: Option<FaultInjector>,
+    ) -> Self {
+        Self {
+            name,
+            committee,
+            store,
+            store_codec,
+            // synchronizer,
+            bls_signature_service,
+            consensus_round,
+            gc_depth,
+            store_retention,
+            prioritize_by_stake,
+            verification_threads,
+            certified_rounds,
+            dropped_certificates,
+            rx_primaries,
+            rx_header_waiter,
+            rx_certificate_waiter,
+            rx_proposer,
+            tx_consensus,
+            tx_assembled,
+            gc_round: 0,
+            last_voted: HashMap::with_capacity(2 * gc_depth as usize),
+            network: ReliableSender::with_retry_policy(retry_policy),
+            cancel_handlers: HashMap::with_capacity(2 * gc_depth as usize),
+            processing_headers: HashMap::new(),
+            processing_vote_aggregators: HashMap::new(),
+            certificate_orderer: CertificateOrderer::default(),
+            processed_certificates: HashMap::with_capacity(2 * gc_depth as usize),
+            stored_keys: BTreeMap::new(),
+            processing_depth: 0,
+            tx_primaries,
+            faults,
+        }
+    }
+
+    /// Returns the addresses of the other primaries, in the order in which we broadcast to them.
+    fn broadcast_addresses(&self) -> Vec<SocketAddr> {
+        let primaries = match self.prioritize_by_stake {
+            true => self.committee.others_primaries_by_stake(&self.name),
+            false => self.committee.others_primaries(&self.name),
+        };
+        primaries
+            .iter()
+            .map(|(_, x)| x.primary_to_primary)
+            .collect()
+    }
+
+    /// Reliably sends a message to the specified primaries. The cancel handlers are kept with the
+    /// messages of the specified round.
+    async fn broadcast(&mut self, round: Round, addresses: Vec<SocketAddr>, bytes: Bytes) {
+        let addresses = self.inject_faults(addresses, &bytes);
+
+        let handlers = self.network.broadcast(addresses, bytes).await;
+        self.cancel_handlers
+            .entry(round)
+            .or_insert_with(Vec::new)
+            .extend(handlers);
+    }
+
+    /// Drops or delays the message to some of the specified primaries. Returns the primaries to which
+    /// the message should be sent right away.
+    fn inject_faults(&mut self, addresses: Vec<SocketAddr>, bytes: &Bytes) -> Vec<SocketAddr> {
+        let faults = match self.faults.as_mut() {
+            Some(faults) => faults,
+            None => return addresses,
+        };
+        let mut immediate = Vec::new();
+        for address in addresses {
+            match faults.next_fault() {
+                Fault::Deliver => immediate.push(address),
+                Fault::Drop => debug!("Dropping message to {}", address),
+                Fault::Delay(delay) => {
+                    let mut network = self.network.clone();
+                    let bytes = bytes.clone();
+                    tokio::spawn(async move {
+                        sleep(Duration::from_millis(delay)).await;
+                        // Keep the cancel handler until the message is acknowledged.
+                        let _ = network.send(address, bytes).await.await;
+                    });
+                }
+            }
+        }
+        immediate
+    }
+
+    /// Sends a conflicting header to half of the specified primaries if we equivocate. Returns the
+    /// primaries that should receive our actual header.
+    async fn equivocate(
+        &mut self,
+        header: &Header,
+        mut addresses: Vec<SocketAddr>,
+    ) -> Vec<SocketAddr> {
+        let conflicting = match self.faults.as_mut() {
+            Some(faults) => faults.conflicting_header(header).await,
+            None => None,
+        };
+        if let Some(conflicting) = conflicting {
+            debug!("Equivocating {:?} with {:?}", header, conflicting);
+            let others = addresses.split_off(addresses.len() / 2);
+            let bytes = bincode::serialize(&PrimaryMessage::Header(conflicting))
+                .expect("Failed to serialize our conflicting header");
+            self.broadcast(header.round, others, Bytes::from(bytes))
+                .await;
+        }
+        addresses
+    }
+
     async fn process_own_header(&mut self, header: Header) -> DagResult<()> {
         // Reset the votes aggregator.
         self.processing_headers
@@ -119,26 +306,42 @@ impl Core {
             .or_insert(VotesAggregator::new());
 
         // Broadcast the new header in a reliable manner.
-        let addresses = self
-            .committee
-            .others_primaries(&self.name)
-            .iter()
-            .map(|(_, x)| x.primary_to_primary)
-            .collect();
+        let addresses = self.broadcast_addresses();
+        let addresses = self.equivocate(&header, addresses).await;
         let bytes = bincode::serialize(&PrimaryMessage::Header(header.clone()))
             .expect("Failed to serialize our own header");
-        let handlers = self.network.broadcast(addresses, Bytes::from(bytes)).await;
-        self.cancel_handlers
-            .entry(header.round)
-            .or_insert_with(Vec::new)
-            .extend(handlers);
+        self.broadcast(header.round, addresses, Bytes::from(bytes))
+            .await;
 
         // Process the header.
         self.process_header(&header).await
     }
 
+    /// Enters the processing of a header, vote, or certificate, failing rather than nesting deeper
+    /// than `MAX_PROCESSING_DEPTH`, e.g. because of a bug making processing call itself. Every
+    /// successful call must be matched by a call to `leave`.
+    fn enter(&mut self) -> DagResult<()> {
+        ensure!(
+            self.processing_depth < MAX_PROCESSING_DEPTH,
+            DagError::ProcessingTooDeep(MAX_PROCESSING_DEPTH)
+        );
+        self.processing_depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.processing_depth -= 1;
+    }
+
     #[async_recursion]
     async fn process_header(&mut self, header: &Header) -> DagResult<()> {
+        self.enter()?;
+        let result = self.handle_header(header).await;
+        self.leave();
+        result
+    }
+
+    async fn handle_header(&mut self, header: &Header) -> DagResult<()> {
         // debug!("Processing {:?}", header);
 
         // Ensure we have the payload. If we don't, the synchronizer will ask our workers to get it, and then
@@ -149,8 +352,8 @@ impl Core {
         // }
 
         // Store the header.
-        let bytes = bincode::serialize(header).expect("Failed to serialize header");
-        self.store.write(header.id.to_vec(), bytes).await;
+        header.write(&mut self.store, self.store_codec).await;
+        self.track_stored(header.round, header.id.to_vec());
 
         // Check if we can vote for this header.
 
@@ -159,9 +362,7 @@ impl Core {
         // debug!("Created {:?}", vote);
 
         if vote.origin == self.name {
-            self.process_vote(vote)
-                .await
-                .expect("Failed to process our own vote");
+            self.process_vote(vote).await?;
         } else {
             let address = self
                 .committee
@@ -170,11 +371,8 @@ impl Core {
                 .primary_to_primary;
             let bytes = bincode::serialize(&PrimaryMessage::Vote(vote))
                 .expect("Failed to serialize our own vote");
-            let handler = self.network.send(address, Bytes::from(bytes)).await;
-            self.cancel_handlers
-                .entry(header.round)
-                .or_insert_with(Vec::new)
-                .push(handler);
+            self.broadcast(header.round, vec![address], Bytes::from(bytes))
+                .await;
         }
 
         Ok(())
@@ -182,6 +380,13 @@ impl Core {
 
     #[async_recursion]
     async fn process_vote(&mut self, vote: Vote) -> DagResult<()> {
+        self.enter()?;
+        let result = self.handle_vote(vote).await;
+        self.leave();
+        result
+    }
+
+    async fn handle_vote(&mut self, vote: Vote) -> DagResult<()> {
         // debug!("Processing {:?}", vote);
 
         if let (Some(header), Some(vote_aggregator)) = (
@@ -194,28 +399,23 @@ impl Core {
             {
                 // debug!("Assembled {:?}", certificate);
 
+                // Sending only fails when nobody subscribed.
+                if let Some(tx_assembled) = &self.tx_assembled {
+                    let _ = tx_assembled.send(certificate.clone());
+                }
+
                 // Broadcast the certificate.
-                let addresses = self
-                    .committee
-                    .others_primaries(&self.name)
-                    .iter()
-                    .map(|(_, x)| x.primary_to_primary)
-                    .collect();
+                let addresses = self.broadcast_addresses();
                 let bytes = bincode::serialize(&PrimaryMessage::Certificate(certificate.clone()))
                     .expect("Failed to serialize our own certificate");
-                let handlers = self.network.broadcast(addresses, Bytes::from(bytes)).await;
-                self.cancel_handlers
-                    .entry(certificate.round)
-                    .or_insert_with(Vec::new)
-                    .extend(handlers);
+                self.broadcast(certificate.round, addresses, Bytes::from(bytes))
+                    .await;
 
                 self.processing_headers.remove(&vote.id);
                 self.processing_vote_aggregators.remove(&vote.id);
 
                 // Process the new certificate.
-                self.process_certificate(certificate)
-                    .await
-                    .expect("Failed to process valid certificate");
+                self.process_certificate(certificate).await?;
             }
         }
         Ok(())
@@ -223,6 +423,13 @@ impl Core {
 
     #[async_recursion]
     async fn process_certificate(&mut self, certificate: Certificate) -> DagResult<()> {
+        self.enter()?;
+        let result = self.handle_certificate(certificate).await;
+        self.leave();
+        result
+    }
+
+    async fn handle_certificate(&mut self, certificate: Certificate) -> DagResult<()> {
         // debug!("Processing {:?}", certificate);
 
         // Process the header embedded in the certificate if we haven't already voted for it (if we already
@@ -238,9 +445,27 @@ impl Core {
         //     self.process_header(&certificate.header).await?;
         // }
 
+        // Skip the certificates we already processed.
+        if !self
+            .processed_certificates
+            .entry(certificate.round)
+            .or_default()
+            .insert(certificate.digest())
+        {
+            debug!("Ignoring duplicate certificate {:?}", certificate);
+            return Ok(());
+        }
+
         // Store the certificate.
-        let bytes = bincode::serialize(&certificate).expect("Failed to serialize certificate");
+        let bytes = self
+            .store_codec
+            .encode(&certificate)
+            .expect("Failed to serialize certificate");
         self.store.write(certificate.digest().to_vec(), bytes).await;
+        self.track_stored(certificate.round, certificate.digest().to_vec());
+
+        // Keep track of how far each authority got.
+        self.certified_rounds.record(&certificate);
 
         #[cfg(feature = "benchmark")]
         {
@@ -260,6 +485,32 @@ impl Core {
         Ok(())
     }
 
+    async fn process_verified_certificate(&mut self, certificate: Certificate) -> DagResult<()> {
+        // The garbage collector may have moved past the certificate while it was being verified. The
+        // `CertificateOrderer` already forgot about it, so it must not be processed.
+        self.check_gc_round(&certificate)?;
+
+        // Verification runs concurrently, so a certificate may be verified before the lower-round
+        // certificates of the same authority. Those are buffered until their predecessors are processed.
+        for certificate in self.certificate_orderer.verified(certificate) {
+            self.process_certificate(certificate).await?;
+        }
+        Ok(())
+    }
+
+    /// Drops a certificate that failed its verification, releasing the higher-round certificates of
+    /// the same authority that waited for it.
+    async fn process_invalid_certificate(
+        &mut self,
+        certificate: Certificate,
+        reason: String,
+    ) -> DagResult<()> {
+        for certificate in self.certificate_orderer.rejected(&certificate) {
+            self.process_certificate(certificate).await?;
+        }
+        Err(DagError::InvalidCertificate(certificate.digest(), reason))
+    }
+
     fn sanitize_header(&mut self, header: &Header) -> DagResult<()> {
         ensure!(
             self.gc_round <= header.round,
@@ -297,19 +548,26 @@ impl Core {
         pool: &ThreadPool,
         committee: Arc<Committee>,
     ) -> DagResult<()> {
-        ensure!(
-            self.gc_round <= certificate.round,
-            DagError::CertificateTooOld(certificate.digest(), certificate.round)
-        );
+        self.check_gc_round(&certificate)?;
 
+        // There is no need to verify again a certificate we already processed.
+        if self
+            .processed_certificates
+            .get(&certificate.round)
+            .map_or(false, |digests| digests.contains(&certificate.digest()))
+        {
+            debug!("Ignoring duplicate certificate {:?}", certificate);
+            return Ok(());
+        }
+
+        self.certificate_orderer.submit(&certificate);
         pool.execute(move || {
-            let _ = certificate.verify(&committee).map_err(DagError::from);
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async {
-                let _ = tx_primaries
-                    .send(PrimaryMessage::VerifiedCertificate(certificate))
-                    .await;
-            });
+            let message = match certificate.verify(&committee) {
+                Ok(()) => PrimaryMessage::VerifiedCertificate(certificate),
+                Err(e) => PrimaryMessage::InvalidCertificate(certificate, e.to_string()),
+            };
+            // The pool threads are outside of the runtime, so they can block until the core has room.
+            let _ = tx_primaries.blocking_send(message);
         });
         Ok(())
 
@@ -319,9 +577,49 @@ impl Core {
         //     .map_err(DagError::from)
     }
 
+    /// Fails if the certificate is below the garbage collection round, in which case it is counted as
+    /// dropped.
+    fn check_gc_round(&self, certificate: &Certificate) -> DagResult<()> {
+        if certificate.round < self.gc_round {
+            self.dropped_certificates.record();
+            bail!(DagError::CertificateTooOld(
+                certificate.digest(),
+                certificate.round
+            ));
+        }
+        Ok(())
+    }
+
+    /// Forgets everything below the garbage collection round.
+    fn garbage_collect(&mut self, gc_round: Round) {
+        self.last_voted.retain(|k, _| k >= &gc_round);
+        // self.processing.retain(|k, _| k >= &gc_round);
+        self.cancel_handlers.retain(|k, _| k >= &gc_round);
+        self.certificate_orderer.cleanup(gc_round);
+        self.processed_certificates.retain(|k, _| k >= &gc_round);
+        self.gc_round = gc_round;
+        // debug!("GC round moved to {}", self.gc_round);
+    }
+
+    /// Remembers a key we stored, to delete it once it falls out of the store retention.
+    fn track_stored(&mut self, round: Round, key: Vec<u8>) {
+        if self.store_retention.is_some() {
+            self.stored_keys.entry(round).or_default().push(key);
+        }
+    }
+
+    /// Deletes from the store the headers and certificates we stored below `round`.
+    async fn prune_store(&mut self, round: Round) {
+        let retained = self.stored_keys.split_off(&round);
+        let pruned = std::mem::replace(&mut self.stored_keys, retained);
+        for key in pruned.into_values().flatten() {
+            self.store.delete(key).await;
+        }
+    }
+
     // Main loop listening to incoming messages.
     pub async fn run(&mut self) {
-        let pool = ThreadPool::new(4);
+        let pool = ThreadPool::new(self.verification_threads);
 
         let committee = Arc::new(self.committee.clone());
         loop {
@@ -347,7 +645,11 @@ impl Core {
                             result
                         },
                         PrimaryMessage::VerifiedCertificate(certificate) => {
-                            let result = self.process_certificate(certificate).await;
+                            let result = self.process_verified_certificate(certificate).await;
+                            result
+                        },
+                        PrimaryMessage::InvalidCertificate(certificate, reason) => {
+                            let result = self.process_invalid_certificate(certificate, reason).await;
                             result
                         },
                         _ => panic!("Unexpected core message")
@@ -381,13 +683,23 @@ impl Core {
             // Cleanup internal state.
             let round = self.consensus_round.load(Ordering::Relaxed);
             if round > self.gc_depth {
-                let gc_round = round - self.gc_depth;
-                self.last_voted.retain(|k, _| k >= &gc_round);
-                // self.processing.retain(|k, _| k >= &gc_round);
-                self.cancel_handlers.retain(|k, _| k >= &gc_round);
-                self.gc_round = gc_round;
-                // debug!("GC round moved to {}", self.gc_round);
+                self.garbage_collect(round - self.gc_depth);
+            }
+            if let Some(retention) = self.store_retention {
+                if round > retention {
+                    self.prune_store(round - retention).await;
+                }
             }
+            release_acked_handlers(&mut self.cancel_handlers);
         }
     }
 }
+
+/// Drops the cancel handlers of the messages that were acknowledged (or given up on) by the network,
+/// so they do not accumulate until the garbage collector reaches their round.
+fn release_acked_handlers(cancel_handlers: &mut HashMap<Round, Vec<CancelHandler>>) {
+    cancel_handlers.retain(|_, handlers| {
+        handlers.retain_mut(|handler| matches!(handler.try_recv(), Err(TryRecvError::Empty)));
+        !handlers.is_empty()
+    });
+}
@@ -0,0 +1,69 @@
+use crate::messages::Certificate;
+use crate::primary::Round;
+use crypto::PublicKey;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+#[cfg(test)]
+#[path = "tests/diagnostics_tests.rs"]
+pub mod diagnostics_tests;
+
+/// Keeps track of the highest round at which we saw a certificate from each authority. An authority
+/// lagging far behind the others is likely slow or faulty. Clones share the same underlying state.
+#[derive(Clone, Default)]
+pub struct CertifiedRounds {
+    inner: Arc<RwLock<HashMap<PublicKey, Round>>>,
+}
+
+impl CertifiedRounds {
+    /// Records a certificate, raising the highest round of its origin if needed.
+    pub fn record(&self, certificate: &Certificate) {
+        let mut rounds = self.inner.write().unwrap();
+        let highest = rounds.entry(certificate.origin).or_insert(0);
+        if certificate.round > *highest {
+            *highest = certificate.round;
+        }
+    }
+
+    /// Returns the highest certified round of an authority, if we saw any of its certificates.
+    pub fn get(&self, name: &PublicKey) -> Option<Round> {
+        self.inner.read().unwrap().get(name).copied()
+    }
+
+    /// Returns the highest certified round of every authority we saw a certificate from.
+    pub fn snapshot(&self) -> HashMap<PublicKey, Round> {
+        self.inner.read().unwrap().clone()
+    }
+
+    /// Returns the authorities whose highest certified round is more than `max_gap` rounds behind
+    /// the highest certified round overall.
+    pub fn lagging(&self, max_gap: Round) -> Vec<(PublicKey, Round)> {
+        let rounds = self.inner.read().unwrap();
+        let top = rounds.values().copied().max().unwrap_or(0);
+        rounds
+            .iter()
+            .filter(|(_, round)| top - **round > max_gap)
+            .map(|(name, round)| (*name, *round))
+            .collect()
+    }
+}
+
+/// Counts the certificates dropped because they fell below the garbage collection round, either on
+/// arrival or while they were being verified. Clones share the same counter.
+#[derive(Clone, Default)]
+pub struct DroppedCertificates {
+    inner: Arc<AtomicU64>,
+}
+
+impl DroppedCertificates {
+    /// Records a certificate dropped by the garbage collector.
+    pub fn record(&self) {
+        self.inner.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of certificates dropped so far.
+    pub fn count(&self) -> u64 {
+        self.inner.load(Ordering::Relaxed)
+    }
+}
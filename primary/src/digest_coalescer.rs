@@ -0,0 +1,63 @@
+use crate::batch_maker::Batch;
+use log::debug;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::time::{sleep, Duration};
+
+#[cfg(test)]
+#[path = "tests/digest_coalescer_tests.rs"]
+pub mod digest_coalescer_tests;
+
+/// Coalesces the sealed batches of the worker into fewer deliveries to the primary: the batches
+/// arriving within `max_delay` ms of the first batch of a group are delivered together, so that the
+/// proposer wakes up once per group rather than once per batch. A batch thus waits at most
+/// `max_delay` ms before being handed to the primary.
+pub struct DigestCoalescer {
+    /// The maximum delay (in ms) a batch waits for other batches.
+    max_delay: u64,
+    /// Receives the sealed batches, from the `BatchMaker` or the `QuorumWaiter`.
+    rx_batch: Receiver<Batch>,
+    /// Channel to deliver the coalesced batches to the primary.
+    tx_digests: Sender<Batch>,
+}
+
+impl DigestCoalescer {
+    pub fn spawn(max_delay: u64, rx_batch: Receiver<Batch>, tx_digests: Sender<Batch>) {
+        tokio::spawn(async move {
+            Self {
+                max_delay,
+                rx_batch,
+                tx_digests,
+            }
+            .run()
+            .await;
+        });
+    }
+
+    /// Main loop opening a group with every batch that arrives while no group is open.
+    async fn run(&mut self) {
+        while let Some(mut group) = self.rx_batch.recv().await {
+            let mut batches = 1;
+            let window = sleep(Duration::from_millis(self.max_delay));
+            tokio::pin!(window);
+            loop {
+                tokio::select! {
+                    Some(batch) = self.rx_batch.recv() => {
+                        group.extend(batch);
+                        batches += 1;
+                    },
+                    () = &mut window => break,
+                }
+            }
+
+            debug!(
+                "Delivering {} coalesced batches ({} transactions) to the primary",
+                batches,
+                group.len()
+            );
+            self.tx_digests
+                .send(group)
+                .await
+                .expect("Failed to deliver batches to the primary");
+        }
+    }
+}
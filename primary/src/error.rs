@@ -1,5 +1,6 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use crate::primary::Round;
+use config::CodecError;
 use crypto::{BlsError, CryptoError, Digest, PublicKey};
 use store::StoreError;
 use thiserror::Error;
@@ -36,12 +37,18 @@ pub enum DagError {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] Box<bincode::ErrorKind>),
 
+    #[error("Serialization error: {0}")]
+    CodecError(#[from] CodecError),
+
     #[error("Invalid header id")]
     InvalidHeaderId,
 
     #[error("Malformed header {0}")]
     MalformedHeader(Digest),
 
+    #[error("The payload of header {0} is not in its shuffled order")]
+    InvalidPayloadOrder(Digest),
+
     #[error("Received message from unknown authority {0}")]
     UnknownAuthority(PublicKey),
 
@@ -51,6 +58,9 @@ pub enum DagError {
     #[error("Received unexpected vote fo header {0}")]
     UnexpectedVote(Digest),
 
+    #[error("Invalid certificate {0}: {1}")]
+    InvalidCertificate(Digest, String),
+
     #[error("Received certificate without a quorum")]
     CertificateRequiresQuorum,
 
@@ -65,4 +75,7 @@ pub enum DagError {
 
     #[error("Certificate {0} (round {1}) too old")]
     CertificateTooOld(Digest, Round),
+
+    #[error("Processing of headers, votes, and certificates nested more than {0} levels deep")]
+    ProcessingTooDeep(usize),
 }
@@ -0,0 +1,91 @@
+use crate::messages::Header;
+use config::FaultConfig;
+use crypto::{Digest, PublicKey, SignatureService};
+use ed25519_dalek::Digest as _;
+use ed25519_dalek::Sha512;
+use std::convert::TryInto as _;
+
+#[cfg(test)]
+#[path = "tests/faults_tests.rs"]
+pub mod faults_tests;
+
+/// What happens to an outgoing message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The message is sent as usual.
+    Deliver,
+    /// The message is never sent.
+    Drop,
+    /// The message is sent after the specified number of milliseconds.
+    Delay(u64),
+}
+
+/// Decides which faults to inject into the messages of a primary. The decisions only depend on the
+/// seed, on the name of the primary, and on the number of decisions taken so far, so that a run can
+/// be reproduced.
+pub struct FaultInjector {
+    config: FaultConfig,
+    /// The seed of this primary, derived from the configured seed and the name of the primary.
+    seed: Digest,
+    /// The number of random values drawn so far.
+    position: u64,
+    /// Signs the conflicting headers we send when equivocating.
+    signature_service: SignatureService,
+}
+
+impl FaultInjector {
+    // Only the primaries built with the `fault-injection` feature make injectors.
+    #[cfg_attr(not(feature = "fault-injection"), allow(dead_code))]
+    pub fn new(config: FaultConfig, name: &PublicKey, signature_service: SignatureService) -> Self {
+        let mut hasher = Sha512::new();
+        hasher.update(config.seed.to_le_bytes());
+        hasher.update(name);
+        let seed = Digest(hasher.finalize().as_slice()[..32].try_into().unwrap());
+        Self {
+            config,
+            seed,
+            position: 0,
+            signature_service,
+        }
+    }
+
+    /// Decides what happens to the next outgoing message.
+    pub fn next_fault(&mut self) -> Fault {
+        let sample = self.random_u64() as f64 / u64::MAX as f64;
+        if sample < self.config.drop_rate {
+            return Fault::Drop;
+        }
+        if self.config.max_delay > 0 {
+            return Fault::Delay(self.random_u64() % (self.config.max_delay + 1));
+        }
+        Fault::Deliver
+    }
+
+    /// Returns a header of the same author and round as `header` but with a different payload, or
+    /// `None` if we do not equivocate. An empty header cannot be equivocated: its digest only depends
+    /// on its author and round.
+    pub async fn conflicting_header(&mut self, header: &Header) -> Option<Header> {
+        if !self.config.equivocate || header.payload.is_empty() {
+            return None;
+        }
+        let mut payload = header.payload.clone();
+        payload.pop();
+        let conflicting = Header::new(
+            header.author,
+            header.round,
+            payload,
+            &mut self.signature_service,
+        )
+        .await;
+        Some(conflicting)
+    }
+
+    /// Returns the next pseudo-random value of the stream derived from the seed.
+    fn random_u64(&mut self) -> u64 {
+        let mut hasher = Sha512::new();
+        hasher.update(&self.seed);
+        hasher.update(self.position.to_le_bytes());
+        self.position += 1;
+        u64::from_le_bytes(hasher.finalize().as_slice()[..8].try_into().unwrap())
+    }
+}
@@ -1,7 +1,8 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
+use crate::messages::Certificate;
 use crate::primary::PrimaryMessage;
 use bytes::Bytes;
-use config::Committee;
+use config::{Committee, StoreCodec};
 use crypto::{Digest, PublicKey};
 use log::{error, warn};
 use network::SimpleSender;
@@ -14,6 +15,8 @@ pub struct Helper {
     committee: Committee,
     /// The persistent storage.
     store: Store,
+    /// The serialization format of the certificates in the store.
+    store_codec: StoreCodec,
     /// Input channel to receive certificates requests.
     rx_primaries: Receiver<(Vec<Digest>, PublicKey)>,
     /// A network sender to reply to the sync requests.
@@ -24,12 +27,14 @@ impl Helper {
     pub fn spawn(
         committee: Committee,
         store: Store,
+        store_codec: StoreCodec,
         rx_primaries: Receiver<(Vec<Digest>, PublicKey)>,
     ) {
         tokio::spawn(async move {
             Self {
                 committee,
                 store,
+                store_codec,
                 rx_primaries,
                 network: SimpleSender::new(),
             }
@@ -56,7 +61,9 @@ impl Helper {
                 match self.store.read(digest.to_vec()).await {
                     Ok(Some(data)) => {
                         // TODO: Remove this deserialization-serialization in the critical path.
-                        let certificate = bincode::deserialize(&data)
+                        let certificate: Certificate = self
+                            .store_codec
+                            .decode(&data)
                             .expect("Failed to deserialize our own certificate");
                         let bytes = bincode::serialize(&PrimaryMessage::Certificate(certificate))
                             .expect("Failed to serialize our own certificate");
@@ -1,15 +1,30 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 #[macro_use]
 mod error;
+mod aborts;
 mod aggregators;
+mod api;
+mod certificate_buffer;
+mod certificate_orderer;
 mod core;
+mod diagnostics;
+mod digest_coalescer;
+mod faults;
 mod garbage_collector;
+// Headers carry their payload and no parents, so the header waiter and the synchronizer have
+// nothing left to sync and stay disabled.
 // mod header_waiter;
 mod helper;
 mod messages;
 mod payload_receiver;
+mod pressure;
 mod primary;
 mod proposer;
+mod quorum_waiter;
+mod receipts;
+mod scheme;
+mod shuffle;
+mod trace;
 // mod synchronizer;
 mod batch_maker;
 mod worker;
@@ -18,6 +33,21 @@ mod worker;
 // #[path = "tests/common.rs"]
 // mod common;
 
+#[cfg(test)]
+#[path = "tests/test_utils.rs"]
+mod test_utils;
+
+pub use crate::aborts::{AbortStats, SenderOutcomes};
+pub use crate::diagnostics::{CertifiedRounds, DroppedCertificates};
 pub use crate::error::DagError;
-pub use crate::messages::{Certificate, Header};
+pub use crate::messages::{Certificate, Header, Vote};
+pub use crate::pressure::ExecutionPressure;
 pub use crate::primary::{Primary, PrimaryWorkerMessage, Round, WorkerPrimaryMessage};
+pub use crate::proposer::load_start_round;
+pub use crate::receipts::{
+    ChainHead, CommitAttestation, CommitGaps, ExecutionReceipt, ExecutionReceipts, ReceiptLookup,
+    StateLookup, StateReader,
+};
+pub use crate::scheme::{Blsttc, ThresholdSigScheme};
+pub use crate::trace::{TraceId, TraceIds, TRACE_ID_HEADER};
+pub use crate::worker::RejectReason;
@@ -2,22 +2,37 @@ use crate::batch_maker::Transaction;
 // Copyright(C) Facebook, Inc. and its affiliates.
 use crate::error::{DagError, DagResult};
 use crate::primary::Round;
-use blsttc::SignatureShareG1;
-use config::Committee;
-use crypto::{
-    combine_key_from_ids, BlsSignatureService, Digest, Hash, PublicKey, Signature, SignatureService,
-};
+use crate::scheme::{Blsttc, ThresholdSigScheme};
+use crate::shuffle::{is_shuffled, shuffle_payload, shuffle_seed};
+use config::{Committee, StoreCodec};
+use crypto::{BlsSignatureService, Digest, Hash, PublicKey, Signature, SignatureService};
 use ed25519_dalek::Digest as _;
 use ed25519_dalek::Sha512;
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
 use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use store::Store;
+
+/// Returns the current time in ms since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Failed to measure time")
+        .as_millis() as u64
+}
 
 #[derive(Clone, Serialize, Deserialize, Default)]
 pub struct Header {
     pub author: PublicKey,
     pub round: Round,
     pub payload: Vec<Transaction>,
+    /// Whether the payload is in the order `shuffle_payload` derives from the content of the header,
+    /// which receivers check.
+    pub shuffled: bool,
+    /// When the author made the header, in ms since the Unix epoch. The committer derives the time
+    /// of the blocks from it.
+    pub timestamp: u64,
     pub id: Digest,
     pub signature: Signature,
 }
@@ -33,9 +48,34 @@ impl Header {
             author,
             round,
             payload,
-            id: Digest::default(),
-            signature: Signature::default(),
+            timestamp: now(),
+            ..Self::default()
+        };
+        header.sign(signature_service).await
+    }
+
+    /// Makes a header whose payload is shuffled (see `shuffle_payload`), so that its author does not
+    /// choose the order in which its transactions execute.
+    pub async fn new_shuffled(
+        author: PublicKey,
+        round: Round,
+        payload: Vec<Transaction>,
+        signature_service: &mut SignatureService,
+    ) -> Self {
+        let seed = shuffle_seed(&author, round, &payload);
+        let header = Self {
+            author,
+            round,
+            payload: shuffle_payload(payload, &seed),
+            shuffled: true,
+            timestamp: now(),
+            ..Self::default()
         };
+        header.sign(signature_service).await
+    }
+
+    async fn sign(self, signature_service: &mut SignatureService) -> Self {
+        let header = self;
         let id = header.digest();
         let signature = signature_service.request_signature(id.clone()).await;
         Self {
@@ -45,6 +85,25 @@ impl Header {
         }
     }
 
+    /// Persists the header under its id. This is the key the `Committer` reads headers from, with
+    /// the same codec.
+    pub async fn write(&self, store: &mut Store, codec: StoreCodec) {
+        let bytes = codec.encode(self).expect("Failed to serialize header");
+        store.write(self.id.to_vec(), bytes).await;
+    }
+
+    /// Loads the header with the specified id, if it is in the store.
+    pub async fn read(
+        store: &mut Store,
+        id: &Digest,
+        codec: StoreCodec,
+    ) -> DagResult<Option<Self>> {
+        match store.read(id.to_vec()).await? {
+            Some(bytes) => Ok(Some(codec.decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
     pub fn verify(&self, committee: &Committee) -> DagResult<()> {
         // Ensure the header id is well formed.
         ensure!(self.digest() == self.id, DagError::InvalidHeaderId);
@@ -53,6 +112,12 @@ impl Header {
         let voting_rights = committee.stake(&self.author);
         ensure!(voting_rights > 0, DagError::UnknownAuthority(self.author));
 
+        // Ensure a shuffled payload is in the order derived from the header.
+        ensure!(
+            !self.shuffled || is_shuffled(&self.author, self.round, &self.payload),
+            DagError::InvalidPayloadOrder(self.id.clone())
+        );
+
         // Check the signature.
         self.signature
             .verify(&self.id, &self.author)
@@ -70,6 +135,10 @@ impl Hash for Header {
                 bcs::to_bytes(x).expect("failed to serialize transaction while hashing header");
             hasher.update(bytes);
         }
+        if self.shuffled {
+            hasher.update(b"shuffled");
+        }
+        hasher.update(self.timestamp.to_le_bytes());
         Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
     }
 }
@@ -87,12 +156,13 @@ impl fmt::Display for Header {
 }
 
 #[derive(Clone, Serialize, Deserialize)]
-pub struct Vote {
+#[serde(bound = "")]
+pub struct Vote<S: ThresholdSigScheme = Blsttc> {
     pub id: Digest,
     pub round: Round,
     pub origin: PublicKey,
     pub author: PublicKey,
-    pub signature: SignatureShareG1,
+    pub signature: S::SignatureShare,
 }
 
 impl Vote {
@@ -101,15 +171,28 @@ impl Vote {
         author: &PublicKey,
         bls_signature_service: &mut BlsSignatureService,
     ) -> Self {
-        let vote = Self {
+        let vote = Self::unsigned(header, author);
+        let signature = bls_signature_service.request_signature(vote.digest()).await;
+        Self { signature, ..vote }
+    }
+}
+
+impl<S: ThresholdSigScheme> Vote<S> {
+    /// Makes a vote for the header, signed with the key share of its author.
+    pub fn signed(header: &Header, author: &PublicKey, secret: &S::SecretKeyShare) -> Self {
+        let vote = Self::unsigned(header, author);
+        let signature = S::sign(&vote.digest(), secret);
+        Self { signature, ..vote }
+    }
+
+    fn unsigned(header: &Header, author: &PublicKey) -> Self {
+        Self {
             id: header.id.clone(),
             round: header.round,
             origin: header.author,
             author: *author,
-            signature: SignatureShareG1::default(),
-        };
-        let signature = bls_signature_service.request_signature(vote.digest()).await;
-        Self { signature, ..vote }
+            signature: S::SignatureShare::default(),
+        }
     }
 
     pub fn verify(&self, committee: &Committee) -> DagResult<()> {
@@ -118,15 +201,15 @@ impl Vote {
             committee.stake(&self.author) > 0,
             DagError::UnknownAuthority(self.author)
         );
-        // Check the signature.
-        // self.signature
-        //     .verify_with_nizk(&self.digest().0, &author_bls_key_g1, &self.zkp)
-        //     .map_err(DagError::from)
-        Ok(())
+
+        // Check the signature share against the key share of the author.
+        let key = S::public_key_share(committee, &self.author)
+            .ok_or(DagError::UnknownAuthority(self.author))?;
+        S::verify(&self.digest(), &key, &self.signature)
     }
 }
 
-impl Hash for Vote {
+impl<S: ThresholdSigScheme> Hash for Vote<S> {
     fn digest(&self) -> Digest {
         let mut hasher = Sha512::new();
         hasher.update(&self.id);
@@ -136,7 +219,7 @@ impl Hash for Vote {
     }
 }
 
-impl fmt::Debug for Vote {
+impl<S: ThresholdSigScheme> fmt::Debug for Vote<S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(
             f,
@@ -150,14 +233,16 @@ impl fmt::Debug for Vote {
 }
 
 #[derive(Clone, Serialize, Deserialize, Default)]
-pub struct Certificate {
+#[serde(bound = "")]
+pub struct Certificate<S: ThresholdSigScheme = Blsttc> {
     pub id: Digest,
     pub round: Round,
     pub origin: PublicKey,
-    pub votes: (u128, SignatureShareG1),
+    /// The bitmap of the signers and their aggregated signature.
+    pub votes: (u128, S::SignatureShare),
 }
 
-impl Certificate {
+impl<S: ThresholdSigScheme> Certificate<S> {
     // pub fn genesis(committee: &Committee) -> Vec<Self> {
     //     committee
     //         .authorities
@@ -202,17 +287,18 @@ impl Certificate {
                 ids.push(idx);
             }
         }
+        // Do not panic on certificates without votes, as they come from the network.
+        ensure!(!ids.is_empty(), DagError::CertificateRequiresQuorum);
 
         // let pks: Vec<PublicKeyShareG2> = ids.iter().map(|i| sorted_keys[*i]).collect();
-        let agg_pk = combine_key_from_ids(ids, &committee.sorted_keys);
+        let agg_pk = S::combine_keys(committee, ids);
 
         // Check the signatures.
-        SignatureShareG1::verify_batch(&self.digest().0, &agg_pk, &self.votes.1)
-            .map_err(DagError::from)
+        S::verify(&self.digest(), &agg_pk, &self.votes.1)
     }
 }
 
-impl Hash for Certificate {
+impl<S: ThresholdSigScheme> Hash for Certificate<S> {
     fn digest(&self) -> Digest {
         let mut hasher = Sha512::new();
         hasher.update(&self.id);
@@ -222,7 +308,7 @@ impl Hash for Certificate {
     }
 }
 
-impl fmt::Debug for Certificate {
+impl<S: ThresholdSigScheme> fmt::Debug for Certificate<S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(
             f,
@@ -235,7 +321,7 @@ impl fmt::Debug for Certificate {
     }
 }
 
-impl PartialEq for Certificate {
+impl<S: ThresholdSigScheme> PartialEq for Certificate<S> {
     fn eq(&self, other: &Self) -> bool {
         let mut ret = self.id == other.id;
         ret &= self.round == other.round;
@@ -0,0 +1,29 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counts the committed blocks that are not executed yet. The consensus raises it when committing
+/// a block and the committer lowers it once the block is executed; the proposer slows down when it
+/// grows too high. Clones share the same counter.
+#[derive(Clone, Default)]
+pub struct ExecutionPressure {
+    pending: Arc<AtomicUsize>,
+}
+
+impl ExecutionPressure {
+    /// Records a block sent to the committer.
+    pub fn block_committed(&self) {
+        self.pending.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a block the committer is done with.
+    pub fn block_executed(&self) {
+        let _ = self
+            .pending
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| x.checked_sub(1));
+    }
+
+    /// Returns the number of committed blocks waiting to be executed.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+}
@@ -1,12 +1,19 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
+use crate::aborts::AbortStats;
+use crate::certificate_buffer::CertificateBuffer;
 use crate::core::Core;
+use crate::diagnostics::{CertifiedRounds, DroppedCertificates};
 use crate::error::DagError;
+#[cfg(feature = "fault-injection")]
+use crate::faults::FaultInjector;
 use crate::garbage_collector::GarbageCollector;
 // use crate::header_waiter::HeaderWaiter;
 use crate::helper::Helper;
 use crate::messages::{Certificate, Header, Vote};
 use crate::payload_receiver::PayloadReceiver;
+use crate::pressure::ExecutionPressure;
 use crate::proposer::Proposer;
+use crate::receipts::ExecutionReceipts;
 // use crate::synchronizer::Synchronizer;
 use crate::worker::Worker;
 use async_trait::async_trait;
@@ -15,12 +22,13 @@ use config::{Committee, Parameters, WorkerId};
 use crypto::{BlsSignatureService, Digest, PublicKey, SignatureService};
 use futures::sink::SinkExt as _;
 use log::info;
-use network::{MessageHandler, Receiver as NetworkReceiver, Writer};
+use network::{MessageHandler, Receiver as NetworkReceiver, RetryPolicy, Writer};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use store::Store;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 /// The default channel capacity for each channel of the primary.
@@ -35,6 +43,7 @@ pub enum PrimaryMessage {
     Vote(Vote),
     Certificate(Certificate),
     VerifiedCertificate(Certificate),
+    InvalidCertificate(Certificate, /* reason */ String),
     CertificatesRequest(Vec<Digest>, /* requestor */ PublicKey),
 }
 
@@ -66,8 +75,15 @@ impl Primary {
         signature_service: SignatureService,
         bls_signature_service: BlsSignatureService,
         store: Store,
+        start_round: Round,
+        certified_rounds: CertifiedRounds,
+        dropped_certificates: DroppedCertificates,
+        receipts: ExecutionReceipts,
+        execution_pressure: ExecutionPressure,
+        rx_abort_stats: Receiver<AbortStats>,
         tx_consensus: Sender<Certificate>,
         rx_consensus: Receiver<Certificate>,
+        tx_assembled: Option<broadcast::Sender<Certificate>>,
     ) {
         let (_tx_others_digests, rx_others_digests) = channel(CHANNEL_CAPACITY);
         let (tx_our_digests, rx_our_digests) = channel(CHANNEL_CAPACITY);
@@ -82,8 +98,9 @@ impl Primary {
         // NOTE: These log entries are needed to compute performance.
         parameters.log(&committee);
 
-        // Atomic variable use to synchronizer all tasks with the latest consensus round. This is only
-        // used for cleanup. The only tasks that write into this variable is `GarbageCollector`.
+        // Atomic variable use to synchronizer all tasks with the latest consensus round. This is used
+        // for cleanup and to bound how far the `Proposer` runs ahead of commits. The only tasks that
+        // write into this variable is `GarbageCollector`.
         let consensus_round = Arc::new(AtomicU64::new(0));
 
         // Spawn the network receiver listening to messages from the other primaries.
@@ -130,6 +147,8 @@ impl Primary {
             committee.clone(),
             parameters.clone(),
             tx_our_digests,
+            receipts.clone(),
+            store.clone(),
         );
         // // The `Synchronizer` provides auxiliary methods helping to `Core` to sync.
         // let synchronizer = Synchronizer::new(
@@ -138,21 +157,59 @@ impl Primary {
         //     /* tx_header_waiter */ tx_sync_headers,
         // );
 
+        // Faults injected into the messages of the `Core`, for testing only.
+        #[cfg(feature = "fault-injection")]
+        let faults = parameters
+            .fault_injection
+            .clone()
+            .map(|config| FaultInjector::new(config, &name, signature_service.clone()));
+        #[cfg(not(feature = "fault-injection"))]
+        let faults = {
+            if parameters.fault_injection.is_some() {
+                log::warn!(
+                    "Ignoring the injected faults: built without the `fault-injection` feature"
+                );
+            }
+            None
+        };
+
+        // If required, the certificates go through the `CertificateBuffer` on their way to the consensus.
+        let tx_consensus = match parameters.certificate_buffer_capacity {
+            Some(capacity) => {
+                let (tx_buffer, rx_buffer) = channel(CHANNEL_CAPACITY);
+                CertificateBuffer::spawn(capacity, rx_buffer, tx_consensus);
+                tx_buffer
+            }
+            None => tx_consensus,
+        };
+
         // The `Core` receives and handles headers, votes, and certificates from the other primaries.
         Core::spawn(
             name,
             committee.clone(),
             store.clone(),
+            parameters.store_codec,
             // synchronizer,
             bls_signature_service.clone(),
             consensus_round.clone(),
             parameters.gc_depth,
+            parameters.store_retention,
+            parameters.prioritize_by_stake,
+            parameters.verification_threads(committee.size()),
+            RetryPolicy {
+                retry_delay: parameters.network_retry_delay,
+                max_retries: parameters.network_max_retries,
+            },
+            certified_rounds,
+            dropped_certificates,
             /* rx_primaries */ rx_primary_messages,
             /* rx_header_waiter */ rx_headers_loopback,
             /* rx_certificate_waiter */ rx_certificates_loopback,
             /* rx_proposer */ rx_headers,
             tx_consensus,
+            tx_assembled,
             tx_primary_messages,
+            faults,
         );
 
         // Keeps track of the latest consensus round and allows other tasks to clean up their their internal state
@@ -190,12 +247,29 @@ impl Primary {
             signature_service,
             parameters.header_size,
             parameters.max_header_delay,
+            parameters.max_txns_per_sender_per_header,
+            parameters.shuffle_header_payload,
+            execution_pressure,
+            parameters.execution_pressure_threshold,
+            parameters.heartbeat_delay,
+            consensus_round.clone(),
+            parameters.max_round_lead,
+            parameters.max_sender_abort_rate,
+            rx_abort_stats,
             /* rx_workers */ rx_our_digests,
             /* tx_core */ tx_headers,
+            store.clone(),
+            start_round,
+            receipts.trace_ids().clone(),
         );
 
         // The `Helper` is dedicated to reply to certificates requests from other primaries.
-        Helper::spawn(committee.clone(), store, rx_cert_requests);
+        Helper::spawn(
+            committee.clone(),
+            store,
+            parameters.store_codec,
+            rx_cert_requests,
+        );
 
         // NOTE: This log entry is used to compute performance.
         info!(
@@ -1,16 +1,48 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
+use crate::aborts::{AbortStats, AbortTracker};
 use crate::batch_maker::Transaction;
 use crate::messages::Header;
+use crate::pressure::ExecutionPressure;
 use crate::primary::Round;
+use crate::trace::TraceIds;
+use aptos_types::account_address::AccountAddress;
 use crypto::{PublicKey, SignatureService};
 #[cfg(feature = "benchmark")]
 use log::info;
+use log::{debug, log_enabled, Level};
+use std::collections::HashMap;
+use std::convert::TryInto as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use store::Store;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::time::{sleep, Duration, Instant};
 
-// #[cfg(test)]
-// #[path = "tests/proposer_tests.rs"]
-// pub mod proposer_tests;
+#[cfg(test)]
+#[path = "tests/proposer_tests.rs"]
+pub mod proposer_tests;
+
+/// The inter-header delay of a saturated proposer is doubled at most this many times.
+const MAX_DELAY_DOUBLINGS: usize = 3;
+
+/// How often (in ms) a proposer paused by its round lead checks whether commits caught up.
+const LEAD_CHECK_INTERVAL: u64 = 50;
+
+/// The store key under which the proposer records the round of its last header.
+pub const LAST_PROPOSED_ROUND_KEY: &[u8] = b"last_proposed_round";
+
+/// Returns the round at which the proposer should start: the round after the last one we
+/// proposed before a restart, or 1 for a fresh node.
+pub async fn load_start_round(store: &mut Store) -> Round {
+    match store.read(LAST_PROPOSED_ROUND_KEY.to_vec()).await {
+        Ok(Some(bytes)) => match bytes.as_slice().try_into() {
+            Ok(bytes) => Round::from_le_bytes(bytes) + 1,
+            Err(_) => panic!("Corrupted last proposed round in storage"),
+        },
+        Ok(None) => 1,
+        Err(e) => panic!("Failed to read last proposed round: {}", e),
+    }
+}
 
 /// The proposer creates new headers and send them to the core for broadcasting and further processing.
 pub struct Proposer {
@@ -22,16 +54,41 @@ pub struct Proposer {
     header_size: usize,
     /// The maximum delay to wait for batches' digests.
     max_header_delay: u64,
+    /// The maximum number of transactions of a single sender to include in a header, if any.
+    max_txns_per_sender: Option<usize>,
+    /// Whether to shuffle the payload of our headers rather than keeping it first-in first-out.
+    shuffle: bool,
+    /// The number of committed blocks waiting to be executed.
+    execution_pressure: ExecutionPressure,
+    /// Above this many blocks waiting to be executed, we stop creating headers as soon as we have
+    /// enough digests and back off the inter-header delay instead. Never throttled if unset.
+    pressure_threshold: Option<usize>,
+    /// If set, we propose an empty header after this long (in ms) without proposing any, so that the
+    /// dag keeps advancing while we receive no transactions.
+    heartbeat_delay: Option<u64>,
+    /// The last committed round, written by the `GarbageCollector`.
+    consensus_round: Arc<AtomicU64>,
+    /// If set, we stop creating headers while our round is more than this many rounds ahead of the
+    /// last committed round.
+    max_round_lead: Option<u64>,
+    /// Tells which senders abort too often to be given priority.
+    abort_tracker: AbortTracker,
+    /// Receives the abort statistics of the executed blocks from the committer.
+    rx_abort_stats: Receiver<AbortStats>,
     /// Receives the batches' digests from our workers.
     rx_workers: Receiver<Vec<Transaction>>,
     /// Sends newly created headers to the `Core`.
     tx_core: Sender<Header>,
+    /// The persistent storage, used to remember the round of our last header.
+    store: Store,
     /// The current round of the dag.
     round: Round,
     /// Holds the batches' digests waiting to be included in the next header.
     txns: Vec<Transaction>,
     /// Keeps track of the size (in bytes) of batches' digests that we received so far.
     payload_size: usize,
+    /// The trace ids under which the transactions are logged.
+    trace_ids: TraceIds,
 }
 
 impl Proposer {
@@ -41,8 +98,20 @@ impl Proposer {
         signature_service: SignatureService,
         header_size: usize,
         max_header_delay: u64,
+        max_txns_per_sender: Option<usize>,
+        shuffle: bool,
+        execution_pressure: ExecutionPressure,
+        pressure_threshold: Option<usize>,
+        heartbeat_delay: Option<u64>,
+        consensus_round: Arc<AtomicU64>,
+        max_round_lead: Option<u64>,
+        max_sender_abort_rate: Option<f64>,
+        rx_abort_stats: Receiver<AbortStats>,
         rx_workers: Receiver<Vec<Transaction>>,
         tx_core: Sender<Header>,
+        store: Store,
+        start_round: Round,
+        trace_ids: TraceIds,
     ) {
         tokio::spawn(async move {
             Self {
@@ -50,26 +119,101 @@ impl Proposer {
                 signature_service,
                 header_size,
                 max_header_delay,
+                max_txns_per_sender,
+                shuffle,
+                execution_pressure,
+                pressure_threshold,
+                heartbeat_delay,
+                consensus_round,
+                max_round_lead,
+                abort_tracker: AbortTracker::new(max_sender_abort_rate),
+                rx_abort_stats,
                 rx_workers,
                 tx_core,
-                round: 1,
+                store,
+                round: start_round,
                 txns: Vec::with_capacity(2 * header_size),
                 payload_size: 0,
+                trace_ids,
             }
             .run()
             .await;
         });
     }
 
+    /// Returns by how many blocks execution is behind the threshold, if it is.
+    fn excess_pressure(&self) -> Option<usize> {
+        let threshold = self.pressure_threshold?;
+        let pending = self.execution_pressure.pending();
+        (pending > threshold).then(|| pending - threshold)
+    }
+
+    /// Returns whether our round is too far ahead of the last committed round to propose.
+    fn lead_exceeded(&self) -> bool {
+        self.max_round_lead.map_or(false, |max| {
+            let committed = self.consensus_round.load(Ordering::Relaxed);
+            self.round.saturating_sub(committed) > max
+        })
+    }
+
+    /// Returns the inter-header delay, doubled for every block execution is behind the threshold.
+    fn header_delay(&self) -> Duration {
+        let doublings = self
+            .excess_pressure()
+            .map_or(0, |excess| excess.min(MAX_DELAY_DOUBLINGS));
+        Duration::from_millis(self.max_header_delay << doublings)
+    }
+
+    /// Takes the payload of the next header out of the buffer. At most `max_txns_per_sender` transactions
+    /// of each sender are taken, and a single one of each sender whose transactions keep aborting,
+    /// after all the others. The rest stays in the buffer (in order) for the next headers.
+    fn take_payload(&mut self) -> Vec<Transaction> {
+        let mut counts: HashMap<AccountAddress, usize> = HashMap::new();
+        let mut payload = Vec::with_capacity(self.txns.len());
+        let mut deprioritized = Vec::new();
+        let mut deferred = Vec::new();
+        for txn in std::mem::take(&mut self.txns) {
+            let sender = txn.sender();
+            let aborting = self.abort_tracker.is_deprioritized(&sender);
+            let max = if aborting {
+                Some(1)
+            } else {
+                self.max_txns_per_sender
+            };
+            let count = counts.entry(sender).or_insert(0);
+            *count += 1;
+            if max.map_or(false, |max| *count > max) {
+                deferred.push(txn);
+            } else if aborting {
+                deprioritized.push(txn);
+            } else {
+                payload.push(txn);
+            }
+        }
+        payload.extend(deprioritized);
+        self.txns = deferred;
+        payload
+    }
+
     async fn make_header(&mut self) {
         // Make a new header.
-        let header = Header::new(
-            self.name,
-            self.round,
-            self.txns.drain(..).collect(),
-            &mut self.signature_service,
-        )
-        .await;
+        let payload = self.take_payload();
+        let header = match self.shuffle {
+            true => {
+                Header::new_shuffled(self.name, self.round, payload, &mut self.signature_service)
+                    .await
+            }
+            false => Header::new(self.name, self.round, payload, &mut self.signature_service).await,
+        };
+        if log_enabled!(Level::Debug) {
+            for transaction in &header.payload {
+                debug!(
+                    "Header {:?} contains transaction (trace {})",
+                    header.id,
+                    self.trace_ids.resolve(transaction)
+                );
+            }
+        }
 
         #[cfg(feature = "benchmark")]
         {
@@ -87,6 +231,14 @@ impl Proposer {
             }
         }
 
+        // Remember the round of this header so that we do not propose it again after a restart.
+        self.store
+            .write(
+                LAST_PROPOSED_ROUND_KEY.to_vec(),
+                self.round.to_le_bytes().to_vec(),
+            )
+            .await;
+
         // Send the new header to the `Core` that will broadcast and process it.
         self.tx_core
             .send(header)
@@ -100,22 +252,39 @@ impl Proposer {
 
         let timer = sleep(Duration::from_millis(self.max_header_delay));
         tokio::pin!(timer);
+        let heartbeat = sleep(Duration::from_millis(
+            self.heartbeat_delay.unwrap_or_default(),
+        ));
+        tokio::pin!(heartbeat);
 
         loop {
             // Check if we can propose a new header. We propose a new header when one of the following
             // conditions is met:
-            // 1. Enough batches' digests;
-            // 2. The specified maximum inter-header delay has passed.
+            // 1. Enough batches' digests, unless execution is saturated;
+            // 2. The (possibly backed off) inter-header delay has passed, and we either have some
+            //    digests or did not propose for a heartbeat delay.
+            // In both cases, we wait for commits to catch up if we are too far ahead of them.
+            let paused = self.lead_exceeded();
             let enough_digests = self.payload_size >= self.header_size;
+            let saturated = self.excess_pressure().is_some();
             let timer_expired = timer.is_elapsed();
-            if (timer_expired && self.payload_size > 0) || enough_digests {
+            let heartbeat_due = self.heartbeat_delay.is_some() && heartbeat.is_elapsed();
+            if !paused
+                && ((timer_expired && (self.payload_size > 0 || heartbeat_due))
+                    || (enough_digests && !saturated))
+            {
                 // Make a new header.
                 self.make_header().await;
-                self.payload_size = 0;
+                self.payload_size = self.txns.iter().map(serialized_len).sum();
 
                 // Reschedule the timer.
-                let deadline = Instant::now() + Duration::from_millis(self.max_header_delay);
+                let deadline = Instant::now() + self.header_delay();
                 timer.as_mut().reset(deadline);
+                if let Some(delay) = self.heartbeat_delay {
+                    heartbeat
+                        .as_mut()
+                        .reset(Instant::now() + Duration::from_millis(delay));
+                }
             }
 
             tokio::select! {
@@ -124,10 +293,19 @@ impl Proposer {
                         transactions.iter().map(serialized_len).sum::<usize>();
                     self.txns.extend(transactions);
                 }
-                () = &mut timer => {
+                Some(stats) = self.rx_abort_stats.recv() => self.abort_tracker.record(&stats),
+                // Once expired, the timer stays expired until we propose: do not spin on it.
+                () = &mut timer, if !timer.is_elapsed() => {
                     // Nothing to do.
 
                 }
+                () = &mut heartbeat, if self.heartbeat_delay.is_some() && !heartbeat.is_elapsed() => {
+                    // Nothing to do.
+                }
+                // Nothing tells us when commits catch up: check again shortly.
+                () = sleep(Duration::from_millis(LEAD_CHECK_INTERVAL)), if paused => {
+                    // Nothing to do.
+                }
             }
         }
     }
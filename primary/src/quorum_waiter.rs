@@ -0,0 +1,124 @@
+// Copyright(C) Facebook, Inc. and its affiliates.
+use crate::batch_maker::Batch;
+use config::{Committee, Stake};
+use crypto::PublicKey;
+use futures::stream::futures_unordered::FuturesUnordered;
+use futures::stream::StreamExt as _;
+use log::warn;
+use network::CancelHandler;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+#[cfg(test)]
+#[path = "tests/quorum_waiter_tests.rs"]
+pub mod quorum_waiter_tests;
+
+#[derive(Debug)]
+pub struct QuorumWaiterMessage {
+    /// The batch we broadcast to the other workers.
+    pub batch: Batch,
+    /// The cancel handlers to receive the acknowledgements of our broadcast.
+    pub handlers: Vec<(PublicKey, CancelHandler)>,
+}
+
+/// The QuorumWaiter waits for a quorum of workers (2f+1 stake, counting ours) to acknowledge
+/// reception of a batch before delivering it to the primary. It waits for the acknowledgements of
+/// several batches at once, so that a batch some workers are slow to store does not hold up the next
+/// ones.
+pub struct QuorumWaiter {
+    /// The committee information.
+    committee: Committee,
+    /// The stake of this authority.
+    stake: Stake,
+    /// Input Channel to receive commands.
+    rx_message: Receiver<QuorumWaiterMessage>,
+    /// Channel to deliver batches for which we have enough acknowledgements.
+    tx_batch: Sender<Batch>,
+}
+
+impl QuorumWaiter {
+    /// Spawn a new QuorumWaiter.
+    pub fn spawn(
+        committee: Committee,
+        stake: Stake,
+        rx_message: Receiver<QuorumWaiterMessage>,
+        tx_batch: Sender<Batch>,
+    ) {
+        tokio::spawn(async move {
+            Self {
+                committee,
+                stake,
+                rx_message,
+                tx_batch,
+            }
+            .run()
+            .await;
+        });
+    }
+
+    /// Helper function. It waits for a future to complete and then delivers a value.
+    async fn waiter(wait_for: CancelHandler, deliver: Stake) -> Stake {
+        match wait_for.await {
+            Ok(_) => deliver,
+            // The message was cancelled: it does not count towards the quorum.
+            Err(_) => 0,
+        }
+    }
+
+    /// Waits until the workers acknowledging a batch hold a quorum of stake (counting ours), or until
+    /// every broadcast completed or was cancelled. Returns the batch with the stake that acknowledged
+    /// it.
+    async fn wait_for_quorum(
+        batch: Batch,
+        acks: Vec<(Stake, CancelHandler)>,
+        mut total_stake: Stake,
+        threshold: Stake,
+    ) -> (Batch, Stake) {
+        let mut acks: FuturesUnordered<_> = acks
+            .into_iter()
+            .map(|(stake, handler)| Self::waiter(handler, stake))
+            .collect();
+        while total_stake < threshold {
+            match acks.next().await {
+                Some(stake) => total_stake += stake,
+                None => break,
+            }
+        }
+        (batch, total_stake)
+    }
+
+    /// Main loop.
+    async fn run(&mut self) {
+        let threshold = self.committee.quorum_threshold();
+        let mut waiting = FuturesUnordered::new();
+        loop {
+            tokio::select! {
+                Some(QuorumWaiterMessage { batch, handlers }) = self.rx_message.recv() => {
+                    let acks = handlers
+                        .into_iter()
+                        .map(|(name, handler)| (self.committee.stake(&name), handler))
+                        .collect();
+                    waiting.push(Self::wait_for_quorum(batch, acks, self.stake, threshold));
+                },
+
+                // Once a quorum of workers sent back an Ack, we consider the batch delivered and we
+                // send it to the primary (that will include it into a header).
+                Some((batch, total_stake)) = waiting.next() => {
+                    if total_stake >= threshold {
+                        self.tx_batch
+                            .send(batch)
+                            .await
+                            .expect("Failed to deliver batch");
+                    } else {
+                        warn!(
+                            "Dropping batch of {} transactions: only {} stake acknowledged it",
+                            batch.len(),
+                            total_stake
+                        );
+                    }
+                },
+
+                else => break,
+            }
+        }
+    }
+}
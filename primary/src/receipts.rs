@@ -0,0 +1,350 @@
+use crate::primary::Round;
+use crate::trace::TraceIds;
+use aptos_types::account_address::AccountAddress;
+use aptos_types::contract_event::ContractEvent;
+use aptos_types::state_store::state_key::StateKey;
+use config::GenesisFramework;
+use crypto::{CryptoError, Digest, PublicKey, Signature};
+use ed25519_dalek::Digest as _;
+use ed25519_dalek::Sha512;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto as _;
+use std::sync::{Arc, RwLock};
+
+#[cfg(test)]
+#[path = "tests/receipts_tests.rs"]
+pub mod receipts_tests;
+
+/// The outcome of a committed transaction, as returned to clients.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionReceipt {
+    /// The hash of the transaction, as returned on submission.
+    pub hash: String,
+    /// The VM status of the transaction.
+    pub status: String,
+    /// Whether the transaction made it into the ledger. Discarded transactions may be committed again.
+    pub kept: bool,
+    /// The gas charged to the sender.
+    pub gas_used: u64,
+    /// The events emitted by the transaction.
+    pub events: Vec<ContractEvent>,
+}
+
+/// The result of looking up the receipt of a transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReceiptLookup {
+    Found(ExecutionReceipt),
+    /// The transaction was executed, but its receipt is no longer retained.
+    Pruned,
+    /// The transaction was never executed, or was pruned long ago.
+    Unknown,
+}
+
+/// The result of reading a state value through `ExecutionReceipts::state_value`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StateLookup {
+    /// The BCS-encoded value.
+    Found(Vec<u8>),
+    /// Nothing is stored at the key.
+    Absent,
+    /// The node does not execute transactions, so it has no state to read.
+    Unavailable,
+    /// The state could not be read.
+    Failed(String),
+}
+
+/// Reads the state left by the executed blocks. The committer registers one with
+/// `ExecutionReceipts::set_state_reader`, through which the transaction API serves state values.
+pub trait StateReader: Send + Sync {
+    /// Returns the BCS-encoded value stored at `key`, if any.
+    fn state_value(&self, key: &StateKey) -> Result<Option<Vec<u8>>, String>;
+
+    /// Returns the sequence number of the next transaction of `address`, from its
+    /// `0x1::account::Account` resource: zero if the account does not exist.
+    fn sequence_number(&self, address: &AccountAddress) -> Result<u64, String>;
+}
+
+/// The rounds skipped by the committed certificates, counted by the committer if it checks that the
+/// committed rounds advance without gaps (`detect_commit_gaps`). Skipped rounds may hold transactions
+/// that were never executed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CommitGaps {
+    /// The number of gaps detected so far.
+    pub gaps: u64,
+    /// The number of rounds skipped so far.
+    pub skipped_rounds: u64,
+    /// The first and last rounds of the most recent gap, if any.
+    pub last_gap: Option<(Round, Round)>,
+}
+
+/// The last block of the hash chain of the executed blocks. Each block hash commits to the hash of the
+/// previous block, so that a light client can verify a contiguous sequence of blocks.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChainHead {
+    /// The highest round committed so far: the highest round of the certificates of the block, or of
+    /// the later commits without transactions to execute, which do not extend the chain.
+    pub round: Round,
+    /// The hash of the block.
+    pub block_hash: String,
+    /// The signature of the node over this head, if it attests the blocks it executes.
+    pub attestation: Option<CommitAttestation>,
+}
+
+impl ChainHead {
+    /// Returns the digest signed by the attestations of this head.
+    pub fn digest(&self) -> Digest {
+        let mut hasher = Sha512::new();
+        hasher.update(self.round.to_le_bytes());
+        hasher.update(self.block_hash.as_bytes());
+        Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+    }
+}
+
+/// A signature over a chain head with the attestation key of the node that executed the chain. The
+/// attestation key is separate from the consensus keys, and is advertised by the status endpoint of
+/// the transaction API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CommitAttestation {
+    /// The attestation key that signed the head.
+    pub signer: PublicKey,
+    pub signature: Signature,
+}
+
+impl CommitAttestation {
+    /// Checks that this attestation of `head` was signed with `key`.
+    pub fn verify(&self, head: &ChainHead, key: &PublicKey) -> Result<(), CryptoError> {
+        self.signature.verify(&head.digest(), key)
+    }
+}
+
+/// The execution receipts of the most recently committed transactions, written by the `Committer` and
+/// read by the transaction API. Once `capacity` receipts are held, the oldest ones are pruned; the
+/// committer also prunes the receipts of the rounds it no longer retains. The hashes of the last
+/// `capacity` pruned receipts are remembered, so that clients can tell them from unknown transactions.
+/// It also keeps the head of the chain of executed blocks and the gaps in the committed rounds, and
+/// gives access to the executed state, from which the sequence numbers of the senders are read, and
+/// to the trace ids assigned by the clients of the transaction API. Clones share the same underlying
+/// state.
+#[derive(Clone)]
+pub struct ExecutionReceipts {
+    inner: Arc<RwLock<Inner>>,
+    trace_ids: TraceIds,
+}
+
+struct Inner {
+    capacity: usize,
+    /// The receipts and the round of the block that executed them.
+    receipts: HashMap<String, (Round, ExecutionReceipt)>,
+    /// The hashes of the receipts and their round, from the oldest to the most recent. A replaced
+    /// receipt leaves a stale entry behind, skipped when pruned.
+    order: VecDeque<(Round, String)>,
+    /// The hashes of the pruned receipts.
+    pruned: HashSet<String>,
+    /// The hashes of the pruned receipts, from the first pruned to the last.
+    pruned_order: VecDeque<String>,
+    /// The last executed block, if any.
+    chain_head: Option<ChainHead>,
+    /// Reads the executed state, if the node executes transactions.
+    state: Option<Arc<dyn StateReader>>,
+    /// The key with which the chain heads are attested, if they are.
+    attestation_key: Option<PublicKey>,
+    /// The framework installed by the genesis of the executor and the hash of the genesis, if known.
+    framework: Option<(GenesisFramework, String)>,
+    /// The rounds skipped by the committed certificates, if they are counted.
+    commit_gaps: Option<CommitGaps>,
+}
+
+impl ExecutionReceipts {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Inner {
+                capacity,
+                receipts: HashMap::new(),
+                order: VecDeque::new(),
+                pruned: HashSet::new(),
+                pruned_order: VecDeque::new(),
+                chain_head: None,
+                state: None,
+                attestation_key: None,
+                framework: None,
+                commit_gaps: None,
+            })),
+            trace_ids: TraceIds::default(),
+        }
+    }
+
+    /// Records the receipt of a transaction executed in the block of `round`, replacing any previous
+    /// receipt of the same transaction (e.g. a transaction discarded then committed again). A kept
+    /// receipt is never replaced by a discarded one: executing a transaction again (e.g. a duplicate
+    /// committed without deduplication) discards it, yet it stays in the ledger.
+    pub fn insert(&self, round: Round, receipt: ExecutionReceipt) {
+        let mut inner = self.inner.write().unwrap();
+        let hash = normalize(&receipt.hash);
+        if !receipt.kept
+            && matches!(inner.receipts.get(&hash), Some((_, previous)) if previous.kept)
+        {
+            return;
+        }
+        inner.pruned.remove(&hash);
+        let previous = inner.receipts.insert(hash.clone(), (round, receipt));
+        if previous.map(|(x, _)| x) != Some(round) {
+            inner.order.push_back((round, hash));
+        }
+        while inner.receipts.len() > inner.capacity {
+            if inner.prune_oldest().is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Prunes the receipts of the transactions executed before `round`.
+    pub fn prune_below(&self, round: Round) {
+        let mut inner = self.inner.write().unwrap();
+        while inner
+            .order
+            .front()
+            .map_or(false, |(oldest, _)| *oldest < round)
+        {
+            inner.prune_oldest();
+        }
+    }
+
+    /// Returns the receipt of a committed transaction, unless it is unknown or was pruned.
+    pub fn get(&self, hash: &str) -> Option<ExecutionReceipt> {
+        match self.lookup(hash) {
+            ReceiptLookup::Found(receipt) => Some(receipt),
+            ReceiptLookup::Pruned | ReceiptLookup::Unknown => None,
+        }
+    }
+
+    /// Returns the receipt of a committed transaction, or whether it was pruned.
+    pub fn lookup(&self, hash: &str) -> ReceiptLookup {
+        let inner = self.inner.read().unwrap();
+        let hash = normalize(hash);
+        match inner.receipts.get(&hash) {
+            Some((_, receipt)) => ReceiptLookup::Found(receipt.clone()),
+            None if inner.pruned.contains(&hash) => ReceiptLookup::Pruned,
+            None => ReceiptLookup::Unknown,
+        }
+    }
+
+    /// Returns the sequence number of the next transaction of `sender` in the executed state, or
+    /// `None` if the node does not execute transactions.
+    pub fn sequence_number(&self, sender: &AccountAddress) -> Option<Result<u64, String>> {
+        let reader = self.inner.read().unwrap().state.clone();
+        reader.map(|reader| reader.sequence_number(sender))
+    }
+
+    /// Records the last executed block.
+    pub fn set_chain_head(&self, head: ChainHead) {
+        self.inner.write().unwrap().chain_head = Some(head);
+    }
+
+    /// Returns the last executed block, if any.
+    pub fn chain_head(&self) -> Option<ChainHead> {
+        self.inner.read().unwrap().chain_head.clone()
+    }
+
+    /// Records the key with which the chain heads are attested.
+    pub fn set_attestation_key(&self, key: PublicKey) {
+        self.inner.write().unwrap().attestation_key = Some(key);
+    }
+
+    /// Returns the key with which the chain heads are attested, if they are.
+    pub fn attestation_key(&self) -> Option<PublicKey> {
+        self.inner.read().unwrap().attestation_key
+    }
+
+    /// Records the framework installed by the genesis of the executor, and the hash of the genesis.
+    pub fn set_framework(&self, release: GenesisFramework, hash: String) {
+        self.inner.write().unwrap().framework = Some((release, hash));
+    }
+
+    /// Returns the framework installed by the genesis of the executor and the hash of the genesis, if
+    /// known.
+    pub fn framework(&self) -> Option<(GenesisFramework, String)> {
+        self.inner.read().unwrap().framework.clone()
+    }
+
+    /// Starts counting the rounds skipped by the committed certificates.
+    pub fn count_commit_gaps(&self) {
+        self.inner
+            .write()
+            .unwrap()
+            .commit_gaps
+            .get_or_insert_with(CommitGaps::default);
+    }
+
+    /// Records that the committed certificates skipped the rounds from `first` to `last` (both
+    /// included), if they are counted.
+    pub fn record_commit_gap(&self, first: Round, last: Round) {
+        if let Some(gaps) = self.inner.write().unwrap().commit_gaps.as_mut() {
+            gaps.gaps += 1;
+            gaps.skipped_rounds += last - first + 1;
+            gaps.last_gap = Some((first, last));
+        }
+    }
+
+    /// Returns the rounds skipped by the committed certificates, unless they are not counted.
+    pub fn commit_gaps(&self) -> Option<CommitGaps> {
+        self.inner.read().unwrap().commit_gaps
+    }
+
+    /// Returns the trace ids assigned by clients to the transactions they submitted.
+    pub fn trace_ids(&self) -> &TraceIds {
+        &self.trace_ids
+    }
+
+    /// Registers the reader of the executed state.
+    pub fn set_state_reader(&self, reader: Arc<dyn StateReader>) {
+        self.inner.write().unwrap().state = Some(reader);
+    }
+
+    /// Returns the value stored at `key` by the blocks executed so far. Values are read while the
+    /// committer executes, so that a value may already reflect part of the block being executed.
+    pub fn state_value(&self, key: &StateKey) -> StateLookup {
+        let reader = self.inner.read().unwrap().state.clone();
+        match reader {
+            Some(reader) => match reader.state_value(key) {
+                Ok(Some(value)) => StateLookup::Found(value),
+                Ok(None) => StateLookup::Absent,
+                Err(e) => StateLookup::Failed(e),
+            },
+            None => StateLookup::Unavailable,
+        }
+    }
+
+    /// Returns the number of receipts currently held.
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().receipts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Inner {
+    /// Prunes the oldest entry of `order`, returning its round. The receipt is only dropped if it was
+    /// not replaced since.
+    fn prune_oldest(&mut self) -> Option<Round> {
+        let (round, hash) = self.order.pop_front()?;
+        if self.receipts.get(&hash).map_or(false, |(x, _)| *x == round) {
+            self.receipts.remove(&hash);
+            self.pruned.insert(hash.clone());
+            self.pruned_order.push_back(hash);
+            while self.pruned_order.len() > self.capacity {
+                if let Some(forgotten) = self.pruned_order.pop_front() {
+                    self.pruned.remove(&forgotten);
+                }
+            }
+        }
+        Some(round)
+    }
+}
+
+/// Hashes are looked up regardless of their case and of a `0x` prefix.
+fn normalize(hash: &str) -> String {
+    hash.trim_start_matches("0x").to_lowercase()
+}
@@ -0,0 +1,82 @@
+use crate::error::{DagError, DagResult};
+use blsttc::{PublicKeyShareG2, SecretKeyShare, SignatureShareG1};
+use config::Committee;
+use crypto::{aggregate_sign, combine_key_from_ids, Digest, PublicKey};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[cfg(test)]
+#[path = "tests/scheme_tests.rs"]
+pub mod scheme_tests;
+
+/// The threshold signature scheme with which authorities vote for headers. Every authority signs
+/// with its key share, and the shares of a quorum aggregate into the signature of a certificate,
+/// verified against the combined key of its signers.
+pub trait ThresholdSigScheme: Clone + Default + Send + Sync + 'static {
+    type SecretKeyShare;
+    type PublicKeyShare: Clone;
+    type SignatureShare: Clone + Default + Serialize + DeserializeOwned + Send + Sync;
+
+    /// Signs a digest with the key share of an authority.
+    fn sign(digest: &Digest, secret: &Self::SecretKeyShare) -> Self::SignatureShare;
+
+    /// Returns the key share of an authority, or `None` if it is not in the committee.
+    fn public_key_share(committee: &Committee, name: &PublicKey) -> Option<Self::PublicKeyShare>;
+
+    /// Returns the position of an authority in the bitmap of the signers of a certificate.
+    fn signer_index(committee: &Committee, key: &Self::PublicKeyShare) -> Option<usize>;
+
+    /// Adds a signature share to an aggregated signature.
+    fn aggregate(
+        aggregated: &Self::SignatureShare,
+        share: &Self::SignatureShare,
+    ) -> Self::SignatureShare;
+
+    /// Combines the key shares of the signers at the provided (non-empty) positions.
+    fn combine_keys(committee: &Committee, signers: Vec<usize>) -> Self::PublicKeyShare;
+
+    /// Checks an aggregated signature against the combined key of its signers.
+    fn verify(
+        digest: &Digest,
+        key: &Self::PublicKeyShare,
+        signature: &Self::SignatureShare,
+    ) -> DagResult<()>;
+}
+
+/// BLS signature shares over G1 with keys over G2, from `blsttc`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Blsttc;
+
+impl ThresholdSigScheme for Blsttc {
+    type SecretKeyShare = SecretKeyShare;
+    type PublicKeyShare = PublicKeyShareG2;
+    type SignatureShare = SignatureShareG1;
+
+    fn sign(digest: &Digest, secret: &SecretKeyShare) -> SignatureShareG1 {
+        SignatureShareG1::new(&digest.0, secret)
+    }
+
+    fn public_key_share(committee: &Committee, name: &PublicKey) -> Option<PublicKeyShareG2> {
+        committee.authorities.get(name).map(|x| x.bls_pubkey_g2)
+    }
+
+    fn signer_index(committee: &Committee, key: &PublicKeyShareG2) -> Option<usize> {
+        committee.sorted_keys.binary_search(key).ok()
+    }
+
+    fn aggregate(aggregated: &SignatureShareG1, share: &SignatureShareG1) -> SignatureShareG1 {
+        aggregate_sign(aggregated, share)
+    }
+
+    fn combine_keys(committee: &Committee, signers: Vec<usize>) -> PublicKeyShareG2 {
+        combine_key_from_ids(signers, &committee.sorted_keys)
+    }
+
+    fn verify(
+        digest: &Digest,
+        key: &PublicKeyShareG2,
+        signature: &SignatureShareG1,
+    ) -> DagResult<()> {
+        SignatureShareG1::verify_batch(&digest.0, key, signature).map_err(DagError::from)
+    }
+}
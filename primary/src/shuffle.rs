@@ -0,0 +1,66 @@
+use crate::batch_maker::Transaction;
+use crate::primary::Round;
+use aptos_types::account_address::AccountAddress;
+use crypto::{Digest, PublicKey};
+use ed25519_dalek::Digest as _;
+use ed25519_dalek::Sha512;
+use std::collections::BTreeMap;
+use std::convert::TryInto as _;
+
+#[cfg(test)]
+#[path = "tests/shuffle_tests.rs"]
+pub mod shuffle_tests;
+
+/// Derives the shuffle seed of a header from its author, its round and the set of its transactions.
+/// The seed does not depend on the order of the transactions, so it is fixed by the content of the
+/// header: the author can only change the order it yields by changing what the header carries.
+pub fn shuffle_seed(author: &PublicKey, round: Round, payload: &[Transaction]) -> Digest {
+    let mut hashes: Vec<_> = payload.iter().map(|x| x.committed_hash()).collect();
+    hashes.sort();
+    let mut hasher = Sha512::new();
+    hasher.update(b"shuffle");
+    hasher.update(author);
+    hasher.update(round.to_le_bytes());
+    for hash in hashes {
+        hasher.update(hash.to_vec());
+    }
+    Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+}
+
+/// Shuffles the senders of the transactions (Fisher-Yates) while the transactions of each sender stay
+/// ordered by sequence number, so that they still execute one after the other. The result only
+/// depends on the seed and on the set of transactions, so every node holding them reconstructs the
+/// same order.
+pub fn shuffle_payload(payload: Vec<Transaction>, seed: &Digest) -> Vec<Transaction> {
+    let mut senders: BTreeMap<AccountAddress, Vec<Transaction>> = BTreeMap::new();
+    for transaction in payload {
+        senders
+            .entry(transaction.sender())
+            .or_default()
+            .push(transaction);
+    }
+    let mut groups: Vec<_> = senders.into_iter().map(|(_, group)| group).collect();
+    for group in &mut groups {
+        group.sort_by_cached_key(|x| (x.sequence_number(), x.committed_hash()));
+    }
+    for i in (1..groups.len()).rev() {
+        let j = (random_u64(seed, i as u64) % (i as u64 + 1)) as usize;
+        groups.swap(i, j);
+    }
+    groups.into_iter().flatten().collect()
+}
+
+/// Returns whether the payload of a header of `author` at `round` is in the order `shuffle_payload`
+/// derives from its content.
+pub fn is_shuffled(author: &PublicKey, round: Round, payload: &[Transaction]) -> bool {
+    let seed = shuffle_seed(author, round, payload);
+    shuffle_payload(payload.to_vec(), &seed) == payload
+}
+
+/// Returns the pseudo-random value of the specified position of the stream derived from the seed.
+fn random_u64(seed: &Digest, position: u64) -> u64 {
+    let mut hasher = Sha512::new();
+    hasher.update(seed);
+    hasher.update(position.to_le_bytes());
+    u64::from_le_bytes(hasher.finalize().as_slice()[..8].try_into().unwrap())
+}
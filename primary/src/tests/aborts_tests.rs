@@ -0,0 +1,67 @@
+use super::*;
+
+// Fixture: the statistics of a block in which `sender` has `executed` and `aborted` transactions.
+fn block(sender: AccountAddress, executed: u64, aborted: u64) -> AbortStats {
+    let mut stats = AbortStats::default();
+    stats
+        .senders
+        .insert(sender, SenderOutcomes { executed, aborted });
+    stats
+}
+
+#[test]
+fn senders_are_judged_on_enough_transactions() {
+    let sender = AccountAddress::new([1; 32]);
+    let mut tracker = AbortTracker::new(Some(0.5));
+
+    // A couple of aborts are not enough to be deprioritized.
+    tracker.record(&block(sender, 0, 2));
+    assert!(!tracker.is_deprioritized(&sender));
+
+    tracker.record(&block(sender, 0, 5));
+    assert!(tracker.is_deprioritized(&sender));
+}
+
+#[test]
+fn senders_recover_once_their_transactions_execute() {
+    let sender = AccountAddress::new([1; 32]);
+    let mut tracker = AbortTracker::new(Some(0.5));
+    for _ in 0..10 {
+        tracker.record(&block(sender, 1, 1));
+    }
+    assert!(!tracker.is_deprioritized(&sender));
+
+    for _ in 0..10 {
+        tracker.record(&block(sender, 0, 1));
+    }
+    assert!(tracker.is_deprioritized(&sender));
+
+    // The old aborts fade away as the new transactions execute.
+    for _ in 0..20 {
+        tracker.record(&block(sender, 1, 0));
+    }
+    assert!(!tracker.is_deprioritized(&sender));
+}
+
+#[test]
+fn idle_senders_are_forgotten() {
+    let sender = AccountAddress::new([1; 32]);
+    let other = AccountAddress::new([2; 32]);
+    let mut tracker = AbortTracker::new(Some(0.5));
+    tracker.record(&block(sender, 0, 10));
+    assert!(tracker.is_deprioritized(&sender));
+
+    for _ in 0..50 {
+        tracker.record(&block(other, 1, 0));
+    }
+    assert!(!tracker.is_deprioritized(&sender));
+    assert!(!tracker.senders.contains_key(&sender));
+}
+
+#[test]
+fn nobody_is_deprioritized_without_a_rate() {
+    let sender = AccountAddress::new([1; 32]);
+    let mut tracker = AbortTracker::new(None);
+    tracker.record(&block(sender, 0, 100));
+    assert!(!tracker.is_deprioritized(&sender));
+}
@@ -0,0 +1,74 @@
+use super::*;
+use config::committee_builder::CommitteeBuilder;
+use crypto::{BlsSignatureService, SignatureService};
+
+#[tokio::test]
+async fn votes_of_unknown_authorities_are_rejected() {
+    let mut test = CommitteeBuilder::new(4).build().unwrap();
+    let committee = test.committee;
+    // An authority of another committee, e.g. one that left ours.
+    let removed = CommitteeBuilder::new(1).build().unwrap().keys.remove(0);
+    let ours = test.keys.remove(0);
+    let header = Header::new(
+        ours.name,
+        1,
+        Vec::new(),
+        &mut SignatureService::new(ours.secret),
+    )
+    .await;
+
+    let mut aggregator = VotesAggregator::new();
+    let mut bls_signature_service = BlsSignatureService::new(removed.bls_secret);
+    let vote = Vote::new(&header, &removed.name, &mut bls_signature_service).await;
+    assert!(matches!(
+        aggregator.append(vote, &committee, &header),
+        Err(DagError::UnknownAuthority(author)) if author == removed.name
+    ));
+
+    // An authority whose key is missing from the sorted keys is rejected as well.
+    let member = test.keys.remove(0);
+    let mut bls_signature_service = BlsSignatureService::new(member.bls_secret);
+    let mut stale = committee.clone();
+    stale.sorted_keys.clear();
+    let vote = Vote::new(&header, &member.name, &mut bls_signature_service).await;
+    assert!(matches!(
+        aggregator.append(vote, &stale, &header),
+        Err(DagError::UnknownAuthority(author)) if author == member.name
+    ));
+
+    // A rejected vote does not count as the vote of its author.
+    let vote = Vote::new(&header, &member.name, &mut bls_signature_service).await;
+    assert!(aggregator.append(vote, &committee, &header).is_ok());
+}
+
+#[tokio::test]
+async fn votes_signed_with_another_key_share_are_rejected() {
+    let mut test = CommitteeBuilder::new(4).build().unwrap();
+    let committee = test.committee;
+    let ours = test.keys.remove(0);
+    let header = Header::new(
+        ours.name,
+        1,
+        Vec::new(),
+        &mut SignatureService::new(ours.secret),
+    )
+    .await;
+
+    // An authority votes with the key share of another.
+    let member = test.keys.remove(0);
+    let other = test.keys.remove(0);
+    let mut bls_signature_service = BlsSignatureService::new(other.bls_secret);
+    let vote = Vote::new(&header, &member.name, &mut bls_signature_service).await;
+    assert!(matches!(
+        vote.verify(&committee),
+        Err(DagError::InvalidBlsSignature(..))
+    ));
+    let mut aggregator = VotesAggregator::new();
+    assert!(aggregator.append(vote, &committee, &header).is_err());
+
+    // The forged vote did not count as its vote: it can still vote with its own key share.
+    let mut bls_signature_service = BlsSignatureService::new(member.bls_secret);
+    let vote = Vote::new(&header, &member.name, &mut bls_signature_service).await;
+    assert!(vote.verify(&committee).is_ok());
+    assert!(aggregator.append(vote, &committee, &header).is_ok());
+}
@@ -0,0 +1,209 @@
+use super::*;
+use crate::test_utils::transaction;
+use tokio::sync::mpsc::channel;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn group_transactions_by_sender() {
+    let (tx_transaction, rx_transaction) = channel(10);
+    let (tx_digests, mut rx_digests) = channel(1);
+
+    // Spawn a batch maker that only seals batches when its timer fires.
+    BatchMaker::spawn(
+        /* batch_size */ 1_000_000,
+        /* max_batch_delay */ 200,
+        rx_transaction,
+        tx_digests,
+        /* max_pending_batches */ 100,
+        /* workers_addresses */ Vec::new(),
+        /* tx_quorum_waiter */ None,
+        /* group_by_sender */ true,
+        TraceIds::default(),
+        /* rx_reload */ channel(1).1,
+    );
+
+    // Interleave the transactions of three senders.
+    let senders: Vec<_> = (1..=3).map(|i| AccountAddress::new([i; 32])).collect();
+    for sequence_number in 0..3 {
+        for sender in &senders {
+            tx_transaction
+                .send(transaction(*sender, sequence_number))
+                .await
+                .unwrap();
+        }
+    }
+
+    // Ensure each sender's transactions are contiguous and in sequence order.
+    let batch = rx_digests.recv().await.unwrap();
+    let order: Vec<_> = batch
+        .iter()
+        .map(|x| (x.sender(), x.sequence_number()))
+        .collect();
+    let expected: Vec<_> = senders
+        .iter()
+        .flat_map(|sender| (0..3).map(move |sequence_number| (*sender, sequence_number)))
+        .collect();
+    assert_eq!(order, expected);
+}
+
+#[test]
+fn group_by_sender_sorts_by_sequence_number() {
+    let sender = AccountAddress::new([1; 32]);
+    let other = AccountAddress::new([2; 32]);
+    let batch = vec![
+        transaction(sender, 1),
+        transaction(other, 0),
+        transaction(sender, 0),
+    ];
+    let order: Vec<_> = group_by_sender(batch)
+        .iter()
+        .map(|x| (x.sender(), x.sequence_number()))
+        .collect();
+    assert_eq!(order, vec![(sender, 0), (sender, 1), (other, 0)]);
+}
+
+#[tokio::test]
+async fn slow_primary_throttles_transactions() {
+    let (tx_transaction, rx_transaction) = channel(1);
+    let (tx_digests, mut rx_digests) = channel(1);
+
+    // Spawn a batch maker sealing a batch per transaction and buffering at most two batches.
+    BatchMaker::spawn(
+        /* batch_size */ 1,
+        /* max_batch_delay */ 1_000_000,
+        rx_transaction,
+        tx_digests,
+        /* max_pending_batches */ 2,
+        /* workers_addresses */ Vec::new(),
+        /* tx_quorum_waiter */ None,
+        /* group_by_sender */ false,
+        TraceIds::default(),
+        /* rx_reload */ channel(1).1,
+    );
+
+    // The primary does not read any batch: the batch maker soon stops accepting transactions.
+    let sender = AccountAddress::new([1; 32]);
+    let mut accepted = 0;
+    for sequence_number in 0..20 {
+        match tx_transaction.try_send(transaction(sender, sequence_number)) {
+            Ok(()) => accepted += 1,
+            Err(TrySendError::Full(_)) => break,
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    // One batch in the channel, two pending, and one transaction waiting to be received.
+    assert_eq!(accepted, 4);
+
+    // Once the primary catches up, every accepted transaction is delivered in order.
+    for sequence_number in 0..accepted {
+        let batch = timeout(Duration::from_secs(1), rx_digests.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let order: Vec<_> = batch.iter().map(|x| x.sequence_number()).collect();
+        assert_eq!(order, vec![sequence_number]);
+    }
+
+    // And the batch maker accepts transactions again.
+    tx_transaction
+        .try_send(transaction(sender, accepted))
+        .unwrap();
+    let batch = timeout(Duration::from_secs(1), rx_digests.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(batch[0].sequence_number(), accepted);
+}
+
+#[tokio::test]
+async fn slow_quorum_waiter_throttles_transactions() {
+    let (tx_transaction, rx_transaction) = channel(1);
+    let (tx_quorum_waiter, mut rx_quorum_waiter) = channel(1);
+
+    // Spawn a batch maker handing a batch per transaction to the quorum waiter, buffering at most
+    // two batches.
+    BatchMaker::spawn(
+        /* batch_size */ 1,
+        /* max_batch_delay */ 1_000_000,
+        rx_transaction,
+        /* tx_digests */ channel(1).0,
+        /* max_pending_batches */ 2,
+        /* workers_addresses */ Vec::new(),
+        Some(tx_quorum_waiter),
+        /* group_by_sender */ false,
+        TraceIds::default(),
+        /* rx_reload */ channel(1).1,
+    );
+
+    // The quorum waiter does not read any batch: the batch maker stops accepting transactions
+    // instead of waiting on it with a transaction in hand.
+    let sender = AccountAddress::new([1; 32]);
+    let mut accepted = 0;
+    for sequence_number in 0..20 {
+        match tx_transaction.try_send(transaction(sender, sequence_number)) {
+            Ok(()) => accepted += 1,
+            Err(TrySendError::Full(_)) => break,
+            Err(e) => panic!("Unexpected error: {}", e),
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(accepted, 4);
+
+    // Once the quorum waiter catches up, every accepted transaction is delivered in order.
+    for sequence_number in 0..accepted {
+        let message = timeout(Duration::from_secs(1), rx_quorum_waiter.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let order: Vec<_> = message.batch.iter().map(|x| x.sequence_number()).collect();
+        assert_eq!(order, vec![sequence_number]);
+    }
+}
+
+#[tokio::test]
+async fn reload_keeps_the_transactions_of_the_current_batch() {
+    let (tx_transaction, rx_transaction) = channel(10);
+    let (tx_digests, mut rx_digests) = channel(10);
+    let (tx_reload, rx_reload) = channel(1);
+
+    // Spawn a batch maker that seals nothing on its own during the test.
+    BatchMaker::spawn(
+        /* batch_size */ 1_000_000,
+        /* max_batch_delay */ 1_000_000,
+        rx_transaction,
+        tx_digests,
+        /* max_pending_batches */ 100,
+        /* workers_addresses */ Vec::new(),
+        /* tx_quorum_waiter */ None,
+        /* group_by_sender */ false,
+        TraceIds::default(),
+        rx_reload,
+    );
+
+    // Accumulate a partial batch.
+    let sender = AccountAddress::new([1; 32]);
+    for sequence_number in 0..3 {
+        tx_transaction
+            .send(transaction(sender, sequence_number))
+            .await
+            .unwrap();
+    }
+    sleep(Duration::from_millis(100)).await;
+    assert!(rx_digests.try_recv().is_err());
+
+    // Reload with a short batch delay: the partial batch is sealed with the new parameters.
+    tx_reload
+        .send(BatchParameters {
+            batch_size: 1_000_000,
+            max_batch_delay: 50,
+        })
+        .await
+        .unwrap();
+    let batch = timeout(Duration::from_secs(1), rx_digests.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let order: Vec<_> = batch.iter().map(|x| x.sequence_number()).collect();
+    assert_eq!(order, vec![0, 1, 2]);
+}
@@ -0,0 +1,31 @@
+use super::*;
+use tokio::sync::oneshot;
+
+#[test]
+fn acked_handlers_are_released_before_gc() {
+    let (tx_acked, acked) = oneshot::channel();
+    let (_tx_pending, pending) = oneshot::channel();
+    let mut cancel_handlers = HashMap::new();
+    cancel_handlers.insert(1, vec![acked]);
+    cancel_handlers.insert(2, vec![pending]);
+
+    // The first message is acknowledged quickly, the GC round did not move.
+    tx_acked.send(Bytes::from("Ack")).unwrap();
+    release_acked_handlers(&mut cancel_handlers);
+
+    // Only the handler still waiting for an ACK is kept.
+    assert!(!cancel_handlers.contains_key(&1));
+    assert_eq!(cancel_handlers.get(&2).map(Vec::len), Some(1));
+}
+
+#[test]
+fn dropped_handlers_are_released() {
+    let (tx_dropped, dropped) = oneshot::channel::<Bytes>();
+    let mut cancel_handlers = HashMap::new();
+    cancel_handlers.insert(1, vec![dropped]);
+
+    // The network gave up on the message.
+    drop(tx_dropped);
+    release_acked_handlers(&mut cancel_handlers);
+    assert!(cancel_handlers.is_empty());
+}
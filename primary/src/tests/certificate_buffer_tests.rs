@@ -0,0 +1,59 @@
+use super::*;
+use crypto::Digest;
+use tokio::sync::mpsc::channel;
+use tokio::time::{timeout, Duration};
+
+// Fixture
+fn certificate(round: u64) -> Certificate {
+    Certificate {
+        round,
+        id: Digest([round as u8; 32]),
+        ..Certificate::default()
+    }
+}
+
+#[tokio::test]
+async fn bursts_are_absorbed_and_forwarded_in_order() {
+    let (tx_core, rx_core) = channel(1);
+    let (tx_consensus, mut rx_consensus) = channel(1);
+    CertificateBuffer::spawn(100, rx_core, tx_consensus);
+
+    // The burst is taken without waiting for the consensus.
+    for round in 1..=50 {
+        timeout(Duration::from_secs(1), tx_core.send(certificate(round)))
+            .await
+            .expect("The buffer did not absorb the burst")
+            .unwrap();
+    }
+
+    for round in 1..=50 {
+        assert_eq!(rx_consensus.recv().await.unwrap().round, round);
+    }
+}
+
+#[tokio::test]
+async fn a_full_buffer_applies_backpressure() {
+    let (tx_core, rx_core) = channel(1);
+    let (tx_consensus, mut rx_consensus) = channel(1);
+    CertificateBuffer::spawn(3, rx_core, tx_consensus);
+
+    // One certificate in the consensus channel, three in the buffer, and one in the core channel.
+    for round in 1..=5 {
+        timeout(Duration::from_secs(1), tx_core.send(certificate(round)))
+            .await
+            .expect("The buffer should have room")
+            .unwrap();
+    }
+    let blocked = timeout(Duration::from_millis(200), tx_core.send(certificate(6))).await;
+    assert!(blocked.is_err(), "The buffer took more than its capacity");
+
+    // Once the consensus takes a certificate, there is room again.
+    assert_eq!(rx_consensus.recv().await.unwrap().round, 1);
+    timeout(Duration::from_secs(1), tx_core.send(certificate(6)))
+        .await
+        .expect("The buffer did not resume")
+        .unwrap();
+    for round in 2..=6 {
+        assert_eq!(rx_consensus.recv().await.unwrap().round, round);
+    }
+}
@@ -0,0 +1,88 @@
+use super::*;
+use crypto::generate_keypair;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+
+// Fixture
+fn certificate(origin: PublicKey, round: Round) -> Certificate {
+    Certificate {
+        origin,
+        round,
+        ..Certificate::default()
+    }
+}
+
+#[test]
+fn out_of_order_verification_is_processed_in_round_order() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let (name, _) = generate_keypair(&mut rng);
+
+    let mut orderer = CertificateOrderer::default();
+    orderer.submit(&certificate(name, 1));
+    orderer.submit(&certificate(name, 2));
+
+    // The verification of the round 2 certificate completes first: it waits for round 1.
+    assert!(orderer.verified(certificate(name, 2)).is_empty());
+
+    // Once round 1 is verified, both are released in round order.
+    let rounds: Vec<_> = orderer
+        .verified(certificate(name, 1))
+        .iter()
+        .map(|x| x.round)
+        .collect();
+    assert_eq!(rounds, vec![1, 2]);
+}
+
+#[test]
+fn authorities_are_ordered_independently() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let (a, _) = generate_keypair(&mut rng);
+    let (b, _) = generate_keypair(&mut rng);
+
+    let mut orderer = CertificateOrderer::default();
+    orderer.submit(&certificate(a, 1));
+    orderer.submit(&certificate(b, 2));
+
+    // A pending certificate of `a` does not hold back the certificates of `b`.
+    assert_eq!(orderer.verified(certificate(b, 2)).len(), 1);
+    assert_eq!(orderer.verified(certificate(a, 1)).len(), 1);
+}
+
+#[test]
+fn rejected_certificates_release_the_higher_rounds() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let (name, _) = generate_keypair(&mut rng);
+
+    let mut orderer = CertificateOrderer::default();
+    orderer.submit(&certificate(name, 1));
+    orderer.submit(&certificate(name, 2));
+    assert!(orderer.verified(certificate(name, 2)).is_empty());
+
+    // Round 1 fails its verification: round 2 no longer waits for it, and round 1 is not released.
+    let rounds: Vec<_> = orderer
+        .rejected(&certificate(name, 1))
+        .iter()
+        .map(|x| x.round)
+        .collect();
+    assert_eq!(rounds, vec![2]);
+}
+
+#[test]
+fn cleanup_drops_buffered_certificates() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let (name, _) = generate_keypair(&mut rng);
+
+    let mut orderer = CertificateOrderer::default();
+    orderer.submit(&certificate(name, 1));
+    orderer.submit(&certificate(name, 2));
+    assert!(orderer.verified(certificate(name, 2)).is_empty());
+
+    // Once round 1 is garbage collected, round 2 no longer waits for it.
+    orderer.cleanup(2);
+    let rounds: Vec<_> = orderer
+        .verified(certificate(name, 3))
+        .iter()
+        .map(|x| x.round)
+        .collect();
+    assert_eq!(rounds, vec![2, 3]);
+}
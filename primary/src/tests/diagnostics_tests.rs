@@ -0,0 +1,35 @@
+use super::*;
+use crypto::generate_keypair;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+
+// Fixture
+fn certificate(origin: PublicKey, round: Round) -> Certificate {
+    Certificate {
+        origin,
+        round,
+        ..Certificate::default()
+    }
+}
+
+#[test]
+fn record_highest_round_per_authority() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let (a, _) = generate_keypair(&mut rng);
+    let (b, _) = generate_keypair(&mut rng);
+    let (c, _) = generate_keypair(&mut rng);
+
+    let rounds = CertifiedRounds::default();
+    rounds.record(&certificate(a, 3));
+    rounds.record(&certificate(b, 10));
+    rounds.record(&certificate(a, 7));
+    // Older certificates do not lower the recorded round.
+    rounds.record(&certificate(b, 4));
+
+    assert_eq!(rounds.get(&a), Some(7));
+    assert_eq!(rounds.get(&b), Some(10));
+    assert_eq!(rounds.get(&c), None);
+    assert_eq!(rounds.snapshot().len(), 2);
+    assert_eq!(rounds.lagging(5), Vec::new());
+    assert_eq!(rounds.lagging(2), vec![(a, 7)]);
+}
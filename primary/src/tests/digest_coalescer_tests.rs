@@ -0,0 +1,48 @@
+use super::*;
+use crate::batch_maker::Transaction;
+use crate::test_utils::transaction;
+use aptos_types::account_address::AccountAddress;
+use tokio::sync::mpsc::channel;
+use tokio::time::{timeout, Instant};
+
+#[tokio::test]
+async fn batches_within_the_window_are_delivered_together() {
+    const MAX_DELAY: u64 = 200;
+    let (tx_batch, rx_batch) = channel(10);
+    let (tx_digests, mut rx_digests) = channel(10);
+    DigestCoalescer::spawn(MAX_DELAY, rx_batch, tx_digests);
+
+    // Feed several small batches in a quick succession.
+    let start = Instant::now();
+    let batches: Vec<Batch> = (0..4)
+        .map(|i| {
+            vec![
+                transaction(AccountAddress::ONE, 2 * i),
+                transaction(AccountAddress::ONE, 2 * i + 1),
+            ]
+        })
+        .collect();
+    for batch in batches.clone() {
+        tx_batch.send(batch).await.unwrap();
+    }
+
+    // They are delivered as a single group, in order, once the window closes.
+    let group = timeout(Duration::from_millis(MAX_DELAY * 5), rx_digests.recv())
+        .await
+        .expect("The batches were not delivered in time")
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(MAX_DELAY));
+    assert_eq!(group, batches.concat());
+
+    // A batch arriving after the window opens a new group.
+    tx_batch
+        .send(vec![transaction(AccountAddress::ONE, 8)])
+        .await
+        .unwrap();
+    let group = timeout(Duration::from_millis(MAX_DELAY * 5), rx_digests.recv())
+        .await
+        .expect("The batch was not delivered in time")
+        .unwrap();
+    assert_eq!(group, vec![transaction(AccountAddress::ONE, 8)]);
+    assert!(rx_digests.try_recv().is_err());
+}
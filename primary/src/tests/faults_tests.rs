@@ -0,0 +1,142 @@
+use super::*;
+use crate::core::Core;
+use crate::diagnostics::{CertifiedRounds, DroppedCertificates};
+use crate::primary::{PrimaryMessage, Round};
+use async_trait::async_trait;
+use bytes::Bytes;
+use config::committee_builder::CommitteeBuilder;
+use config::StoreCodec;
+use crypto::{generate_keypair, BlsSignatureService};
+use futures::future::join_all;
+use futures::sink::SinkExt as _;
+use network::{MessageHandler, Receiver as NetworkReceiver, RetryPolicy, Writer};
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use std::error::Error;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use store::Store;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::time::{sleep, timeout, Duration};
+
+/// Forwards the messages of the other primaries to the core, like the handler of the `Primary`.
+#[derive(Clone)]
+struct Forwarder(Sender<PrimaryMessage>);
+
+#[async_trait]
+impl MessageHandler for Forwarder {
+    async fn dispatch(&self, writer: &mut Writer, serialized: Bytes) -> Result<(), Box<dyn Error>> {
+        let _ = writer.send(Bytes::from("Ack")).await;
+        let message: PrimaryMessage = bincode::deserialize(&serialized)?;
+        let _ = self.0.send(message).await;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn faults_are_deterministic_by_seed() {
+    let (name, secret) = generate_keypair(&mut StdRng::from_seed([0; 32]));
+    let config = FaultConfig {
+        drop_rate: 0.2,
+        max_delay: 50,
+        seed: 7,
+        ..FaultConfig::default()
+    };
+    let signature_service = SignatureService::new(secret);
+    let mut first = FaultInjector::new(config.clone(), &name, signature_service.clone());
+    let mut second = FaultInjector::new(config, &name, signature_service);
+
+    let faults: Vec<_> = (0..10_000).map(|_| first.next_fault()).collect();
+    let replayed: Vec<_> = (0..10_000).map(|_| second.next_fault()).collect();
+    assert_eq!(faults, replayed);
+
+    // Roughly the configured fraction of the messages is dropped, the others are delayed.
+    let dropped = faults.iter().filter(|x| **x == Fault::Drop).count();
+    assert!((1_800..2_200).contains(&dropped), "{} dropped", dropped);
+    assert!(faults
+        .iter()
+        .all(|x| matches!(x, Fault::Drop | Fault::Delay(0..=50))));
+}
+
+#[tokio::test]
+async fn progress_despite_dropped_messages() {
+    const ROUNDS: Round = 10;
+
+    let test = CommitteeBuilder::new(4).base_port(17_000).build().unwrap();
+    let committee = test.committee;
+
+    // Run the core of every primary, each dropping 20% of its outgoing messages.
+    let mut proposers = Vec::new();
+    let mut outputs = Vec::new();
+    for keys in test.keys {
+        let name = keys.name;
+        let (tx_primaries, rx_primaries) = channel(1_000);
+        let (_tx_header_waiter, rx_header_waiter) = channel(1);
+        let (_tx_certificate_waiter, rx_certificate_waiter) = channel(1);
+        let (tx_proposer, rx_proposer) = channel(1_000);
+        let (tx_consensus, rx_consensus) = channel(1_000);
+
+        let address = committee.primary(&name).unwrap().primary_to_primary;
+        NetworkReceiver::spawn(address, Forwarder(tx_primaries.clone()));
+
+        let signature_service = SignatureService::new(keys.secret);
+        let config = FaultConfig {
+            drop_rate: 0.2,
+            seed: 42,
+            ..FaultConfig::default()
+        };
+        let faults = FaultInjector::new(config, &name, signature_service.clone());
+        Core::spawn(
+            name,
+            committee.clone(),
+            Store::new_in_memory(),
+            StoreCodec::default(),
+            BlsSignatureService::new(keys.bls_secret),
+            Arc::new(AtomicU64::new(0)),
+            /* gc_depth */ 50,
+            /* store_retention */ None,
+            /* prioritize_by_stake */ false,
+            /* verification_threads */ 1,
+            RetryPolicy::default(),
+            CertifiedRounds::default(),
+            DroppedCertificates::default(),
+            rx_primaries,
+            rx_header_waiter,
+            rx_certificate_waiter,
+            rx_proposer,
+            tx_consensus,
+            /* tx_assembled */ None,
+            tx_primaries,
+            Some(faults),
+        );
+        proposers.push((name, signature_service, tx_proposer));
+        outputs.push(rx_consensus);
+    }
+
+    // Every primary proposes a header at each round.
+    tokio::spawn(async move {
+        for round in 1..=ROUNDS {
+            for (name, signature_service, tx_proposer) in &mut proposers {
+                let header = Header::new(*name, round, Vec::new(), signature_service).await;
+                tx_proposer.send(header).await.unwrap();
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        // Keep the proposers alive until the test ends.
+        sleep(Duration::from_secs(60)).await;
+    });
+
+    // Every primary still delivers certificates up to the last round.
+    let highest_rounds = outputs.into_iter().map(|mut rx_consensus| async move {
+        let mut highest = 0;
+        while highest < ROUNDS {
+            let certificate = rx_consensus.recv().await.unwrap();
+            highest = highest.max(certificate.round);
+        }
+        highest
+    });
+    let highest_rounds = timeout(Duration::from_secs(20), join_all(highest_rounds))
+        .await
+        .expect("The primaries stopped making progress");
+    assert!(highest_rounds.iter().all(|x| *x == ROUNDS));
+}
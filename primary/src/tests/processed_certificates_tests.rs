@@ -0,0 +1,466 @@
+use super::*;
+use crate::certificate_buffer::CertificateBuffer;
+use config::committee_builder::{CommitteeBuilder, TestCommittee};
+use config::BlsKeyPair;
+use crypto::{generate_keypair, SignatureService};
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use std::fs;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::channel;
+
+// Fixture: a core that is never run, so that its handlers can be driven directly.
+fn core(name: PublicKey, store: Store, tx_consensus: Sender<Certificate>) -> Core {
+    let test = CommitteeBuilder::new(4).build().unwrap();
+    let (tx_primaries, rx_primaries) = channel(1);
+    let (_, rx_header_waiter) = channel(1);
+    let (_, rx_certificate_waiter) = channel(1);
+    let (_, rx_proposer) = channel(1);
+    Core::new(
+        name,
+        test.committee,
+        store,
+        StoreCodec::default(),
+        BlsSignatureService::new(BlsKeyPair::default().secret),
+        Arc::new(AtomicU64::new(0)),
+        test.parameters.gc_depth,
+        test.parameters.store_retention,
+        test.parameters.prioritize_by_stake,
+        /* verification_threads */ 1,
+        RetryPolicy::default(),
+        CertifiedRounds::default(),
+        DroppedCertificates::default(),
+        rx_primaries,
+        rx_header_waiter,
+        rx_certificate_waiter,
+        rx_proposer,
+        tx_consensus,
+        /* tx_assembled */ None,
+        tx_primaries,
+        None,
+    )
+}
+
+// Fixture: a certificate of the first authority at `round`, with the votes of every authority.
+async fn certificate(test: &TestCommittee, round: Round) -> Certificate {
+    let mut header = Header {
+        author: test.keys[0].name,
+        round,
+        ..Header::default()
+    };
+    header.id = header.digest();
+    let mut aggregator = VotesAggregator::new();
+    for keys in &test.keys {
+        let mut bls_signature_service = BlsSignatureService::new(keys.bls_secret.clone());
+        let vote = Vote::new(&header, &keys.name, &mut bls_signature_service).await;
+        if let Some(certificate) = aggregator.append(vote, &test.committee, &header).unwrap() {
+            return certificate;
+        }
+    }
+    panic!("The votes of every authority are not a quorum")
+}
+
+#[tokio::test]
+async fn duplicate_certificates_are_forwarded_once() {
+    let (name, _) = generate_keypair(&mut StdRng::from_seed([0; 32]));
+    let path = ".db_test_duplicate_certificates_are_forwarded_once";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+    let (tx_consensus, mut rx_consensus) = channel(10);
+    let mut core = core(name, store, tx_consensus);
+
+    // The same certificate reaches us three times, e.g. from three different peers.
+    let certificate = Certificate {
+        origin: name,
+        round: 1,
+        ..Certificate::default()
+    };
+    for _ in 0..3 {
+        core.process_certificate(certificate.clone()).await.unwrap();
+    }
+
+    // A different certificate of the same round is still processed.
+    let other = Certificate {
+        origin: name,
+        round: 1,
+        id: Digest([1; 32]),
+        ..Certificate::default()
+    };
+    core.process_certificate(other.clone()).await.unwrap();
+
+    // Only one copy of the duplicated certificate made it to consensus.
+    drop(core);
+    let mut forwarded = Vec::new();
+    while let Some(certificate) = rx_consensus.recv().await {
+        forwarded.push(certificate.digest());
+    }
+    assert_eq!(forwarded, vec![certificate.digest(), other.digest()]);
+}
+
+#[tokio::test]
+async fn certificates_beyond_the_store_retention_are_deleted() {
+    let (name, _) = generate_keypair(&mut StdRng::from_seed([0; 32]));
+    let path = ".db_test_certificates_beyond_the_store_retention_are_deleted";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+    let (tx_consensus, _rx_consensus) = channel(10);
+    let mut core = core(name, store.clone(), tx_consensus);
+    core.store_retention = Some(2);
+
+    // Store the certificates of rounds 1 to 4.
+    let certificates: Vec<_> = (1..=4)
+        .map(|round| Certificate {
+            origin: name,
+            round,
+            id: Digest([round as u8; 32]),
+            ..Certificate::default()
+        })
+        .collect();
+    for certificate in &certificates {
+        core.process_certificate(certificate.clone()).await.unwrap();
+    }
+
+    // Once the consensus reaches round 4, only the last two rounds are retained.
+    core.prune_store(4 - 2).await;
+    let key = certificates[0].digest().to_vec();
+    assert!(store.read(key).await.unwrap().is_none());
+    for certificate in &certificates[1..] {
+        let key = certificate.digest().to_vec();
+        assert!(store.read(key).await.unwrap().is_some());
+    }
+}
+
+#[tokio::test]
+async fn certificates_collected_during_verification_are_dropped() {
+    let test = CommitteeBuilder::new(4).build().unwrap();
+    let path = ".db_test_certificates_collected_during_verification_are_dropped";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+    let (tx_consensus, mut rx_consensus) = channel(10);
+    let mut core = core(test.keys[0].name, store, tx_consensus);
+    core.committee = test.committee.clone();
+
+    // Send a certificate for verification.
+    let certificate = certificate(&test, 3).await;
+    let (tx_verified, mut rx_verified) = channel(1);
+    let pool = ThreadPool::new(1);
+    let committee = Arc::new(core.committee.clone());
+    core.sanitize_certificate(certificate.clone(), tx_verified, &pool, committee)
+        .unwrap();
+
+    // The garbage collector moves past its round before its verification completes.
+    core.garbage_collect(5);
+    let verified = match rx_verified.recv().await {
+        Some(PrimaryMessage::VerifiedCertificate(certificate)) => certificate,
+        _ => panic!("Expected a verified certificate"),
+    };
+    assert!(matches!(
+        core.process_verified_certificate(verified).await,
+        Err(DagError::CertificateTooOld(..))
+    ));
+    assert_eq!(core.dropped_certificates.count(), 1);
+
+    // Copies arriving later are dropped before verification, and counted as well.
+    let (tx_verified, _rx_verified) = channel(1);
+    let committee = Arc::new(core.committee.clone());
+    assert!(matches!(
+        core.sanitize_certificate(certificate, tx_verified, &pool, committee),
+        Err(DagError::CertificateTooOld(..))
+    ));
+    assert_eq!(core.dropped_certificates.count(), 2);
+
+    // The certificate never reached the consensus.
+    drop(core);
+    assert!(rx_consensus.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn certificates_failing_verification_are_not_processed() {
+    let test = CommitteeBuilder::new(4).build().unwrap();
+    let name = test.keys[0].name;
+    let (tx_consensus, mut rx_consensus) = channel(10);
+    let mut core = core(name, Store::new_in_memory(), tx_consensus);
+    core.committee = test.committee.clone();
+
+    // A certificate without the signatures of its voters, followed by a valid one.
+    let invalid = Certificate {
+        origin: name,
+        round: 1,
+        votes: (0b111, Default::default()),
+        ..Certificate::default()
+    };
+    let valid = certificate(&test, 2).await;
+    let (tx_verified, mut rx_verified) = channel(2);
+    let pool = ThreadPool::new(1);
+    for certificate in [invalid, valid.clone()] {
+        let committee = Arc::new(core.committee.clone());
+        core.sanitize_certificate(certificate, tx_verified.clone(), &pool, committee)
+            .unwrap();
+    }
+    let (first, second) = (rx_verified.recv().await, rx_verified.recv().await);
+
+    // The valid certificate finishes its verification first and waits for the lower round.
+    match second {
+        Some(PrimaryMessage::VerifiedCertificate(certificate)) => core
+            .process_verified_certificate(certificate)
+            .await
+            .unwrap(),
+        _ => panic!("Expected a verified certificate"),
+    }
+    assert!(rx_consensus.try_recv().is_err());
+
+    // The invalid certificate is dropped with its verification error, which releases the valid one.
+    match first {
+        Some(PrimaryMessage::InvalidCertificate(certificate, reason)) => assert!(matches!(
+            core.process_invalid_certificate(certificate, reason).await,
+            Err(DagError::InvalidCertificate(..))
+        )),
+        _ => panic!("Expected an invalid certificate"),
+    }
+    drop(core);
+    let mut forwarded = Vec::new();
+    while let Some(certificate) = rx_consensus.recv().await {
+        forwarded.push(certificate.digest());
+    }
+    assert_eq!(forwarded, vec![valid.digest()]);
+}
+
+#[tokio::test]
+async fn subscribers_receive_assembled_certificates_once() {
+    let mut test = CommitteeBuilder::new(4).build().unwrap();
+    let name = test.keys[0].name;
+    let (tx_consensus, mut rx_consensus) = channel(10);
+    let mut core = core(name, Store::new_in_memory(), tx_consensus);
+    core.committee = test.committee;
+    let ours = test.keys.remove(0);
+    let (tx_assembled, mut first) = broadcast::channel(10);
+    let mut second = tx_assembled.subscribe();
+    core.tx_assembled = Some(tx_assembled);
+
+    // We collect the votes of every authority on our header.
+    let header = Header::new(name, 1, Vec::new(), &mut SignatureService::new(ours.secret)).await;
+    core.processing_headers
+        .insert(header.id.clone(), header.clone());
+    core.processing_vote_aggregators
+        .insert(header.id.clone(), VotesAggregator::new());
+    let voters = std::iter::once((ours.name, ours.bls_secret)).chain(
+        test.keys
+            .into_iter()
+            .map(|keys| (keys.name, keys.bls_secret)),
+    );
+    for (author, bls_secret) in voters {
+        let mut bls_signature_service = BlsSignatureService::new(bls_secret);
+        let vote = Vote::new(&header, &author, &mut bls_signature_service).await;
+        core.process_vote(vote).await.unwrap();
+    }
+
+    // Every subscriber sees the certificate once, even though later votes still arrived.
+    let certificate = rx_consensus.recv().await.unwrap();
+    for subscriber in [&mut first, &mut second] {
+        let assembled = subscriber.try_recv().unwrap();
+        assert_eq!(assembled.digest(), certificate.digest());
+        assert_eq!(assembled.id, header.id);
+        assert!(subscriber.try_recv().is_err());
+    }
+}
+
+#[tokio::test]
+async fn long_chains_of_certificates_do_not_nest() {
+    const ROUNDS: Round = 2_000;
+
+    let (name, _) = generate_keypair(&mut StdRng::from_seed([0; 32]));
+    let path = ".db_test_long_chains_of_certificates_do_not_nest";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+    let (tx_consensus, mut rx_consensus) = channel(ROUNDS as usize);
+    let mut core = core(name, store, tx_consensus);
+
+    // The certificates of many rounds of the same authority finish their verification in reverse
+    // order, so that the first one releases all the others at once.
+    let certificates: Vec<_> = (1..=ROUNDS)
+        .map(|round| Certificate {
+            origin: name,
+            round,
+            id: Digest([(round % 256) as u8; 32]),
+            ..Certificate::default()
+        })
+        .collect();
+    for certificate in &certificates {
+        core.certificate_orderer.submit(certificate);
+    }
+    for certificate in certificates.into_iter().rev() {
+        core.process_verified_certificate(certificate)
+            .await
+            .unwrap();
+    }
+
+    // They are processed one after the other rather than nested in each other.
+    assert_eq!(core.processing_depth, 0);
+    drop(core);
+    let mut forwarded = 0;
+    while rx_consensus.recv().await.is_some() {
+        forwarded += 1;
+    }
+    assert_eq!(forwarded, ROUNDS);
+}
+
+#[tokio::test]
+async fn processing_beyond_the_maximum_depth_fails() {
+    let (name, _) = generate_keypair(&mut StdRng::from_seed([0; 32]));
+    let path = ".db_test_processing_beyond_the_maximum_depth_fails";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+    let (tx_consensus, mut rx_consensus) = channel(1);
+    let mut core = core(name, store, tx_consensus);
+
+    // As if we were already nested as deep as allowed.
+    core.processing_depth = MAX_PROCESSING_DEPTH;
+    let certificate = Certificate {
+        origin: name,
+        round: 1,
+        ..Certificate::default()
+    };
+    assert!(matches!(
+        core.process_certificate(certificate).await,
+        Err(DagError::ProcessingTooDeep(MAX_PROCESSING_DEPTH))
+    ));
+    assert_eq!(core.processing_depth, MAX_PROCESSING_DEPTH);
+
+    drop(core);
+    assert!(rx_consensus.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn core_runs_on_a_committee_built_in_memory() {
+    let test = CommitteeBuilder::new(4).base_port(17_500).build().unwrap();
+    assert_eq!(test.committee.authorities.len(), 4);
+    assert_eq!((test.parameters.n, test.parameters.f), (4, 1));
+
+    // Run the core of the first authority; nobody listens on the addresses of the others.
+    let mut keys = test.keys.into_iter();
+    let ours = keys.next().unwrap();
+    let (tx_primaries, rx_primaries) = channel(10);
+    let (_tx_header_waiter, rx_header_waiter) = channel(1);
+    let (_tx_certificate_waiter, rx_certificate_waiter) = channel(1);
+    let (tx_proposer, rx_proposer) = channel(1);
+    let (tx_consensus, mut rx_consensus) = channel(1);
+    Core::spawn(
+        ours.name,
+        test.committee,
+        Store::new_in_memory(),
+        StoreCodec::default(),
+        BlsSignatureService::new(ours.bls_secret),
+        Arc::new(AtomicU64::new(0)),
+        test.parameters.gc_depth,
+        test.parameters.store_retention,
+        test.parameters.prioritize_by_stake,
+        /* verification_threads */ 1,
+        RetryPolicy::default(),
+        CertifiedRounds::default(),
+        DroppedCertificates::default(),
+        rx_primaries,
+        rx_header_waiter,
+        rx_certificate_waiter,
+        rx_proposer,
+        tx_consensus,
+        /* tx_assembled */ None,
+        tx_primaries.clone(),
+        None,
+    );
+
+    // We propose a header and the other authorities vote for it.
+    let mut signature_service = SignatureService::new(ours.secret);
+    let header = Header::new(ours.name, 1, Vec::new(), &mut signature_service).await;
+    tx_proposer.send(header.clone()).await.unwrap();
+    for keys in keys {
+        let mut bls_signature_service = BlsSignatureService::new(keys.bls_secret);
+        let vote = Vote::new(&header, &keys.name, &mut bls_signature_service).await;
+        tx_primaries.send(PrimaryMessage::Vote(vote)).await.unwrap();
+    }
+
+    // The votes of a quorum certify our header.
+    let certificate = rx_consensus.recv().await.unwrap();
+    assert_eq!(certificate.id, header.id);
+    assert_eq!(certificate.origin, ours.name);
+}
+
+#[tokio::test]
+async fn core_keeps_processing_during_certificate_bursts() {
+    let test = CommitteeBuilder::new(4).base_port(17_600).build().unwrap();
+    let mut keys = test.keys.into_iter();
+    let ours = keys.next().unwrap();
+
+    // The consensus takes nothing for now, and the buffer absorbs the certificates meanwhile.
+    let (tx_buffer, rx_buffer) = channel(1);
+    let (tx_consensus, mut rx_consensus) = channel(1);
+    CertificateBuffer::spawn(100, rx_buffer, tx_consensus);
+
+    let (tx_primaries, rx_primaries) = channel(10);
+    let (_tx_header_waiter, rx_header_waiter) = channel(1);
+    let (tx_certificate_waiter, rx_certificate_waiter) = channel(100);
+    let (tx_proposer, rx_proposer) = channel(1);
+    let (tx_assembled, mut rx_assembled) = broadcast::channel(1);
+    Core::spawn(
+        ours.name,
+        test.committee,
+        Store::new_in_memory(),
+        StoreCodec::default(),
+        BlsSignatureService::new(ours.bls_secret),
+        Arc::new(AtomicU64::new(0)),
+        test.parameters.gc_depth,
+        test.parameters.store_retention,
+        test.parameters.prioritize_by_stake,
+        /* verification_threads */ 1,
+        RetryPolicy::default(),
+        CertifiedRounds::default(),
+        DroppedCertificates::default(),
+        rx_primaries,
+        rx_header_waiter,
+        rx_certificate_waiter,
+        rx_proposer,
+        /* tx_consensus */ tx_buffer,
+        Some(tx_assembled),
+        tx_primaries.clone(),
+        None,
+    );
+
+    // A burst of certificates reaches the core.
+    let burst: Vec<_> = (1..=50)
+        .map(|round| Certificate {
+            origin: ours.name,
+            round,
+            id: Digest([round as u8; 32]),
+            ..Certificate::default()
+        })
+        .collect();
+    for certificate in &burst {
+        tx_certificate_waiter
+            .send(certificate.clone())
+            .await
+            .unwrap();
+    }
+
+    // The core still processes our header and its votes, and assembles its certificate.
+    let mut signature_service = SignatureService::new(ours.secret);
+    let header = Header::new(ours.name, 51, Vec::new(), &mut signature_service).await;
+    tx_proposer.send(header.clone()).await.unwrap();
+    for keys in keys {
+        let mut bls_signature_service = BlsSignatureService::new(keys.bls_secret);
+        let vote = Vote::new(&header, &keys.name, &mut bls_signature_service).await;
+        tx_primaries.send(PrimaryMessage::Vote(vote)).await.unwrap();
+    }
+    let assembled = tokio::time::timeout(Duration::from_secs(5), rx_assembled.recv())
+        .await
+        .expect("The core stalled on the burst of certificates")
+        .unwrap();
+    assert_eq!(assembled.id, header.id);
+
+    // The consensus eventually receives every certificate, in order.
+    for certificate in &burst {
+        assert_eq!(
+            rx_consensus.recv().await.unwrap().digest(),
+            certificate.digest()
+        );
+    }
+    assert_eq!(rx_consensus.recv().await.unwrap().id, header.id);
+}
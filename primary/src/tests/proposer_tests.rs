@@ -1,68 +1,367 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use super::*;
-use crate::common::{committee, keys};
+use crate::test_utils::transactions;
+use crypto::generate_keypair;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use std::fs;
 use tokio::sync::mpsc::channel;
 
+// Fixture
+fn keys() -> (PublicKey, SignatureService) {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let (name, secret) = generate_keypair(&mut rng);
+    (name, SignatureService::new(secret))
+}
+
 #[tokio::test]
 async fn propose_empty() {
-    let (name, secret) = keys().pop().unwrap();
-    let signature_service = SignatureService::new(secret);
+    let (name, signature_service) = keys();
+    let path = ".db_test_propose_empty";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
 
-    let (_tx_parents, rx_parents) = channel(1);
     let (_tx_our_digests, rx_our_digests) = channel(1);
     let (tx_headers, mut rx_headers) = channel(1);
 
     // Spawn the proposer.
     Proposer::spawn(
         name,
-        &committee(),
         signature_service,
-        /* header_size */ 1_000,
-        /* max_header_delay */ 20,
-        /* rx_core */ rx_parents,
+        /* header_size */ 0,
+        /* max_header_delay */ 1_000_000, // Ensure it is not triggered.
+        /* max_txns_per_sender */ None,
+        /* shuffle */ false,
+        ExecutionPressure::default(),
+        /* pressure_threshold */ None,
+        /* heartbeat_delay */ None,
+        /* consensus_round */ Arc::new(AtomicU64::new(0)),
+        /* max_round_lead */ None,
+        /* max_sender_abort_rate */ None,
+        /* rx_abort_stats */ channel(1).1,
         /* rx_workers */ rx_our_digests,
         /* tx_core */ tx_headers,
+        store,
+        /* start_round */ 1,
+        TraceIds::default(),
     );
 
     // Ensure the proposer makes a correct empty header.
     let header = rx_headers.recv().await.unwrap();
     assert_eq!(header.round, 1);
+    assert_eq!(header.author, name);
     assert!(header.payload.is_empty());
-    assert!(header.verify(&committee()).is_ok());
 }
 
 #[tokio::test]
-async fn propose_payload() {
-    let (name, secret) = keys().pop().unwrap();
-    let signature_service = SignatureService::new(secret);
+async fn propose_from_start_round() {
+    let (name, signature_service) = keys();
+    let path = ".db_test_propose_from_start_round";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    let (_tx_our_digests, rx_our_digests) = channel(1);
+    let (tx_headers, mut rx_headers) = channel(1);
+
+    // Spawn the proposer as if it restarted after proposing round 41.
+    Proposer::spawn(
+        name,
+        signature_service,
+        /* header_size */ 0,
+        /* max_header_delay */ 1_000_000,
+        /* max_txns_per_sender */ None,
+        /* shuffle */ false,
+        ExecutionPressure::default(),
+        /* pressure_threshold */ None,
+        /* heartbeat_delay */ None,
+        /* consensus_round */ Arc::new(AtomicU64::new(0)),
+        /* max_round_lead */ None,
+        /* max_sender_abort_rate */ None,
+        /* rx_abort_stats */ channel(1).1,
+        /* rx_workers */ rx_our_digests,
+        /* tx_core */ tx_headers,
+        store.clone(),
+        /* start_round */ 42,
+        TraceIds::default(),
+    );
+
+    // Ensure the first header is at the start round, and that it is remembered.
+    let header = rx_headers.recv().await.unwrap();
+    assert_eq!(header.round, 42);
+    assert_eq!(load_start_round(&mut store.clone()).await, 43);
+}
+
+#[tokio::test]
+async fn fresh_node_starts_at_round_one() {
+    let path = ".db_test_fresh_node_starts_at_round_one";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+    assert_eq!(load_start_round(&mut store).await, 1);
+}
+
+#[tokio::test]
+async fn cap_transactions_per_sender() {
+    let (name, signature_service) = keys();
+    let path = ".db_test_cap_transactions_per_sender";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
 
-    let (_tx_parents, rx_parents) = channel(1);
     let (tx_our_digests, rx_our_digests) = channel(1);
+    let (tx_headers, mut rx_headers) = channel(4);
+
+    // Spawn the proposer. Headers are only created when the timer fires.
+    Proposer::spawn(
+        name,
+        signature_service,
+        /* header_size */ 1_000_000,
+        /* max_header_delay */ 10,
+        /* max_txns_per_sender */ Some(5),
+        /* shuffle */ false,
+        ExecutionPressure::default(),
+        /* pressure_threshold */ None,
+        /* heartbeat_delay */ None,
+        /* consensus_round */ Arc::new(AtomicU64::new(0)),
+        /* max_round_lead */ None,
+        /* max_sender_abort_rate */ None,
+        /* rx_abort_stats */ channel(1).1,
+        /* rx_workers */ rx_our_digests,
+        /* tx_core */ tx_headers,
+        store,
+        /* start_round */ 1,
+        TraceIds::default(),
+    );
+
+    // Send 20 transactions from the same sender.
+    let txns = transactions(AccountAddress::new([1; 32]), 20);
+    tx_our_digests.send(txns.clone()).await.unwrap();
+
+    // Ensure they are spread, in order, across four headers.
+    for expected in txns.chunks(5) {
+        let header = rx_headers.recv().await.unwrap();
+        assert_eq!(header.payload, expected.to_vec());
+    }
+}
+
+// Sends the transactions to the proposer one at a time, every 10ms.
+async fn send_one_by_one(tx_our_digests: &Sender<Vec<Transaction>>, txns: &[Transaction]) {
+    for txn in txns {
+        tx_our_digests.send(vec![txn.clone()]).await.unwrap();
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn execution_pressure_slows_down_headers() {
+    let (name, signature_service) = keys();
+    let path = ".db_test_execution_pressure_slows_down_headers";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    let (tx_our_digests, rx_our_digests) = channel(1);
+    let (tx_headers, mut rx_headers) = channel(100);
+
+    // Spawn the proposer. Every transaction fills a header on its own.
+    let execution_pressure = ExecutionPressure::default();
+    Proposer::spawn(
+        name,
+        signature_service,
+        /* header_size */ 1,
+        /* max_header_delay */ 50,
+        /* max_txns_per_sender */ None,
+        /* shuffle */ false,
+        execution_pressure.clone(),
+        /* pressure_threshold */ Some(2),
+        /* heartbeat_delay */ None,
+        /* consensus_round */ Arc::new(AtomicU64::new(0)),
+        /* max_round_lead */ None,
+        /* max_sender_abort_rate */ None,
+        /* rx_abort_stats */ channel(1).1,
+        /* rx_workers */ rx_our_digests,
+        /* tx_core */ tx_headers,
+        store,
+        /* start_round */ 1,
+        TraceIds::default(),
+    );
+
+    let txns = transactions(AccountAddress::new([1; 32]), 40);
+    let (before, after) = txns.split_at(20);
+
+    // Without pressure, the proposer creates a header for (almost) every transaction.
+    send_one_by_one(&tx_our_digests, before).await;
+    sleep(Duration::from_millis(20)).await;
+    let mut created = 0;
+    while rx_headers.try_recv().is_ok() {
+        created += 1;
+    }
+    assert!(created >= 10, "{} headers created", created);
+
+    // Once execution is saturated, the proposer waits for its (backed off) header delay.
+    for _ in 0..5 {
+        execution_pressure.block_committed();
+    }
+    send_one_by_one(&tx_our_digests, after).await;
+    let mut created = 0;
+    while rx_headers.try_recv().is_ok() {
+        created += 1;
+    }
+    assert!(created <= 2, "{} headers created", created);
+}
+
+#[tokio::test]
+async fn heartbeats_while_idle() {
+    let (name, signature_service) = keys();
+    let path = ".db_test_heartbeats_while_idle";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    let (_tx_our_digests, rx_our_digests) = channel(1);
+    let (tx_headers, mut rx_headers) = channel(10);
+
+    // Spawn a proposer that never receives transactions.
+    Proposer::spawn(
+        name,
+        signature_service,
+        /* header_size */ 1_000,
+        /* max_header_delay */ 10,
+        /* max_txns_per_sender */ None,
+        /* shuffle */ false,
+        ExecutionPressure::default(),
+        /* pressure_threshold */ None,
+        /* heartbeat_delay */ Some(200),
+        /* consensus_round */ Arc::new(AtomicU64::new(0)),
+        /* max_round_lead */ None,
+        /* max_sender_abort_rate */ None,
+        /* rx_abort_stats */ channel(1).1,
+        /* rx_workers */ rx_our_digests,
+        /* tx_core */ tx_headers,
+        store,
+        /* start_round */ 1,
+        TraceIds::default(),
+    );
+
+    // It still proposes empty headers, one per heartbeat delay.
+    let start = Instant::now();
+    for _ in 0..3 {
+        let header = rx_headers.recv().await.unwrap();
+        assert!(header.payload.is_empty());
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(600), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn pause_while_too_far_ahead_of_commits() {
+    let (name, signature_service) = keys();
+    let path = ".db_test_pause_while_too_far_ahead_of_commits";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    let (_tx_our_digests, rx_our_digests) = channel(1);
     let (tx_headers, mut rx_headers) = channel(1);
+    let consensus_round = Arc::new(AtomicU64::new(0));
 
-    // Spawn the proposer.
+    // Spawn a proposer at round 10 while nothing is committed, allowing a lead of 5 rounds.
     Proposer::spawn(
         name,
-        &committee(),
         signature_service,
-        /* header_size */ 32,
-        /* max_header_delay */ 1_000_000, // Ensure it is not triggered.
-        /* rx_core */ rx_parents,
+        /* header_size */ 0,
+        /* max_header_delay */ 1_000_000,
+        /* max_txns_per_sender */ None,
+        /* shuffle */ false,
+        ExecutionPressure::default(),
+        /* pressure_threshold */ None,
+        /* heartbeat_delay */ None,
+        consensus_round.clone(),
+        /* max_round_lead */ Some(5),
+        /* max_sender_abort_rate */ None,
+        /* rx_abort_stats */ channel(1).1,
         /* rx_workers */ rx_our_digests,
         /* tx_core */ tx_headers,
+        store,
+        /* start_round */ 10,
+        TraceIds::default(),
     );
 
-    // Send enough digests for the header payload.
-    let digest = Digest(name.0);
-    let worker_id = 0;
-    tx_our_digests
-        .send((digest.clone(), worker_id))
+    // The committer is stalled: the proposer does not propose.
+    let stalled = tokio::time::timeout(Duration::from_millis(500), rx_headers.recv()).await;
+    assert!(stalled.is_err(), "Proposed while too far ahead of commits");
+
+    // Once commits catch up to within the lead, the proposer resumes.
+    consensus_round.store(5, Ordering::Relaxed);
+    let header = tokio::time::timeout(Duration::from_secs(1), rx_headers.recv())
         .await
+        .expect("Did not resume proposing")
         .unwrap();
+    assert_eq!(header.round, 10);
+}
 
-    // Ensure the proposer makes a correct header from the provided payload.
-    let header = rx_headers.recv().await.unwrap();
-    assert_eq!(header.round, 1);
-    assert_eq!(header.payload.get(&digest), Some(&worker_id));
-    assert!(header.verify(&committee()).is_ok());
+#[tokio::test]
+async fn deprioritize_senders_whose_transactions_abort() {
+    let (name, signature_service) = keys();
+    let path = ".db_test_deprioritize_senders_whose_transactions_abort";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    let (tx_our_digests, rx_our_digests) = channel(1);
+    let (tx_abort_stats, rx_abort_stats) = channel(100);
+    let (tx_headers, mut rx_headers) = channel(4);
+
+    // Spawn the proposer. Headers are only created when the timer fires.
+    Proposer::spawn(
+        name,
+        signature_service,
+        /* header_size */ 1_000_000,
+        /* max_header_delay */ 10,
+        /* max_txns_per_sender */ None,
+        /* shuffle */ false,
+        ExecutionPressure::default(),
+        /* pressure_threshold */ None,
+        /* heartbeat_delay */ None,
+        /* consensus_round */ Arc::new(AtomicU64::new(0)),
+        /* max_round_lead */ None,
+        /* max_sender_abort_rate */ Some(0.5),
+        rx_abort_stats,
+        /* rx_workers */ rx_our_digests,
+        /* tx_core */ tx_headers,
+        store,
+        /* start_round */ 1,
+        TraceIds::default(),
+    );
+
+    let aborting = AccountAddress::new([1; 32]);
+    let honest = AccountAddress::new([2; 32]);
+    let aborting_txns = transactions(aborting, 6);
+    let honest_txns = transactions(honest, 6);
+
+    // Without any statistics, the transactions are proposed as they arrive.
+    let batch: Vec<_> = aborting_txns[..3]
+        .iter()
+        .chain(&honest_txns[..3])
+        .cloned()
+        .collect();
+    tx_our_digests.send(batch.clone()).await.unwrap();
+    assert_eq!(rx_headers.recv().await.unwrap().payload, batch);
+
+    // Every transaction of one sender aborts, block after block.
+    for _ in 0..10 {
+        let mut stats = AbortStats::default();
+        stats.record(aborting, /* executed */ false);
+        stats.record(honest, /* executed */ true);
+        tx_abort_stats.send(stats).await.unwrap();
+    }
+    sleep(Duration::from_millis(50)).await;
+
+    // Its transactions now come last, one per header.
+    let batch: Vec<_> = aborting_txns[3..]
+        .iter()
+        .chain(&honest_txns[3..])
+        .cloned()
+        .collect();
+    tx_our_digests.send(batch).await.unwrap();
+    let mut expected = honest_txns[3..].to_vec();
+    expected.push(aborting_txns[3].clone());
+    assert_eq!(rx_headers.recv().await.unwrap().payload, expected);
+    for txn in &aborting_txns[4..] {
+        assert_eq!(rx_headers.recv().await.unwrap().payload, vec![txn.clone()]);
+    }
 }
@@ -0,0 +1,99 @@
+// Copyright(C) Facebook, Inc. and its affiliates.
+use super::*;
+use bytes::Bytes;
+use config::committee_builder::CommitteeBuilder;
+use tokio::sync::mpsc::channel;
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration};
+
+#[tokio::test]
+async fn wait_for_quorum() {
+    // Four authorities with one unit of stake each, so a quorum is 3 (2f+1 with f=1).
+    let committee = CommitteeBuilder::new(4).build().unwrap().committee;
+    let mut names: Vec<_> = committee.authorities.keys().cloned().collect();
+    let myself = names.remove(0);
+
+    let (tx_message, rx_message) = channel(1);
+    let (tx_batch, mut rx_batch) = channel(1);
+    QuorumWaiter::spawn(
+        committee.clone(),
+        committee.stake(&myself),
+        rx_message,
+        tx_batch,
+    );
+
+    // Hand over a batch along with one cancel handler per other worker.
+    let mut acks = Vec::new();
+    let mut handlers = Vec::new();
+    for name in names {
+        let (sender, receiver) = oneshot::channel();
+        acks.push(sender);
+        handlers.push((name, receiver));
+    }
+    let message = QuorumWaiterMessage {
+        batch: Vec::new(),
+        handlers,
+    };
+    tx_message.send(message).await.unwrap();
+
+    // Our stake plus a single ack is not a quorum: the batch must not be delivered.
+    let mut acks = acks.into_iter();
+    acks.next().unwrap().send(Bytes::from("Ack")).unwrap();
+    let withheld = timeout(Duration::from_millis(100), rx_batch.recv()).await;
+    assert!(withheld.is_err());
+
+    // A second ack completes the quorum, even though the last worker never acks.
+    let _withheld_ack = acks.next_back().unwrap();
+    acks.next().unwrap().send(Bytes::from("Ack")).unwrap();
+    let delivered = timeout(Duration::from_millis(1_000), rx_batch.recv()).await;
+    assert!(delivered.unwrap().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn batches_wait_for_their_quorum_concurrently() {
+    // Four authorities with one unit of stake each, so a quorum is 3 (2f+1 with f=1).
+    let committee = CommitteeBuilder::new(4).build().unwrap().committee;
+    let mut names: Vec<_> = committee.authorities.keys().cloned().collect();
+    let myself = names.remove(0);
+
+    let (tx_message, rx_message) = channel(2);
+    let (tx_batch, mut rx_batch) = channel(2);
+    QuorumWaiter::spawn(
+        committee.clone(),
+        committee.stake(&myself),
+        rx_message,
+        tx_batch,
+    );
+
+    // Two batches, each with one cancel handler per other worker.
+    let mut acks = Vec::new();
+    for _ in 0..2 {
+        let mut batch_acks = Vec::new();
+        let mut handlers = Vec::new();
+        for name in &names {
+            let (sender, receiver) = oneshot::channel();
+            batch_acks.push(sender);
+            handlers.push((*name, receiver));
+        }
+        let message = QuorumWaiterMessage {
+            batch: Vec::new(),
+            handlers,
+        };
+        tx_message.send(message).await.unwrap();
+        acks.push(batch_acks);
+    }
+
+    // The second batch is acknowledged by every worker while the first one is not: it is delivered
+    // without waiting for the first one.
+    let second_acks = acks.pop().unwrap();
+    for ack in second_acks {
+        ack.send(Bytes::from("Ack")).unwrap();
+    }
+    let delivered = timeout(Duration::from_millis(1_000), rx_batch.recv()).await;
+    assert!(delivered.unwrap().is_some());
+
+    // Once its broadcasts are cancelled, the first batch is dropped rather than delivered.
+    drop(acks);
+    let dropped = timeout(Duration::from_millis(100), rx_batch.recv()).await;
+    assert!(dropped.is_err());
+}
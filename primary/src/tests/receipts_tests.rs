@@ -0,0 +1,165 @@
+use super::*;
+
+// Fixture
+fn receipt(hash: &str) -> ExecutionReceipt {
+    ExecutionReceipt {
+        hash: hash.to_string(),
+        status: "Executed".to_string(),
+        kept: true,
+        gas_used: 10,
+        events: Vec::new(),
+    }
+}
+
+#[test]
+fn lookup_by_hash() {
+    let receipts = ExecutionReceipts::new(10);
+    receipts.insert(1, receipt("0xab"));
+    assert_eq!(receipts.get("0xab"), Some(receipt("0xab")));
+    assert_eq!(receipts.get("AB"), Some(receipt("0xab")));
+    assert_eq!(receipts.get("0xcd"), None);
+}
+
+#[test]
+fn oldest_receipts_are_pruned() {
+    let receipts = ExecutionReceipts::new(2);
+    receipts.insert(1, receipt("0x01"));
+    receipts.insert(1, receipt("0x02"));
+    receipts.insert(1, receipt("0x03"));
+    assert_eq!(receipts.len(), 2);
+    assert_eq!(receipts.get("0x01"), None);
+    assert!(receipts.get("0x02").is_some());
+    assert!(receipts.get("0x03").is_some());
+}
+
+#[test]
+fn receipts_are_replaced() {
+    let receipts = ExecutionReceipts::new(2);
+    receipts.insert(
+        1,
+        ExecutionReceipt {
+            kept: false,
+            ..receipt("0x01")
+        },
+    );
+    receipts.insert(1, receipt("0x01"));
+    assert_eq!(receipts.len(), 1);
+    assert_eq!(receipts.get("0x01"), Some(receipt("0x01")));
+
+    // But a kept receipt is not replaced by a discarded one.
+    receipts.insert(
+        2,
+        ExecutionReceipt {
+            kept: false,
+            ..receipt("0x01")
+        },
+    );
+    assert_eq!(receipts.get("0x01"), Some(receipt("0x01")));
+}
+
+#[test]
+fn receipts_of_old_rounds_are_pruned() {
+    let receipts = ExecutionReceipts::new(10);
+    for round in 1..=5 {
+        receipts.insert(round, receipt(&format!("0x0{}", round)));
+    }
+    // A transaction discarded at round 1 and executed again at round 4 is retained.
+    receipts.insert(1, receipt("0xaa"));
+    receipts.insert(4, receipt("0xaa"));
+
+    receipts.prune_below(3);
+    assert_eq!(receipts.len(), 4);
+    assert_eq!(receipts.lookup("0x01"), ReceiptLookup::Pruned);
+    assert_eq!(receipts.lookup("0x02"), ReceiptLookup::Pruned);
+    assert_eq!(
+        receipts.lookup("0x03"),
+        ReceiptLookup::Found(receipt("0x03"))
+    );
+    assert_eq!(
+        receipts.lookup("0xaa"),
+        ReceiptLookup::Found(receipt("0xaa"))
+    );
+    assert_eq!(receipts.lookup("0xcd"), ReceiptLookup::Unknown);
+    assert_eq!(receipts.get("0x01"), None);
+
+    // Receipts pruned because of the capacity are reported as pruned as well.
+    let receipts = ExecutionReceipts::new(1);
+    receipts.insert(1, receipt("0x01"));
+    receipts.insert(2, receipt("0x02"));
+    assert_eq!(receipts.lookup("0x01"), ReceiptLookup::Pruned);
+
+    // Only the last `capacity` pruned hashes are remembered.
+    receipts.insert(3, receipt("0x03"));
+    assert_eq!(receipts.lookup("0x01"), ReceiptLookup::Unknown);
+    assert_eq!(receipts.lookup("0x02"), ReceiptLookup::Pruned);
+}
+
+// Fixture: a state of raw values, in which only `AccountAddress::ONE` has sent transactions.
+struct Reader(HashMap<StateKey, Vec<u8>>);
+
+impl StateReader for Reader {
+    fn state_value(&self, key: &StateKey) -> Result<Option<Vec<u8>>, String> {
+        match key == &StateKey::raw(b"unreadable") {
+            true => Err("unreadable".to_string()),
+            false => Ok(self.0.get(key).cloned()),
+        }
+    }
+
+    fn sequence_number(&self, address: &AccountAddress) -> Result<u64, String> {
+        Ok(if *address == AccountAddress::ONE {
+            3
+        } else {
+            0
+        })
+    }
+}
+
+#[test]
+fn state_is_read_through_the_registered_reader() {
+    let receipts = ExecutionReceipts::new(2);
+    let key = StateKey::raw(b"balance");
+    assert_eq!(receipts.state_value(&key), StateLookup::Unavailable);
+
+    let states = HashMap::from([(key.clone(), vec![1, 2, 3])]);
+    receipts.set_state_reader(Arc::new(Reader(states)));
+    assert_eq!(
+        receipts.state_value(&key),
+        StateLookup::Found(vec![1, 2, 3])
+    );
+    assert_eq!(
+        receipts.state_value(&StateKey::raw(b"missing")),
+        StateLookup::Absent
+    );
+    assert_eq!(
+        receipts.state_value(&StateKey::raw(b"unreadable")),
+        StateLookup::Failed("unreadable".to_string())
+    );
+}
+
+#[test]
+fn sequence_numbers_are_read_from_the_state() {
+    let receipts = ExecutionReceipts::new(2);
+    assert_eq!(receipts.sequence_number(&AccountAddress::ONE), None);
+
+    receipts.set_state_reader(Arc::new(Reader(HashMap::new())));
+    assert_eq!(receipts.sequence_number(&AccountAddress::ONE), Some(Ok(3)));
+    assert_eq!(receipts.sequence_number(&AccountAddress::TWO), Some(Ok(0)));
+}
+
+#[test]
+fn commit_gaps_are_only_counted_once_enabled() {
+    let receipts = ExecutionReceipts::new(2);
+    receipts.record_commit_gap(3, 4);
+    assert_eq!(receipts.commit_gaps(), None);
+
+    // The counters stay bounded however many gaps there are.
+    receipts.count_commit_gaps();
+    receipts.record_commit_gap(3, 4);
+    receipts.record_commit_gap(10, 10);
+    let expected = CommitGaps {
+        gaps: 2,
+        skipped_rounds: 3,
+        last_gap: Some((10, 10)),
+    };
+    assert_eq!(receipts.commit_gaps(), Some(expected));
+}
@@ -0,0 +1,134 @@
+use super::*;
+use crate::aggregators::VotesAggregator;
+use crate::messages::{Certificate, Header, Vote};
+use config::committee_builder::CommitteeBuilder;
+use crypto::SignatureService;
+use serde::Deserialize;
+use std::convert::TryInto as _;
+
+/// A linear scheme over integers: the key share of the authority of id `i` is `i + 1`, and a
+/// signature is the product of the key and the first bytes of the digest. Insecure, but the shares
+/// of several signers aggregate like BLS ones.
+#[derive(Clone, Copy, Debug, Default)]
+struct MockScheme;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct MockSignature(u64);
+
+fn message(digest: &Digest) -> u64 {
+    u64::from_le_bytes(digest.0[..8].try_into().unwrap())
+}
+
+impl ThresholdSigScheme for MockScheme {
+    type SecretKeyShare = u64;
+    type PublicKeyShare = u64;
+    type SignatureShare = MockSignature;
+
+    fn sign(digest: &Digest, secret: &u64) -> MockSignature {
+        MockSignature(message(digest).wrapping_mul(*secret))
+    }
+
+    fn public_key_share(committee: &Committee, name: &PublicKey) -> Option<u64> {
+        committee.authorities.get(name).map(|x| x.id as u64 + 1)
+    }
+
+    fn signer_index(_committee: &Committee, key: &u64) -> Option<usize> {
+        Some(*key as usize - 1)
+    }
+
+    fn aggregate(aggregated: &MockSignature, share: &MockSignature) -> MockSignature {
+        MockSignature(aggregated.0.wrapping_add(share.0))
+    }
+
+    fn combine_keys(_committee: &Committee, signers: Vec<usize>) -> u64 {
+        signers.iter().map(|i| *i as u64 + 1).sum()
+    }
+
+    fn verify(digest: &Digest, key: &u64, signature: &MockSignature) -> DagResult<()> {
+        ensure!(
+            message(digest).wrapping_mul(*key) == signature.0,
+            DagError::CertificateRequiresQuorum
+        );
+        Ok(())
+    }
+}
+
+/// Aggregates the votes of every authority on the header into a certificate, through the scheme `S`.
+fn certify<S: ThresholdSigScheme>(
+    committee: &Committee,
+    header: &Header,
+    signers: Vec<(PublicKey, S::SecretKeyShare)>,
+) -> Certificate<S> {
+    let mut aggregator = VotesAggregator::<S>::new();
+    let mut certificates = Vec::new();
+    for (author, secret) in &signers {
+        let vote = Vote::<S>::signed(header, author, secret);
+        if let Some(certificate) = aggregator.append(vote, committee, header).unwrap() {
+            certificates.push(certificate);
+        }
+    }
+    // The quorum is only reached once.
+    assert_eq!(certificates.len(), 1);
+    certificates.pop().unwrap()
+}
+
+#[tokio::test]
+async fn votes_are_certified_through_a_mock_scheme() {
+    let mut test = CommitteeBuilder::new(4).build().unwrap();
+    let committee = test.committee;
+    let names: Vec<_> = test.keys.iter().map(|keys| keys.name).collect();
+    let ours = test.keys.remove(0);
+    let header = Header::new(
+        ours.name,
+        1,
+        Vec::new(),
+        &mut SignatureService::new(ours.secret),
+    )
+    .await;
+
+    let signers = names
+        .into_iter()
+        .map(|name| {
+            let share = MockScheme::public_key_share(&committee, &name).unwrap();
+            (name, share)
+        })
+        .collect();
+    let certificate = certify::<MockScheme>(&committee, &header, signers);
+    assert_eq!(certificate.id, header.id);
+    assert!(certificate.verify(&committee).is_ok());
+
+    // The certificate survives the network with the signature of the scheme.
+    let bytes = bincode::serialize(&certificate).unwrap();
+    let received: Certificate<MockScheme> = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(received.votes, certificate.votes);
+    assert!(received.verify(&committee).is_ok());
+
+    // Leaving out a signer breaks the aggregated signature.
+    let mut forged = certificate;
+    forged.votes.0 &= !1;
+    assert!(forged.verify(&committee).is_err());
+}
+
+#[tokio::test]
+async fn votes_are_certified_through_blsttc() {
+    let mut test = CommitteeBuilder::new(4).build().unwrap();
+    let committee = test.committee;
+    let ours = test.keys.remove(0);
+    let header = Header::new(
+        ours.name,
+        1,
+        Vec::new(),
+        &mut SignatureService::new(ours.secret),
+    )
+    .await;
+    let signers = std::iter::once((ours.name, ours.bls_secret))
+        .chain(
+            test.keys
+                .into_iter()
+                .map(|keys| (keys.name, keys.bls_secret)),
+        )
+        .collect();
+
+    let certificate: Certificate = certify::<Blsttc>(&committee, &header, signers);
+    assert!(certificate.verify(&committee).is_ok());
+}
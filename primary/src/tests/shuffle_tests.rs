@@ -0,0 +1,87 @@
+use super::*;
+use crate::error::DagError;
+use crate::messages::Header;
+use crate::test_utils::transactions;
+use config::committee_builder::CommitteeBuilder;
+use crypto::{Hash as _, SignatureService};
+
+// Fixture: the transactions of five senders, sender by sender.
+fn payload() -> Vec<Transaction> {
+    (1..=5u8)
+        .flat_map(|i| transactions(AccountAddress::new([i; 32]), 4))
+        .collect()
+}
+
+#[test]
+fn all_nodes_reconstruct_the_same_order() {
+    let test = CommitteeBuilder::new(4).build().unwrap();
+    let author = test.keys[0].name;
+    let original = payload();
+
+    // The seed does not depend on the order the author received the transactions in.
+    let mut reversed = original.clone();
+    reversed.reverse();
+    let seed = shuffle_seed(&author, 7, &original);
+    assert_eq!(seed, shuffle_seed(&author, 7, &reversed));
+
+    let first = shuffle_payload(original.clone(), &seed);
+    let second = shuffle_payload(reversed, &seed);
+    assert_eq!(first, second);
+    assert!(is_shuffled(&author, 7, &first));
+
+    // The shuffle reorders the senders without losing any transaction.
+    assert_ne!(first, original);
+    assert_eq!(first.len(), original.len());
+}
+
+#[test]
+fn transactions_of_a_sender_stay_in_order() {
+    let test = CommitteeBuilder::new(4).build().unwrap();
+    let author = test.keys[0].name;
+    let mut original = payload();
+    original.reverse();
+
+    let shuffled = shuffle_payload(original.clone(), &shuffle_seed(&author, 1, &original));
+    for i in 1..=5u8 {
+        let sender = AccountAddress::new([i; 32]);
+        let sequence_numbers: Vec<_> = shuffled
+            .iter()
+            .filter(|x| x.sender() == sender)
+            .map(|x| x.sequence_number())
+            .collect();
+        assert_eq!(sequence_numbers, vec![0, 1, 2, 3]);
+    }
+}
+
+#[test]
+fn different_seeds_give_different_orders() {
+    let first = shuffle_payload(payload(), &Digest([0; 32]));
+    let second = shuffle_payload(payload(), &Digest([1; 32]));
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn headers_with_a_reordered_payload_are_rejected() {
+    let mut test = CommitteeBuilder::new(4).build().unwrap();
+    let keys = test.keys.remove(0);
+    let mut signature_service = SignatureService::new(keys.secret);
+
+    let header = Header::new_shuffled(keys.name, 1, payload(), &mut signature_service).await;
+    assert!(header.verify(&test.committee).is_ok());
+
+    // The author moves its first transaction to the end, and signs the result.
+    let mut payload = header.payload.clone();
+    payload.rotate_left(1);
+    let reordered = Header { payload, ..header };
+    let id = reordered.digest();
+    let signature = signature_service.request_signature(id.clone()).await;
+    let reordered = Header {
+        id,
+        signature,
+        ..reordered
+    };
+    assert!(matches!(
+        reordered.verify(&test.committee),
+        Err(DagError::InvalidPayloadOrder(_))
+    ));
+}
@@ -0,0 +1,40 @@
+use crate::batch_maker::Transaction;
+use aptos_crypto::ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature};
+use aptos_crypto::Uniform as _;
+use aptos_types::account_address::AccountAddress;
+use aptos_types::chain_id::ChainId;
+use aptos_types::transaction::{RawTransaction, Script, SignedTransaction, TransactionPayload};
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+
+// Fixture
+pub fn transaction(sender: AccountAddress, sequence_number: u64) -> Transaction {
+    transaction_with_gas_price(sender, sequence_number, 100)
+}
+
+// Fixture: an empty script of `sender`, signed with a dummy signature (the primary never checks it).
+pub fn transaction_with_gas_price(
+    sender: AccountAddress,
+    sequence_number: u64,
+    gas_unit_price: u64,
+) -> Transaction {
+    let mut rng = StdRng::from_seed([1; 32]);
+    let public_key = Ed25519PublicKey::from(&Ed25519PrivateKey::generate(&mut rng));
+    let raw = RawTransaction::new(
+        sender,
+        sequence_number,
+        TransactionPayload::Script(Script::new(vec![], vec![], vec![])),
+        /* max_gas_amount */ 1_000,
+        gas_unit_price,
+        /* expiration_timestamp_secs */ u64::MAX,
+        ChainId::test(),
+    );
+    SignedTransaction::new(raw, public_key, Ed25519Signature::dummy_signature())
+}
+
+// Fixture: the transactions of `sender` with sequence numbers 0 to `count` (excluded).
+pub fn transactions(sender: AccountAddress, count: u64) -> Vec<Transaction> {
+    (0..count)
+        .map(|sequence_number| transaction(sender, sequence_number))
+        .collect()
+}
@@ -0,0 +1,25 @@
+use super::*;
+use crate::test_utils::transaction;
+use aptos_types::account_address::AccountAddress;
+
+#[test]
+fn trace_ids_are_parsed_as_displayed() {
+    let trace_id = TraceId(0xdead_beef);
+    assert_eq!(trace_id.to_string(), "00000000deadbeef");
+    assert_eq!("00000000deadbeef".parse::<TraceId>(), Ok(trace_id));
+    assert!("deadbeef".parse::<TraceId>().is_err());
+    assert!("00000000deadbeeg".parse::<TraceId>().is_err());
+}
+
+#[test]
+fn assigned_trace_ids_replace_the_derived_ones() {
+    let trace_ids = TraceIds::default();
+    let assigned = transaction(AccountAddress::new([1; 32]), 0);
+    let other = transaction(AccountAddress::new([1; 32]), 1);
+    assert_eq!(trace_ids.resolve(&assigned), TraceId::of(&assigned));
+
+    trace_ids.assign(&assigned, TraceId(7));
+    assert_eq!(trace_ids.resolve(&assigned), TraceId(7));
+    assert_eq!(trace_ids.clone().resolve(&assigned), TraceId(7));
+    assert_eq!(trace_ids.resolve(&other), TraceId::of(&other));
+}
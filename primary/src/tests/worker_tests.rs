@@ -0,0 +1,269 @@
+use super::*;
+use crate::test_utils::{transaction, transaction_with_gas_price};
+use aptos_crypto::ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature};
+use aptos_crypto::Uniform as _;
+use aptos_types::account_address::AccountAddress;
+use aptos_types::chain_id::ChainId;
+use aptos_types::transaction::authenticator::AccountAuthenticator;
+use aptos_types::transaction::{RawTransaction, Script, SignedTransaction, TransactionPayload};
+use config::committee_builder::CommitteeBuilder;
+use network::ReliableSender;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use std::net::IpAddr;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration};
+
+/// A multi-agent transaction co-signed by `signatures` of its two secondary signers.
+fn multi_agent_transaction(sequence_number: u64, signatures: usize) -> Transaction {
+    let mut rng = StdRng::from_seed([1; 32]);
+    let mut authenticator = || {
+        let public_key = Ed25519PublicKey::from(&Ed25519PrivateKey::generate(&mut rng));
+        AccountAuthenticator::ed25519(public_key, Ed25519Signature::dummy_signature())
+    };
+    let raw = RawTransaction::new(
+        AccountAddress::ONE,
+        sequence_number,
+        TransactionPayload::Script(Script::new(vec![], vec![], vec![])),
+        /* max_gas_amount */ 1_000,
+        /* gas_unit_price */ 100,
+        /* expiration_timestamp_secs */ u64::MAX,
+        ChainId::test(),
+    );
+    SignedTransaction::new_multi_agent(
+        raw,
+        authenticator(),
+        vec![AccountAddress::TWO, AccountAddress::THREE],
+        (0..signatures).map(|_| authenticator()).collect(),
+    )
+}
+
+#[tokio::test]
+async fn transactions_beyond_the_queue_wait_for_room() {
+    let (tx_batch_maker, mut rx_batch_maker) = channel(2);
+    let handler = TxReceiverHandler {
+        tx_batch_maker,
+        admission: Admission {
+            min_gas_unit_price: 0,
+            require_secondary_signatures: false,
+        },
+    };
+
+    // The queue takes two transactions, the next one waits rather than being dropped.
+    for i in 0..2 {
+        handler
+            .forward(transaction(AccountAddress::ONE, i))
+            .await
+            .unwrap();
+    }
+    let waiting = timeout(
+        Duration::from_millis(100),
+        handler.forward(transaction(AccountAddress::ONE, 2)),
+    );
+    assert!(waiting.await.is_err());
+
+    // It goes through once the batch maker catches up.
+    assert_eq!(rx_batch_maker.recv().await.unwrap().sequence_number(), 0);
+    timeout(
+        Duration::from_millis(1_000),
+        handler.forward(transaction(AccountAddress::ONE, 2)),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(rx_batch_maker.recv().await.unwrap().sequence_number(), 1);
+    assert_eq!(rx_batch_maker.recv().await.unwrap().sequence_number(), 2);
+
+    // A stopped batch maker does not bring the handler down.
+    drop(rx_batch_maker);
+    assert_eq!(
+        handler.forward(transaction(AccountAddress::ONE, 3)).await,
+        Err(RejectReason::NotRunning)
+    );
+}
+
+#[tokio::test]
+async fn transactions_below_the_gas_price_floor_are_not_admitted() {
+    let admission = Admission {
+        min_gas_unit_price: 100,
+        require_secondary_signatures: false,
+    };
+    let (tx_batch_maker, mut rx_batch_maker) = channel(10);
+    let handler = TxReceiverHandler {
+        tx_batch_maker,
+        admission,
+    };
+
+    // A zero-fee transaction is rejected with the reason, one at the floor is batched.
+    let reason = admission
+        .check(&transaction_with_gas_price(AccountAddress::ONE, 0, 0))
+        .unwrap_err();
+    assert_eq!(
+        reason.to_string(),
+        "Gas unit price 0 is below the minimum of 100"
+    );
+    let _ = handler
+        .forward(transaction_with_gas_price(AccountAddress::ONE, 0, 0))
+        .await;
+    let _ = handler
+        .forward(transaction_with_gas_price(AccountAddress::ONE, 1, 99))
+        .await;
+    handler
+        .forward(transaction_with_gas_price(AccountAddress::ONE, 2, 100))
+        .await
+        .unwrap();
+    assert_eq!(rx_batch_maker.recv().await.unwrap().sequence_number(), 2);
+    assert!(rx_batch_maker.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn every_dropped_transaction_has_a_reason() {
+    let (tx_batch_maker, rx_batch_maker) = channel(1);
+    let handler = TxReceiverHandler {
+        tx_batch_maker,
+        admission: Admission {
+            min_gas_unit_price: 100,
+            require_secondary_signatures: false,
+        },
+    };
+
+    // Bytes that are not a transaction.
+    let reason = handler.receive(&[0xff; 3]).await.unwrap_err();
+    assert!(matches!(reason, RejectReason::Malformed(_)), "{:?}", reason);
+
+    // A transaction below the gas price floor.
+    let bytes = bcs::to_bytes(&transaction_with_gas_price(AccountAddress::ONE, 0, 99)).unwrap();
+    assert_eq!(
+        handler.receive(&bytes).await,
+        Err(RejectReason::GasPriceTooLow {
+            gas_unit_price: 99,
+            min_gas_unit_price: 100
+        })
+    );
+
+    // A transaction while the batch maker is stopped.
+    drop(rx_batch_maker);
+    let bytes = bcs::to_bytes(&transaction(AccountAddress::ONE, 1)).unwrap();
+    assert_eq!(handler.receive(&bytes).await, Err(RejectReason::NotRunning));
+}
+
+#[tokio::test]
+async fn multi_agent_transactions_missing_signatures_are_not_admitted() {
+    let (tx_batch_maker, mut rx_batch_maker) = channel(10);
+    let handler = TxReceiverHandler {
+        tx_batch_maker,
+        admission: Admission {
+            min_gas_unit_price: 0,
+            require_secondary_signatures: true,
+        },
+    };
+
+    // The transaction lacks the signature of its second secondary signer: it is never batched.
+    let bytes = bcs::to_bytes(&multi_agent_transaction(0, 1)).unwrap();
+    assert_eq!(
+        handler.receive(&bytes).await,
+        Err(RejectReason::MissingSecondarySignatures {
+            signers: 2,
+            signatures: 1
+        })
+    );
+    assert!(rx_batch_maker.try_recv().is_err());
+
+    // Complete multi-agent transactions and single-signer ones are batched.
+    let bytes = bcs::to_bytes(&multi_agent_transaction(1, 2)).unwrap();
+    assert_eq!(handler.receive(&bytes).await, Ok(()));
+    handler
+        .forward(transaction(AccountAddress::ONE, 2))
+        .await
+        .unwrap();
+    assert_eq!(rx_batch_maker.recv().await.unwrap().sequence_number(), 1);
+    assert_eq!(rx_batch_maker.recv().await.unwrap().sequence_number(), 2);
+
+    // Without the check, the incomplete transaction is left to fail at execution.
+    let admission = Admission {
+        min_gas_unit_price: 0,
+        require_secondary_signatures: false,
+    };
+    assert!(admission.check(&multi_agent_transaction(3, 1)).is_ok());
+}
+
+#[tokio::test]
+async fn batches_are_acknowledged_once_stored() {
+    let address: SocketAddr = "127.0.0.1:17990".parse().unwrap();
+    let mut store = Store::new_in_memory();
+    Receiver::spawn(
+        address,
+        WorkerReceiverHandler {
+            store: store.clone(),
+        },
+    );
+    sleep(Duration::from_millis(50)).await;
+    let mut network = ReliableSender::new();
+
+    // A batch is acknowledged, and stored by then.
+    let serialized = bincode::serialize(&WorkerMessage::Batch(vec![transaction(
+        AccountAddress::ONE,
+        1,
+    )]))
+    .unwrap();
+    let ack = network.send(address, Bytes::from(serialized.clone())).await;
+    assert_eq!(ack.await.unwrap(), Bytes::from("Ack"));
+    let stored = store
+        .read(batch_digest(&serialized).to_vec())
+        .await
+        .unwrap();
+    assert_eq!(stored, Some(serialized));
+
+    // A message that cannot be decoded is never acknowledged.
+    let ack = network.send(address, Bytes::from(vec![0xff; 3])).await;
+    assert!(timeout(Duration::from_millis(200), ack).await.is_err());
+}
+
+// Fixture: spawns the only worker of a committee whose ports start at `base_port`, and returns the
+// address the committee advertises for its client transactions.
+async fn spawn_worker(base_port: u16, worker_listen_ip: Option<IpAddr>) -> SocketAddr {
+    let mut test = CommitteeBuilder::new(1)
+        .workers(1)
+        .base_port(base_port)
+        .build()
+        .unwrap();
+    test.parameters.worker_listen_ip = worker_listen_ip;
+    let name = test.keys[0].name;
+    let advertised = test.committee.worker(&name, &0).unwrap().transactions;
+    let (tx_digests, _rx_digests) = channel(1);
+    Worker::spawn(
+        name,
+        0,
+        test.committee,
+        test.parameters,
+        tx_digests,
+        ExecutionReceipts::new(1),
+        Store::new_in_memory(),
+    );
+    sleep(Duration::from_millis(100)).await;
+    advertised
+}
+
+// Fixture: whether something accepts connections on `address`.
+async fn listening(address: SocketAddr) -> bool {
+    TcpStream::connect(address).await.is_ok()
+}
+
+#[tokio::test]
+async fn workers_listen_on_the_configured_ip_only() {
+    let ip: IpAddr = "127.0.0.2".parse().unwrap();
+    let advertised = spawn_worker(17_900, Some(ip)).await;
+
+    let configured = SocketAddr::new(ip, advertised.port());
+    assert!(listening(configured).await);
+    assert!(!listening(advertised).await);
+}
+
+#[tokio::test]
+async fn workers_listen_on_the_ip_of_the_committee_by_default() {
+    let advertised = spawn_worker(17_950, None).await;
+
+    let other = SocketAddr::new("127.0.0.2".parse().unwrap(), advertised.port());
+    assert!(listening(advertised).await);
+    assert!(!listening(other).await);
+}
@@ -0,0 +1,99 @@
+use crate::batch_maker::Transaction;
+use aptos_crypto::HashValue;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto as _;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+#[path = "tests/trace_tests.rs"]
+pub mod trace_tests;
+
+/// The HTTP header with which clients assign a trace id to the transactions they submit, as 16 hex
+/// digits.
+pub const TRACE_ID_HEADER: &str = "x-trace-id";
+
+/// The number of trace ids assigned by clients that are remembered. Once reached, the ids of the
+/// oldest transactions are forgotten, and these transactions are traced by their derived id.
+pub const MAX_ASSIGNED_TRACE_IDS: usize = 100_000;
+
+/// Correlates the log entries of a client transaction across the worker, the DAG, and the committer.
+/// Clients may assign it when submitting the transaction (see `TraceIds`); otherwise it is derived
+/// from the hash of the transaction, so every subsystem finds the same id without any change to the
+/// batches, headers, or certificates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TraceId(u64);
+
+impl TraceId {
+    /// Returns the trace id derived from a transaction: the first 8 bytes of its committed hash.
+    pub fn of(transaction: &Transaction) -> Self {
+        let hash = transaction.committed_hash().to_vec();
+        Self(u64::from_be_bytes(hash[..8].try_into().unwrap()))
+    }
+}
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for TraceId {
+    type Err = String;
+
+    /// Parses a trace id as displayed: 16 hex digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 16 {
+            return Err(format!("expected 16 hex digits, got {:?}", s));
+        }
+        u64::from_str_radix(s, 16)
+            .map(Self)
+            .map_err(|e| format!("invalid trace id {:?}: {}", s, e))
+    }
+}
+
+/// The trace ids assigned by clients to the transactions they submitted to this node, by hash. The
+/// subsystems of the node trace transactions through `resolve`, which falls back to the derived id
+/// (`TraceId::of`) for the transactions without an assigned id, e.g. those received from other
+/// nodes. Clones share the same ids.
+#[derive(Clone, Default)]
+pub struct TraceIds {
+    inner: Arc<Mutex<AssignedIds>>,
+}
+
+#[derive(Default)]
+struct AssignedIds {
+    ids: HashMap<HashValue, TraceId>,
+    /// The hashes of the transactions, from the first assigned to the last.
+    order: VecDeque<HashValue>,
+}
+
+impl TraceIds {
+    /// Traces `transaction` under `id` rather than its derived id.
+    pub fn assign(&self, transaction: &Transaction, id: TraceId) {
+        let hash = transaction.committed_hash();
+        let mut inner = self.inner.lock().unwrap();
+        if inner.ids.insert(hash, id).is_none() {
+            inner.order.push_back(hash);
+        }
+        while inner.order.len() > MAX_ASSIGNED_TRACE_IDS {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.ids.remove(&oldest);
+            }
+        }
+    }
+
+    /// Returns the trace id of `transaction`: the id assigned by its client, if any, or its derived
+    /// id.
+    pub fn resolve(&self, transaction: &Transaction) -> TraceId {
+        let assigned = self
+            .inner
+            .lock()
+            .unwrap()
+            .ids
+            .get(&transaction.committed_hash())
+            .copied();
+        assigned.unwrap_or_else(|| TraceId::of(transaction))
+    }
+}
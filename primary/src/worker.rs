@@ -1,20 +1,136 @@
+use crate::api::ApiServer;
 use crate::batch_maker::{Batch, BatchMaker, Transaction};
+use crate::digest_coalescer::DigestCoalescer;
+use crate::quorum_waiter::QuorumWaiter;
+use crate::receipts::ExecutionReceipts;
+use crate::trace::TraceId;
+use aptos_types::transaction::authenticator::TransactionAuthenticator;
 use async_trait::async_trait;
 use bytes::Bytes;
 use config::{Committee, Parameters, WorkerId};
 use crypto::{Digest, PublicKey};
-use log::{info, warn};
+use ed25519_dalek::Digest as _;
+use ed25519_dalek::Sha512;
+use futures::sink::SinkExt as _;
+use log::{debug, info, warn};
 use network::{MessageHandler, Receiver, Writer};
 use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
 use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use store::Store;
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::mpsc::{channel, Sender};
 
-// #[cfg(test)]
-// #[path = "tests/worker_tests.rs"]
-// pub mod worker_tests;
+#[cfg(test)]
+#[path = "tests/worker_tests.rs"]
+pub mod worker_tests;
 
-/// The default channel capacity for each channel of the worker.
-pub const CHANNEL_CAPACITY: usize = 1_000;
+/// Why the worker drops a client transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The transaction could not be decoded.
+    Malformed(String),
+    /// The gas unit price of the transaction is below the minimum admitted by the worker.
+    GasPriceTooLow {
+        gas_unit_price: u64,
+        min_gas_unit_price: u64,
+    },
+    /// The multi-agent transaction does not carry the signature of every secondary signer, so it
+    /// would fail at execution.
+    MissingSecondarySignatures { signers: usize, signatures: usize },
+    /// The batch maker does not keep up: the client should retry later.
+    Overloaded,
+    /// The batch maker stopped.
+    NotRunning,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Malformed(e) => write!(f, "Failed to decode transaction: {}", e),
+            Self::GasPriceTooLow {
+                gas_unit_price,
+                min_gas_unit_price,
+            } => write!(
+                f,
+                "Gas unit price {} is below the minimum of {}",
+                gas_unit_price, min_gas_unit_price
+            ),
+            Self::MissingSecondarySignatures {
+                signers,
+                signatures,
+            } => write!(
+                f,
+                "{} secondary signers but {} secondary signatures",
+                signers, signatures
+            ),
+            Self::Overloaded => write!(f, "The worker is overloaded, retry later"),
+            Self::NotRunning => write!(f, "The batch maker is not running"),
+        }
+    }
+}
+
+impl<T> From<TrySendError<T>> for RejectReason {
+    fn from(e: TrySendError<T>) -> Self {
+        match e {
+            TrySendError::Full(_) => Self::Overloaded,
+            TrySendError::Closed(_) => Self::NotRunning,
+        }
+    }
+}
+
+/// Checks client transactions before they are batched, so that the transactions the committee does
+/// not want do not consume the bandwidth of the dag.
+#[derive(Clone, Copy, Debug)]
+pub struct Admission {
+    /// The lowest gas unit price admitted.
+    pub min_gas_unit_price: u64,
+    /// Whether to reject the multi-agent transactions without a signature for every secondary signer.
+    pub require_secondary_signatures: bool,
+}
+
+impl Admission {
+    /// Returns why a transaction is not admitted, if it is not.
+    pub fn check(&self, txn: &Transaction) -> Result<(), RejectReason> {
+        if txn.gas_unit_price() < self.min_gas_unit_price {
+            return Err(RejectReason::GasPriceTooLow {
+                gas_unit_price: txn.gas_unit_price(),
+                min_gas_unit_price: self.min_gas_unit_price,
+            });
+        }
+        if self.require_secondary_signatures {
+            if let Some((signers, signatures)) = secondary_signers(txn) {
+                if signers != signatures {
+                    return Err(RejectReason::MissingSecondarySignatures {
+                        signers,
+                        signatures,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the number of secondary signers of a multi-agent transaction and the number of their
+/// signatures, or `None` if the transaction has a single signer.
+fn secondary_signers(txn: &Transaction) -> Option<(usize, usize)> {
+    match txn.authenticator_ref() {
+        TransactionAuthenticator::MultiAgent {
+            secondary_signer_addresses,
+            secondary_signers,
+            ..
+        }
+        | TransactionAuthenticator::FeePayer {
+            secondary_signer_addresses,
+            secondary_signers,
+            ..
+        } => Some((secondary_signer_addresses.len(), secondary_signers.len())),
+        _ => None,
+    }
+}
 
 /// The message exchanged between workers.
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,6 +149,10 @@ pub struct Worker {
     /// The configuration parameters.
     parameters: Parameters,
     tx_digests: Sender<Vec<Transaction>>,
+    /// The receipts of the committed transactions, served by the transaction API.
+    receipts: ExecutionReceipts,
+    /// Holds the batches received from the other workers.
+    store: Store,
 }
 
 impl Worker {
@@ -42,6 +162,8 @@ impl Worker {
         committee: Committee,
         parameters: Parameters,
         tx_digests: Sender<Vec<Transaction>>,
+        receipts: ExecutionReceipts,
+        store: Store,
     ) {
         // Define a worker instance.
         let worker = Self {
@@ -50,11 +172,15 @@ impl Worker {
             committee,
             parameters,
             tx_digests,
+            receipts,
+            store,
         };
 
         // Spawn all worker tasks.
-        // let (tx_primary, rx_primary) = channel(CHANNEL_CAPACITY);
         worker.handle_clients_transactions();
+        if worker.parameters.batch_quorum_ack {
+            worker.handle_workers_messages();
+        }
 
         // NOTE: This log entry is used to compute performance.
         info!(
@@ -69,30 +195,109 @@ impl Worker {
         );
     }
 
+    /// Returns the address to listen on for the given address of the committee: the same port, on
+    /// `worker_listen_ip` if set.
+    fn listen_address(&self, mut address: SocketAddr) -> SocketAddr {
+        if let Some(ip) = self.parameters.worker_listen_ip {
+            address.set_ip(ip);
+        }
+        address
+    }
+
     /// Spawn all tasks responsible to handle clients transactions.
     fn handle_clients_transactions(&self) {
-        let (tx_batch_maker, rx_batch_maker) = channel(CHANNEL_CAPACITY);
+        let (tx_batch_maker, rx_batch_maker) = channel(self.parameters.transactions_queue_capacity);
+        let (tx_reload, rx_reload) = channel(1);
+        let admission = Admission {
+            min_gas_unit_price: self.parameters.min_gas_unit_price,
+            require_secondary_signatures: self.parameters.reject_missing_secondary_signatures,
+        };
 
         // We first receive clients' transactions from the network.
-        let mut address = self
+        let address = self.listen_address(
+            self.committee
+                .worker(&self.name, &self.id)
+                .expect("Our public key or worker id is not in the committee")
+                .transactions,
+        );
+        let client_connections = Receiver::spawn_with_connection_limit(
+            address,
+            /* handler */
+            TxReceiverHandler {
+                tx_batch_maker: tx_batch_maker.clone(),
+                admission,
+            },
+            self.parameters.max_client_connections,
+        );
+
+        // Clients may also submit transactions over HTTP, if the API is enabled for this worker.
+        let api = self
             .committee
             .worker(&self.name, &self.id)
             .expect("Our public key or worker id is not in the committee")
-            .transactions;
-        address.set_ip("0.0.0.0".parse().unwrap());
-        Receiver::spawn(
-            address,
-            /* handler */ TxReceiverHandler { tx_batch_maker },
-        );
+            .api;
+        if let Some(api) = api {
+            let api = self.listen_address(api);
+            ApiServer::spawn(
+                api,
+                tx_batch_maker,
+                tx_reload,
+                self.receipts.clone(),
+                admission,
+                client_connections,
+            );
+            info!(
+                "Worker {} listening to client transactions over HTTP on {}",
+                self.id, api
+            );
+        }
 
-        // The transactions are sent to the `BatchMaker` that assembles them into batches. It then broadcasts
-        // (in a reliable manner) the batches to all other workers that share the same `id` as us. Finally, it
-        // gathers the 'cancel handlers' of the messages and send them to the `QuorumWaiter`.
+        // If required, the sealed batches go through the `DigestCoalescer` on their way to the primary.
+        let tx_digests = match self.parameters.digest_coalesce_delay {
+            Some(max_delay) => {
+                let (tx_coalescer, rx_coalescer) = channel(self.parameters.batches_queue_capacity);
+                DigestCoalescer::spawn(max_delay, rx_coalescer, self.tx_digests.clone());
+                tx_coalescer
+            }
+            None => self.tx_digests.clone(),
+        };
+
+        // If required, batches are only delivered to the primary once a quorum of workers received them.
+        let (workers_addresses, tx_quorum_waiter) = match self.parameters.batch_quorum_ack {
+            true => {
+                let (tx_quorum_waiter, rx_quorum_waiter) =
+                    channel(self.parameters.batches_queue_capacity);
+                QuorumWaiter::spawn(
+                    self.committee.clone(),
+                    /* stake */ self.committee.stake(&self.name),
+                    /* rx_message */ rx_quorum_waiter,
+                    /* tx_batch */ tx_digests.clone(),
+                );
+                let addresses = self
+                    .committee
+                    .others_workers(&self.name, &self.id)
+                    .into_iter()
+                    .map(|(name, addresses)| (name, addresses.worker_to_worker))
+                    .collect();
+                (addresses, Some(tx_quorum_waiter))
+            }
+            false => (Vec::new(), None),
+        };
+
+        // The transactions are sent to the `BatchMaker` that assembles them into batches. If required, it
+        // then broadcasts (in a reliable manner) the batches to all other workers that share the same `id`
+        // as us. Finally, it gathers the 'cancel handlers' of the messages and send them to the `QuorumWaiter`.
         BatchMaker::spawn(
             self.parameters.batch_size,
             self.parameters.max_batch_delay,
             /* rx_transaction */ rx_batch_maker,
-            self.tx_digests.clone(),
+            tx_digests,
+            self.parameters.max_pending_batches,
+            workers_addresses,
+            tx_quorum_waiter,
+            self.parameters.group_batches_by_sender,
+            self.receipts.trace_ids().clone(),
+            rx_reload,
         );
 
         info!(
@@ -100,32 +305,127 @@ impl Worker {
             self.id, address
         );
     }
+
+    /// Spawn all tasks responsible to handle messages from other workers.
+    fn handle_workers_messages(&self) {
+        let address = self.listen_address(
+            self.committee
+                .worker(&self.name, &self.id)
+                .expect("Our public key or worker id is not in the committee")
+                .worker_to_worker,
+        );
+        Receiver::spawn(
+            address,
+            /* handler */
+            WorkerReceiverHandler {
+                store: self.store.clone(),
+            },
+        );
+
+        info!(
+            "Worker {} listening to worker messages on {}",
+            self.id, address
+        );
+    }
+}
+
+/// Decodes a BCS-encoded client transaction.
+pub fn decode_transaction(bytes: &[u8]) -> Result<Transaction, bcs::Error> {
+    bcs::from_bytes(bytes)
 }
 
 /// Defines how the network receiver handles incoming transactions.
 #[derive(Clone)]
 struct TxReceiverHandler {
     tx_batch_maker: Sender<Transaction>,
+    admission: Admission,
 }
 
-#[async_trait]
-impl MessageHandler for TxReceiverHandler {
-    async fn dispatch(&self, _writer: &mut Writer, message: Bytes) -> Result<(), Box<dyn Error>> {
-        // Parse the transaction and forward it to the batch maker.
-        let txn: Transaction = match bcs::from_bytes(message.as_ref()) {
+impl TxReceiverHandler {
+    /// Decodes a client transaction and hands it to the batch maker. Returns (and logs) why the
+    /// transaction is dropped, if it is.
+    async fn receive(&self, bytes: &[u8]) -> Result<(), RejectReason> {
+        let txn = match decode_transaction(bytes) {
             Ok(txn) => txn,
             Err(e) => {
-                warn!("Failed to decode incoming transaction: {}", e);
-                return Ok(());
+                let reason = RejectReason::Malformed(e.to_string());
+                warn!("Dropping client transaction: {}", reason);
+                return Err(reason);
             }
         };
-        self.tx_batch_maker
-            .send(txn)
-            .await
-            .expect("Failed to send transaction");
+        debug!("Received transaction (trace {})", TraceId::of(&txn));
+        self.forward(txn).await
+    }
+
+    /// Hands a transaction to the batch maker, waiting for room in its queue: the connection of a
+    /// client sending faster than we batch is slowed down rather than its transactions dropped. The
+    /// transaction is only dropped if it is not admitted or if the batch maker stopped: clients are
+    /// expected to resubmit what does not get committed. Unlike the transaction API, this endpoint
+    /// does not reply to clients.
+    async fn forward(&self, txn: Transaction) -> Result<(), RejectReason> {
+        if let Err(reason) = self.admission.check(&txn) {
+            debug!(
+                "Rejected transaction (trace {}): {}",
+                TraceId::of(&txn),
+                reason
+            );
+            return Err(reason);
+        }
+        if self.tx_batch_maker.send(txn).await.is_err() {
+            let reason = RejectReason::NotRunning;
+            warn!("Dropping client transaction: {}", reason);
+            return Err(reason);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageHandler for TxReceiverHandler {
+    async fn dispatch(&self, _writer: &mut Writer, message: Bytes) -> Result<(), Box<dyn Error>> {
+        // Parse the transaction and forward it to the batch maker. Dropped transactions are logged
+        // with their reason: this endpoint does not reply to clients.
+        let _ = self.receive(message.as_ref()).await;
 
         // Give the change to schedule other tasks.
         tokio::task::yield_now().await;
         Ok(())
     }
 }
+
+/// Defines how the network receiver handles incoming workers messages.
+#[derive(Clone)]
+struct WorkerReceiverHandler {
+    store: Store,
+}
+
+#[async_trait]
+impl MessageHandler for WorkerReceiverHandler {
+    async fn dispatch(&self, writer: &mut Writer, serialized: Bytes) -> Result<(), Box<dyn Error>> {
+        match bincode::deserialize(&serialized) {
+            // Our acknowledgement counts towards the quorum of workers holding the batch, so we only
+            // reply with an ACK once the batch is stored.
+            Ok(WorkerMessage::Batch(batch)) => {
+                debug!("Received batch of {} transactions", batch.len());
+                let digest = batch_digest(&serialized);
+                self.store
+                    .clone()
+                    .write(digest.to_vec(), serialized.to_vec())
+                    .await;
+                let _ = writer.send(Bytes::from("Ack")).await;
+            }
+            Ok(WorkerMessage::BatchRequest(..)) => {}
+            Err(e) => warn!("Failed to decode worker message: {}", e),
+        }
+        Ok(())
+    }
+}
+
+/// Returns the digest of a serialized batch, under which the batch is stored.
+fn batch_digest(serialized: &[u8]) -> Digest {
+    Digest(
+        Sha512::digest(serialized).as_slice()[..32]
+            .try_into()
+            .unwrap(),
+    )
+}
@@ -27,4 +27,7 @@ sleep 5
 printf '\nRunning Aptos integration transfer test...\n'
 cargo run --quiet --manifest-path "$REPO_ROOT/Cargo.toml" -p aptos_executor --bin integration_test
 
+printf '\nRunning HTTP transaction API integration test...\n'
+cargo run --quiet --manifest-path "$REPO_ROOT/Cargo.toml" -p aptos_executor --bin http_integration_test
+
 printf '\nIntegration test completed successfully.\n'
@@ -14,7 +14,9 @@ Arguments:
 Environment:
   BASE_PORT       Starting port number for the first node (default: 3000).
                   Each node consumes three consecutive ports: consensus,
-                  primary, and worker-to-primary.
+                  primary, and worker-to-primary, followed by four ports per
+                  worker: primary-to-worker, transactions, worker-to-worker
+                  and the HTTP transaction API.
   PROFILE         Cargo profile to build/run (default: debug). Set to
                   "release" for an optimized binary or any custom cargo
                   profile name for advanced use.
@@ -64,7 +66,7 @@ if ! [[ "$WORKERS_VALUE" =~ ^[0-9]+$ ]] || (( WORKERS_VALUE <= 0 )); then
     exit 1
 fi
 WORKERS_PER_NODE=$((WORKERS_VALUE))
-NODE_PORT_STRIDE=$((3 + WORKERS_PER_NODE * 3))
+NODE_PORT_STRIDE=$((3 + WORKERS_PER_NODE * 4))
 
 if ! command -v cargo >/dev/null 2>&1; then
     echo "Error: cargo is required but not found in PATH." >&2
@@ -184,11 +186,12 @@ for idx, name in enumerate(names):
     workers = {}
     worker_port_base = port_cursor + 3
     for worker_id in range(workers_per_node):
-        base = worker_port_base + worker_id * 3
+        base = worker_port_base + worker_id * 4
         workers[worker_id] = {
             "primary_to_worker": f"127.0.0.1:{base}",
             "transactions": f"127.0.0.1:{base + 1}",
-            "worker_to_worker": f"127.0.0.1:{base + 2}"
+            "worker_to_worker": f"127.0.0.1:{base + 2}",
+            "api": f"127.0.0.1:{base + 3}"
         }
 
     authorities[name] = {
@@ -207,7 +210,7 @@ for idx, name in enumerate(names):
         "workers": workers
     }
 
-    port_cursor += 3 + workers_per_node * 3
+    port_cursor += 3 + workers_per_node * 4
 
 committee = {"authorities": authorities}
 with open(committee_path, 'w', encoding='utf-8') as f:
@@ -15,37 +15,95 @@ type Value = Vec<u8>;
 
 pub enum StoreCommand {
     Write(Key, Value),
+    Delete(Key),
     Read(Key, oneshot::Sender<StoreResult<Option<Value>>>),
     NotifyRead(Key, oneshot::Sender<StoreResult<Value>>),
 }
 
+/// Where a store keeps its data.
+enum Backend {
+    RocksDb(rocksdb::DB),
+    /// Lost when the process exits; for tests and experiments.
+    Memory(HashMap<Key, Value>),
+}
+
+impl Backend {
+    fn put(&mut self, key: &Key, value: &Value) -> StoreResult<()> {
+        match self {
+            Self::RocksDb(db) => db.put(key, value),
+            Self::Memory(map) => {
+                map.insert(key.clone(), value.clone());
+                Ok(())
+            }
+        }
+    }
+
+    fn delete(&mut self, key: &Key) -> StoreResult<()> {
+        match self {
+            Self::RocksDb(db) => db.delete(key),
+            Self::Memory(map) => {
+                map.remove(key);
+                Ok(())
+            }
+        }
+    }
+
+    fn get(&self, key: &Key) -> StoreResult<Option<Value>> {
+        match self {
+            Self::RocksDb(db) => db.get(key),
+            Self::Memory(map) => Ok(map.get(key).cloned()),
+        }
+    }
+}
+
+/// Handle to the node's persistent storage. A node opens a single store and hands clones of it to
+/// every component, so that data written by one component (e.g. headers written by the primary's
+/// `Core`) can be read by another (e.g. the consensus `Committer`). The key namespace is:
+///  - headers: `Header` encoded with `Parameters::store_codec`, keyed by header id;
+///  - certificates: `Certificate` encoded with `Parameters::store_codec`, keyed by certificate digest;
+///  - batches: keyed by batch digest followed by the worker id (primary) or by batch digest (worker);
+///  - consensus blocks: `Block` encoded with `Parameters::store_codec`, keyed by block digest.
 #[derive(Clone)]
 pub struct Store {
     channel: Sender<StoreCommand>,
+    path: String,
 }
 
 impl Store {
+    /// Opens (or creates) a RocksDB-backed store at `path`.
     pub fn new(path: &str) -> StoreResult<Self> {
         let db = rocksdb::DB::open_default(path)?;
+        Ok(Self::spawn(Backend::RocksDb(db), path))
+    }
+
+    /// Creates an empty store kept in memory.
+    pub fn new_in_memory() -> Self {
+        Self::spawn(Backend::Memory(HashMap::new()), ":memory:")
+    }
+
+    fn spawn(mut backend: Backend, path: &str) -> Self {
         let mut obligations = HashMap::<_, VecDeque<oneshot::Sender<_>>>::new();
         let (tx, mut rx) = channel(100);
         tokio::spawn(async move {
             while let Some(command) = rx.recv().await {
                 match command {
                     StoreCommand::Write(key, value) => {
-                        let _ = db.put(&key, &value);
+                        let _ = backend.put(&key, &value);
                         if let Some(mut senders) = obligations.remove(&key) {
                             while let Some(s) = senders.pop_front() {
                                 let _ = s.send(Ok(value.clone()));
                             }
                         }
                     }
+                    StoreCommand::Delete(key) => {
+                        let _ = backend.delete(&key);
+                    }
                     StoreCommand::Read(key, sender) => {
-                        let response = db.get(&key);
+                        let response = backend.get(&key);
                         let _ = sender.send(response);
                     }
                     StoreCommand::NotifyRead(key, sender) => {
-                        let response = db.get(&key);
+                        let response = backend.get(&key);
                         match response {
                             Ok(None) => obligations
                                 .entry(key)
@@ -59,7 +117,15 @@ impl Store {
                 }
             }
         });
-        Ok(Self { channel: tx })
+        Self {
+            channel: tx,
+            path: path.to_string(),
+        }
+    }
+
+    /// Returns the path of the underlying database.
+    pub fn path(&self) -> &str {
+        &self.path
     }
 
     pub async fn write(&mut self, key: Key, value: Value) {
@@ -68,6 +134,12 @@ impl Store {
         }
     }
 
+    pub async fn delete(&mut self, key: Key) {
+        if let Err(e) = self.channel.send(StoreCommand::Delete(key)).await {
+            panic!("Failed to send Delete command to store: {}", e);
+        }
+    }
+
     pub async fn read(&mut self, key: Key) -> StoreResult<Option<Value>> {
         let (sender, receiver) = oneshot::channel();
         if let Err(e) = self.channel.send(StoreCommand::Read(key, sender)).await {
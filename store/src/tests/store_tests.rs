@@ -72,3 +72,34 @@ async fn read_notify() {
     store.write(key, value).await;
     assert!(handle.await.is_ok());
 }
+
+#[tokio::test]
+async fn in_memory_read_write_value() {
+    let mut store = Store::new_in_memory();
+
+    // Write value to the store.
+    let key = vec![0u8, 1u8, 2u8, 3u8];
+    let value = vec![4u8, 5u8, 6u8, 7u8];
+    store.write(key.clone(), value.clone()).await;
+
+    // Read value.
+    let result = store.read(key).await;
+    assert_eq!(result.unwrap(), Some(value));
+}
+
+#[tokio::test]
+async fn delete_value() {
+    // Create new store.
+    let path = ".db_test_delete_value";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Write a value, then delete it.
+    let key = vec![0u8, 1u8, 2u8, 3u8];
+    store.write(key.clone(), vec![4u8, 5u8, 6u8, 7u8]).await;
+    store.delete(key.clone()).await;
+
+    // The value is gone.
+    let result = store.read(key).await;
+    assert!(result.unwrap().is_none());
+}
@@ -41,6 +41,7 @@ pub fn committee() -> Committee {
                         primary_to_worker: format!("127.0.0.1:{}", 300 + i).parse().unwrap(),
                         transactions: format!("127.0.0.1:{}", 400 + i).parse().unwrap(),
                         worker_to_worker: format!("127.0.0.1:{}", 500 + i).parse().unwrap(),
+                        api: None,
                     },
                 )]
                 .iter()